@@ -0,0 +1,197 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{CryptError, CryptResult, KeyId, SecretKey, KEY_ID_SIZE};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The version of the encrypted payload format.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// The size of the nonce of an encrypted payload in bytes.
+pub const NONCE_SIZE: usize = 24;
+
+/// The size of the authentication tag of an encrypted payload in bytes.
+pub const TAG_SIZE: usize = 16;
+
+/// The number of bytes encryption adds to a payload.
+pub const PAYLOAD_OVERHEAD: usize = HEADER_SIZE + NONCE_SIZE + TAG_SIZE;
+
+/// The size of the header of an encrypted payload: the version and the key ID.
+const HEADER_SIZE: usize = 1 + KEY_ID_SIZE;
+
+/// The domain the encryption key is derived in.
+const ENCRYPTION_INFO: &[u8] = b"cryptdag/encryption";
+
+/// The domain the nonce key is derived in.
+const NONCE_INFO: &[u8] = b"cryptdag/nonce";
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Encrypts `plaintext` with `key`.
+///
+/// The payload is laid out as `version || key id || nonce || ciphertext || tag` and is encrypted
+/// with XChaCha20-Poly1305, the header being authenticated along with the ciphertext.
+///
+/// Encryption is deterministic: the nonce is a MAC of the plaintext, so the same plaintext
+/// encrypted with the same key always gives the same payload. This keeps CIDs over ciphertext
+/// stable, so identical blocks are still deduplicated, at the cost of revealing to the store that
+/// two blocks are equal.
+///
+/// ## Examples
+///
+/// ```
+/// use monoutils_cryptdag::{self as cryptdag, SecretKey};
+///
+/// let key = SecretKey::generate();
+/// let payload = cryptdag::encrypt(&key, b"hello");
+/// assert_eq!(cryptdag::decrypt(&key, &payload)?, b"hello");
+/// # Ok::<(), cryptdag::CryptError>(())
+/// ```
+pub fn encrypt(key: &SecretKey, plaintext: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(plaintext.len() + PAYLOAD_OVERHEAD);
+    payload.push(PAYLOAD_VERSION);
+    payload.extend_from_slice(key.get_id().as_bytes());
+
+    let nonce = synthetic_nonce(key, plaintext);
+    let ciphertext = cipher(key)
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &payload,
+            },
+        )
+        .expect("payloads of any size can be encrypted");
+
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    payload
+}
+
+/// Decrypts a payload encrypted with [`encrypt`].
+///
+/// ## Errors
+///
+/// Returns [`CryptError::KeyMismatch`] if the payload was encrypted with another key, and
+/// [`CryptError::Decryption`] if it was tampered with.
+pub fn decrypt(key: &SecretKey, payload: &[u8]) -> CryptResult<Vec<u8>> {
+    if payload.len() < PAYLOAD_OVERHEAD {
+        return Err(CryptError::MalformedPayload(payload.len()));
+    }
+
+    let (header, rest) = payload.split_at(HEADER_SIZE);
+    if header[0] != PAYLOAD_VERSION {
+        return Err(CryptError::UnsupportedVersion(header[0]));
+    }
+
+    let found = get_payload_key_id(payload)?;
+    let expected = key.get_id();
+    if found != expected {
+        return Err(CryptError::KeyMismatch { expected, found });
+    }
+
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    cipher(key)
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| CryptError::Decryption)
+}
+
+/// Returns the ID of the key a payload was encrypted with.
+pub fn get_payload_key_id(payload: &[u8]) -> CryptResult<KeyId> {
+    let id = payload
+        .get(1..HEADER_SIZE)
+        .ok_or(CryptError::MalformedPayload(payload.len()))?;
+
+    let mut bytes = [0; KEY_ID_SIZE];
+    bytes.copy_from_slice(id);
+    Ok(KeyId::from_bytes(bytes))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the cipher for the encryption key derived from `key`.
+fn cipher(key: &SecretKey) -> XChaCha20Poly1305 {
+    let encryption_key = key.derive_bytes(ENCRYPTION_INFO);
+    XChaCha20Poly1305::new(Key::from_slice(&encryption_key))
+}
+
+/// Returns the nonce for `plaintext`, a MAC of it with the nonce key derived from `key`.
+fn synthetic_nonce(key: &SecretKey, plaintext: &[u8]) -> [u8; NONCE_SIZE] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key.derive_bytes(NONCE_INFO))
+        .expect("hmac accepts keys of any size");
+    mac.update(plaintext);
+
+    let mut nonce = [0; NONCE_SIZE];
+    nonce.copy_from_slice(&mac.finalize().into_bytes()[..NONCE_SIZE]);
+    nonce
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() -> anyhow::Result<()> {
+        let key = SecretKey::generate();
+        for plaintext in [&b""[..], b"hello", &[42; 10_000]] {
+            let payload = encrypt(&key, plaintext);
+            assert_eq!(payload.len(), plaintext.len() + PAYLOAD_OVERHEAD);
+            assert_eq!(get_payload_key_id(&payload)?, key.get_id());
+            assert_eq!(decrypt(&key, &payload)?, plaintext);
+        }
+
+        // Encryption is deterministic per key
+        assert_eq!(encrypt(&key, b"hello"), encrypt(&key, b"hello"));
+        assert_ne!(encrypt(&key, b"hello"), encrypt(&key, b"hellp"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_key_and_tampering() {
+        let key = SecretKey::generate();
+        let other = SecretKey::generate();
+        let mut payload = encrypt(&key, b"secret");
+
+        assert!(matches!(
+            decrypt(&other, &payload),
+            Err(CryptError::KeyMismatch { .. })
+        ));
+
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert_eq!(decrypt(&key, &payload), Err(CryptError::Decryption));
+
+        assert_eq!(
+            decrypt(&key, &payload[..10]),
+            Err(CryptError::MalformedPayload(10))
+        );
+
+        payload[0] = 9;
+        assert_eq!(
+            decrypt(&key, &payload),
+            Err(CryptError::UnsupportedVersion(9))
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+use crate::KeyId;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The result of a cryptdag operation.
+pub type CryptResult<T> = Result<T, CryptError>;
+
+/// An error that occurred during a cryptdag operation.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CryptError {
+    /// The payload is too short to be an encrypted payload.
+    #[error("Malformed encrypted payload of {0} bytes")]
+    MalformedPayload(usize),
+
+    /// The payload was encrypted with a format version this library does not know.
+    #[error("Unsupported encrypted payload version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// The payload was encrypted with a different key.
+    #[error("Key mismatch: payload was encrypted with key {found}, not {expected}")]
+    KeyMismatch {
+        /// The ID of the key the payload was decrypted with.
+        expected: KeyId,
+
+        /// The ID of the key the payload was encrypted with.
+        found: KeyId,
+    },
+
+    /// The payload failed authentication, i.e. it was tampered with.
+    #[error("Failed to decrypt payload")]
+    Decryption,
+
+    /// A key or key ID was not valid.
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+}
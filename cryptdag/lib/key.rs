@@ -0,0 +1,228 @@
+use std::{
+    fmt::{self, Debug, Display},
+    str::FromStr,
+};
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::{CryptError, CryptResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The size of a secret key in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// The size of a key ID in bytes.
+pub const KEY_ID_SIZE: usize = 8;
+
+/// The domain the key of a child is derived in.
+const CHILD_INFO: &[u8] = b"cryptdag/child/";
+
+/// The domain the ID of a key is derived in.
+const ID_INFO: &[u8] = b"cryptdag/id";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A symmetric key that a node of a tree, and everything below it, is encrypted with.
+///
+/// Keys form a tree of their own: [`derive_child`][Self::derive_child] derives the key of a
+/// child from the key of its parent and the child's name. Derivation goes one way only, so the
+/// key of a child reveals nothing about the key of its parent or of its siblings.
+///
+/// The key bytes are zeroed when the key is dropped.
+///
+/// ## Examples
+///
+/// ```
+/// use monoutils_cryptdag::SecretKey;
+///
+/// let root = SecretKey::generate();
+/// let docs = root.derive_child("docs");
+///
+/// // Derivation is deterministic
+/// assert_eq!(docs, root.derive_child("docs"));
+/// assert_eq!(docs.derive_child("a.txt"), root.derive_path(["docs", "a.txt"]));
+/// assert_ne!(docs, root.derive_child("pics"));
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretKey([u8; KEY_SIZE]);
+
+/// A public identifier of a [`SecretKey`], stored next to payloads so the key they need can be
+/// told apart without trying it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId([u8; KEY_ID_SIZE]);
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SecretKey {
+    /// Generates a new random key.
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    /// Creates a key from its bytes.
+    pub fn from_bytes(bytes: [u8; KEY_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+        &self.0
+    }
+
+    /// Returns the ID of the key.
+    pub fn get_id(&self) -> KeyId {
+        let mut id = [0; KEY_ID_SIZE];
+        id.copy_from_slice(&self.derive_bytes(ID_INFO)[..KEY_ID_SIZE]);
+        KeyId(id)
+    }
+
+    /// Derives the key of the child named `name`.
+    ///
+    /// ## Arguments
+    /// * `name` - The name of the child, e.g. a directory entry name
+    pub fn derive_child(&self, name: impl AsRef<[u8]>) -> SecretKey {
+        let info = [CHILD_INFO, name.as_ref()].concat();
+        SecretKey(self.derive_bytes(&info))
+    }
+
+    /// Derives the key of the descendant at `path` by deriving the key of every child along it.
+    ///
+    /// ## Arguments
+    /// * `path` - The names of the children from this key's node to the descendant
+    pub fn derive_path<I>(&self, path: I) -> SecretKey
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        path.into_iter()
+            .fold(self.clone(), |key, name| key.derive_child(name))
+    }
+
+    /// Derives key material for a purpose described by `info`.
+    pub(crate) fn derive_bytes(&self, info: &[u8]) -> [u8; KEY_SIZE] {
+        let mut okm = [0; KEY_SIZE];
+        Hkdf::<Sha256>::new(None, &self.0)
+            .expand(info, &mut okm)
+            .expect("key size is a valid hkdf output length");
+        okm
+    }
+}
+
+impl KeyId {
+    /// Creates a key ID from its bytes.
+    pub fn from_bytes(bytes: [u8; KEY_ID_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the bytes of the key ID.
+    pub fn as_bytes(&self) -> &[u8; KEY_ID_SIZE] {
+        &self.0
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretKey").field(&self.get_id()).finish()
+    }
+}
+
+impl FromStr for SecretKey {
+    type Err = CryptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(decode_hex(s)?))
+    }
+}
+
+impl Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl Debug for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyId({})", self)
+    }
+}
+
+impl FromStr for KeyId {
+    type Err = CryptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(decode_hex(s)?))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Decodes a hex string of exactly `N` bytes.
+fn decode_hex<const N: usize>(s: &str) -> CryptResult<[u8; N]> {
+    let mut bytes = [0; N];
+    hex::decode_to_slice(s.trim(), &mut bytes)
+        .map_err(|e| CryptError::InvalidKey(format!("expected {} hex encoded bytes: {}", N, e)))?;
+    Ok(bytes)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_derivation() {
+        let root = SecretKey::from_bytes([7; KEY_SIZE]);
+
+        let a = root.derive_child("a");
+        assert_eq!(a, root.derive_child("a"));
+        assert_ne!(a, root.derive_child("b"));
+        assert_ne!(a, root);
+
+        // Paths derive through every child, and don't collide with a child named after the path
+        assert_eq!(root.derive_path(["a", "b"]), a.derive_child("b"));
+        assert_ne!(root.derive_path(["a", "b"]), root.derive_child("a/b"));
+        assert_eq!(root.derive_path(Vec::<&str>::new()), root);
+
+        assert_ne!(a.get_id(), root.get_id());
+        assert_eq!(a.get_id(), root.derive_child("a").get_id());
+    }
+
+    #[test]
+    fn test_secret_key_and_key_id_parse_from_hex() -> anyhow::Result<()> {
+        let key = SecretKey::generate();
+        let parsed: SecretKey = hex::encode(key.as_bytes()).parse()?;
+        assert_eq!(parsed, key);
+
+        let id = key.get_id();
+        assert_eq!(id.to_string().parse::<KeyId>()?, id);
+
+        assert!("abcd".parse::<SecretKey>().is_err());
+        assert!(!format!("{:?}", key).contains(&hex::encode(key.as_bytes())));
+
+        Ok(())
+    }
+}
@@ -1,8 +1,27 @@
 //! `cryptdag` is a key generation library for data structures that can be represented as trees or DAGs.
+//!
+//! It provides [`CryptStore`], a layer over any [`IpldStore`][ipldstore::IpldStore] that encrypts
+//! every node and byte payload before it reaches the store. Blocks are encrypted with
+//! XChaCha20-Poly1305 and their CIDs are computed over the ciphertext, so the underlying store
+//! learns nothing about their contents or the links between them.
+//!
+//! Keys are derived along the tree: the key of a child is derived from the key of its parent and
+//! the child's name, and derivation is one-way. Sharing the key of a subtree therefore grants
+//! access to that subtree and nothing above or beside it.
 
 #![warn(missing_docs)]
 #![allow(clippy::module_inception)]
 
+mod cipher;
+mod error;
+mod key;
+mod store;
+
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
+
+pub use cipher::*;
+pub use error::*;
+pub use key::*;
+pub use store::*;
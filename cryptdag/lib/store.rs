@@ -0,0 +1,432 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    pin::Pin,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use getset::Getters;
+use ipldstore::{
+    ipld::cid::Cid, Codec, IpldReferences, IpldStore, IpldStoreSeekable, RawStore, StoreError,
+    StoreResult,
+};
+use monoutils::SeekableReader;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{cipher, KeyId, SecretKey, PAYLOAD_OVERHEAD};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A store that encrypts everything it is given before passing it on to another store.
+///
+/// Nodes are serialized as DAG-CBOR and encrypted into raw blocks, so their CIDs are computed
+/// over the ciphertext and the underlying store sees neither their contents nor the links between
+/// them. Unlike with plain stores, node CIDs therefore use the `Raw` codec. Byte payloads are
+/// encrypted as a whole before being chunked by the underlying store, which only learns their
+/// size.
+///
+/// Every payload is encrypted with the store's key. [`derive_child`][Self::derive_child] returns
+/// a view of the same store that encrypts with the key of a child node, so a tree can encrypt
+/// each subtree with its own key and hand out access to a subtree without exposing the rest of
+/// the tree.
+///
+/// Encryption is deterministic per key, see [`encrypt`][crate::encrypt].
+///
+/// Keys can be rotated without re-encrypting what was written before: a store given its former
+/// keys with [`with_retired_keys`][Self::with_retired_keys] encrypts with its current key but
+/// still decrypts payloads encrypted with any of them.
+///
+/// ## Examples
+///
+/// ```
+/// use ipldstore::{IpldStore, MemoryStore, RawStore};
+/// use monoutils_cryptdag::{CryptStore, SecretKey};
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let store = CryptStore::new(MemoryStore::default(), SecretKey::generate());
+///
+/// let cid = store.put_node(&"hello".to_string()).await?;
+/// let node: String = store.get_node(&cid).await?;
+/// assert_eq!(node, "hello");
+///
+/// // The underlying store only holds ciphertext
+/// let block = store.get_store().get_raw_block(&cid).await?;
+/// assert!(!block.windows(5).any(|window| window == b"hello"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct CryptStore<S>
+where
+    S: IpldStore,
+{
+    /// The store the encrypted blocks are kept in.
+    store: S,
+
+    /// The key payloads are encrypted with.
+    key: SecretKey,
+
+    /// Former keys that payloads are still decrypted with, by key ID.
+    retired_keys: Arc<HashMap<KeyId, SecretKey>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<S> CryptStore<S>
+where
+    S: IpldStore,
+{
+    /// Creates a new store that encrypts with `key` and keeps encrypted blocks in `store`.
+    pub fn new(store: S, key: SecretKey) -> Self {
+        Self {
+            store,
+            key,
+            retired_keys: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Returns a view of the same underlying store that encrypts with `key` instead.
+    ///
+    /// The view keeps no retired keys, since they belong to the key being replaced.
+    pub fn with_key(&self, key: SecretKey) -> Self {
+        Self::new(self.store.clone(), key)
+    }
+
+    /// Adds former keys that payloads are still decrypted with.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use ipldstore::{IpldStore, MemoryStore};
+    /// use monoutils_cryptdag::{CryptStore, SecretKey};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let old_key = SecretKey::generate();
+    /// let old = CryptStore::new(MemoryStore::default(), old_key.clone());
+    /// let cid = old.put_node(&"written before rotation".to_string()).await?;
+    ///
+    /// let new = old.with_key(SecretKey::generate()).with_retired_keys([old_key]);
+    /// assert_eq!(new.get_node::<String>(&cid).await?, "written before rotation");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_retired_keys(mut self, keys: impl IntoIterator<Item = SecretKey>) -> Self {
+        let retired_keys = Arc::make_mut(&mut self.retired_keys);
+        for key in keys {
+            retired_keys.insert(key.get_id(), key);
+        }
+
+        self
+    }
+
+    /// Returns a view of the same underlying store that encrypts with the key of the child named
+    /// `name`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use ipldstore::{IpldStore, MemoryStore};
+    /// use monoutils_cryptdag::{CryptStore, SecretKey};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let root = CryptStore::new(MemoryStore::default(), SecretKey::generate());
+    /// let docs = root.derive_child("docs");
+    /// let cid = docs.put_node(&"notes".to_string()).await?;
+    ///
+    /// // Only the key of the subtree opens it
+    /// assert!(root.get_node::<String>(&cid).await.is_err());
+    /// let shared = CryptStore::new(root.get_store().clone(), docs.get_key().clone());
+    /// assert_eq!(shared.get_node::<String>(&cid).await?, "notes");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn derive_child(&self, name: impl AsRef<[u8]>) -> Self {
+        let name = name.as_ref();
+        self.with_key(self.key.derive_child(name))
+            .with_retired_keys(self.retired_keys.values().map(|key| key.derive_child(name)))
+    }
+
+    /// Decrypts a payload read from the underlying store, with the retired key it was encrypted
+    /// with if it was not encrypted with the current key.
+    fn decrypt(&self, payload: &[u8]) -> StoreResult<Vec<u8>> {
+        let key = cipher::get_payload_key_id(payload)
+            .ok()
+            .and_then(|id| self.retired_keys.get(&id))
+            .unwrap_or(&self.key);
+
+        cipher::decrypt(key, payload).map_err(StoreError::custom)
+    }
+
+    /// Reads and decrypts a byte payload from the underlying store.
+    async fn read_bytes(&self, cid: &Cid) -> StoreResult<Vec<u8>> {
+        let mut payload = Vec::new();
+        self.store
+            .get_bytes(cid)
+            .await?
+            .read_to_end(&mut payload)
+            .await
+            .map_err(StoreError::custom)?;
+
+        self.decrypt(&payload)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl<S> IpldStore for CryptStore<S>
+where
+    S: IpldStore + Send + Sync,
+{
+    async fn put_node<T>(&self, node: &T) -> StoreResult<Cid>
+    where
+        T: Serialize + IpldReferences + Sync,
+    {
+        let bytes = serde_ipld_dagcbor::to_vec(node).map_err(StoreError::custom)?;
+        if let Some(max_size) = self.get_max_node_block_size().await? {
+            if bytes.len() as u64 > max_size {
+                return Err(StoreError::NodeBlockTooLarge(bytes.len() as u64, max_size));
+            }
+        }
+
+        self.store
+            .put_raw_block(cipher::encrypt(&self.key, &bytes))
+            .await
+    }
+
+    async fn put_bytes(&self, reader: impl AsyncRead + Send + Sync) -> StoreResult<Cid> {
+        let mut bytes = Vec::new();
+        Box::pin(reader)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(StoreError::custom)?;
+
+        let payload = cipher::encrypt(&self.key, &bytes);
+        self.store.put_bytes(Cursor::new(payload)).await
+    }
+
+    async fn get_node<D>(&self, cid: &Cid) -> StoreResult<D>
+    where
+        D: DeserializeOwned + Send,
+    {
+        let payload = self.store.get_raw_block(cid).await?;
+        let bytes = self.decrypt(&payload)?;
+        serde_ipld_dagcbor::from_slice(&bytes).map_err(StoreError::custom)
+    }
+
+    async fn get_bytes(&self, cid: &Cid) -> StoreResult<Pin<Box<dyn AsyncRead + Send>>> {
+        Ok(Box::pin(Cursor::new(self.read_bytes(cid).await?)))
+    }
+
+    async fn get_bytes_size(&self, cid: &Cid) -> StoreResult<u64> {
+        let size = self.store.get_bytes_size(cid).await?;
+        Ok(size.saturating_sub(PAYLOAD_OVERHEAD as u64))
+    }
+
+    async fn has(&self, cid: &Cid) -> bool {
+        self.store.has(cid).await
+    }
+
+    async fn get_supported_codecs(&self) -> HashSet<Codec> {
+        HashSet::from([Codec::Raw])
+    }
+
+    async fn get_max_node_block_size(&self) -> StoreResult<Option<u64>> {
+        let max_size = self.store.get_max_raw_block_size().await?;
+        Ok(max_size.map(|size| size.saturating_sub(PAYLOAD_OVERHEAD as u64)))
+    }
+
+    async fn is_empty(&self) -> StoreResult<bool> {
+        self.store.is_empty().await
+    }
+
+    async fn get_block_count(&self) -> StoreResult<u64> {
+        self.store.get_block_count().await
+    }
+}
+
+#[async_trait]
+impl<S> RawStore for CryptStore<S>
+where
+    S: IpldStore + Send + Sync,
+{
+    async fn put_raw_block(&self, bytes: impl Into<Bytes> + Send) -> StoreResult<Cid> {
+        let payload = cipher::encrypt(&self.key, &bytes.into());
+        self.store.put_raw_block(payload).await
+    }
+
+    async fn get_raw_block(&self, cid: &Cid) -> StoreResult<Bytes> {
+        let payload = self.store.get_raw_block(cid).await?;
+        Ok(Bytes::from(self.decrypt(&payload)?))
+    }
+
+    async fn get_max_raw_block_size(&self) -> StoreResult<Option<u64>> {
+        let max_size = self.store.get_max_raw_block_size().await?;
+        Ok(max_size.map(|size| size.saturating_sub(PAYLOAD_OVERHEAD as u64)))
+    }
+}
+
+#[async_trait]
+impl<S> IpldStoreSeekable for CryptStore<S>
+where
+    S: IpldStore + Send + Sync,
+{
+    async fn get_seekable_bytes(
+        &self,
+        cid: &Cid,
+    ) -> StoreResult<Pin<Box<dyn SeekableReader + Send + 'static>>> {
+        Ok(Box::pin(Cursor::new(self.read_bytes(cid).await?)))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ipldstore::{ipld::ipld::Ipld, utils, IpldStoreExt, MemoryStore};
+
+    use crate::{CryptError, KeyId};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_crypt_store_round_trips_plaintext() -> anyhow::Result<()> {
+        let memory = MemoryStore::default();
+        let store = CryptStore::new(memory.clone(), SecretKey::generate());
+
+        // Nodes
+        let node = Ipld::Map(BTreeMap::from([
+            ("name".to_string(), Ipld::String("secret.txt".to_string())),
+            ("size".to_string(), Ipld::Integer(42)),
+        ]));
+        let cid = store.put_node(&node).await?;
+        assert_eq!(store.get_node::<Ipld>(&cid).await?, node);
+
+        // The CID is computed over the ciphertext
+        let block = memory.get_raw_block(&cid).await?;
+        assert_eq!(utils::generate_cid(Codec::Raw, &block), cid);
+        assert!(!block.windows(10).any(|window| window == b"secret.txt"));
+
+        // Raw blocks
+        let cid = store.put_raw_block(b"raw secret".to_vec()).await?;
+        assert_eq!(&store.get_raw_block(&cid).await?[..], b"raw secret");
+
+        // Byte payloads spanning several chunks
+        let data = (0..600_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let cid = store.put_bytes(&data[..]).await?;
+        assert_eq!(store.read_all(&cid).await?, data);
+        assert_eq!(store.get_bytes_size(&cid).await?, data.len() as u64);
+        assert!(memory.read_all(&cid).await? != data);
+
+        // The same content gets the same CID
+        assert_eq!(store.put_node(&node).await?, store.put_node(&node).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crypt_store_subtree_keys() -> anyhow::Result<()> {
+        let root = CryptStore::new(MemoryStore::default(), SecretKey::generate());
+
+        // A tree of nodes, each encrypted with the key derived along its path
+        let docs = root.derive_child("docs");
+        let file_cid = docs
+            .derive_child("a.txt")
+            .put_node(&"a".to_string())
+            .await?;
+        let docs_cid = docs
+            .put_node(&Ipld::Map(BTreeMap::from([(
+                "a.txt".to_string(),
+                Ipld::Link(file_cid),
+            )])))
+            .await?;
+        let root_cid = root
+            .put_node(&Ipld::Map(BTreeMap::from([(
+                "docs".to_string(),
+                Ipld::Link(docs_cid),
+            )])))
+            .await?;
+
+        // The root key opens everything by deriving down the tree
+        let Ipld::Map(entries) = root.get_node::<Ipld>(&root_cid).await? else {
+            panic!("root is not a map");
+        };
+        assert_eq!(entries["docs"], Ipld::Link(docs_cid));
+        let file: String = root
+            .with_key(root.get_key().derive_path(["docs", "a.txt"]))
+            .get_node(&file_cid)
+            .await?;
+        assert_eq!(file, "a");
+
+        // The key of the subtree opens the subtree but not the root
+        let shared = CryptStore::new(root.get_store().clone(), docs.get_key().clone());
+        assert!(shared.get_node::<Ipld>(&docs_cid).await.is_ok());
+        assert!(shared
+            .derive_child("a.txt")
+            .get_node::<String>(&file_cid)
+            .await
+            .is_ok());
+
+        let err = shared.get_node::<Ipld>(&root_cid).await.unwrap_err();
+        let StoreError::Custom(err) = err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(
+            err.downcast::<CryptError>(),
+            Some(&CryptError::KeyMismatch {
+                expected: docs.get_key().get_id(),
+                found: root.get_key().get_id(),
+            })
+        );
+        assert_ne!(docs.get_key().get_id(), KeyId::from_bytes([0; 8]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crypt_store_decrypts_with_retired_keys() -> anyhow::Result<()> {
+        let old = CryptStore::new(MemoryStore::default(), SecretKey::generate());
+        let node_cid = old.put_node(&"old node".to_string()).await?;
+        let child_cid = old
+            .derive_child("docs")
+            .put_node(&"old child".to_string())
+            .await?;
+        let bytes_cid = old.put_bytes(&b"old bytes"[..]).await?;
+
+        // The rotated store writes with the new key and reads with either
+        let new = old
+            .with_key(SecretKey::generate())
+            .with_retired_keys([old.get_key().clone()]);
+        assert_eq!(new.get_node::<String>(&node_cid).await?, "old node");
+        assert_eq!(&new.read_all(&bytes_cid).await?[..], b"old bytes");
+        assert_eq!(
+            new.derive_child("docs")
+                .get_node::<String>(&child_cid)
+                .await?,
+            "old child"
+        );
+
+        let new_cid = new.put_node(&"old node".to_string()).await?;
+        assert_ne!(new_cid, node_cid);
+        assert!(old.get_node::<String>(&new_cid).await.is_err());
+
+        Ok(())
+    }
+}
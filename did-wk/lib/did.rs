@@ -0,0 +1,304 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use getset::Getters;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{DidDocument, DidError, DidResult, PublicKey};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The scheme all DIDs start with.
+pub const DID_SCHEME: &str = "did";
+
+/// The path hosted `did:wk` documents are served at, relative to their locator.
+pub const WELL_KNOWN_DID_PATH: &str = ".well-known/did.json";
+
+/// The fragment of the verification method of a hosted `did:wk` document.
+const WK_KEY_FRAGMENT: &str = "keys-1";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A DID method supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DidMethod {
+    /// `did:key`, an identifier that is a public key and nothing else.
+    Key,
+
+    /// `did:wk`, a public key that may also point to a DID document hosted on the web.
+    Wk,
+}
+
+/// A `did:key` or `did:wk` decentralized identifier.
+///
+/// Both methods embed a multicodec encoded, multibase public key in the identifier, so anything
+/// signed by the DID can be verified without resolving it. A `did:wk` may also carry a locator,
+/// the host and path its signed DID document is served under.
+///
+/// ## Examples
+///
+/// ```
+/// use monoutils_did::{Did, DidMethod, KeyPair, KeyType};
+///
+/// let keypair = KeyPair::generate(KeyType::Ed25519);
+/// let did = Did::key(keypair.get_public_key());
+/// assert!(did.to_string().starts_with("did:key:z6Mk"));
+///
+/// let signature = keypair.sign(b"hello");
+/// assert!(did.verify(b"hello", &signature).is_ok());
+///
+/// let did: Did = "did:wk:z6MkwFK7L2unwCxXNaws5wxbWKbzEiC84mCYno7RW5dZ7rzq@steve.monocore.dev/pub"
+///     .parse()?;
+/// assert_eq!(did.get_method(), &DidMethod::Wk);
+/// assert_eq!(
+///     did.get_document_url().as_deref(),
+///     Some("https://steve.monocore.dev/pub/.well-known/did.json")
+/// );
+/// # Ok::<(), monoutils_did::DidError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct Did {
+    /// The method of the DID.
+    method: DidMethod,
+
+    /// The public key the DID is made of.
+    public_key: PublicKey,
+
+    /// The host, port and path the DID document of a `did:wk` is hosted under, if any.
+    locator: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Did {
+    /// Creates a `did:key` for the given public key.
+    pub fn key(public_key: PublicKey) -> Self {
+        Self {
+            method: DidMethod::Key,
+            public_key,
+            locator: None,
+        }
+    }
+
+    /// Creates a `did:wk` for the given public key.
+    ///
+    /// ## Arguments
+    /// * `public_key` - The public key of the DID
+    /// * `locator` - The host, optional port and optional path the DID document is hosted under
+    pub fn wk(public_key: PublicKey, locator: Option<String>) -> DidResult<Self> {
+        if let Some(locator) = &locator {
+            validate_locator(locator)?;
+        }
+
+        Ok(Self {
+            method: DidMethod::Wk,
+            public_key,
+            locator,
+        })
+    }
+
+    /// Returns the ID of the verification method of the DID's public key.
+    ///
+    /// This is the DID followed by the multibase key as a fragment, as `did:key` specifies, or
+    /// by `#keys-1` for a hosted `did:wk` document.
+    pub fn get_verification_method_id(&self) -> String {
+        match self.locator {
+            Some(_) => format!("{}#{}", self, WK_KEY_FRAGMENT),
+            None => format!("{}#{}", self, self.public_key.to_multibase()),
+        }
+    }
+
+    /// Returns the URL the DID document of a `did:wk` with a locator is hosted at.
+    pub fn get_document_url(&self) -> Option<String> {
+        self.locator.as_ref().map(|locator| {
+            format!(
+                "https://{}/{}",
+                locator.trim_end_matches('/'),
+                WELL_KNOWN_DID_PATH
+            )
+        })
+    }
+
+    /// Resolves the DID to its DID document.
+    ///
+    /// The document of a DID without a locator is derived from its public key. The document of a
+    /// `did:wk` with a locator is fetched from [`get_document_url`][Self::get_document_url] and
+    /// only returned if its proof was signed by the DID's key.
+    pub async fn resolve(&self) -> DidResult<DidDocument> {
+        let Some(url) = self.get_document_url() else {
+            return Ok(DidDocument::new(self));
+        };
+
+        let document: DidDocument = reqwest::get(&url).await?.error_for_status()?.json().await?;
+        document.verify(self)?;
+
+        Ok(document)
+    }
+
+    /// Verifies a signature made by the DID over `message`.
+    ///
+    /// ## Arguments
+    /// * `message` - The message that was signed
+    /// * `signature` - The signature, as returned by [`KeyPair::sign`][crate::KeyPair::sign]
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> DidResult<()> {
+        self.public_key.verify(message, signature)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Checks that a locator is a host, with an optional port and path.
+fn validate_locator(locator: &str) -> DidResult<()> {
+    let host = locator.split(['/', ':']).next().unwrap_or_default();
+    if host.is_empty() || locator.contains(['@', '?', '#']) || locator.contains(char::is_whitespace)
+    {
+        return Err(DidError::InvalidDid(format!(
+            "invalid locator: {}",
+            locator
+        )));
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Display for DidMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key => write!(f, "key"),
+            Self::Wk => write!(f, "wk"),
+        }
+    }
+}
+
+impl Display for Did {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            DID_SCHEME,
+            self.method,
+            self.public_key.to_multibase()
+        )?;
+        if let Some(locator) = &self.locator {
+            write!(f, "@{}", locator)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Did {
+    type Err = DidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(DID_SCHEME), Some(method), Some(id)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(DidError::InvalidDid(s.to_string()));
+        };
+
+        match method {
+            "key" => Ok(Self::key(PublicKey::from_multibase(id)?)),
+            "wk" => {
+                let (key, locator) = match id.split_once('@') {
+                    Some((key, locator)) => (key, Some(locator.to_string())),
+                    None => (id, None),
+                };
+                Self::wk(PublicKey::from_multibase(key)?, locator)
+            }
+            method => Err(DidError::UnsupportedMethod(method.to_string())),
+        }
+    }
+}
+
+impl Serialize for Did {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Did {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::KeyType;
+
+    use super::*;
+
+    #[test]
+    fn test_did_parses_known_dids() -> anyhow::Result<()> {
+        // Examples from the did:key specification
+        let ed25519 = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+        let did: Did = ed25519.parse()?;
+        assert_eq!(did.get_method(), &DidMethod::Key);
+        assert_eq!(did.get_public_key().get_key_type(), KeyType::Ed25519);
+        assert_eq!(did.to_string(), ed25519);
+        assert_eq!(
+            did.get_verification_method_id(),
+            format!(
+                "{}#z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK",
+                ed25519
+            )
+        );
+
+        let p256 = "did:key:zDnaerDaTF5BXEavCrfRZEk316dpbLsfPDZ3WJ5hRTPFU2169";
+        let did: Did = p256.parse()?;
+        assert_eq!(did.get_public_key().get_key_type(), KeyType::P256);
+        assert_eq!(did.to_string(), p256);
+
+        let wk = "did:wk:z6MkwFK7L2unwCxXNaws5wxbWKbzEiC84mCYno7RW5dZ7rzq@steve.monocore.dev:8443";
+        let did: Did = wk.parse()?;
+        assert_eq!(
+            did.get_locator().as_deref(),
+            Some("steve.monocore.dev:8443")
+        );
+        assert_eq!(did.to_string(), wk);
+        assert_eq!(
+            serde_json::from_str::<Did>(&serde_json::to_string(&did)?)?,
+            did
+        );
+
+        for invalid in [
+            "did:key",
+            "did:web:example.com",
+            "key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK",
+            "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2do",
+            "did:key:fed012e6fcce36701dc791488e0d0b1745cc1e33a4c1c9fcc41c63bd343dbbe0970e6",
+            "did:wk:z6MkwFK7L2unwCxXNaws5wxbWKbzEiC84mCYno7RW5dZ7rzq@",
+        ] {
+            assert!(invalid.parse::<Did>().is_err(), "{} parsed", invalid);
+        }
+
+        Ok(())
+    }
+}
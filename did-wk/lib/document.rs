@@ -0,0 +1,346 @@
+use chrono::{DateTime, Utc};
+use getset::Getters;
+use multibase::Base;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Did, DidError, DidResult, KeyPair, KeyType, PublicKey};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The JSON-LD context of DID documents.
+pub const DID_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+
+/// The JSON-LD context of `Multikey` verification methods.
+pub const MULTIKEY_CONTEXT: &str = "https://w3id.org/security/multikey/v1";
+
+/// The type of verification methods whose key is given as a multibase string.
+pub const MULTIKEY_TYPE: &str = "Multikey";
+
+/// The purpose of DID document proofs.
+pub const ASSERTION_METHOD_PURPOSE: &str = "assertionMethod";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The DID document of a `did:key` or `did:wk`.
+///
+/// A document lists the keys that act for its DID. Documents of DIDs without a locator are
+/// derived from their key with [`new`][Self::new]. Documents hosted for a `did:wk` carry a
+/// [`Proof`] made with the DID's key, which links the document to the DID.
+///
+/// ## Examples
+///
+/// ```
+/// use monoutils_did::{Did, DidDocument, KeyPair, KeyType};
+///
+/// let keypair = KeyPair::generate(KeyType::P256);
+/// let did = Did::wk(keypair.get_public_key(), Some("example.com".into()))?;
+///
+/// let mut document = DidDocument::new(&did);
+/// document.sign(&keypair)?;
+/// assert!(document.verify(&did).is_ok());
+/// # Ok::<(), monoutils_did::DidError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[serde(rename_all = "camelCase")]
+#[getset(get = "pub with_prefix")]
+pub struct DidDocument {
+    /// The JSON-LD contexts of the document.
+    #[serde(rename = "@context")]
+    context: Vec<String>,
+
+    /// The DID the document belongs to.
+    id: String,
+
+    /// The keys that act for the DID.
+    verification_method: Vec<VerificationMethod>,
+
+    /// The IDs of the verification methods the DID authenticates with.
+    #[serde(default)]
+    authentication: Vec<String>,
+
+    /// The IDs of the verification methods the DID makes assertions, e.g. signatures, with.
+    #[serde(default)]
+    assertion_method: Vec<String>,
+
+    /// The proof linking the document to the DID's key, for hosted documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<Proof>,
+}
+
+/// A key that acts for a DID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[serde(rename_all = "camelCase")]
+#[getset(get = "pub with_prefix")]
+pub struct VerificationMethod {
+    /// The ID of the verification method, the DID followed by a fragment.
+    id: String,
+
+    /// The type of the verification method.
+    #[serde(rename = "type")]
+    r#type: String,
+
+    /// The DID that controls the key.
+    controller: String,
+
+    /// The multicodec encoded public key as a multibase string.
+    public_key_multibase: String,
+}
+
+/// A signature over a DID document, made by one of its verification methods.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[serde(rename_all = "camelCase")]
+#[getset(get = "pub with_prefix")]
+pub struct Proof {
+    /// The type of the signature.
+    #[serde(rename = "type")]
+    r#type: String,
+
+    /// When the proof was made.
+    created: DateTime<Utc>,
+
+    /// The ID of the verification method the proof was made with.
+    verification_method: String,
+
+    /// What the proof is for, always `assertionMethod`.
+    proof_purpose: String,
+
+    /// A random value that makes every proof unique.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+
+    /// The base64 encoded signature over the canonical form of the document without its proof,
+    /// followed by the canonical form of the proof without its signature.
+    signature_value: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl DidDocument {
+    /// Creates the document of `did`, with its public key as the only verification method.
+    pub fn new(did: &Did) -> Self {
+        let id = did.to_string();
+        let method_id = did.get_verification_method_id();
+
+        Self {
+            context: vec![DID_CONTEXT.to_string(), MULTIKEY_CONTEXT.to_string()],
+            verification_method: vec![VerificationMethod {
+                id: method_id.clone(),
+                r#type: MULTIKEY_TYPE.to_string(),
+                controller: id.clone(),
+                public_key_multibase: did.get_public_key().to_multibase(),
+            }],
+            authentication: vec![method_id.clone()],
+            assertion_method: vec![method_id],
+            proof: None,
+            id,
+        }
+    }
+
+    /// Returns the public key of the verification method with the given ID.
+    pub fn get_public_key(&self, method_id: &str) -> DidResult<PublicKey> {
+        let method = self
+            .verification_method
+            .iter()
+            .find(|method| method.id == method_id)
+            .ok_or_else(|| {
+                DidError::InvalidDocument(format!("no verification method {}", method_id))
+            })?;
+
+        PublicKey::from_multibase(&method.public_key_multibase)
+    }
+
+    /// Signs the document with `keypair`, replacing its proof.
+    ///
+    /// The key pair must be the key of one of the document's verification methods.
+    pub fn sign(&mut self, keypair: &KeyPair) -> DidResult<()> {
+        let public_key = keypair.get_public_key().to_multibase();
+        let method = self
+            .verification_method
+            .iter()
+            .find(|method| method.public_key_multibase == public_key)
+            .ok_or_else(|| {
+                DidError::InvalidDocument(format!("no verification method for key {}", public_key))
+            })?;
+
+        let nonce = Base::Base58Btc.encode(rand::random::<[u8; 16]>());
+        let mut proof = Proof {
+            r#type: get_proof_type(keypair.get_key_type()).to_string(),
+            created: Utc::now(),
+            verification_method: method.id.clone(),
+            proof_purpose: ASSERTION_METHOD_PURPOSE.to_string(),
+            nonce: Some(nonce),
+            signature_value: String::new(),
+        };
+
+        let signature = keypair.sign(&self.get_signing_input(&proof)?);
+        proof.signature_value = Base::Base64.encode(signature);
+        self.proof = Some(proof);
+
+        Ok(())
+    }
+
+    /// Verifies that the document belongs to `did` and carries a proof made with the DID's key.
+    pub fn verify(&self, did: &Did) -> DidResult<()> {
+        if self.id != did.to_string() {
+            return Err(DidError::InvalidDocument(format!(
+                "document of {} is not the document of {}",
+                self.id, did
+            )));
+        }
+
+        let proof = self
+            .proof
+            .as_ref()
+            .ok_or_else(|| DidError::InvalidDocument("document has no proof".to_string()))?;
+        if proof.proof_purpose != ASSERTION_METHOD_PURPOSE {
+            return Err(DidError::InvalidDocument(format!(
+                "unexpected proof purpose: {}",
+                proof.proof_purpose
+            )));
+        }
+
+        if proof.created > Utc::now() {
+            return Err(DidError::InvalidDocument(format!(
+                "proof created in the future: {}",
+                proof.created
+            )));
+        }
+
+        // The proof must be made with the key the DID is made of
+        let public_key = self.get_public_key(&proof.verification_method)?;
+        if &public_key != did.get_public_key() {
+            return Err(DidError::InvalidDocument(format!(
+                "proof made with {}, not the key of {}",
+                proof.verification_method, did
+            )));
+        }
+
+        let signature = Base::Base64
+            .decode(&proof.signature_value)
+            .map_err(|_| DidError::InvalidSignature)?;
+        public_key.verify(&self.get_signing_input(proof)?, &signature)
+    }
+
+    /// Returns the bytes a proof signs: the canonical JSON of the document without its proof,
+    /// followed by the canonical JSON of the proof without its signature.
+    fn get_signing_input(&self, proof: &Proof) -> DidResult<Vec<u8>> {
+        let document = Self {
+            proof: None,
+            ..self.clone()
+        };
+        let proof = Proof {
+            signature_value: String::new(),
+            ..proof.clone()
+        };
+
+        let mut input = canonicalize(serde_json::to_value(document)?)?;
+        input.extend(canonicalize(serde_json::to_value(proof)?)?);
+        Ok(input)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the type of proofs made with keys of the given type.
+fn get_proof_type(key_type: KeyType) -> &'static str {
+    match key_type {
+        KeyType::Ed25519 => "Ed25519Signature2020",
+        KeyType::P256 => "EcdsaSecp256r1Signature2019",
+    }
+}
+
+/// Serializes a JSON value compactly with the keys of every object sorted, so the same document
+/// always gives the same bytes.
+fn canonicalize(value: Value) -> DidResult<Vec<u8>> {
+    fn sort(value: Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries = map.into_iter().collect::<Vec<_>>();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Value::Object(entries.into_iter().map(|(k, v)| (k, sort(v))).collect())
+            }
+            Value::Array(values) => Value::Array(values.into_iter().map(sort).collect()),
+            value => value,
+        }
+    }
+
+    Ok(serde_json::to_vec(&sort(value))?)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_did_key_document() -> anyhow::Result<()> {
+        let did: Did = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK".parse()?;
+        let document = DidDocument::new(&did);
+
+        let json = serde_json::to_value(&document)?;
+        assert_eq!(json["id"], did.to_string());
+        assert_eq!(
+            json["verificationMethod"][0]["publicKeyMultibase"],
+            "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"
+        );
+        assert_eq!(json["verificationMethod"][0]["type"], MULTIKEY_TYPE);
+        assert_eq!(json["assertionMethod"][0], did.get_verification_method_id());
+        assert!(json.get("proof").is_none());
+
+        assert_eq!(
+            &document.get_public_key(&did.get_verification_method_id())?,
+            did.get_public_key()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_did_wk_document_proof() -> anyhow::Result<()> {
+        for key_type in [KeyType::Ed25519, KeyType::P256] {
+            let keypair = KeyPair::generate(key_type);
+            let did = Did::wk(keypair.get_public_key(), Some("example.com/pub".into()))?;
+
+            let mut document = DidDocument::new(&did);
+            assert!(document.verify(&did).is_err());
+            document.sign(&keypair)?;
+            assert_eq!(
+                document.get_proof().as_ref().unwrap().get_type(),
+                get_proof_type(key_type)
+            );
+
+            // The proof survives a round trip through JSON
+            let document: DidDocument = serde_json::from_str(&serde_json::to_string(&document)?)?;
+            document.verify(&did)?;
+
+            // A document for another DID, or a tampered one, doesn't verify
+            let other = Did::wk(KeyPair::generate(key_type).get_public_key(), None)?;
+            assert!(document.verify(&other).is_err());
+
+            let mut tampered = document.clone();
+            tampered.authentication.clear();
+            assert!(matches!(
+                tampered.verify(&did),
+                Err(DidError::InvalidSignature)
+            ));
+
+            // Only keys of the document can sign it
+            let mut document = document;
+            assert!(document.sign(&KeyPair::generate(key_type)).is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use thiserror::Error;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The result of a DID operation.
+pub type DidResult<T> = Result<T, DidError>;
+
+/// An error that occurred during a DID operation.
+#[derive(Debug, Error)]
+pub enum DidError {
+    /// The DID is not a valid `did:key` or `did:wk` DID.
+    #[error("Invalid DID: {0}")]
+    InvalidDid(String),
+
+    /// The DID method is not supported.
+    #[error("Unsupported DID method: {0}")]
+    UnsupportedMethod(String),
+
+    /// The multicodec of a key is not one of the supported key types.
+    #[error("Unsupported key codec: 0x{0:x}")]
+    UnsupportedKeyCodec(u64),
+
+    /// The key type is not supported.
+    #[error("Unsupported key type: {0}")]
+    UnsupportedKeyType(String),
+
+    /// The bytes of a key are not a valid key of its type.
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    /// A multibase string could not be decoded.
+    #[error("Multibase error: {0}")]
+    Multibase(#[from] multibase::Error),
+
+    /// A signature is malformed or does not verify.
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    /// A DID document is not valid for its DID.
+    #[error("Invalid DID document: {0}")]
+    InvalidDocument(String),
+
+    /// A DID document could not be fetched.
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// A DID document could not be serialized or deserialized.
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
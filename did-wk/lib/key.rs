@@ -0,0 +1,453 @@
+use std::{
+    fmt::{self, Debug, Display},
+    str::FromStr,
+};
+
+use ed25519_dalek::{Signer as _, Verifier as _};
+use multibase::Base;
+use zeroize::Zeroizing;
+
+use crate::{DidError, DidResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The multicodec code of Ed25519 public keys.
+pub const ED25519_PUB_CODEC: u64 = 0xed;
+
+/// The multicodec code of P-256 public keys.
+pub const P256_PUB_CODEC: u64 = 0x1200;
+
+/// The multicodec code of Ed25519 private keys.
+pub const ED25519_PRIV_CODEC: u64 = 0x1300;
+
+/// The multicodec code of P-256 private keys.
+pub const P256_PRIV_CODEC: u64 = 0x1306;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The type of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    /// An Ed25519 key, signing with EdDSA.
+    Ed25519,
+
+    /// A NIST P-256 key, signing with ECDSA over SHA-256.
+    P256,
+}
+
+/// The public key of a DID, which signatures made by the DID are verified with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    /// An Ed25519 public key.
+    Ed25519(ed25519_dalek::VerifyingKey),
+
+    /// A P-256 public key.
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+/// A key pair that signs on behalf of a DID.
+///
+/// The private key is zeroed when the key pair is dropped, and is never shown by [`Debug`].
+///
+/// ## Examples
+///
+/// ```
+/// use monoutils_did::{KeyPair, KeyType};
+///
+/// let keypair = KeyPair::generate(KeyType::Ed25519);
+/// let signature = keypair.sign(b"hello");
+/// assert!(keypair.get_public_key().verify(b"hello", &signature).is_ok());
+/// assert!(keypair.get_public_key().verify(b"hellp", &signature).is_err());
+/// ```
+#[derive(Clone)]
+pub enum KeyPair {
+    /// An Ed25519 key pair.
+    Ed25519(ed25519_dalek::SigningKey),
+
+    /// A P-256 key pair.
+    P256(p256::ecdsa::SigningKey),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl KeyType {
+    /// Returns the multicodec code of public keys of this type.
+    pub fn get_public_codec(&self) -> u64 {
+        match self {
+            Self::Ed25519 => ED25519_PUB_CODEC,
+            Self::P256 => P256_PUB_CODEC,
+        }
+    }
+
+    /// Returns the multicodec code of private keys of this type.
+    pub fn get_private_codec(&self) -> u64 {
+        match self {
+            Self::Ed25519 => ED25519_PRIV_CODEC,
+            Self::P256 => P256_PRIV_CODEC,
+        }
+    }
+}
+
+impl PublicKey {
+    /// Creates a public key from its raw bytes: 32 bytes for Ed25519, a SEC1 encoded point for
+    /// P-256.
+    pub fn from_bytes(key_type: KeyType, bytes: &[u8]) -> DidResult<Self> {
+        match key_type {
+            KeyType::Ed25519 => {
+                let bytes = bytes.try_into().map_err(|_| {
+                    DidError::InvalidKey(format!("ed25519 keys are 32 bytes, not {}", bytes.len()))
+                })?;
+                ed25519_dalek::VerifyingKey::from_bytes(bytes)
+                    .map(Self::Ed25519)
+                    .map_err(|e| DidError::InvalidKey(e.to_string()))
+            }
+            KeyType::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::P256)
+                .map_err(|e| DidError::InvalidKey(e.to_string())),
+        }
+    }
+
+    /// Returns the type of the key.
+    pub fn get_key_type(&self) -> KeyType {
+        match self {
+            Self::Ed25519(_) => KeyType::Ed25519,
+            Self::P256(_) => KeyType::P256,
+        }
+    }
+
+    /// Returns the raw bytes of the key. P-256 keys are compressed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            Self::P256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the raw bytes of the key prefixed with the multicodec code of its type.
+    pub fn to_multicodec(&self) -> Vec<u8> {
+        encode_multicodec(self.get_key_type().get_public_codec(), &self.to_bytes())
+    }
+
+    /// Creates a public key from its raw bytes prefixed with the multicodec code of its type.
+    pub fn from_multicodec(bytes: &[u8]) -> DidResult<Self> {
+        let (codec, key) = decode_multicodec(bytes)?;
+        let key_type = match codec {
+            ED25519_PUB_CODEC => KeyType::Ed25519,
+            P256_PUB_CODEC => KeyType::P256,
+            codec => return Err(DidError::UnsupportedKeyCodec(codec)),
+        };
+
+        Self::from_bytes(key_type, key)
+    }
+
+    /// Returns the multicodec encoded key as a base58btc multibase string, as used in DIDs.
+    pub fn to_multibase(&self) -> String {
+        multibase::encode(Base::Base58Btc, self.to_multicodec())
+    }
+
+    /// Creates a public key from a base58btc multibase string of its multicodec encoding.
+    pub fn from_multibase(s: &str) -> DidResult<Self> {
+        let bytes = decode_base58btc(s)?;
+        Self::from_multicodec(&bytes)
+    }
+
+    /// Verifies a signature made over `message` with the private key of this key.
+    ///
+    /// ## Arguments
+    /// * `message` - The message that was signed
+    /// * `signature` - The signature, as returned by [`KeyPair::sign`]
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> DidResult<()> {
+        match self {
+            Self::Ed25519(key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|_| DidError::InvalidSignature)?;
+                key.verify(message, &signature)
+                    .map_err(|_| DidError::InvalidSignature)
+            }
+            Self::P256(key) => {
+                let signature = p256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|_| DidError::InvalidSignature)?;
+                key.verify(message, &signature)
+                    .map_err(|_| DidError::InvalidSignature)
+            }
+        }
+    }
+}
+
+impl KeyPair {
+    /// Generates a new random key pair of the given type.
+    pub fn generate(key_type: KeyType) -> Self {
+        loop {
+            // Not every 32 bytes are a valid P-256 scalar, though almost all are
+            let bytes = Zeroizing::new(rand::random::<[u8; 32]>());
+            if let Ok(keypair) = Self::from_bytes(key_type, bytes.as_ref()) {
+                return keypair;
+            }
+        }
+    }
+
+    /// Creates a key pair from the raw bytes of its private key.
+    pub fn from_bytes(key_type: KeyType, bytes: &[u8]) -> DidResult<Self> {
+        match key_type {
+            KeyType::Ed25519 => {
+                let bytes = bytes.try_into().map_err(|_| {
+                    DidError::InvalidKey(format!("ed25519 keys are 32 bytes, not {}", bytes.len()))
+                })?;
+                Ok(Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(bytes)))
+            }
+            KeyType::P256 => p256::ecdsa::SigningKey::from_slice(bytes)
+                .map(Self::P256)
+                .map_err(|e| DidError::InvalidKey(e.to_string())),
+        }
+    }
+
+    /// Returns the type of the key pair.
+    pub fn get_key_type(&self) -> KeyType {
+        match self {
+            Self::Ed25519(_) => KeyType::Ed25519,
+            Self::P256(_) => KeyType::P256,
+        }
+    }
+
+    /// Returns the public key of the key pair.
+    pub fn get_public_key(&self) -> PublicKey {
+        match self {
+            Self::Ed25519(key) => PublicKey::Ed25519(key.verifying_key()),
+            Self::P256(key) => PublicKey::P256(*key.verifying_key()),
+        }
+    }
+
+    /// Returns the raw bytes of the private key.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(match self {
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            Self::P256(key) => key.to_bytes().to_vec(),
+        })
+    }
+
+    /// Returns the multicodec encoded private key as a base58btc multibase string, the format
+    /// key pairs are stored in.
+    pub fn to_multibase(&self) -> Zeroizing<String> {
+        let bytes = Zeroizing::new(encode_multicodec(
+            self.get_key_type().get_private_codec(),
+            &self.to_bytes(),
+        ));
+        Zeroizing::new(multibase::encode(Base::Base58Btc, bytes.as_slice()))
+    }
+
+    /// Creates a key pair from a base58btc multibase string of its multicodec encoded private
+    /// key.
+    pub fn from_multibase(s: &str) -> DidResult<Self> {
+        let bytes = Zeroizing::new(decode_base58btc(s.trim())?);
+        let (codec, key) = decode_multicodec(&bytes)?;
+        let key_type = match codec {
+            ED25519_PRIV_CODEC => KeyType::Ed25519,
+            P256_PRIV_CODEC => KeyType::P256,
+            codec => return Err(DidError::UnsupportedKeyCodec(codec)),
+        };
+
+        Self::from_bytes(key_type, key)
+    }
+
+    /// Signs `message`.
+    ///
+    /// Ed25519 signatures are 64 bytes of EdDSA. P-256 signatures are the 64 bytes `r || s` of
+    /// ECDSA over the SHA-256 digest of the message.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Ed25519(key) => key.sign(message).to_bytes().to_vec(),
+            Self::P256(key) => {
+                let signature: p256::ecdsa::Signature = key.sign(message);
+                signature.to_bytes().to_vec()
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Prefixes `bytes` with `codec` as an unsigned varint.
+fn encode_multicodec(mut codec: u64, bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 2);
+    while codec >= 0x80 {
+        encoded.push((codec as u8) | 0x80);
+        codec >>= 7;
+    }
+    encoded.push(codec as u8);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// Decodes a multibase string, which keys are only ever encoded in as base58btc.
+fn decode_base58btc(s: &str) -> DidResult<Vec<u8>> {
+    match multibase::decode(s)? {
+        (Base::Base58Btc, bytes) => Ok(bytes),
+        (base, _) => Err(DidError::InvalidKey(format!(
+            "keys are base58btc multibase encoded, not {:?}",
+            base
+        ))),
+    }
+}
+
+/// Splits the unsigned varint codec prefix off `bytes`.
+fn decode_multicodec(bytes: &[u8]) -> DidResult<(u64, &[u8])> {
+    let mut codec = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        codec |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((codec, &bytes[i + 1..]));
+        }
+    }
+
+    Err(DidError::InvalidKey(
+        "malformed multicodec prefix".to_string(),
+    ))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ed25519 => write!(f, "ed25519"),
+            Self::P256 => write!(f, "p256"),
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = DidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ed25519" => Ok(Self::Ed25519),
+            "p256" | "p-256" => Ok(Self::P256),
+            _ => Err(DidError::UnsupportedKeyType(s.to_string())),
+        }
+    }
+}
+
+impl Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_multibase())
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = DidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_multibase(s)
+    }
+}
+
+impl Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("KeyPair")
+            .field(&self.get_public_key().to_multibase())
+            .finish()
+    }
+}
+
+impl PartialEq for KeyPair {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_key_type() == other.get_key_type() && self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for KeyPair {}
+
+impl FromStr for KeyPair {
+    type Err = DidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_multibase(s)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keypair_sign_and_verify() {
+        for key_type in [KeyType::Ed25519, KeyType::P256] {
+            let keypair = KeyPair::generate(key_type);
+            let public_key = keypair.get_public_key();
+            let signature = keypair.sign(b"message");
+
+            assert_eq!(signature.len(), 64);
+            assert!(public_key.verify(b"message", &signature).is_ok());
+            assert!(public_key.verify(b"massage", &signature).is_err());
+            assert!(public_key.verify(b"message", &signature[1..]).is_err());
+
+            let other = KeyPair::generate(key_type).get_public_key();
+            assert!(other.verify(b"message", &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn test_keys_round_trip_through_multibase() -> anyhow::Result<()> {
+        for key_type in [KeyType::Ed25519, KeyType::P256] {
+            let keypair = KeyPair::generate(key_type);
+            let parsed: KeyPair = keypair.to_multibase().parse()?;
+            assert_eq!(parsed, keypair);
+            assert!(!format!("{:?}", keypair).contains(keypair.to_multibase().as_str()));
+
+            let public_key = keypair.get_public_key();
+            assert_eq!(public_key.to_string().parse::<PublicKey>()?, public_key);
+        }
+
+        // The multicodec prefixes give the well-known leading characters
+        let ed25519 = KeyPair::generate(KeyType::Ed25519).get_public_key();
+        assert!(ed25519.to_multibase().starts_with("z6Mk"));
+        let p256 = KeyPair::generate(KeyType::P256).get_public_key();
+        assert!(p256.to_multibase().starts_with("zDn"));
+
+        // Private keys are not public keys
+        let private = KeyPair::generate(KeyType::Ed25519).to_multibase();
+        assert!(matches!(
+            private.parse::<PublicKey>(),
+            Err(DidError::UnsupportedKeyCodec(ED25519_PRIV_CODEC))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys_only_decode_from_base58btc() {
+        let keypair = KeyPair::generate(KeyType::Ed25519);
+        let public_key = keypair.get_public_key();
+
+        // The same multicodec bytes in base16 and base64 are rejected
+        for base in [Base::Base16Lower, Base::Base64] {
+            let encoded = multibase::encode(base, public_key.to_multicodec());
+            assert!(matches!(
+                PublicKey::from_multibase(&encoded),
+                Err(DidError::InvalidKey(_))
+            ));
+        }
+
+        let private = multibase::decode(keypair.to_multibase().as_str())
+            .unwrap()
+            .1;
+        assert!(matches!(
+            KeyPair::from_multibase(&multibase::encode(Base::Base16Lower, private)),
+            Err(DidError::InvalidKey(_))
+        ));
+    }
+}
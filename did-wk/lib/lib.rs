@@ -1,8 +1,37 @@
 //! `did-wk` is a library for working with DID-Web-Key and DID-Key decentralized identifiers (DIDs) methods.
+//!
+//! It provides [`Did`], a `did:key` or `did:wk` identifier made of an Ed25519 or P-256 public key
+//! in its multicodec, multibase encoding, the [`KeyPair`]s that sign on behalf of DIDs, and the
+//! [`DidDocument`]s DIDs resolve to. A `did:wk` may carry a locator pointing to a DID document
+//! hosted on the web, which is only trusted if it is signed by the DID's own key.
+//!
+//! ## Examples
+//!
+//! ```
+//! use monoutils_did::{Did, KeyPair, KeyType};
+//!
+//! let keypair = KeyPair::generate(KeyType::Ed25519);
+//! let did = Did::key(keypair.get_public_key());
+//!
+//! let signature = keypair.sign(b"hello");
+//! let did: Did = did.to_string().parse()?;
+//! assert!(did.verify(b"hello", &signature).is_ok());
+//! # Ok::<(), monoutils_did::DidError>(())
+//! ```
 
 #![warn(missing_docs)]
 #![allow(clippy::module_inception)]
 
+mod did;
+mod document;
+mod error;
+mod key;
+
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
+
+pub use did::*;
+pub use document::*;
+pub use error::*;
+pub use key::*;
@@ -38,6 +38,14 @@ pub enum StoreError {
     #[error("Unexpected block codec: expected: {0:?} got: {1:?}")]
     UnexpectedBlockCodec(Codec, Codec),
 
+    /// The bytes of a block do not hash to the CID they were stored under.
+    #[error("Block does not match its CID: {0}")]
+    BlockCidMismatch(Cid),
+
+    /// A block exchange peer returned an error.
+    #[error("Block exchange error: {0}")]
+    Exchange(String),
+
     /// Custom error.
     #[error("Custom error: {0}")]
     Custom(#[from] AnyError),
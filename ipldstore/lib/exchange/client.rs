@@ -0,0 +1,505 @@
+use std::{collections::HashSet, pin::Pin, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use getset::Getters;
+use ipld_core::cid::Cid;
+use monoutils::SeekableReader;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::AsyncRead;
+
+use crate::{
+    utils, Chunker, Codec, FastCDCChunker, FlatLayout, IpldReferences, IpldStore,
+    IpldStoreSeekable, Layout, LayoutSeekable, RawStore, StoreError, StoreResult,
+    DEFAULT_MAX_NODE_BLOCK_SIZE,
+};
+
+use super::{DAG_CBOR_CONTENT_TYPE, MAX_WANT_LIST_LEN};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A store on another machine, reached over the block exchange protocol served by
+/// [`exchange_router`][super::exchange_router].
+///
+/// Every block fetched from the remote store is checked against its CID before it is returned,
+/// so a faulty or malicious server cannot hand out corrupted data. Data is chunked and laid out
+/// locally before its blocks are sent, just like with a local store.
+///
+/// A remote store is slow compared to a local one. Layer it under a local store with a
+/// [`DualStore`][crate::DualStore] to read from the local store first.
+///
+/// ## Example
+/// ```no_run
+/// use ipldstore::{Choice, DualStore, DualStoreConfig, IpldStore, MemoryStore, RemoteStore};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let remote = RemoteStore::new("http://blocks.local:3050");
+/// let store = DualStore::new(
+///     MemoryStore::default(),
+///     remote,
+///     DualStoreConfig {
+///         read_from: Choice::A,
+///         write_to: Choice::A,
+///     },
+/// );
+///
+/// let cid = store.put_bytes(b"Hello, World!".as_slice()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RemoteStoreImpl<C = FastCDCChunker, L = FlatLayout>
+where
+    C: Chunker + Default,
+    L: Layout + Default,
+{
+    /// The HTTP client used to talk to the remote store.
+    #[getset(skip)]
+    client: reqwest::Client,
+
+    /// The base URL of the remote store.
+    url: String,
+
+    /// The chunking algorithm used to split data into chunks.
+    chunker: Arc<C>,
+
+    /// The layout strategy used to store chunked data.
+    layout: Arc<L>,
+}
+
+/// A store on another machine, reached over the block exchange protocol.
+///
+/// This version of the store uses a [`FastCDCChunker`] for chunking and [`FlatLayout`] for layout.
+pub type RemoteStore = RemoteStoreImpl<FastCDCChunker, FlatLayout>;
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<C, L> RemoteStoreImpl<C, L>
+where
+    C: Chunker + Default,
+    L: Layout + Default,
+{
+    /// Creates a new `RemoteStore` for the server at the given base URL with default chunker and
+    /// layout.
+    ///
+    /// ## Example
+    /// ```
+    /// use ipldstore::RemoteStore;
+    ///
+    /// let store = RemoteStore::new("http://127.0.0.1:3050/");
+    /// assert_eq!(store.get_url(), "http://127.0.0.1:3050");
+    /// ```
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+            chunker: Arc::new(C::default()),
+            layout: Arc::new(L::default()),
+        }
+    }
+
+    /// Fetches the blocks with the given CIDs in as few requests as possible.
+    ///
+    /// Blocks the remote store does not have are left out of the result. Every returned block has
+    /// been checked against its CID.
+    pub async fn get_blocks(&self, cids: &[Cid]) -> StoreResult<Vec<(Cid, Bytes)>> {
+        let mut blocks = Vec::with_capacity(cids.len());
+        for want_list in cids.chunks(MAX_WANT_LIST_LEN) {
+            let response = self
+                .client
+                .post(format!("{}/want", self.url))
+                .header(reqwest::header::CONTENT_TYPE, DAG_CBOR_CONTENT_TYPE)
+                .body(super::encode_want_list(want_list)?)
+                .send()
+                .await
+                .map_err(StoreError::custom)?;
+
+            let body = check_response(response)
+                .await?
+                .bytes()
+                .await
+                .map_err(StoreError::custom)?;
+
+            let wanted = want_list.iter().collect::<HashSet<_>>();
+            for (cid, bytes) in super::decode_blocks(&body)? {
+                if !wanted.contains(&cid) {
+                    return Err(StoreError::Exchange(format!("unwanted block: {}", cid)));
+                }
+
+                verify_block(&cid, &bytes)?;
+                blocks.push((cid, bytes));
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Returns true if the remote store has the block with the given CID.
+    ///
+    /// Unlike [`IpldStore::has`], failing to reach the remote store is an error rather than an
+    /// answer that the block is missing.
+    pub async fn has_block(&self, cid: &Cid) -> StoreResult<bool> {
+        let response = self
+            .client
+            .head(self.block_url(cid))
+            .send()
+            .await
+            .map_err(StoreError::custom)?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        check_response(response).await?;
+        Ok(true)
+    }
+
+    /// Returns the URL of the block with the given CID.
+    fn block_url(&self, cid: &Cid) -> String {
+        format!("{}/blocks/{}", self.url, cid)
+    }
+
+    /// Fetches the encoded bytes of a block and checks them against its CID.
+    async fn fetch_block(&self, cid: &Cid) -> StoreResult<Bytes> {
+        let response = self
+            .client
+            .get(self.block_url(cid))
+            .send()
+            .await
+            .map_err(StoreError::custom)?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(StoreError::BlockNotFound(*cid));
+        }
+
+        let bytes = check_response(response)
+            .await?
+            .bytes()
+            .await
+            .map_err(StoreError::custom)?;
+
+        verify_block(cid, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Sends the encoded bytes of a block to the remote store.
+    async fn send_block(&self, bytes: Bytes, codec: Codec) -> StoreResult<Cid> {
+        let cid = utils::generate_cid(codec, &bytes);
+        let response = self
+            .client
+            .put(self.block_url(&cid))
+            .body(bytes)
+            .send()
+            .await
+            .map_err(StoreError::custom)?;
+
+        check_response(response).await?;
+        Ok(cid)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Checks that the bytes of a block hash to its CID.
+fn verify_block(cid: &Cid, bytes: &[u8]) -> StoreResult<()> {
+    let codec: Codec = cid.codec().try_into()?;
+    if utils::generate_cid(codec, bytes) != *cid {
+        return Err(StoreError::BlockCidMismatch(*cid));
+    }
+
+    Ok(())
+}
+
+/// Turns an unsuccessful response into an error.
+async fn check_response(response: reqwest::Response) -> StoreResult<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let message = response.text().await.unwrap_or_default();
+    Err(StoreError::Exchange(format!("{}: {}", status, message)))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl<C, L> IpldStore for RemoteStoreImpl<C, L>
+where
+    C: Chunker + Default + Clone + Send + Sync + 'static,
+    L: Layout + Default + Clone + Send + Sync + 'static,
+{
+    async fn put_node<T>(&self, node: &T) -> StoreResult<Cid>
+    where
+        T: Serialize + IpldReferences + Sync,
+    {
+        let bytes = Bytes::from(serde_ipld_dagcbor::to_vec(&node).map_err(StoreError::custom)?);
+
+        // Check if the data exceeds the node maximum block size.
+        if let Some(max_size) = self.get_max_node_block_size().await? {
+            if bytes.len() as u64 > max_size {
+                return Err(StoreError::NodeBlockTooLarge(bytes.len() as u64, max_size));
+            }
+        }
+
+        self.send_block(bytes, Codec::DagCbor).await
+    }
+
+    async fn put_bytes(&self, reader: impl AsyncRead + Send + Sync) -> StoreResult<Cid> {
+        let chunk_stream = self.chunker.chunk(reader).await?;
+        let mut cid_stream = self.layout.organize(chunk_stream, self.clone()).await?;
+
+        // Take the last `Cid` from the stream.
+        let mut cid = cid_stream.next().await.unwrap()?;
+        while let Some(result) = cid_stream.next().await {
+            cid = result?;
+        }
+
+        Ok(cid)
+    }
+
+    async fn get_node<D>(&self, cid: &Cid) -> StoreResult<D>
+    where
+        D: DeserializeOwned + Send,
+    {
+        match cid.codec().try_into()? {
+            Codec::DagCbor => {
+                let bytes = self.fetch_block(cid).await?;
+                serde_ipld_dagcbor::from_slice(&bytes).map_err(StoreError::custom)
+            }
+            codec => Err(StoreError::UnexpectedBlockCodec(Codec::DagCbor, codec)),
+        }
+    }
+
+    async fn get_bytes(&self, cid: &Cid) -> StoreResult<Pin<Box<dyn AsyncRead + Send>>> {
+        self.layout.retrieve(cid, self.clone()).await
+    }
+
+    async fn get_bytes_size(&self, cid: &Cid) -> StoreResult<u64> {
+        self.layout.get_size(cid, self.clone()).await
+    }
+
+    async fn has(&self, cid: &Cid) -> bool {
+        match self.has_block(cid).await {
+            Ok(has) => has,
+            Err(e) => {
+                tracing::warn!("failed to reach remote store {}: {}", self.url, e);
+                false
+            }
+        }
+    }
+
+    async fn get_supported_codecs(&self) -> HashSet<Codec> {
+        let mut codecs = HashSet::new();
+        codecs.insert(Codec::DagCbor);
+        codecs.insert(Codec::Raw);
+        codecs
+    }
+
+    async fn get_max_node_block_size(&self) -> StoreResult<Option<u64>> {
+        Ok(Some(DEFAULT_MAX_NODE_BLOCK_SIZE))
+    }
+
+    async fn get_block_count(&self) -> StoreResult<u64> {
+        let response = self
+            .client
+            .get(format!("{}/blocks", self.url))
+            .send()
+            .await
+            .map_err(StoreError::custom)?;
+
+        let count = check_response(response)
+            .await?
+            .text()
+            .await
+            .map_err(StoreError::custom)?;
+
+        count
+            .trim()
+            .parse()
+            .map_err(|_| StoreError::Exchange(format!("invalid block count: {}", count)))
+    }
+}
+
+#[async_trait]
+impl<C, L> RawStore for RemoteStoreImpl<C, L>
+where
+    C: Chunker + Default + Clone + Send + Sync,
+    L: Layout + Default + Clone + Send + Sync,
+{
+    async fn put_raw_block(&self, bytes: impl Into<Bytes> + Send) -> StoreResult<Cid> {
+        let bytes = bytes.into();
+        if let Some(max_size) = self.get_max_raw_block_size().await? {
+            if bytes.len() as u64 > max_size {
+                return Err(StoreError::RawBlockTooLarge(bytes.len() as u64, max_size));
+            }
+        }
+
+        self.send_block(bytes, Codec::Raw).await
+    }
+
+    async fn get_raw_block(&self, cid: &Cid) -> StoreResult<Bytes> {
+        match cid.codec().try_into()? {
+            Codec::Raw => self.fetch_block(cid).await,
+            codec => Err(StoreError::UnexpectedBlockCodec(Codec::Raw, codec)),
+        }
+    }
+
+    async fn get_max_raw_block_size(&self) -> StoreResult<Option<u64>> {
+        Ok(self
+            .chunker
+            .chunk_max_size()
+            .await?
+            .max(Some(DEFAULT_MAX_NODE_BLOCK_SIZE)))
+    }
+}
+
+#[async_trait]
+impl<C, L> IpldStoreSeekable for RemoteStoreImpl<C, L>
+where
+    C: Chunker + Default + Clone + Send + Sync + 'static,
+    L: LayoutSeekable + Default + Clone + Send + Sync + 'static,
+{
+    async fn get_seekable_bytes(
+        &self,
+        cid: &Cid,
+    ) -> StoreResult<Pin<Box<dyn SeekableReader + Send + 'static>>> {
+        self.layout.retrieve_seekable(cid, self.clone()).await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Router};
+    use ipld_core::ipld::Ipld;
+    use tokio::io::AsyncReadExt;
+
+    use crate::{exchange_router, Choice, DualStore, DualStoreConfig, MemoryStore};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remote_store_round_trip() -> anyhow::Result<()> {
+        let served = MemoryStore::default();
+        let store = RemoteStore::new(helper::serve(exchange_router(served.clone())).await?);
+
+        // Bytes are chunked locally and their blocks land in the served store
+        let data = vec![7u8; 3 * 1024 * 1024];
+        let cid = store.put_bytes(data.as_slice()).await?;
+        assert!(served.has(&cid).await);
+        assert_eq!(
+            store.get_block_count().await?,
+            served.get_block_count().await?
+        );
+
+        let mut retrieved = Vec::new();
+        store
+            .get_bytes(&cid)
+            .await?
+            .read_to_end(&mut retrieved)
+            .await?;
+        assert_eq!(retrieved, data);
+        assert_eq!(store.get_bytes_size(&cid).await?, data.len() as u64);
+
+        // Nodes are stored under the same CID a local store would give them
+        let node = Ipld::List(vec![Ipld::Link(cid)]);
+        let node_cid = store.put_node(&node).await?;
+        assert_eq!(node_cid, served.put_node(&node).await?);
+        assert_eq!(store.get_node::<Ipld>(&node_cid).await?, node);
+
+        // Missing blocks are reported as such
+        let missing = utils::generate_cid(Codec::Raw, b"missing");
+        assert!(!store.has(&missing).await);
+        assert!(matches!(
+            store.get_raw_block(&missing).await,
+            Err(StoreError::BlockNotFound(_))
+        ));
+
+        // Want-lists only return the blocks the server has
+        let raw_cid = store.put_raw_block(b"raw".to_vec()).await?;
+        let blocks = store.get_blocks(&[node_cid, missing, raw_cid]).await?;
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.contains(&(raw_cid, Bytes::from_static(b"raw"))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_store_rejects_tampered_blocks() -> anyhow::Result<()> {
+        // A server that answers every block request with the same bytes
+        let router = Router::new().route("/blocks/{cid}", get(|| async { "tampered" }));
+        let store = RemoteStore::new(helper::serve(router).await?);
+
+        let cid = utils::generate_cid(Codec::Raw, b"original");
+        assert!(matches!(
+            store.get_raw_block(&cid).await,
+            Err(StoreError::BlockCidMismatch(_))
+        ));
+
+        // The server refuses blocks that do not match their CID too
+        let served = MemoryStore::default();
+        let url = helper::serve(exchange_router(served.clone())).await?;
+        let response = reqwest::Client::new()
+            .put(format!("{}/blocks/{}", url, cid))
+            .body("tampered")
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert!(!served.has(&cid).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_store_under_dual_store() -> anyhow::Result<()> {
+        let served = MemoryStore::default();
+        let cid = served.put_raw_block(b"remote".to_vec()).await?;
+
+        let cache = MemoryStore::default();
+        let store = DualStore::new(
+            cache.clone(),
+            RemoteStore::new(helper::serve(exchange_router(served.clone())).await?),
+            DualStoreConfig {
+                read_from: Choice::A,
+                write_to: Choice::A,
+            },
+        );
+
+        // Reads fall back to the remote store, writes stay local
+        assert_eq!(store.get_raw_block(&cid).await?.as_ref(), b"remote");
+        let local = store.put_raw_block(b"local".to_vec()).await?;
+        assert!(cache.has(&local).await);
+        assert!(!served.has(&local).await);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod helper {
+    use axum::Router;
+    use tokio::net::TcpListener;
+
+    /// Serves the router on a free local port and returns its base URL.
+    pub(super) async fn serve(router: Router) -> anyhow::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        Ok(format!("http://{}", addr))
+    }
+}
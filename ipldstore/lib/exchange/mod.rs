@@ -0,0 +1,77 @@
+//! Block exchange between stores over HTTP.
+//!
+//! Any [`IpldStore`][crate::IpldStore] can be served to other machines with [`exchange_router`],
+//! and reached from them with a [`RemoteStore`]. The protocol is plain HTTP:
+//!
+//! - `GET /blocks` returns the number of blocks in the store as text
+//! - `HEAD /blocks/{cid}` answers whether the store has a block
+//! - `GET /blocks/{cid}` returns the encoded bytes of a block
+//! - `PUT /blocks/{cid}` stores the encoded bytes of a block after checking them against the CID
+//! - `POST /want` takes a DAG-CBOR list of CIDs and returns a DAG-CBOR list of `[cid, bytes]`
+//!   pairs for the blocks the store has
+//!
+//! Blocks never leave a [`RemoteStore`] or enter a served store without being checked against
+//! their CID, so neither side has to trust the other.
+
+mod client;
+mod server;
+
+use bytes::Bytes;
+use ipld_core::{cid::Cid, ipld::Ipld};
+
+use crate::{StoreError, StoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use client::*;
+pub use server::*;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of CIDs in a single want-list request.
+pub const MAX_WANT_LIST_LEN: usize = 256;
+
+/// The content type of DAG-CBOR request and response bodies.
+const DAG_CBOR_CONTENT_TYPE: &str = "application/vnd.ipld.dag-cbor";
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Encodes a want-list.
+fn encode_want_list(cids: &[Cid]) -> StoreResult<Vec<u8>> {
+    serde_ipld_dagcbor::to_vec(&cids).map_err(StoreError::custom)
+}
+
+/// Decodes a want-list.
+fn decode_want_list(bytes: &[u8]) -> StoreResult<Vec<Cid>> {
+    serde_ipld_dagcbor::from_slice(bytes).map_err(StoreError::custom)
+}
+
+/// Encodes the blocks answering a want-list.
+fn encode_blocks(blocks: Vec<(Cid, Bytes)>) -> StoreResult<Vec<u8>> {
+    let blocks = blocks
+        .into_iter()
+        .map(|(cid, bytes)| (cid, Ipld::Bytes(bytes.to_vec())))
+        .collect::<Vec<_>>();
+
+    serde_ipld_dagcbor::to_vec(&blocks).map_err(StoreError::custom)
+}
+
+/// Decodes the blocks answering a want-list.
+fn decode_blocks(bytes: &[u8]) -> StoreResult<Vec<(Cid, Bytes)>> {
+    let blocks: Vec<(Cid, Ipld)> =
+        serde_ipld_dagcbor::from_slice(bytes).map_err(StoreError::custom)?;
+
+    blocks
+        .into_iter()
+        .map(|(cid, bytes)| match bytes {
+            Ipld::Bytes(bytes) => Ok((cid, Bytes::from(bytes))),
+            _ => Err(StoreError::Exchange(format!("malformed block: {}", cid))),
+        })
+        .collect()
+}
@@ -0,0 +1,152 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use ipld_core::cid::Cid;
+
+use crate::{IpldStore, IpldStoreExt, StoreError, StoreResult};
+
+use super::{DAG_CBOR_CONTENT_TYPE, MAX_WANT_LIST_LEN};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Creates a router that serves the given store over the block exchange protocol understood by
+/// [`RemoteStore`][super::RemoteStore].
+///
+/// ## Example
+/// ```no_run
+/// use ipldstore::{self, MemoryStore};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let store = MemoryStore::default();
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:3050").await?;
+/// axum::serve(listener, ipldstore::exchange_router(store)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn exchange_router<S>(store: S) -> Router
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/blocks", get(get_block_count::<S>))
+        .route(
+            "/blocks/{cid}",
+            get(get_block::<S>).head(has_block::<S>).put(put_block::<S>),
+        )
+        .route("/want", post(want::<S>))
+        .with_state(store)
+}
+
+/// Returns the number of blocks in the store.
+async fn get_block_count<S>(State(store): State<S>) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    match store.get_block_count().await {
+        Ok(count) => count.to_string().into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Answers whether the store has a block.
+async fn has_block<S>(State(store): State<S>, Path(cid): Path<String>) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    let cid = match parse_cid(&cid) {
+        Ok(cid) => cid,
+        Err(e) => return error_response(e),
+    };
+
+    if store.has(&cid).await {
+        StatusCode::OK.into_response()
+    } else {
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+/// Returns the encoded bytes of a block.
+async fn get_block<S>(State(store): State<S>, Path(cid): Path<String>) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    let result = async { store.get_block(&parse_cid(&cid)?).await }.await;
+    match result {
+        Ok(bytes) => bytes.into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Stores the encoded bytes of a block.
+async fn put_block<S>(State(store): State<S>, Path(cid): Path<String>, bytes: Bytes) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    let result = async { store.put_block(&parse_cid(&cid)?, bytes).await }.await;
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Returns the blocks of a want-list that the store has.
+async fn want<S>(State(store): State<S>, body: Bytes) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    let result = async {
+        let cids = super::decode_want_list(&body)?;
+        if cids.len() > MAX_WANT_LIST_LEN {
+            return Err(StoreError::Exchange(format!(
+                "want-list too long: {} > {}",
+                cids.len(),
+                MAX_WANT_LIST_LEN
+            )));
+        }
+
+        let mut blocks = Vec::with_capacity(cids.len());
+        for cid in cids {
+            match store.get_block(&cid).await {
+                Ok(bytes) => blocks.push((cid, bytes)),
+                Err(StoreError::BlockNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        super::encode_blocks(blocks)
+    }
+    .await;
+
+    match result {
+        Ok(body) => ([(header::CONTENT_TYPE, DAG_CBOR_CONTENT_TYPE)], body).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Parses a CID from a path segment.
+fn parse_cid(cid: &str) -> StoreResult<Cid> {
+    Cid::try_from(cid).map_err(|e| StoreError::Exchange(format!("invalid CID {}: {}", cid, e)))
+}
+
+/// Maps an error to the status code a [`RemoteStore`][super::RemoteStore] expects for it.
+fn error_response(error: StoreError) -> Response {
+    let status = match &error {
+        StoreError::BlockNotFound(_) => StatusCode::NOT_FOUND,
+        StoreError::BlockCidMismatch(_)
+        | StoreError::UnsupportedCodec(_)
+        | StoreError::NodeBlockTooLarge(_, _)
+        | StoreError::RawBlockTooLarge(_, _)
+        | StoreError::Exchange(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    tracing::error!("block exchange request failed: {}", error);
+    (status, error.to_string()).into_response()
+}
@@ -437,7 +437,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::DEFAULT_MAX_CHUNK_SIZE;
+    use crate::{IpldStoreExt, DEFAULT_MAX_CHUNK_SIZE};
 
     use super::{helper::TestNode, *};
     use multihash_codetable::{Code, MultihashDigest};
@@ -776,6 +776,37 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_memory_store_block_copy() -> anyhow::Result<()> {
+        let source = MemoryStore::default();
+        let destination = MemoryStore::default();
+
+        // Build a small DAG in the source store
+        let raw_cid = source.put_raw_block(b"leaf".to_vec()).await?;
+        let node = TestNode {
+            name: "root".to_string(),
+            value: 42,
+            refs: vec![raw_cid],
+        };
+        let node_cid = source.put_node(&node).await?;
+
+        // Copy the blocks children first
+        for cid in [raw_cid, node_cid] {
+            let bytes = source.get_block(&cid).await?;
+            destination.put_block(&cid, bytes).await?;
+        }
+
+        let copied: TestNode = destination.get_node(&node_cid).await?;
+        assert_eq!(copied, node);
+        assert_eq!(destination.get_raw_block(&raw_cid).await?.as_ref(), b"leaf");
+
+        // Bytes that do not match the CID are rejected
+        let result = destination.put_block(&raw_cid, b"tampered".to_vec()).await;
+        assert!(matches!(result, Err(StoreError::BlockCidMismatch(_))));
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
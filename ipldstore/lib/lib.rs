@@ -6,6 +6,7 @@
 mod chunker;
 mod constants;
 mod error;
+mod exchange;
 mod implementations;
 mod layout;
 mod merkle;
@@ -21,6 +22,7 @@ pub mod utils;
 pub use chunker::*;
 pub use constants::*;
 pub use error::*;
+pub use exchange::*;
 pub use implementations::*;
 pub use layout::*;
 pub use merkle::*;
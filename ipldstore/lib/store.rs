@@ -2,12 +2,12 @@ use std::{collections::HashSet, future::Future, pin::Pin};
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use ipld_core::cid::Cid;
+use ipld_core::{cid::Cid, ipld::Ipld};
 use monoutils::SeekableReader;
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-use super::{IpldReferences, StoreError, StoreResult};
+use super::{utils, IpldReferences, StoreError, StoreResult};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -297,6 +297,65 @@ pub trait IpldStoreExt: IpldStore {
             Ok(Bytes::from(bytes))
         }
     }
+
+    /// Gets the encoded bytes of the block with the given CID, whatever its codec.
+    ///
+    /// Raw blocks are returned as is, while DAG-CBOR nodes are re-encoded canonically so the
+    /// returned bytes hash to the same CID. This is what gets sent when blocks are copied between
+    /// stores.
+    fn get_block(&self, cid: &Cid) -> impl Future<Output = StoreResult<Bytes>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            match cid.codec().try_into()? {
+                Codec::Raw => self.get_raw_block(cid).await,
+                Codec::DagCbor => {
+                    let node: Ipld = self.get_node(cid).await?;
+                    let bytes = serde_ipld_dagcbor::to_vec(&node).map_err(StoreError::custom)?;
+                    Ok(Bytes::from(bytes))
+                }
+                codec => Err(StoreError::UnsupportedCodec(codec.into())),
+            }
+        }
+    }
+
+    /// Stores the encoded bytes of a block under the given CID.
+    ///
+    /// The bytes are checked against the CID before anything is written, so a corrupted or
+    /// mislabelled block is rejected with [`StoreError::BlockCidMismatch`].
+    fn put_block(
+        &self,
+        cid: &Cid,
+        bytes: impl Into<Bytes> + Send,
+    ) -> impl Future<Output = StoreResult<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let bytes = bytes.into();
+            let codec: Codec = cid.codec().try_into()?;
+            if utils::generate_cid(codec.clone(), &bytes) != *cid {
+                return Err(StoreError::BlockCidMismatch(*cid));
+            }
+
+            let stored = match codec {
+                Codec::Raw => self.put_raw_block(bytes).await?,
+                Codec::DagCbor => {
+                    let node: Ipld =
+                        serde_ipld_dagcbor::from_slice(&bytes).map_err(StoreError::custom)?;
+                    self.put_node(&node).await?
+                }
+                codec => return Err(StoreError::UnsupportedCodec(codec.into())),
+            };
+
+            if stored != *cid {
+                return Err(StoreError::BlockCidMismatch(*cid));
+            }
+
+            Ok(())
+        }
+    }
 }
 
 /// `IpldStoreSeekable` is a trait that extends the `IpldStore` trait to allow for seeking.
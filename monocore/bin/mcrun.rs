@@ -22,6 +22,10 @@
 //!     --args="-m" --args="http.server" --args="8080"
 //! ```
 //!
+//! The sandbox runs in a libkrun MicroVM by default. Passing `--backend=process` runs its exec
+//! command as a host process rooted at the root filesystem instead, which doesn't need
+//! virtualization.
+//!
 //! ### Supervisor Mode
 //!
 //! To run as a supervisor:
@@ -29,20 +33,37 @@
 //! mcrun supervisor \
 //!     --log-dir=/path/to/logs \
 //!     --child-name=my_vm \
-//!     --db-path=/path/to/mcrun.db
+//!     --sandbox-db-path=/path/to/sandbox.db \
+//!     --root-path=/path/to/rootfs \
+//!     --num-vcpus=1 \
+//!     --ram-mib=1024 \
+//!     --exec-path=/usr/bin/python3 \
+//!     --args="-m" --args="http.server" --args="8080"
 //! ```
+//!
+//! The supervisor takes the same MicroVM options as the `microvm` mode and passes them on to the
+//! MicroVM it starts. Passing `--restart=on-failure:3` or `--restart=always` has it restart the
+//! MicroVM when it exits, waiting longer between restarts when it keeps failing. Passing a
+//! `--healthcheck` as JSON has the MicroVM check its health and record it in the sandbox database.
+//! Passing `--assigned-ip` gives the MicroVM the address of the sandbox in its group.
+//! The supervisor samples the resource usage of the MicroVM and its child processes into the
+//! sandbox database every `--metrics-interval` seconds (default: 5).
 
-use std::env;
+use std::{env, time::Duration};
 
 use anyhow::Result;
 use clap::Parser;
 use monocore::{
     cli::{McrunArgs, McrunSubcommand},
-    config::{EnvPair, PathPair, PortPair},
-    runtime::MicroVmMonitor,
-    vm::MicroVm,
+    config::{EnvPair, Healthcheck, PathPair, PortPair},
+    runtime::{HealthChecker, MicroVmMonitor},
+    vm::MicroVmConfig,
 };
 use monoutils::runtime::Supervisor;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    time::Instant,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Functions: main
@@ -67,6 +88,11 @@ async fn main() -> Result<()> {
             exec_path,
             args,
             env,
+            backend,
+            assigned_ip,
+            healthcheck,
+            sandbox_db_path,
+            sandbox_name,
         } => {
             // Parse mapped directories
             let mapped_dirs: Vec<PathPair> = mapped_dirs
@@ -83,13 +109,13 @@ async fn main() -> Result<()> {
             // Parse environment variables
             let env: Vec<EnvPair> = env.iter().map(|s| s.parse()).collect::<Result<_, _>>()?;
 
-            // Create and configure MicroVM
-            let mut builder = MicroVm::builder()
+            // Create and configure the sandbox
+            let mut builder = MicroVmConfig::builder()
                 .root_path(root_path)
                 .num_vcpus(num_vcpus)
                 .ram_mib(ram_mib)
                 .mapped_dirs(mapped_dirs)
-                .port_map(port_map)
+                .port_map(port_map.clone())
                 .exec_path(exec_path)
                 .args(args.iter().map(|s| s.as_str()))
                 .env(env);
@@ -98,16 +124,72 @@ async fn main() -> Result<()> {
                 builder = builder.workdir_path(workdir_path);
             }
 
-            // Build and start the MicroVM
-            let vm = builder.build()?;
+            if let Some(assigned_ip) = assigned_ip {
+                builder = builder.assigned_ip(assigned_ip);
+            }
 
-            tracing::info!("Starting MicroVM");
-            vm.start()?;
+            // Create and start the sandbox with the chosen backend
+            let mut sandbox = backend.new_backend(builder.build());
+            sandbox.create().await?;
+
+            tracing::info!("starting sandbox with {} backend", backend);
+            sandbox.start().await?;
+
+            // Check the health of the sandbox if it has a healthcheck
+            let mut checker = match (healthcheck, sandbox_db_path, sandbox_name) {
+                (Some(healthcheck), Some(sandbox_db_path), Some(sandbox_name)) => {
+                    let healthcheck: Healthcheck = serde_json::from_str(&healthcheck)?;
+                    Some(
+                        HealthChecker::new(healthcheck, port_map, sandbox_db_path, sandbox_name)
+                            .await?,
+                    )
+                }
+                _ => None,
+            };
+
+            // Wait for the sandbox to exit, checking its health in the meantime and stopping it
+            // if we are asked to
+            let mut sigterm = signal(SignalKind::terminate())?;
+            let status = loop {
+                let next_check = checker.as_ref().map(HealthChecker::get_next_check);
+                tokio::select! {
+                    status = sandbox.wait() => break status?,
+                    _ = sigterm.recv() => {
+                        sandbox.stop().await?;
+                        break sandbox.wait().await?;
+                    }
+                    _ = tokio::time::sleep_until(next_check.unwrap_or_else(Instant::now)),
+                        if next_check.is_some() =>
+                    {
+                        if let Some(checker) = checker.as_mut() {
+                            if let Err(e) = checker.check(sandbox.as_mut()).await {
+                                tracing::warn!("failed to record health check: {}", e);
+                            }
+                        }
+                    }
+                }
+            };
+
+            std::process::exit(status);
         }
         McrunSubcommand::Supervisor {
             log_dir,
             child_name,
             sandbox_db_path,
+            root_path,
+            num_vcpus,
+            ram_mib,
+            mapped_dirs,
+            port_map,
+            workdir_path,
+            exec_path,
+            args,
+            env,
+            backend,
+            assigned_ip,
+            restart,
+            healthcheck,
+            metrics_interval,
         } => {
             // Get current executable path
             let child_exe = env::current_exe()?;
@@ -117,14 +199,38 @@ async fn main() -> Result<()> {
 
             // Create microvm monitor
             let process_monitor =
-                MicroVmMonitor::new(supervisor_pid, sandbox_db_path, log_dir.clone()).await?;
+                MicroVmMonitor::new(supervisor_pid, &sandbox_db_path, log_dir.clone())
+                    .await?
+                    .with_metrics_interval(Duration::from_secs(metrics_interval));
 
-            // Compose child arguments - these are placeholders that will be overridden
-            let child_args = vec![
+            // Compose child arguments from the microvm configuration
+            let mut child_args = vec![
                 "microvm".to_string(),
-                "--root-path=/".to_string(),
-                "--ram-mib=512".to_string(),
+                format!("--root-path={}", root_path.display()),
+                format!("--num-vcpus={}", num_vcpus),
+                format!("--ram-mib={}", ram_mib),
+                format!("--exec-path={}", exec_path),
+                format!("--backend={}", backend),
             ];
+            child_args.extend(
+                mapped_dirs
+                    .iter()
+                    .map(|dir| format!("--mapped-dirs={}", dir)),
+            );
+            child_args.extend(port_map.iter().map(|port| format!("--port-map={}", port)));
+            child_args.extend(args.iter().map(|arg| format!("--args={}", arg)));
+            child_args.extend(env.iter().map(|env| format!("--env={}", env)));
+            if let Some(workdir_path) = workdir_path {
+                child_args.push(format!("--workdir-path={}", workdir_path));
+            }
+            if let Some(assigned_ip) = assigned_ip {
+                child_args.push(format!("--assigned-ip={}", assigned_ip));
+            }
+            if let Some(healthcheck) = healthcheck {
+                child_args.push(format!("--healthcheck={}", healthcheck));
+                child_args.push(format!("--sandbox-db-path={}", sandbox_db_path.display()));
+                child_args.push(format!("--sandbox-name={}", child_name));
+            }
 
             // Compose child environment variables
             let child_envs = vec![("RUST_LOG", "info")];
@@ -137,7 +243,8 @@ async fn main() -> Result<()> {
                 child_name,
                 log_dir,
                 process_monitor,
-            );
+            )
+            .with_restart_policy(restart);
 
             supervisor.start().await?;
         }
@@ -1,8 +1,33 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    net::Ipv4Addr,
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use chrono::SecondsFormat;
 use clap::{CommandFactory, Parser};
 use monocore::{
     cli::{MonocoreArgs, MonocoreSubcommand},
-    management, MonocoreResult,
+    management::{
+        self, HealthCheckRecord, LogFilter, LogFollower, ResourceUsage, SandboxMetrics,
+        SandboxState, LOG_FOLLOW_POLL_INTERVAL,
+    },
+    utils::{LOG_SUBDIR, MONOCORE_ENV_DIR},
+    MonocoreError, MonocoreResult,
 };
+use monoutils::{LogRecord, LogStream};
+use tokio::{signal, time};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The pager logs are shown in when `PAGER` isn't set. Exits right away if the logs fit on the
+/// screen.
+const DEFAULT_PAGER: &str = "less -FRX";
 
 //--------------------------------------------------------------------------------------------------
 // Functions: main
@@ -21,6 +46,10 @@ async fn main() -> MonocoreResult<()> {
 
     // Parse command line arguments
     let args = MonocoreArgs::parse();
+
+    // Mark sandboxes of the current project that stopped unnoticed as failed
+    management::reconcile(None).await?;
+
     match args.subcommand {
         Some(MonocoreSubcommand::Init { path }) => {
             tracing::info!("initializing monocore project: path={path:?}");
@@ -36,6 +65,134 @@ async fn main() -> MonocoreResult<()> {
             management::pull_image(name, image, image_group).await?;
             tracing::info!("successfully pulled image");
         }
+        Some(MonocoreSubcommand::Up {
+            sandbox,
+            group,
+            names,
+            backend,
+        }) => {
+            check_target_flags(sandbox, group)?;
+            tracing::info!("starting sandboxes: names={names:?}, group={group}");
+            management::up(None, &names, group, backend).await?;
+            tracing::info!("successfully started sandboxes");
+        }
+        Some(MonocoreSubcommand::Down {
+            sandbox,
+            group,
+            names,
+        }) => {
+            check_target_flags(sandbox, group)?;
+            tracing::info!("stopping sandboxes: names={names:?}, group={group}");
+            management::down(None, &names, group).await?;
+            tracing::info!("successfully stopped sandboxes");
+        }
+        Some(MonocoreSubcommand::Status {
+            sandbox,
+            group,
+            names,
+        }) => {
+            check_target_flags(sandbox, group)?;
+            let states = management::status(None, &names, group).await?;
+            let metrics = management::metrics(None).await?;
+            print_status(&states, &metrics);
+
+            // Show the recent health checks of the sandboxes asked about
+            if !names.is_empty() {
+                for state in &states {
+                    let checks = management::health_checks(None, state.get_name()).await?;
+                    print_health_checks(state.get_name(), &checks);
+                }
+            }
+        }
+        Some(MonocoreSubcommand::Log {
+            name,
+            follow,
+            no_pager,
+            tail,
+            count,
+            level,
+            stdout,
+            stderr,
+            timestamps,
+            ..
+        }) => {
+            let log_dir = PathBuf::from(MONOCORE_ENV_DIR).join(LOG_SUBDIR);
+            let stream = match (stdout, stderr) {
+                (true, _) => Some(LogStream::Stdout),
+                (_, true) => Some(LogStream::Stderr),
+                _ => None,
+            };
+            let filter = LogFilter::builder().level(level).stream(stream).build();
+
+            // Start following before reading so no line falls in between
+            let mut follower = match follow {
+                true => Some(LogFollower::new(&log_dir, &name, filter.clone()).await?),
+                false => None,
+            };
+
+            let segments = management::get_log_segments(&log_dir, &name).await?;
+            if segments.is_empty() && !follow {
+                return Err(MonocoreError::LogNotFound(name));
+            }
+
+            let mut records = match tail {
+                Some(tail) => management::tail_log_records(&segments, tail, &filter).await?,
+                None => management::read_log_records(&segments, &filter).await?,
+            };
+            if let Some(count) = count {
+                records.truncate(count);
+            }
+            let lines = format_log_records(&records, timestamps);
+
+            match follower.as_mut() {
+                Some(follower) => {
+                    print_lines(&lines);
+                    loop {
+                        let records = follower.poll().await?;
+                        print_lines(&format_log_records(&records, timestamps));
+                        tokio::select! {
+                            _ = time::sleep(LOG_FOLLOW_POLL_INTERVAL) => {}
+                            _ = signal::ctrl_c() => break,
+                        }
+                    }
+                }
+                None if no_pager || !io::stdout().is_terminal() => print_lines(&lines),
+                None => page_lines(&lines)?,
+            }
+        }
+        Some(MonocoreSubcommand::Top { interval }) => {
+            let interval = Duration::from_secs(interval.max(1));
+            loop {
+                let states = management::status(None, &[], false).await?;
+                let metrics = management::metrics(None).await?;
+
+                // Clear the screen and move the cursor to its top left corner
+                print!("\x1b[2J\x1b[H");
+                print_top(&states, &metrics);
+
+                tokio::select! {
+                    _ = time::sleep(interval) => {}
+                    _ = signal::ctrl_c() => break,
+                }
+            }
+        }
+        Some(MonocoreSubcommand::Serve { port, dns_port, .. }) => {
+            // The proxy keeps serving without the DNS responder, which needs privileges to listen
+            // on the default DNS port
+            let dns = async {
+                if let Err(e) =
+                    management::serve_dns(None, (Ipv4Addr::LOCALHOST, dns_port).into()).await
+                {
+                    tracing::error!("dns responder stopped: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            };
+
+            tokio::select! {
+                result = management::serve_proxy(None, (Ipv4Addr::UNSPECIFIED, port).into()) => result?,
+                _ = dns => {}
+            }
+        }
         Some(_) => (), // TODO: implement other subcommands
         None => {
             MonocoreArgs::command().print_help()?;
@@ -48,3 +205,249 @@ async fn main() -> MonocoreResult<()> {
 //--------------------------------------------------------------------------------------------------
 // Functions: *
 //--------------------------------------------------------------------------------------------------
+
+/// Returns the lines of log records, prefixed with when and to which stream they were written if
+/// `timestamps` is set.
+fn format_log_records(records: &[LogRecord], timestamps: bool) -> Vec<String> {
+    records
+        .iter()
+        .map(|record| match timestamps {
+            true => format!(
+                "{} {} {}",
+                record
+                    .get_timestamp()
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+                record.get_stream(),
+                record.get_line()
+            ),
+            false => record.get_line().to_string(),
+        })
+        .collect()
+}
+
+/// Prints lines to stdout.
+fn print_lines(lines: &[String]) {
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+/// Shows lines in the pager set by `PAGER`, or in `less`, printing them if there is no pager.
+fn page_lines(lines: &[String]) -> MonocoreResult<()> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        print_lines(lines);
+        return Ok(());
+    };
+
+    let mut child = match Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            print_lines(lines);
+            return Ok(());
+        }
+        Err(e) => return Err(MonocoreError::PagerError(e.to_string())),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        for line in lines {
+            // The pager closes its input when it is quit before reading everything
+            match writeln!(stdin, "{}", line) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(MonocoreError::PagerError(e.to_string())),
+            }
+        }
+    }
+
+    child
+        .wait()
+        .map_err(|e| MonocoreError::PagerError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Checks that a command doesn't target both sandboxes and groups.
+fn check_target_flags(sandbox: bool, group: bool) -> MonocoreResult<()> {
+    if sandbox && group {
+        return Err(MonocoreError::InvalidArgument(
+            "both sandbox and group cannot be true".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Prints the states of sandboxes as a table, with the latest resource usage of active ones.
+fn print_status(states: &[SandboxState], metrics: &[SandboxMetrics]) {
+    let rows: Vec<[String; 11]> = states
+        .iter()
+        .map(|state| {
+            let (cpu, memory) = match get_active_usage(state, metrics) {
+                Some(usage) => (
+                    format!("{:.1}%", usage.get_cpu_usage()),
+                    format_bytes(*usage.get_memory_usage()),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let pid = match state.get_supervisor_pid() {
+                Some(pid) if state.get_status().is_active() => pid.to_string(),
+                _ => "-".to_string(),
+            };
+            let exit_code = state
+                .get_exit_code()
+                .map_or_else(|| "-".to_string(), |code| code.to_string());
+            let health = match state.get_health() {
+                Some(health) if state.get_status().is_active() => health.to_string(),
+                _ => "-".to_string(),
+            };
+            let ip = state
+                .get_group_ip()
+                .map_or_else(|| "-".to_string(), |ip| ip.to_string());
+
+            [
+                state.get_name().clone(),
+                state.get_status().as_str().to_string(),
+                health,
+                ip,
+                pid,
+                cpu,
+                memory,
+                exit_code,
+                state.get_restart_count().to_string(),
+                state.get_modified_at().clone(),
+                state.get_last_error().clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let header = [
+        "NAME",
+        "STATUS",
+        "HEALTH",
+        "IP",
+        "PID",
+        "CPU",
+        "MEMORY",
+        "EXIT CODE",
+        "RESTARTS",
+        "SINCE",
+        "LAST ERROR",
+    ];
+    print_table(header, &rows);
+}
+
+/// Prints the latest resource usage of the active sandboxes as a table.
+fn print_top(states: &[SandboxState], metrics: &[SandboxMetrics]) {
+    let optional_bytes = |bytes: &Option<u64>| bytes.map_or_else(|| "-".to_string(), format_bytes);
+    let rows: Vec<[String; 8]> = states
+        .iter()
+        .filter(|state| state.get_status().is_active())
+        .map(|state| {
+            let usage = get_active_usage(state, metrics)
+                .cloned()
+                .unwrap_or_default();
+            [
+                state.get_name().clone(),
+                state.get_status().as_str().to_string(),
+                format!("{:.1}%", usage.get_cpu_usage()),
+                format_bytes(*usage.get_memory_usage()),
+                optional_bytes(usage.get_disk_read_bytes()),
+                optional_bytes(usage.get_disk_write_bytes()),
+                optional_bytes(usage.get_net_rx_bytes()),
+                optional_bytes(usage.get_net_tx_bytes()),
+            ]
+        })
+        .collect();
+
+    let header = [
+        "NAME",
+        "STATUS",
+        "CPU",
+        "MEMORY",
+        "DISK READ",
+        "DISK WRITE",
+        "NET RX",
+        "NET TX",
+    ];
+    print_table(header, &rows);
+}
+
+/// Prints rows under a header, with each column as wide as its widest value.
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let header = header.map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(column, width)| format!("{:<width$}", column, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// Returns the latest resource usage of a sandbox if it is active and has been sampled.
+fn get_active_usage<'a>(
+    state: &SandboxState,
+    metrics: &'a [SandboxMetrics],
+) -> Option<&'a ResourceUsage> {
+    if !state.get_status().is_active() {
+        return None;
+    }
+
+    metrics
+        .iter()
+        .find(|metrics| metrics.get_name() == state.get_name())
+        .map(SandboxMetrics::get_usage)
+}
+
+/// Formats a number of bytes with a binary unit, like `1.5 MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Prints the recorded health checks of a sandbox, oldest first.
+fn print_health_checks(name: &str, checks: &[HealthCheckRecord]) {
+    if checks.is_empty() {
+        return;
+    }
+
+    println!("\nhealth checks of {}:", name);
+    for check in checks {
+        let result = if *check.get_passed() {
+            "passed"
+        } else {
+            "failed"
+        };
+        println!(
+            "  {}  {}  {}",
+            check.get_timestamp(),
+            result,
+            check.get_output()
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use std::path::PathBuf;
+use std::{net::Ipv4Addr, path::PathBuf};
 
 use clap::{Parser, Subcommand};
+use monoutils::RestartPolicy;
 
-use crate::cli::styles;
+use crate::{cli::styles, management::DEFAULT_METRICS_INTERVAL, runtime::SandboxBackendType};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -51,12 +52,33 @@ pub enum McrunSubcommand {
         exec_path: String,
 
         /// Arguments for the executable
-        #[arg(long)]
+        #[arg(long, allow_hyphen_values = true)]
         args: Vec<String>,
 
         /// Environment variables (KEY=VALUE format)
         #[arg(long)]
         env: Vec<String>,
+
+        /// Backend to run the sandbox with (microvm, container or process)
+        #[arg(long, default_value_t)]
+        backend: SandboxBackendType,
+
+        /// Address of the sandbox in its group. Connections the MicroVM makes to 127.0.0.1 are
+        /// rewritten to it
+        #[arg(long)]
+        assigned_ip: Option<Ipv4Addr>,
+
+        /// Healthcheck to run against the sandbox, as JSON
+        #[arg(long, requires_all = ["sandbox_db_path", "sandbox_name"])]
+        healthcheck: Option<String>,
+
+        /// Path to the sandbox database health checks are recorded in
+        #[arg(long)]
+        sandbox_db_path: Option<PathBuf>,
+
+        /// Name of the sandbox health checks are recorded for
+        #[arg(long)]
+        sandbox_name: Option<String>,
     },
     /// Run as supervisor
     Supervisor {
@@ -71,5 +93,62 @@ pub enum McrunSubcommand {
         /// Path to the sandbox metrics and metadata database file
         #[arg(long)]
         sandbox_db_path: PathBuf,
+
+        /// Root filesystem path
+        #[arg(long)]
+        root_path: PathBuf,
+
+        /// Number of virtual CPUs
+        #[arg(long)]
+        num_vcpus: u8,
+
+        /// RAM size in MiB
+        #[arg(long)]
+        ram_mib: u32,
+
+        /// Directory mappings (host:guest format)
+        #[arg(long)]
+        mapped_dirs: Vec<String>,
+
+        /// Port mappings (host:guest format)
+        #[arg(long)]
+        port_map: Vec<String>,
+
+        /// Working directory path
+        #[arg(long)]
+        workdir_path: Option<String>,
+
+        /// Executable path
+        #[arg(long)]
+        exec_path: String,
+
+        /// Arguments for the executable
+        #[arg(long, allow_hyphen_values = true)]
+        args: Vec<String>,
+
+        /// Environment variables (KEY=VALUE format)
+        #[arg(long)]
+        env: Vec<String>,
+
+        /// Backend to run the sandbox with (microvm, container or process)
+        #[arg(long, default_value_t)]
+        backend: SandboxBackendType,
+
+        /// Address of the sandbox in its group
+        #[arg(long)]
+        assigned_ip: Option<Ipv4Addr>,
+
+        /// When to restart the sandbox after it exits (never, always, on-failure or
+        /// on-failure:<max retries>)
+        #[arg(long, default_value_t)]
+        restart: RestartPolicy,
+
+        /// Healthcheck to run against the sandbox, as JSON
+        #[arg(long)]
+        healthcheck: Option<String>,
+
+        /// Seconds between samples of the resource usage of the sandbox
+        #[arg(long, default_value_t = DEFAULT_METRICS_INTERVAL.as_secs())]
+        metrics_interval: u64,
     },
 }
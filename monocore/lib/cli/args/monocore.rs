@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use crate::{cli::styles, oci::Reference};
+use crate::{
+    cli::styles,
+    management::{LogLevel, DEFAULT_DNS_PORT, DEFAULT_PROXY_PORT},
+    oci::Reference,
+    runtime::SandboxBackendType,
+};
 use clap::Parser;
 use typed_path::Utf8UnixPathBuf;
 
@@ -171,9 +176,21 @@ pub enum MonocoreSubcommand {
         #[arg(long)]
         count: Option<usize>,
 
-        /// Log level
+        /// Only show lines logged at this level or above (trace, debug, info, warn or error)
         #[arg(short = 'L')]
-        level: Option<String>,
+        level: Option<LogLevel>,
+
+        /// Only show lines written to stdout
+        #[arg(long, conflicts_with = "stderr")]
+        stdout: bool,
+
+        /// Only show lines written to stderr
+        #[arg(long)]
+        stderr: bool,
+
+        /// Show when each line was written and to which stream
+        #[arg(short, long)]
+        timestamps: bool,
     },
 
     /// Show tree of layers that make up a build, sandbox, or group component
@@ -362,6 +379,11 @@ pub enum MonocoreSubcommand {
 
         /// Names of components to start
         names: Vec<String>,
+
+        /// Backend to run the sandboxes with (microvm, container or process). Detected from the
+        /// host if not set here, in MONOCORE_BACKEND or in the sandbox configuration
+        #[arg(long)]
+        backend: Option<SandboxBackendType>,
     },
 
     /// Stop project sandboxes
@@ -394,6 +416,14 @@ pub enum MonocoreSubcommand {
         names: Vec<String>,
     },
 
+    /// Show live resource usage of running sandboxes
+    #[command(name = "top")]
+    Top {
+        /// Seconds between refreshes
+        #[arg(short, long, default_value_t = 2)]
+        interval: u64,
+    },
+
     /// Clean project data
     #[command(name = "clean")]
     Clean,
@@ -471,9 +501,13 @@ pub enum MonocoreSubcommand {
     /// Start a monocore proxy server for orchestrating sandboxes
     #[command(name = "serve")]
     Serve {
-        /// Port to listen on
-        #[arg(long)]
-        port: Option<u16>,
+        /// Port the reverse proxy to sandboxes listens on, on all interfaces
+        #[arg(long, default_value_t = DEFAULT_PROXY_PORT)]
+        port: u16,
+
+        /// Port the DNS responder resolving sandbox names listens on, on localhost
+        #[arg(long, default_value_t = DEFAULT_DNS_PORT)]
+        dns_port: u16,
 
         /// Daemon control
         #[arg(long)]
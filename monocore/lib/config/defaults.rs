@@ -1,4 +1,6 @@
-use std::{path::PathBuf, sync::LazyLock};
+use std::{net::Ipv4Addr, path::PathBuf, sync::LazyLock};
+
+use ipnetwork::Ipv4Network;
 
 use crate::utils::MONOCORE_HOME_DIR;
 
@@ -12,10 +14,23 @@ pub const DEFAULT_NUM_VCPUS: u8 = 1;
 /// The default amount of RAM in MiB to use for the MicroVm.
 pub const DEFAULT_RAM_MIB: u32 = 1024;
 
+/// The shell sandbox scripts are run with if the sandbox doesn't set one.
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// The script run when a sandbox is started. Without it, the image's entrypoint is run.
+pub const DEFAULT_SCRIPT: &str = "start";
+
 /// The path where all monocore global data is stored.
 pub static DEFAULT_MONOCORE_HOME: LazyLock<PathBuf> =
     LazyLock::new(|| dirs::home_dir().unwrap().join(MONOCORE_HOME_DIR));
 
+/// The network group subnets are taken from when the configuration doesn't set one.
+pub static DEFAULT_GROUP_SUPERNET: LazyLock<Ipv4Network> =
+    LazyLock::new(|| Ipv4Network::new(Ipv4Addr::new(10, 0, 0, 0), 8).unwrap());
+
+/// The prefix length of the subnets groups are given from [`DEFAULT_GROUP_SUPERNET`].
+pub const DEFAULT_GROUP_PREFIX: u8 = 24;
+
 /// The default OCI registry domain.
 pub const DEFAULT_OCI_REGISTRY: &str = "sandboxes.io";
 
@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The default number of seconds between health checks.
+pub const DEFAULT_HEALTHCHECK_INTERVAL: u64 = 10;
+
+/// The default number of seconds a health check can take before it fails.
+pub const DEFAULT_HEALTHCHECK_TIMEOUT: u64 = 5;
+
+/// The default number of failed health checks in a row that make a sandbox unhealthy.
+pub const DEFAULT_HEALTHCHECK_RETRIES: u32 = 3;
+
+/// The default path requested by HTTP health checks.
+pub const DEFAULT_HEALTHCHECK_HTTP_PATH: &str = "/";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// How to check that a sandbox is ready and keeps working.
+///
+/// A sandbox starts out with a `starting` health. It becomes `healthy` as soon as a check
+/// passes and `unhealthy` once `retries` checks in a row have failed. Checks that fail during the
+/// start period don't count, giving slow sandboxes time to start up.
+///
+/// ## Examples
+///
+/// ```yaml
+/// healthcheck:
+///   http:
+///     port: 80
+///     path: /health
+///   interval: 5
+///   retries: 5
+///   start_period: 10
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct Healthcheck {
+    /// The probe run by each check.
+    #[serde(flatten)]
+    pub(super) probe: HealthProbe,
+
+    /// The number of seconds between checks.
+    #[serde(default = "Healthcheck::default_interval")]
+    #[builder(default = Healthcheck::default_interval())]
+    pub(super) interval: u64,
+
+    /// The number of seconds a check can take before it fails.
+    #[serde(default = "Healthcheck::default_timeout")]
+    #[builder(default = Healthcheck::default_timeout())]
+    pub(super) timeout: u64,
+
+    /// The number of failed checks in a row that make the sandbox unhealthy.
+    #[serde(default = "Healthcheck::default_retries")]
+    #[builder(default = Healthcheck::default_retries())]
+    pub(super) retries: u32,
+
+    /// The number of seconds after the sandbox starts during which failed checks don't count.
+    #[serde(default)]
+    #[builder(default)]
+    pub(super) start_period: u64,
+}
+
+/// A probe that checks the health of a sandbox.
+///
+/// Ports are guest ports. A probe connects to the host port the guest port is mapped to, or to
+/// the same port on the host if the guest port isn't mapped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthProbe {
+    /// Passes if a TCP connection to the port can be made.
+    Tcp(u16),
+
+    /// Passes if a GET request to the port and path gets a successful response.
+    Http {
+        /// The port to send the request to.
+        port: u16,
+
+        /// The path to request.
+        #[serde(default = "HealthProbe::default_http_path")]
+        path: String,
+    },
+
+    /// Passes if the command exits with status 0 when run in the sandbox. The microvm backend
+    /// can't run commands in its sandbox, so it doesn't support this probe.
+    Exec(Vec<String>),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Healthcheck {
+    /// Returns the default number of seconds between checks.
+    pub fn default_interval() -> u64 {
+        DEFAULT_HEALTHCHECK_INTERVAL
+    }
+
+    /// Returns the default number of seconds a check can take.
+    pub fn default_timeout() -> u64 {
+        DEFAULT_HEALTHCHECK_TIMEOUT
+    }
+
+    /// Returns the default number of failed checks that make a sandbox unhealthy.
+    pub fn default_retries() -> u32 {
+        DEFAULT_HEALTHCHECK_RETRIES
+    }
+
+    /// Returns the time between checks.
+    pub fn get_interval_duration(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    /// Returns the time a check can take before it fails.
+    pub fn get_timeout_duration(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// Returns the time after the sandbox starts during which failed checks don't count.
+    pub fn get_start_period_duration(&self) -> Duration {
+        Duration::from_secs(self.start_period)
+    }
+}
+
+impl HealthProbe {
+    /// Returns the default path requested by HTTP probes.
+    pub fn default_http_path() -> String {
+        DEFAULT_HEALTHCHECK_HTTP_PATH.to_string()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_healthcheck_deserializes_probes_with_defaults() -> anyhow::Result<()> {
+        let healthcheck: Healthcheck = serde_yaml::from_str("tcp: 5432")?;
+        assert_eq!(healthcheck.get_probe(), &HealthProbe::Tcp(5432));
+        assert_eq!(healthcheck.get_interval(), &DEFAULT_HEALTHCHECK_INTERVAL);
+        assert_eq!(healthcheck.get_retries(), &DEFAULT_HEALTHCHECK_RETRIES);
+        assert_eq!(healthcheck.get_start_period(), &0);
+
+        let healthcheck: Healthcheck = serde_yaml::from_str(
+            r#"
+            http:
+              port: 80
+            interval: 2
+            timeout: 1
+            start_period: 30
+            "#,
+        )?;
+        assert_eq!(
+            healthcheck.get_probe(),
+            &HealthProbe::Http {
+                port: 80,
+                path: "/".to_string()
+            }
+        );
+        assert_eq!(healthcheck.get_interval_duration(), Duration::from_secs(2));
+        assert_eq!(
+            healthcheck.get_start_period_duration(),
+            Duration::from_secs(30)
+        );
+
+        let healthcheck: Healthcheck = serde_yaml::from_str(r#"exec: ["pg_isready", "-q"]"#)?;
+        assert_eq!(
+            healthcheck.get_probe(),
+            &HealthProbe::Exec(vec!["pg_isready".to_string(), "-q".to_string()])
+        );
+
+        // The probe survives being passed around as JSON
+        let json = serde_json::to_string(&healthcheck)?;
+        assert_eq!(serde_json::from_str::<Healthcheck>(&json)?, healthcheck);
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 
 mod defaults;
 mod env_pair;
+mod healthcheck;
 // mod merge;
 mod monocore;
 mod path_pair;
@@ -14,6 +15,7 @@ mod port_pair;
 
 pub use defaults::*;
 pub use env_pair::*;
+pub use healthcheck::*;
 pub use monocore::*;
 pub use path_pair::*;
 pub use port_pair::*;
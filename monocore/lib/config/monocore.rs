@@ -1,18 +1,28 @@
 //! Monocore configuration types and helpers.
 
-use std::{collections::HashMap, net::Ipv4Addr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::Ipv4Addr,
+    time::Duration,
+};
 
 use getset::Getters;
 use ipnetwork::Ipv4Network as Ipv4Net;
-use oci_spec::distribution::Reference;
+use monoutils::RestartPolicy;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 use typed_path::Utf8UnixPathBuf;
 
-use super::{EnvPair, PathPair, PortPair, DEFAULT_NUM_VCPUS, DEFAULT_RAM_MIB};
+use super::{
+    EnvPair, HealthProbe, Healthcheck, PathPair, PortPair, DEFAULT_NUM_VCPUS, DEFAULT_RAM_MIB,
+};
 
-use crate::MonocoreResult;
+use crate::{
+    management::is_host_address, oci::Reference, runtime::SandboxBackendType, MonocoreError,
+    MonocoreResult,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -338,6 +348,7 @@ pub struct Sandbox {
     pub(super) cpus: u8,
 
     /// The volumes to mount.
+    #[serde(default)]
     #[builder(default)]
     pub(super) volumes: Vec<PathPair>,
 
@@ -352,6 +363,7 @@ pub struct Sandbox {
     pub(super) envs: Option<Vec<EnvPair>>,
 
     /// The environment file to use.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
     #[serde(
         serialize_with = "serialize_optional_path",
         deserialize_with = "deserialize_optional_path"
@@ -367,7 +379,7 @@ pub struct Sandbox {
     /// The sandboxes to depend on.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     #[builder(default)]
-    pub(super) depends_on: Option<Vec<String>>,
+    pub(super) depends_on: Option<Vec<Dependency>>,
 
     /// The working directory to use.
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -415,6 +427,69 @@ pub struct Sandbox {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     #[builder(default)]
     pub(super) proxy: Option<ProxyConfig>,
+
+    /// The backend to run the sandbox with. If not set, the backend that suits the host is used.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[builder(default)]
+    pub(super) backend: Option<SandboxBackendType>,
+
+    /// When the sandbox is restarted after it exits. If not set, it is never restarted.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[builder(default)]
+    pub(super) restart: Option<RestartPolicy>,
+
+    /// How to check that the sandbox is healthy. If not set, the sandbox isn't checked.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[builder(default)]
+    pub(super) healthcheck: Option<Healthcheck>,
+}
+
+/// A sandbox another sandbox depends on, and what to wait for before starting the dependent.
+///
+/// Written as the name of the sandbox, or as a map with its name and condition:
+///
+/// ```yaml
+/// depends_on:
+///   - cache
+///   - name: db
+///     condition: healthy
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Getters)]
+#[serde(from = "DependencyRepr", into = "DependencyRepr")]
+#[getset(get = "pub with_prefix")]
+pub struct Dependency {
+    /// The name of the sandbox depended on.
+    pub(super) name: String,
+
+    /// What to wait for before starting the dependent sandbox.
+    pub(super) condition: DependencyCondition,
+}
+
+/// What to wait for a dependency to be before starting the sandbox that depends on it.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyCondition {
+    /// The dependency has been started.
+    #[default]
+    Started,
+
+    /// The dependency has passed a health check. It must have a healthcheck.
+    Healthy,
+}
+
+/// The ways a dependency can be written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DependencyRepr {
+    /// The name of the dependency, started condition implied.
+    Name(String),
+
+    /// The name and condition of the dependency.
+    Full {
+        name: String,
+        #[serde(default)]
+        condition: DependencyCondition,
+    },
 }
 
 /// Configuration for a sandbox's group membership.
@@ -522,9 +597,274 @@ impl Monocore {
     }
 
     /// Validates the configuration.
+    ///
+    /// Checks that sandbox names are unique, that `depends_on` only names sandboxes in this
+    /// configuration, that sandboxes only wait for dependencies with a healthcheck to be
+    /// healthy, that dependencies have no cycles and chains no longer than
+    /// [`MAX_DEPENDENCY_DEPTH`][Self::MAX_DEPENDENCY_DEPTH], and that sandboxes only join one
+    /// group, defined in this configuration. Static IPs have to be host addresses in the subnet of
+    /// their group and unique within it, and group subnets must not overlap. Legacy proxies need a
+    /// port to forward to, a valid keep alive and concurrency, and a prefix of their own. Exec
+    /// healthchecks need a backend that can run commands in the sandbox.
     pub fn validate(&self) -> MonocoreResult<()> {
-        // TODO: Add validation logic here
-        Ok(())
+        let mut errors = Vec::new();
+        let sandboxes = self.sandboxes.as_deref().unwrap_or_default();
+
+        let mut names = HashSet::new();
+        let mut static_ips = HashMap::new();
+        let mut proxy_prefixes = HashMap::new();
+        for sandbox in sandboxes {
+            if !names.insert(sandbox.name.as_str()) {
+                errors.push(format!("duplicate sandbox name '{}'", sandbox.name));
+            }
+
+            for dependency in sandbox.depends_on.iter().flatten() {
+                match self.get_sandbox(&dependency.name) {
+                    _ if dependency.name == sandbox.name => {
+                        errors.push(format!("sandbox '{}' depends on itself", sandbox.name));
+                    }
+                    None => errors.push(format!(
+                        "sandbox '{}' depends on unknown sandbox '{}'",
+                        sandbox.name, dependency.name
+                    )),
+                    Some(depended)
+                        if dependency.condition == DependencyCondition::Healthy
+                            && depended.healthcheck.is_none() =>
+                    {
+                        errors.push(format!(
+                            "sandbox '{}' waits for sandbox '{}' to be healthy, which has no \
+                             healthcheck",
+                            sandbox.name, dependency.name
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            for group in sandbox.groups.iter().flat_map(|groups| groups.keys()) {
+                if self.get_group(group).is_none() {
+                    errors.push(format!(
+                        "sandbox '{}' belongs to unknown group '{}'",
+                        sandbox.name, group
+                    ));
+                }
+            }
+
+            if let (Some(backend), Some(healthcheck)) = (sandbox.backend, &sandbox.healthcheck) {
+                if matches!(healthcheck.probe, HealthProbe::Exec(_)) && !backend.supports_exec() {
+                    errors.push(format!(
+                        "sandbox '{}' has an exec healthcheck, which the {} backend can't run",
+                        sandbox.name, backend
+                    ));
+                }
+            }
+
+            // A sandbox gets a single address, from its group
+            if sandbox
+                .groups
+                .as_ref()
+                .is_some_and(|groups| groups.len() > 1)
+            {
+                errors.push(format!(
+                    "sandbox '{}' belongs to more than one group",
+                    sandbox.name
+                ));
+            }
+
+            for (group_name, group_config) in sandbox.groups.iter().flatten() {
+                let (Some(ip), Some(group)) = (
+                    group_config.network.as_ref().and_then(|network| network.ip),
+                    self.get_group(group_name),
+                ) else {
+                    continue;
+                };
+
+                match group.network.as_ref().and_then(|network| network.subnet) {
+                    None => errors.push(format!(
+                        "sandbox '{}' has a static ip in group '{}', which has no subnet",
+                        sandbox.name, group_name
+                    )),
+                    Some(subnet) if !is_host_address(subnet, ip) => errors.push(format!(
+                        "ip {} of sandbox '{}' is not a host address in subnet {} of group '{}'",
+                        ip, sandbox.name, subnet, group_name
+                    )),
+                    Some(_) => {
+                        if let Some(other) = static_ips.insert((group_name, ip), &sandbox.name) {
+                            errors.push(format!(
+                                "sandboxes '{}' and '{}' have the same ip {} in group '{}'",
+                                other, sandbox.name, ip, group_name
+                            ));
+                        }
+                    }
+                }
+            }
+            if let Some(ProxyConfig::Legacy {
+                keep_alive,
+                concurrency,
+                port,
+                ..
+            }) = &sandbox.proxy
+            {
+                if port.is_none() && sandbox.ports.is_empty() {
+                    errors.push(format!(
+                        "proxy of sandbox '{}' has no port to forward to",
+                        sandbox.name
+                    ));
+                }
+
+                if let Some(keep_alive) = keep_alive {
+                    if ProxyConfig::parse_keep_alive(keep_alive).is_none() {
+                        errors.push(format!(
+                            "proxy of sandbox '{}' has an invalid keep alive '{}'",
+                            sandbox.name, keep_alive
+                        ));
+                    }
+                }
+
+                if *concurrency == Some(0) {
+                    errors.push(format!(
+                        "proxy of sandbox '{}' has a concurrency of 0",
+                        sandbox.name
+                    ));
+                }
+
+                let prefix = sandbox.get_proxy_prefix().unwrap_or_default();
+                if let Some(other) = proxy_prefixes.insert(prefix.clone(), &sandbox.name) {
+                    errors.push(format!(
+                        "sandboxes '{}' and '{}' are proxied under the same prefix '/{}'",
+                        other, sandbox.name, prefix
+                    ));
+                }
+            }
+        }
+
+        let subnets: Vec<_> = self
+            .groups
+            .iter()
+            .flatten()
+            .filter_map(|group| Some((&group.name, group.network.as_ref()?.subnet?)))
+            .collect();
+        for (i, (name, subnet)) in subnets.iter().enumerate() {
+            if subnet.size() < 4 {
+                errors.push(format!(
+                    "subnet {} of group '{}' has no room for sandboxes",
+                    subnet, name
+                ));
+            }
+
+            for (other_name, other_subnet) in &subnets[..i] {
+                if subnet.overlaps(*other_subnet) {
+                    errors.push(format!(
+                        "subnet {} of group '{}' overlaps subnet {} of group '{}'",
+                        subnet, name, other_subnet, other_name
+                    ));
+                }
+            }
+        }
+
+        // Only check the dependency graph once all its edges are known to be valid
+        if errors.is_empty() {
+            let mut depths = HashMap::new();
+            for sandbox in sandboxes {
+                if let Err(e) = self.get_dependency_depth(sandbox, &mut Vec::new(), &mut depths) {
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(MonocoreError::ConfigValidation(errors.remove(0))),
+            _ => Err(MonocoreError::ConfigValidationErrors(errors)),
+        }
+    }
+
+    /// Returns the length of the longest dependency chain starting at the sandbox, memoized in
+    /// `depths`. `path` holds the sandboxes currently being visited, to detect cycles.
+    fn get_dependency_depth<'a>(
+        &'a self,
+        sandbox: &'a Sandbox,
+        path: &mut Vec<&'a str>,
+        depths: &mut HashMap<&'a str, usize>,
+    ) -> Result<usize, String> {
+        if let Some(depth) = depths.get(sandbox.name.as_str()) {
+            return Ok(*depth);
+        }
+
+        if let Some(start) = path.iter().position(|name| *name == sandbox.name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(&sandbox.name);
+            return Err(format!("dependency cycle: {}", cycle.join(" -> ")));
+        }
+
+        path.push(&sandbox.name);
+        let mut depth = 1;
+        for dependency in sandbox.depends_on.iter().flatten() {
+            if let Some(dependency) = self.get_sandbox(&dependency.name) {
+                depth = depth.max(self.get_dependency_depth(dependency, path, depths)? + 1);
+            }
+        }
+        path.pop();
+
+        if depth > Self::MAX_DEPENDENCY_DEPTH {
+            return Err(format!(
+                "dependency chain of sandbox '{}' is longer than {}",
+                sandbox.name,
+                Self::MAX_DEPENDENCY_DEPTH
+            ));
+        }
+
+        depths.insert(&sandbox.name, depth);
+        Ok(depth)
+    }
+}
+
+impl Sandbox {
+    /// Returns the path prefix its legacy proxy routes requests to the sandbox under, without
+    /// leading or trailing slashes. Defaults to the name of the sandbox.
+    pub fn get_proxy_prefix(&self) -> Option<String> {
+        match &self.proxy {
+            Some(ProxyConfig::Legacy { prefix, .. }) => Some(
+                prefix
+                    .as_deref()
+                    .map(|prefix| prefix.trim_matches('/').to_string())
+                    .unwrap_or_else(|| self.name.clone()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl ProxyConfig {
+    /// Parses a keep alive duration: a number of seconds, or a number followed by `ms`, `s`, `m`
+    /// or `h`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use monocore::config::ProxyConfig;
+    ///
+    /// assert_eq!(ProxyConfig::parse_keep_alive("90"), Some(Duration::from_secs(90)));
+    /// assert_eq!(ProxyConfig::parse_keep_alive("5m"), Some(Duration::from_secs(300)));
+    /// assert_eq!(ProxyConfig::parse_keep_alive("soon"), None);
+    /// ```
+    pub fn parse_keep_alive(keep_alive: &str) -> Option<Duration> {
+        let keep_alive = keep_alive.trim();
+        let split = keep_alive
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(keep_alive.len());
+        let (value, unit) = keep_alive.split_at(split);
+        let value: u64 = value.parse().ok()?;
+
+        match unit {
+            "ms" => Some(Duration::from_millis(value)),
+            "" | "s" => Some(Duration::from_secs(value)),
+            "m" => Some(Duration::from_secs(value.checked_mul(60)?)),
+            "h" => Some(Duration::from_secs(value.checked_mul(60 * 60)?)),
+            _ => None,
+        }
     }
 }
 
@@ -674,6 +1014,277 @@ where
     })
 }
 
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for SandboxNetworkReach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Public => write!(f, "public"),
+            Self::Any => write!(f, "any"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl From<String> for Dependency {
+    fn from(name: String) -> Self {
+        Self {
+            name,
+            condition: DependencyCondition::Started,
+        }
+    }
+}
+
+impl From<DependencyRepr> for Dependency {
+    fn from(repr: DependencyRepr) -> Self {
+        match repr {
+            DependencyRepr::Name(name) => name.into(),
+            DependencyRepr::Full { name, condition } => Self { name, condition },
+        }
+    }
+}
+
+impl From<Dependency> for DependencyRepr {
+    fn from(dependency: Dependency) -> Self {
+        match dependency.condition {
+            DependencyCondition::Started => Self::Name(dependency.name),
+            condition => Self::Full {
+                name: dependency.name,
+                condition,
+            },
+        }
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monocore_validate_dependencies() -> anyhow::Result<()> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: db
+                image: postgres
+              - name: api
+                image: alpine
+                depends_on: [db]
+              - name: web
+                image: alpine
+                depends_on: [api, db]
+            "#,
+        )?;
+        config.validate()?;
+
+        // Unknown and self dependencies
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: api
+                image: alpine
+                depends_on: [api, db]
+            "#,
+        )?;
+        assert!(matches!(
+            config.validate(),
+            Err(MonocoreError::ConfigValidationErrors(errors)) if errors.len() == 2
+        ));
+
+        // Cycles
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: a
+                image: alpine
+                depends_on: [c]
+              - name: b
+                image: alpine
+                depends_on: [a]
+              - name: c
+                image: alpine
+                depends_on: [b]
+            "#,
+        )?;
+        assert!(matches!(
+            config.validate(),
+            Err(MonocoreError::ConfigValidation(e)) if e == "dependency cycle: a -> c -> b -> a"
+        ));
+
+        // Waiting for a dependency to be healthy needs it to have a healthcheck
+        let yaml = r#"
+            sandboxes:
+              - name: db
+                image: postgres
+              - name: api
+                image: alpine
+                depends_on:
+                  - name: db
+                    condition: healthy
+            "#;
+        let config: Monocore = serde_yaml::from_str(yaml)?;
+        let dependency = &config
+            .get_sandbox("api")
+            .unwrap()
+            .get_depends_on()
+            .as_ref()
+            .unwrap()[0];
+        assert_eq!(dependency.get_condition(), &DependencyCondition::Healthy);
+        assert!(config.validate().is_err());
+
+        let config: Monocore = serde_yaml::from_str(&yaml.replace(
+            "image: postgres",
+            "image: postgres\n                healthcheck: { tcp: 5432 }",
+        ))?;
+        config.validate()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_monocore_validate_names_groups_and_depth() -> anyhow::Result<()> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: api
+                image: alpine
+                groups:
+                  backend: {}
+              - name: api
+                image: alpine
+            "#,
+        )?;
+        let Err(MonocoreError::ConfigValidationErrors(errors)) = config.validate() else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(
+            errors,
+            [
+                "sandbox 'api' belongs to unknown group 'backend'",
+                "duplicate sandbox name 'api'"
+            ]
+        );
+
+        // A chain one longer than the maximum depth
+        let image: Reference = "alpine".parse()?;
+        let sandboxes = (0..=Monocore::MAX_DEPENDENCY_DEPTH)
+            .map(|i| {
+                Sandbox::builder()
+                    .name(format!("s{i}"))
+                    .image(image.clone())
+                    .depends_on((i > 0).then(|| vec![format!("s{}", i - 1).into()]))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let config = MonocoreBuilder::new()
+            .sandboxes(sandboxes[..Monocore::MAX_DEPENDENCY_DEPTH].to_vec())
+            .build_unchecked();
+        config.validate()?;
+
+        let config = MonocoreBuilder::new()
+            .sandboxes(sandboxes)
+            .build_unchecked();
+        assert!(matches!(
+            config.validate(),
+            Err(MonocoreError::ConfigValidation(e)) if e.starts_with("dependency chain")
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_monocore_validate_group_networks() -> anyhow::Result<()> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: api
+                image: alpine
+                groups:
+                  backend: { network: { ip: 10.1.0.2 } }
+              - name: db
+                image: alpine
+                groups:
+                  backend: { network: { ip: 10.1.0.2 } }
+              - name: cache
+                image: alpine
+                groups:
+                  backend: { network: { ip: 10.1.0.255 } }
+              - name: web
+                image: alpine
+                groups:
+                  frontend: { network: { ip: 10.0.0.2 } }
+                  backend: {}
+            groups:
+              - name: backend
+                network: { subnet: 10.1.0.0/24 }
+              - name: frontend
+              - name: admin
+                network: { subnet: 10.1.0.128/25 }
+            "#,
+        )?;
+        let Err(MonocoreError::ConfigValidationErrors(mut errors)) = config.validate() else {
+            panic!("expected validation errors");
+        };
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                "ip 10.1.0.255 of sandbox 'cache' is not a host address in subnet 10.1.0.0/24 of \
+                 group 'backend'",
+                "sandbox 'web' belongs to more than one group",
+                "sandbox 'web' has a static ip in group 'frontend', which has no subnet",
+                "sandboxes 'api' and 'db' have the same ip 10.1.0.2 in group 'backend'",
+                "subnet 10.1.0.128/25 of group 'admin' overlaps subnet 10.1.0.0/24 of group \
+                 'backend'",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_monocore_validate_proxies() -> anyhow::Result<()> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: api
+                image: alpine
+                ports: ["8080:80"]
+                proxy: { type: legacy, keep_alive: 30s, concurrency: 4 }
+              - name: web
+                image: alpine
+                proxy: { type: legacy, prefix: /api/, port: "3000:3000", keep_alive: a while }
+              - name: worker
+                image: alpine
+                proxy: { type: legacy, concurrency: 0 }
+            "#,
+        )?;
+        assert_eq!(
+            config.get_sandbox("web").unwrap().get_proxy_prefix(),
+            Some("api".to_string())
+        );
+
+        let Err(MonocoreError::ConfigValidationErrors(mut errors)) = config.validate() else {
+            panic!("expected validation errors");
+        };
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                "proxy of sandbox 'web' has an invalid keep alive 'a while'",
+                "proxy of sandbox 'worker' has a concurrency of 0",
+                "proxy of sandbox 'worker' has no port to forward to",
+                "sandboxes 'api' and 'web' are proxied under the same prefix '/api'",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -282,6 +282,30 @@ pub enum MonocoreError {
     #[error("feature not yet implemented: {0}")]
     NotImplemented(String),
 
+    /// An error that occurred when a sandbox was moved to a status it can't reach from its current one
+    #[error("sandbox '{name}' cannot go from {from} to {to}")]
+    InvalidSandboxTransition {
+        /// The name of the sandbox
+        name: String,
+        /// The current status of the sandbox
+        from: String,
+        /// The status the sandbox was to be moved to
+        to: String,
+    },
+
+    /// An error that occurred when a sandbox that was waited for didn't become healthy
+    #[error("sandbox '{0}' did not become healthy: {1}")]
+    SandboxNotHealthy(String, String),
+
+    /// An error that occurred when a group couldn't be given a subnet or a sandbox an address in
+    /// its group
+    #[error("ip allocation error: {0}")]
+    IpAllocation(String),
+
+    /// An error that occurred when the network policy of a sandbox couldn't be applied
+    #[error("network policy error: {0}")]
+    NetworkPolicy(String),
+
     /// An error that occurred when a CID error occurred
     #[error("CID error: {0}")]
     CidError(#[from] ipld::cid::Error),
@@ -4,7 +4,7 @@ use oci_spec::image::{ImageConfiguration, ImageIndex, ImageManifest, MediaType,
 use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
 use tokio::fs;
 
-use crate::MonocoreResult;
+use crate::{MonocoreError, MonocoreResult};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -19,6 +19,29 @@ pub static OCI_DB_MIGRATOR: Migrator = sqlx::migrate!("lib/management/migrations
 /// Migrator for the monoimage database
 pub static MONOIMAGE_DB_MIGRATOR: Migrator = sqlx::migrate!("lib/management/migrations/monoimage");
 
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The parts of a pulled image's configuration a sandbox is run with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ImageRunConfig {
+    /// The digests of the image layers, ordered from the base layer up.
+    pub(crate) layer_digests: Vec<String>,
+
+    /// The entrypoint of the image.
+    pub(crate) entrypoint: Vec<String>,
+
+    /// The default arguments to the entrypoint.
+    pub(crate) cmd: Vec<String>,
+
+    /// The environment variables of the image in `KEY=VALUE` format.
+    pub(crate) env: Vec<String>,
+
+    /// The working directory of the image.
+    pub(crate) working_dir: Option<String>,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -430,6 +453,89 @@ pub(crate) async fn image_exists(pool: &Pool<Sqlite>, reference: &str) -> Monoco
     Ok(record.get::<i64, _>("count") > 0)
 }
 
+/// Gets the configuration a pulled image is run with.
+///
+/// The layers are found through the diff IDs of the image configuration since layers shared
+/// between images are only recorded against the manifest that last pulled them.
+///
+/// ## Arguments
+///
+/// * `pool` - The database connection pool
+/// * `reference` - The reference string of the image
+pub(crate) async fn get_image_run_config(
+    pool: &Pool<Sqlite>,
+    reference: &str,
+) -> MonocoreResult<Option<ImageRunConfig>> {
+    let record = sqlx::query(
+        r#"
+        SELECT c.config_entrypoint_json, c.config_cmd_json, c.config_env_json,
+            c.config_working_dir, c.rootfs_diff_ids_json
+        FROM configs c
+        JOIN manifests m ON c.manifest_id = m.id
+        JOIN images i ON m.image_id = i.id
+        WHERE i.reference = ?
+        ORDER BY c.id DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(reference)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(record) = record else {
+        return Ok(None);
+    };
+
+    let parse_list = |column: &str| -> MonocoreResult<Vec<String>> {
+        let json = record.get::<Option<String>, _>(column);
+        Ok(match json.as_deref() {
+            Some(json) if !json.is_empty() => {
+                serde_json::from_str::<Option<Vec<String>>>(json)?.unwrap_or_default()
+            }
+            _ => Vec::new(),
+        })
+    };
+
+    let mut config = ImageRunConfig {
+        entrypoint: parse_list("config_entrypoint_json")?,
+        cmd: parse_list("config_cmd_json")?,
+        env: parse_list("config_env_json")?,
+        working_dir: record
+            .get::<Option<String>, _>("config_working_dir")
+            .filter(|dir| !dir.is_empty()),
+        ..Default::default()
+    };
+
+    let diff_ids = record
+        .get::<Option<String>, _>("rootfs_diff_ids_json")
+        .unwrap_or_default();
+    for diff_id in diff_ids.split(',').filter(|id| !id.is_empty()) {
+        let digest = sqlx::query(
+            r#"
+            SELECT digest
+            FROM layers
+            WHERE diff_id = ?
+            LIMIT 1
+            "#,
+        )
+        .bind(diff_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match digest {
+            Some(digest) => config.layer_digests.push(digest.get("digest")),
+            None => {
+                return Err(MonocoreError::LayerExtraction(format!(
+                    "layer {} of image {} was not pulled",
+                    diff_id, reference
+                )))
+            }
+        }
+    }
+
+    Ok(Some(config))
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
@@ -474,6 +580,10 @@ mod tests {
             table_names.contains(&"sandbox_metrics".to_string()),
             "sandbox_metrics table not found"
         );
+        assert!(
+            table_names.contains(&"sandbox_transitions".to_string()),
+            "sandbox_transitions table not found"
+        );
 
         Ok(())
     }
@@ -0,0 +1,609 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use sqlx::{Pool, Row, Sqlite};
+use tokio::{fs, net::UdpSocket, time};
+
+use crate::{
+    config::Sandbox,
+    utils::path::{MONOCORE_ENV_DIR, SANDBOX_DB_FILENAME},
+    MonocoreResult,
+};
+
+use super::{
+    db::{self, SANDBOX_DB_MIGRATOR},
+    lifecycle::ACTIVE_STATUSES,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The port the DNS responder listens on by default.
+pub const DEFAULT_DNS_PORT: u16 = 53;
+
+/// How long resolvers can cache the addresses of sandboxes, in seconds. It is kept short as
+/// sandboxes come and go.
+pub const DNS_RECORD_TTL: u32 = 5;
+
+/// How often the DNS responder reloads the addresses of sandboxes.
+pub const DNS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the DNS responder waits for the host resolver to answer a forwarded query.
+pub const DNS_FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The file the host resolver is read from.
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// The largest DNS message the responder reads.
+const MAX_DNS_MESSAGE_LEN: usize = 4096;
+
+/// The length of the header of a DNS message.
+const DNS_HEADER_LEN: usize = 12;
+
+/// The record type of IPv4 addresses.
+const DNS_TYPE_A: u16 = 1;
+
+/// The record type of queries for records of any type.
+const DNS_TYPE_ANY: u16 = 255;
+
+/// The internet record class.
+const DNS_CLASS_IN: u16 = 1;
+
+/// The response code of a query the responder couldn't parse.
+const DNS_RCODE_FORMERR: u8 = 1;
+
+/// The response code of a query the responder couldn't answer.
+const DNS_RCODE_SERVFAIL: u8 = 2;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The addresses the DNS responder answers with, by lowercase name. It is shared with the
+/// responder, so replacing the records changes the answers to the queries that follow.
+#[derive(Debug, Clone, Default)]
+pub struct DnsRecords(Arc<RwLock<HashMap<String, Vec<Ipv4Addr>>>>);
+
+/// A DNS responder answering A queries for the names of sandboxes with their addresses, and
+/// forwarding all other queries to the host resolver.
+///
+/// ## Example
+///
+/// ```no_run
+/// use monocore::management::{DnsRecords, DnsServer};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let records = DnsRecords::default();
+/// let server = DnsServer::bind("127.0.0.1:5300".parse()?, records.clone(), None).await?;
+///
+/// records.replace([("api".to_string(), vec!["10.0.0.1".parse()?])].into());
+/// server.run().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DnsServer {
+    /// The socket queries are received on.
+    socket: Arc<UdpSocket>,
+
+    /// The addresses answered with.
+    records: DnsRecords,
+
+    /// The resolver the queries for other names are forwarded to.
+    upstream: Option<SocketAddr>,
+}
+
+/// The parts of a DNS query the responder looks at.
+#[derive(Debug)]
+struct DnsQuery {
+    /// The lowercase name asked about, without the trailing dot.
+    name: String,
+
+    /// The record type asked for.
+    qtype: u16,
+
+    /// The record class asked for.
+    qclass: u16,
+
+    /// Where the question ends in the message.
+    question_end: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl DnsRecords {
+    /// Replaces the records with new ones.
+    pub fn replace(&self, records: HashMap<String, Vec<Ipv4Addr>>) {
+        let records = records
+            .into_iter()
+            .map(|(name, ips)| (normalize_name(&name), ips))
+            .collect();
+        *self.0.write().unwrap() = records;
+    }
+
+    /// Returns the addresses of a name, or None if there is no record for it.
+    pub fn lookup(&self, name: &str) -> Option<Vec<Ipv4Addr>> {
+        self.0.read().unwrap().get(&normalize_name(name)).cloned()
+    }
+}
+
+impl DnsServer {
+    /// Binds a DNS responder to a UDP address.
+    ///
+    /// ## Arguments
+    ///
+    /// * `addr` - The address to listen on
+    /// * `records` - The addresses to answer with
+    /// * `upstream` - The resolver to forward queries for other names to. Such queries fail if
+    ///   it isn't set
+    pub async fn bind(
+        addr: SocketAddr,
+        records: DnsRecords,
+        upstream: Option<SocketAddr>,
+    ) -> MonocoreResult<Self> {
+        Ok(Self {
+            socket: Arc::new(UdpSocket::bind(addr).await?),
+            records,
+            upstream,
+        })
+    }
+
+    /// Returns the address the responder listens on.
+    pub fn local_addr(&self) -> MonocoreResult<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Answers queries until receiving one fails. Malformed queries, and queries the responder
+    /// doesn't handle like those with several questions, are refused as malformed.
+    pub async fn run(self) -> MonocoreResult<()> {
+        let mut buf = vec![0; MAX_DNS_MESSAGE_LEN];
+        loop {
+            let (len, client) = self.socket.recv_from(&mut buf).await?;
+            let message = &buf[..len];
+
+            let response = match parse_query(message) {
+                // Names of sandboxes are answered even for record types they have none of, so
+                // the query isn't sent on to resolvers that don't know them
+                Some(query) => self.records.lookup(&query.name).map(|ips| {
+                    let ips = match (query.qtype, query.qclass) {
+                        (DNS_TYPE_A | DNS_TYPE_ANY, DNS_CLASS_IN) => ips,
+                        _ => Vec::new(),
+                    };
+                    build_response(message, &query, &ips)
+                }),
+                None => match build_error_response(message, DNS_RCODE_FORMERR) {
+                    Some(response) => Some(response),
+                    None => continue,
+                },
+            };
+
+            if let Some(response) = response {
+                if let Err(e) = self.socket.send_to(&response, client).await {
+                    tracing::warn!("failed to answer dns query from {}: {}", client, e);
+                }
+                continue;
+            }
+
+            let socket = self.socket.clone();
+            let upstream = self.upstream;
+            let message = message.to_vec();
+            tokio::spawn(async move {
+                let response = match upstream {
+                    Some(upstream) => forward_query(&message, upstream).await,
+                    None => None,
+                };
+
+                let Some(response) =
+                    response.or_else(|| build_error_response(&message, DNS_RCODE_SERVFAIL))
+                else {
+                    return;
+                };
+                if let Err(e) = socket.send_to(&response, client).await {
+                    tracing::warn!("failed to answer dns query from {}: {}", client, e);
+                }
+            });
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Runs a DNS responder for the sandboxes of a project until interrupted.
+///
+/// The responder answers with the addresses the active sandboxes of the project hold in their
+/// groups. Each sandbox is known by its name and by the domains configured for it in its group.
+/// The addresses are reloaded every [`DNS_REFRESH_INTERVAL`], so sandboxes are resolvable soon
+/// after they start and stop being so soon after they stop. Queries for other names are
+/// forwarded to the first nameserver of the host.
+///
+/// ## Arguments
+///
+/// * `project_path` - The path to the project, or the current directory if None
+/// * `addr` - The address to listen on
+pub async fn serve_dns(project_path: Option<PathBuf>, addr: SocketAddr) -> MonocoreResult<()> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+
+    // The responder must not forward queries to itself
+    let upstream = get_host_resolver()
+        .await
+        .filter(|upstream| *upstream != addr);
+    if upstream.is_none() {
+        tracing::warn!("no host resolver found, only sandbox names will be resolved");
+    }
+
+    let records = DnsRecords::default();
+    records.replace(get_dns_records(&pool).await?);
+
+    let server = DnsServer::bind(addr, records.clone(), upstream).await?;
+    tracing::info!("dns responder listening on {}", server.local_addr()?);
+
+    let refresh = async {
+        loop {
+            time::sleep(DNS_REFRESH_INTERVAL).await;
+            match get_dns_records(&pool).await {
+                Ok(latest) => records.replace(latest),
+                Err(e) => tracing::error!("failed to reload dns records: {}", e),
+            }
+        }
+    };
+
+    tokio::select! {
+        result = server.run() => result,
+        _ = refresh => Ok(()),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+/// Returns the addresses of the active sandboxes holding one in their group, by their names and
+/// by the domains configured for them in the group. A name claimed by several sandboxes has the
+/// addresses of all of them.
+pub async fn get_dns_records(
+    pool: &Pool<Sqlite>,
+) -> MonocoreResult<HashMap<String, Vec<Ipv4Addr>>> {
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT s.name, s.group_ip, s.config, g.name AS group_name
+        FROM sandboxes s
+        LEFT JOIN groups g ON g.id = s.group_id
+        WHERE s.group_ip IS NOT NULL AND s.status IN ({})
+            AND s.id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+        "#,
+        ACTIVE_STATUSES
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut records: HashMap<String, Vec<Ipv4Addr>> = HashMap::new();
+    for row in rows {
+        let name: String = row.get("name");
+        let Ok(ip) = row.get::<&str, _>("group_ip").parse::<Ipv4Addr>() else {
+            tracing::warn!("sandbox {} holds an invalid address", name);
+            continue;
+        };
+
+        let group_name: Option<String> = row.get("group_name");
+        let config = row
+            .get::<Option<&str>, _>("config")
+            .and_then(|config| serde_json::from_str::<Sandbox>(config).ok());
+        let domains = config
+            .as_ref()
+            .zip(group_name.as_ref())
+            .and_then(|(sandbox, group_name)| sandbox.get_groups().as_ref()?.get(group_name))
+            .and_then(|group| group.get_network().as_ref()?.get_domains().clone())
+            .unwrap_or_default();
+
+        for name in std::iter::once(name).chain(domains) {
+            let ips = records.entry(normalize_name(&name)).or_default();
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the first nameserver in the resolver configuration of the host.
+async fn get_host_resolver() -> Option<SocketAddr> {
+    let resolv_conf = fs::read_to_string(RESOLV_CONF_PATH).await.ok()?;
+    resolv_conf.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("nameserver") => words
+                .next()?
+                .parse::<IpAddr>()
+                .ok()
+                .map(|ip| SocketAddr::new(ip, DEFAULT_DNS_PORT)),
+            _ => None,
+        }
+    })
+}
+
+/// Sends a query to a resolver and returns its answer, or None if it didn't answer in time.
+async fn forward_query(message: &[u8], upstream: SocketAddr) -> Option<Vec<u8>> {
+    let bind_addr: SocketAddr = match upstream {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let forward = async {
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(upstream).await?;
+        socket.send(message).await?;
+
+        // Answers to other queries can't arrive on the socket, but stray packets can
+        let mut buf = vec![0; MAX_DNS_MESSAGE_LEN];
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            if len >= 2 && buf[..2] == message[..2] {
+                buf.truncate(len);
+                return Ok::<_, std::io::Error>(buf);
+            }
+        }
+    };
+
+    match time::timeout(DNS_FORWARD_TIMEOUT, forward).await {
+        Ok(Ok(response)) => Some(response),
+        Ok(Err(e)) => {
+            tracing::warn!("failed to forward dns query to {}: {}", upstream, e);
+            None
+        }
+        Err(_) => {
+            tracing::warn!("dns query forwarded to {} timed out", upstream);
+            None
+        }
+    }
+}
+
+/// Parses a standard query with a single question, returning None for anything else.
+fn parse_query(message: &[u8]) -> Option<DnsQuery> {
+    if message.len() < DNS_HEADER_LEN {
+        return None;
+    }
+
+    // Responses, opcodes other than a standard query and multiple questions aren't handled
+    let is_response = message[2] & 0x80 != 0;
+    let opcode = (message[2] >> 3) & 0x0f;
+    let qdcount = u16::from_be_bytes([message[4], message[5]]);
+    if is_response || opcode != 0 || qdcount != 1 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut offset = DNS_HEADER_LEN;
+    loop {
+        let len = *message.get(offset)? as usize;
+        offset += 1;
+        if len == 0 {
+            break;
+        }
+
+        // Names in questions are never compressed, so pointers aren't followed
+        if len > 63 {
+            return None;
+        }
+
+        let label = message.get(offset..offset + len)?;
+        labels.push(String::from_utf8_lossy(label).to_lowercase());
+        offset += len;
+    }
+
+    let fields = message.get(offset..offset + 4)?;
+    Some(DnsQuery {
+        name: labels.join("."),
+        qtype: u16::from_be_bytes([fields[0], fields[1]]),
+        qclass: u16::from_be_bytes([fields[2], fields[3]]),
+        question_end: offset + 4,
+    })
+}
+
+/// Builds an authoritative answer to a query with A records for the given addresses.
+fn build_response(message: &[u8], query: &DnsQuery, ips: &[Ipv4Addr]) -> Vec<u8> {
+    let mut response = Vec::with_capacity(query.question_end + ips.len() * 16);
+    response.extend_from_slice(&message[..2]);
+
+    // Response with the opcode and recursion desired flag of the query, authoritative, recursion
+    // available and no error
+    response.push(0x80 | 0x04 | (message[2] & 0x01));
+    response.push(0x80);
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&(ips.len() as u16).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(&message[DNS_HEADER_LEN..query.question_end]);
+
+    for ip in ips {
+        // The name is a pointer to the one in the question
+        response.extend_from_slice(&[0xc0, DNS_HEADER_LEN as u8]);
+        response.extend_from_slice(&DNS_TYPE_A.to_be_bytes());
+        response.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&DNS_RECORD_TTL.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&ip.octets());
+    }
+
+    response
+}
+
+/// Builds a response with no records and an error code, or None if the message is too short to
+/// answer or is a response itself.
+fn build_error_response(message: &[u8], rcode: u8) -> Option<Vec<u8>> {
+    if message.len() < DNS_HEADER_LEN || message[2] & 0x80 != 0 {
+        return None;
+    }
+
+    let mut response = Vec::with_capacity(DNS_HEADER_LEN);
+    response.extend_from_slice(&message[..2]);
+    response.push(0x80 | (message[2] & 0x79));
+    response.push(0x80 | rcode);
+    response.extend_from_slice(&[0; 8]);
+
+    Some(response)
+}
+
+/// Returns a name in lowercase without its trailing dot.
+fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use crate::{
+        config::Monocore,
+        management::{ipam, lifecycle, SandboxStatus},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dns_server_answers_and_forwards_queries() -> anyhow::Result<()> {
+        // A resolver answering every query with the same canned answer
+        let upstream = UdpSocket::bind("127.0.0.1:0").await?;
+        let upstream_addr = upstream.local_addr()?;
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            while let Ok((len, client)) = upstream.recv_from(&mut buf).await {
+                let mut response = buf[..len].to_vec();
+                response[2] |= 0x80;
+                response.extend_from_slice(b"upstream");
+                let _ = upstream.send_to(&response, client).await;
+            }
+        });
+
+        let records = DnsRecords::default();
+        records.replace([("api".to_string(), vec![Ipv4Addr::new(10, 0, 0, 1)])].into());
+        let server =
+            DnsServer::bind("127.0.0.1:0".parse()?, records.clone(), Some(upstream_addr)).await?;
+        let server_addr = server.local_addr()?;
+        tokio::spawn(server.run());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await?;
+        client.connect(server_addr).await?;
+        let query = |name: &str, qtype: u16| {
+            let client = &client;
+            let message = make_query(name, qtype);
+            async move {
+                client.send(&message).await?;
+                let mut buf = [0; 512];
+                let len = time::timeout(Duration::from_secs(5), client.recv(&mut buf)).await??;
+                anyhow::Ok((message, buf[..len].to_vec()))
+            }
+        };
+
+        // Names of sandboxes are answered with their addresses, whatever their case
+        let (message, response) = query("API.", DNS_TYPE_A).await?;
+        assert_eq!(response[..2], message[..2]);
+        assert_eq!(response[3] & 0x0f, 0);
+        assert_eq!(u16::from_be_bytes([response[6], response[7]]), 1);
+        assert_eq!(response[response.len() - 4..], [10, 0, 0, 1]);
+
+        // Other record types of sandbox names have no answers
+        let (_, response) = query("api", 28).await?;
+        assert_eq!(response[3] & 0x0f, 0);
+        assert_eq!(u16::from_be_bytes([response[6], response[7]]), 0);
+
+        // Other names are forwarded
+        let (_, response) = query("example.com", DNS_TYPE_A).await?;
+        assert!(response.ends_with(b"upstream"));
+
+        // Records are updated live
+        records.replace([("web".to_string(), vec![Ipv4Addr::new(10, 0, 1, 1)])].into());
+        let (_, response) = query("api", DNS_TYPE_A).await?;
+        assert!(response.ends_with(b"upstream"));
+        let (_, response) = query("web", DNS_TYPE_A).await?;
+        assert_eq!(response[response.len() - 4..], [10, 0, 1, 1]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_dns_records() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            groups:
+              - name: backend
+            sandboxes:
+              - name: api
+                image: alpine
+                groups:
+                  backend:
+                    network:
+                      domains: [api.internal, Service.Internal]
+              - { name: worker, image: alpine, groups: { backend: {} } }
+              - { name: cli, image: alpine }
+            "#,
+        )?;
+        config.validate()?;
+
+        for name in ["api", "worker", "cli"] {
+            let sandbox = config.get_sandbox(name).unwrap();
+            lifecycle::create_sandbox_record(
+                &pool,
+                name,
+                Path::new("/rootfs"),
+                &serde_json::to_string(sandbox)?,
+            )
+            .await?;
+            ipam::assign_sandbox_ip(&pool, &config, sandbox).await?;
+            lifecycle::transition_sandbox(&pool, name, SandboxStatus::Starting).await?;
+        }
+
+        // Stopped sandboxes aren't resolvable
+        lifecycle::transition_sandbox(&pool, "worker", SandboxStatus::Stopping).await?;
+        lifecycle::transition_sandbox(&pool, "worker", SandboxStatus::Exited(0)).await?;
+
+        let records = get_dns_records(&pool).await?;
+        let api = vec![Ipv4Addr::new(10, 0, 0, 1)];
+        assert_eq!(
+            records,
+            [
+                ("api".to_string(), api.clone()),
+                ("api.internal".to_string(), api.clone()),
+                ("service.internal".to_string(), api),
+            ]
+            .into()
+        );
+
+        Ok(())
+    }
+
+    /// Returns a standard query for a record of a name.
+    fn make_query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.trim_end_matches('.').split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.push(0);
+        message.extend_from_slice(&qtype.to_be_bytes());
+        message.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        message
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use getset::Getters;
+use sqlx::{Pool, Row, Sqlite};
+use tokio::time;
+
+use crate::{MonocoreError, MonocoreResult};
+
+use super::lifecycle;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The number of health check results kept for each sandbox.
+pub const HEALTH_HISTORY_LEN: i64 = 10;
+
+/// How often the health of a sandbox is checked while waiting for it to be healthy.
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The health of a sandbox with a healthcheck.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// No check has passed yet and not enough have failed to make the sandbox unhealthy.
+    Starting,
+
+    /// The last check passed.
+    Healthy,
+
+    /// The last checks failed as many times in a row as the healthcheck allows.
+    Unhealthy,
+}
+
+/// The result of a health check of a sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct HealthCheckRecord {
+    /// Whether the check passed.
+    passed: bool,
+
+    /// What the probe reported.
+    output: String,
+
+    /// When the check was made, in UTC.
+    timestamp: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl HealthStatus {
+    /// Returns the name the health is stored under.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Healthy => "healthy",
+            Self::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Sets the health of a sandbox without recording a check, like when its checks start.
+pub(crate) async fn set_sandbox_health(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    health: HealthStatus,
+) -> MonocoreResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE sandboxes
+        SET health = ?
+        WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        "#,
+    )
+    .bind(health.as_str())
+    .bind(name)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Records the result of a health check of a sandbox along with the health it leaves the
+/// sandbox in. Only the last [`HEALTH_HISTORY_LEN`] results are kept.
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+/// * `name` - The name of the sandbox
+/// * `passed` - Whether the check passed
+/// * `output` - What the probe reported
+/// * `health` - The health of the sandbox after the check
+pub(crate) async fn record_health_check(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    passed: bool,
+    output: &str,
+    health: HealthStatus,
+) -> MonocoreResult<()> {
+    let mut tx = pool.begin().await?;
+    let Some(row) = sqlx::query(
+        r#"
+        UPDATE sandboxes
+        SET health = ?
+        WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        RETURNING id
+        "#,
+    )
+    .bind(health.as_str())
+    .bind(name)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Err(MonocoreError::ServiceNotFound(name.to_string()));
+    };
+    let id = row.get::<i64, _>("id");
+
+    sqlx::query("INSERT INTO sandbox_health_checks (sandbox_id, passed, output) VALUES (?, ?, ?)")
+        .bind(id)
+        .bind(passed)
+        .bind(output)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM sandbox_health_checks
+        WHERE sandbox_id = ?1 AND id NOT IN (
+            SELECT id FROM sandbox_health_checks WHERE sandbox_id = ?1 ORDER BY id DESC LIMIT ?2
+        )
+        "#,
+    )
+    .bind(id)
+    .bind(HEALTH_HISTORY_LEN)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Returns the recorded health check results of a sandbox, oldest first.
+pub async fn get_health_checks(
+    pool: &Pool<Sqlite>,
+    name: &str,
+) -> MonocoreResult<Vec<HealthCheckRecord>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT h.passed, h.output, datetime(h.timestamp) AS timestamp
+        FROM sandbox_health_checks h
+        WHERE h.sandbox_id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        ORDER BY h.id
+        "#,
+    )
+    .bind(name)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| HealthCheckRecord {
+            passed: row.get("passed"),
+            output: row.get("output"),
+            timestamp: row.get("timestamp"),
+        })
+        .collect())
+}
+
+/// Waits for a sandbox to be healthy.
+///
+/// ## Returns
+///
+/// An error if the sandbox becomes unhealthy or stops before it is healthy.
+pub(crate) async fn wait_for_healthy(pool: &Pool<Sqlite>, name: &str) -> MonocoreResult<()> {
+    tracing::info!("waiting for sandbox {} to be healthy", name);
+    loop {
+        let state = lifecycle::get_sandbox_state(pool, name)
+            .await?
+            .ok_or_else(|| MonocoreError::ServiceNotFound(name.to_string()))?;
+
+        match state.get_health() {
+            Some(HealthStatus::Healthy) => return Ok(()),
+            Some(HealthStatus::Unhealthy) => {
+                return Err(MonocoreError::SandboxNotHealthy(
+                    name.to_string(),
+                    "it is unhealthy".to_string(),
+                ))
+            }
+            _ if !state.get_status().is_active() => {
+                return Err(MonocoreError::SandboxNotHealthy(
+                    name.to_string(),
+                    format!("it is {}", state.get_status()),
+                ))
+            }
+            _ => time::sleep(HEALTH_POLL_INTERVAL).await,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for HealthStatus {
+    type Err = MonocoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "starting" => Ok(Self::Starting),
+            "healthy" => Ok(Self::Healthy),
+            "unhealthy" => Ok(Self::Unhealthy),
+            _ => Err(MonocoreError::InvalidArgument(format!(
+                "unknown sandbox health '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use crate::management::{db, SandboxStatus, SANDBOX_DB_MIGRATOR};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_records_checks_and_waits_for_healthy() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+
+        lifecycle::create_sandbox_record(&pool, "db", Path::new("/rootfs/db"), "{}").await?;
+        lifecycle::transition_sandbox(&pool, "db", SandboxStatus::Starting).await?;
+        set_sandbox_health(&pool, "db", HealthStatus::Starting).await?;
+
+        for i in 0..HEALTH_HISTORY_LEN + 2 {
+            let output = format!("connection refused {}", i);
+            record_health_check(&pool, "db", false, &output, HealthStatus::Starting).await?;
+        }
+
+        // Only the latest results are kept
+        let checks = get_health_checks(&pool, "db").await?;
+        assert_eq!(checks.len() as i64, HEALTH_HISTORY_LEN);
+        assert_eq!(checks[0].get_output(), "connection refused 2");
+
+        // Waiting returns once a check passes
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { wait_for_healthy(&pool, "db").await }
+        });
+        record_health_check(&pool, "db", true, "connected", HealthStatus::Healthy).await?;
+        waiter.await??;
+
+        let state = lifecycle::get_sandbox_state(&pool, "db").await?.unwrap();
+        assert_eq!(state.get_health(), &Some(HealthStatus::Healthy));
+
+        // An unhealthy sandbox fails the wait
+        record_health_check(&pool, "db", false, "timed out", HealthStatus::Unhealthy).await?;
+        assert!(matches!(
+            wait_for_healthy(&pool, "db").await,
+            Err(MonocoreError::SandboxNotHealthy(..))
+        ));
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    management::db::{self, OCI_DB_MIGRATOR},
+    management::db::{self, ImageRunConfig, OCI_DB_MIGRATOR},
     oci::{DockerRegistry, OciRegistryPull, Reference},
     utils::{
         env::get_monocore_home_path,
@@ -8,6 +8,7 @@ use crate::{
     MonocoreError, MonocoreResult,
 };
 use futures::future;
+use sqlx::{Pool, Sqlite};
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 use tokio::{fs, process::Command};
@@ -23,7 +24,7 @@ const SANDBOXES_REGISTRY: &str = "sandboxes.io";
 const DOCKER_REGISTRY: &str = "docker.io";
 
 /// The suffix added to extracted layer directories
-const EXTRACTED_LAYER_SUFFIX: &str = "extracted";
+pub(crate) const EXTRACTED_LAYER_SUFFIX: &str = "extracted";
 
 //--------------------------------------------------------------------------------------------------
 // Functions
@@ -206,6 +207,43 @@ pub async fn pull_sandboxes_registry_image_group(_group: &Reference) -> Monocore
     ));
 }
 
+/// Gets the configuration a sandbox runs an image with, pulling the image first if it hasn't been
+/// pulled yet.
+///
+/// Images that fell back to the Docker registry when pulled from the Sandboxes.io registry are
+/// found under their Docker registry reference.
+///
+/// ## Arguments
+///
+/// * `image` - The reference to the image
+pub(crate) async fn get_or_pull_image_config(image: &Reference) -> MonocoreResult<ImageRunConfig> {
+    let db_path = get_monocore_home_path().join(OCI_DB_FILENAME);
+    let pool = db::get_or_create_db_pool(&db_path, &OCI_DB_MIGRATOR).await?;
+
+    let mut references = vec![image.to_string()];
+    if image.get_registry() == SANDBOXES_REGISTRY {
+        let mut docker_ref = image.clone();
+        docker_ref.set_registry(DOCKER_REGISTRY.to_string());
+        references.push(docker_ref.to_string());
+    }
+
+    if let Some(config) = find_image_run_config(&pool, &references).await? {
+        return Ok(config);
+    }
+
+    tracing::info!("image {} has not been pulled, pulling it", image);
+    pull_image(image.clone(), false, false).await?;
+
+    find_image_run_config(&pool, &references)
+        .await?
+        .ok_or_else(|| {
+            MonocoreError::ImageReferenceError(format!(
+                "image {} was not found after pulling it",
+                image
+            ))
+        })
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
@@ -271,6 +309,20 @@ async fn extract_layer(
     Ok(())
 }
 
+/// Finds the run configuration of the first of the references that has been pulled.
+async fn find_image_run_config(
+    pool: &Pool<Sqlite>,
+    references: &[String],
+) -> MonocoreResult<Option<ImageRunConfig>> {
+    for reference in references {
+        if let Some(config) = db::get_image_run_config(pool, reference).await? {
+            return Ok(Some(config));
+        }
+    }
+
+    Ok(None)
+}
+
 /// Collects all layer files in the given directory that start with "sha256:".
 async fn collect_layer_files(dir: impl AsRef<Path>) -> MonocoreResult<Vec<PathBuf>> {
     let mut layer_paths = Vec::new();
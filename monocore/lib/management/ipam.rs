@@ -0,0 +1,583 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::Ipv4Addr,
+};
+
+use ipnetwork::Ipv4Network as Ipv4Net;
+use sqlx::{Pool, Row, Sqlite, SqliteConnection};
+
+use crate::{
+    config::{
+        Group, GroupNetworkConfig, Monocore, Sandbox, DEFAULT_GROUP_PREFIX, DEFAULT_GROUP_SUPERNET,
+    },
+    MonocoreError, MonocoreResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns whether an address can be given to a sandbox in a subnet: it is in the subnet and is
+/// neither its network nor its broadcast address.
+pub fn is_host_address(subnet: Ipv4Net, ip: Ipv4Addr) -> bool {
+    subnet.contains(ip) && ip != subnet.network() && ip != subnet.broadcast()
+}
+
+/// Assigns a sandbox an address in its group and records it on the sandbox, along with the
+/// group and its subnet.
+///
+/// Groups get the subnet set in the configuration, or keep the one they were given before.
+/// Groups without one are given the first free /24 of [`DEFAULT_GROUP_SUPERNET`]. A subnet is
+/// free if it doesn't overlap the subnet set for another group in the configuration, or the
+/// subnet of another group whose sandboxes hold addresses.
+///
+/// Sandboxes get their static IP, or keep the address they held before. Other sandboxes get the
+/// first address of the subnet that isn't held by a sandbox or the static IP of another.
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+/// * `config` - The validated configuration the sandbox is from
+/// * `sandbox` - The sandbox to assign an address to
+///
+/// ## Returns
+///
+/// The address of the sandbox with the prefix of the subnet of its group, like `10.0.0.2/24`, or
+/// None if it doesn't belong to a group.
+pub(crate) async fn assign_sandbox_ip(
+    pool: &Pool<Sqlite>,
+    config: &Monocore,
+    sandbox: &Sandbox,
+) -> MonocoreResult<Option<Ipv4Net>> {
+    let name = sandbox.get_name();
+    let Some((group_name, group_config)) = sandbox.get_groups().iter().flatten().next() else {
+        sqlx::query(
+            r#"
+            UPDATE sandboxes
+            SET group_id = NULL, group_ip = NULL
+            WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+            "#,
+        )
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+        return Ok(None);
+    };
+
+    let group = config.get_group(group_name).ok_or_else(|| {
+        MonocoreError::IpAllocation(format!(
+            "sandbox '{}' belongs to unknown group '{}'",
+            name, group_name
+        ))
+    })?;
+
+    let mut tx = pool.begin().await?;
+    let (group_id, subnet) = assign_group_subnet(&mut tx, config, group, Some(name)).await?;
+
+    // The addresses held by the other sandboxes of the group
+    let held: HashMap<Ipv4Addr, String> = sqlx::query(
+        r#"
+        SELECT name, group_ip FROM sandboxes
+        WHERE group_id = ? AND group_ip IS NOT NULL AND name != ?
+            AND id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+        "#,
+    )
+    .bind(group_id)
+    .bind(name)
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(|row| Ok((parse_ip(row.get("group_ip"))?, row.get("name"))))
+    .collect::<MonocoreResult<_>>()?;
+
+    let ip = match group_config
+        .get_network()
+        .as_ref()
+        .and_then(|n| *n.get_ip())
+    {
+        Some(ip) => {
+            if !is_host_address(subnet, ip) {
+                return Err(MonocoreError::IpAllocation(format!(
+                    "ip {} of sandbox '{}' is not a host address in subnet {} of group '{}'",
+                    ip, name, subnet, group_name
+                )));
+            }
+
+            if let Some(holder) = held.get(&ip) {
+                return Err(MonocoreError::IpAllocation(format!(
+                    "ip {} of sandbox '{}' is held by sandbox '{}'",
+                    ip, name, holder
+                )));
+            }
+
+            ip
+        }
+        None => {
+            // Static IPs are kept free for their sandboxes even before they are started
+            let reserved: HashSet<Ipv4Addr> = config
+                .get_sandboxes()
+                .iter()
+                .flatten()
+                .filter(|other| other.get_name() != name)
+                .filter_map(|other| other.get_groups().as_ref()?.get(group_name))
+                .filter_map(|other| *other.get_network().as_ref()?.get_ip())
+                .collect();
+            let is_free = |ip: &Ipv4Addr| {
+                is_host_address(subnet, *ip) && !held.contains_key(ip) && !reserved.contains(ip)
+            };
+
+            let previous = sqlx::query(
+                r#"
+                SELECT group_ip FROM sandboxes
+                WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?) AND group_id = ?
+                "#,
+            )
+            .bind(name)
+            .bind(group_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .and_then(|row| row.get::<Option<String>, _>("group_ip"))
+            .map(|ip| parse_ip(&ip))
+            .transpose()?;
+
+            previous
+                .filter(is_free)
+                .or_else(|| subnet.iter().find(is_free))
+                .ok_or_else(|| {
+                    MonocoreError::IpAllocation(format!(
+                        "no free address left in subnet {} of group '{}'",
+                        subnet, group_name
+                    ))
+                })?
+        }
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE sandboxes
+        SET group_id = ?, group_ip = ?
+        WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        "#,
+    )
+    .bind(group_id)
+    .bind(ip.to_string())
+    .bind(name)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(
+        Ipv4Net::new(ip, subnet.prefix())
+            .map_err(|e| MonocoreError::IpAllocation(e.to_string()))?,
+    ))
+}
+
+/// Releases the address a sandbox holds in its group, so other sandboxes can be given it. The
+/// sandbox stays recorded as a member of the group.
+pub(crate) async fn release_sandbox_ip(pool: &Pool<Sqlite>, name: &str) -> MonocoreResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE sandboxes
+        SET group_ip = NULL
+        WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        "#,
+    )
+    .bind(name)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Records the subnet of every group in the configuration, choosing the subnets the
+/// configuration doesn't set.
+///
+/// This runs before any sandbox is started, so the network policy of a sandbox covers the subnets
+/// of the groups whose sandboxes haven't been started yet. Groups with a subnet set in the
+/// configuration are recorded first, so the groups given a subnet before move out of their way.
+/// A group that can't be given a subnet keeps the one recorded for it before, and fails once one
+/// of its sandboxes is started.
+pub(crate) async fn assign_group_subnets(
+    pool: &Pool<Sqlite>,
+    config: &Monocore,
+) -> MonocoreResult<()> {
+    let mut groups: Vec<_> = config.get_groups().iter().flatten().collect();
+    groups.sort_by_key(|group| {
+        group
+            .get_network()
+            .as_ref()
+            .and_then(|n| *n.get_subnet())
+            .is_none()
+    });
+
+    let mut tx = pool.begin().await?;
+    for group in groups {
+        if let Err(e) = assign_group_subnet(&mut tx, config, group, None).await {
+            tracing::warn!(
+                "failed to assign a subnet to group {}: {}",
+                group.get_name(),
+                e
+            );
+        }
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Returns the subnets recorded for groups, taking the latest subnet of each group.
+pub(crate) async fn get_group_subnets(pool: &Pool<Sqlite>) -> MonocoreResult<Vec<Ipv4Net>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT subnet FROM groups
+        WHERE id IN (SELECT MAX(id) FROM groups GROUP BY name)
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| parse_subnet(row.get("subnet")))
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Records a group with its subnet, choosing the subnet if the configuration doesn't set one.
+///
+/// ## Arguments
+///
+/// * `conn` - The sandbox database
+/// * `config` - The configuration the group is from
+/// * `group` - The group to record
+/// * `sandbox_name` - The sandbox being given an address in the group, if any, which doesn't keep
+///   the group from changing subnet
+///
+/// ## Returns
+///
+/// The id of the record of the group and its subnet.
+async fn assign_group_subnet(
+    conn: &mut SqliteConnection,
+    config: &Monocore,
+    group: &Group,
+    sandbox_name: Option<&str>,
+) -> MonocoreResult<(i64, Ipv4Net)> {
+    let name = group.get_name();
+    let existing =
+        match sqlx::query("SELECT id, subnet FROM groups WHERE name = ? ORDER BY id DESC LIMIT 1")
+            .bind(name)
+            .fetch_optional(&mut *conn)
+            .await?
+        {
+            Some(row) => Some((row.get::<i64, _>("id"), parse_subnet(row.get("subnet"))?)),
+            None => None,
+        };
+
+    // The subnets of other groups, set in the configuration or held by their sandboxes
+    let mut taken: Vec<(String, Ipv4Net)> = config
+        .get_groups()
+        .iter()
+        .flatten()
+        .filter(|other| other.get_name() != name)
+        .filter_map(|other| {
+            Some((
+                other.get_name().clone(),
+                (*other.get_network().as_ref()?.get_subnet())?,
+            ))
+        })
+        .collect();
+    // The subnets recorded for the other groups in the configuration, which they leave for the
+    // subnets set in the configuration as long as their sandboxes don't hold addresses
+    let mut recorded = Vec::new();
+    let rows = sqlx::query(
+        r#"
+        SELECT g.name, g.subnet, EXISTS (
+            SELECT 1 FROM sandboxes s
+            WHERE s.group_id = g.id AND s.group_ip IS NOT NULL
+                AND s.id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+        ) AS held
+        FROM groups g
+        WHERE g.name != ?
+        "#,
+    )
+    .bind(name)
+    .fetch_all(&mut *conn)
+    .await?;
+    for row in rows {
+        let other = (row.get("name"), parse_subnet(row.get("subnet"))?);
+        if row.get("held") {
+            taken.push(other);
+        } else if config.get_group(&other.0).is_some() {
+            recorded.push(other);
+        }
+    }
+
+    let overlapping = |subnet: Ipv4Net| taken.iter().find(|(_, other)| other.overlaps(subnet));
+    let subnet = match group.get_network().as_ref().and_then(|n| *n.get_subnet()) {
+        Some(subnet) => {
+            if let Some((other_name, other)) = overlapping(subnet) {
+                return Err(MonocoreError::IpAllocation(format!(
+                    "subnet {} of group '{}' overlaps subnet {} of group '{}'",
+                    subnet, name, other, other_name
+                )));
+            }
+
+            subnet
+        }
+        None => {
+            let is_free = |subnet: &Ipv4Net| {
+                overlapping(*subnet).is_none()
+                    && !recorded.iter().any(|(_, other)| other.overlaps(*subnet))
+            };
+
+            existing
+                .map(|(_, subnet)| subnet)
+                .filter(is_free)
+                .or_else(|| get_default_subnets().find(is_free))
+                .ok_or_else(|| {
+                    MonocoreError::IpAllocation(format!(
+                        "no free subnet left in {} for group '{}'",
+                        *DEFAULT_GROUP_SUPERNET, name
+                    ))
+                })?
+        }
+    };
+
+    let reach = GroupNetworkConfig::default_reach()
+        .map(|reach| reach.to_string())
+        .unwrap_or_default();
+    let full_json = serde_json::to_string(group)?;
+    let id = match existing {
+        Some((id, previous)) => {
+            // Addresses held in the previous subnet could clash with those given out from the new
+            if previous != subnet {
+                let held = sqlx::query(
+                    r#"
+                    SELECT name, group_ip FROM sandboxes
+                    WHERE group_id = ? AND group_ip IS NOT NULL AND name IS NOT ?
+                        AND id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+                    "#,
+                )
+                .bind(id)
+                .bind(sandbox_name)
+                .fetch_all(&mut *conn)
+                .await?;
+                for row in held {
+                    let ip = parse_ip(row.get("group_ip"))?;
+                    if !subnet.contains(ip) {
+                        return Err(MonocoreError::IpAllocation(format!(
+                            "group '{}' can't move to subnet {} while sandbox '{}' holds {}",
+                            name,
+                            subnet,
+                            row.get::<&str, _>("name"),
+                            ip
+                        )));
+                    }
+                }
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE groups
+                SET subnet = ?, reach = ?, full_json = ?, modified_at = CURRENT_TIMESTAMP
+                WHERE id = ?
+                "#,
+            )
+            .bind(subnet.to_string())
+            .bind(reach)
+            .bind(full_json)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+            id
+        }
+        None => sqlx::query(
+            r#"
+            INSERT INTO groups (name, subnet, reach, full_json)
+            VALUES (?, ?, ?, ?)
+            RETURNING id
+            "#,
+        )
+        .bind(name)
+        .bind(subnet.to_string())
+        .bind(reach)
+        .bind(full_json)
+        .fetch_one(&mut *conn)
+        .await?
+        .get("id"),
+    };
+
+    Ok((id, subnet))
+}
+
+/// Returns the subnets of [`DEFAULT_GROUP_PREFIX`] in [`DEFAULT_GROUP_SUPERNET`], in order.
+fn get_default_subnets() -> impl Iterator<Item = Ipv4Net> {
+    let supernet = *DEFAULT_GROUP_SUPERNET;
+    let start = u32::from(supernet.network());
+    let count = 1u32 << (DEFAULT_GROUP_PREFIX - supernet.prefix());
+    (0..count).filter_map(move |i| {
+        let network = start + (i << (32 - DEFAULT_GROUP_PREFIX));
+        Ipv4Net::new(Ipv4Addr::from(network), DEFAULT_GROUP_PREFIX).ok()
+    })
+}
+
+/// Parses an address stored in the sandbox database.
+fn parse_ip(ip: &str) -> MonocoreResult<Ipv4Addr> {
+    ip.parse()
+        .map_err(|_| MonocoreError::IpAllocation(format!("invalid recorded ip '{}'", ip)))
+}
+
+/// Parses a subnet stored in the sandbox database.
+fn parse_subnet(subnet: &str) -> MonocoreResult<Ipv4Net> {
+    subnet
+        .parse()
+        .map_err(|_| MonocoreError::IpAllocation(format!("invalid recorded subnet '{}'", subnet)))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use crate::{
+        config::SandboxNetworkReach,
+        management::{db, lifecycle, SANDBOX_DB_MIGRATOR},
+        runtime::{NetworkPolicy, RuleAction},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_assign_and_release_sandbox_ips() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            groups:
+              - name: backend
+                network:
+                  subnet: 10.1.0.0/29
+              - name: frontend
+            sandboxes:
+              - name: db
+                image: alpine
+                groups:
+                  backend:
+                    network:
+                      ip: 10.1.0.2
+              - name: web
+                image: alpine
+                groups:
+                  frontend: {}
+              - name: cli
+                image: alpine
+              - { name: api, image: alpine, groups: { backend: {} } }
+              - { name: worker, image: alpine, groups: { backend: {} } }
+              - { name: a, image: alpine, groups: { backend: {} } }
+              - { name: b, image: alpine, groups: { backend: {} } }
+              - { name: c, image: alpine, groups: { backend: {} } }
+              - { name: d, image: alpine, groups: { backend: {} } }
+              - { name: e, image: alpine, groups: { backend: {} } }
+            "#,
+        )?;
+        config.validate()?;
+
+        let assign = |config: Monocore, name: &'static str| {
+            let pool = pool.clone();
+            async move {
+                lifecycle::create_sandbox_record(&pool, name, Path::new("/rootfs"), "{}").await?;
+                assign_sandbox_ip(&pool, &config, config.get_sandbox(name).unwrap()).await
+            }
+        };
+
+        // The static IP of db is kept free before db is started
+        assert_eq!(
+            assign(config.clone(), "api").await?,
+            Some("10.1.0.1/29".parse()?)
+        );
+        assert_eq!(
+            assign(config.clone(), "worker").await?,
+            Some("10.1.0.3/29".parse()?)
+        );
+        assert_eq!(
+            assign(config.clone(), "db").await?,
+            Some("10.1.0.2/29".parse()?)
+        );
+        assert_eq!(assign(config.clone(), "cli").await?, None);
+
+        // Groups without a subnet get one that doesn't overlap the others
+        assert_eq!(
+            assign(config.clone(), "web").await?,
+            Some("10.0.0.1/24".parse()?)
+        );
+
+        // Public sandboxes in one group can reach the subnets recorded for the others
+        let group_subnets = get_group_subnets(&pool).await?;
+        assert_eq!(
+            group_subnets,
+            vec!["10.1.0.0/29".parse()?, "10.0.0.0/24".parse()?]
+        );
+        let policy = NetworkPolicy::compile(
+            &SandboxNetworkReach::Public,
+            "10.1.0.1/29".parse()?,
+            &group_subnets,
+        );
+        let reach = policy
+            .get_rules()
+            .iter()
+            .find(|rule| rule.get_destination().contains(Ipv4Addr::new(10, 0, 0, 1)))
+            .map(|rule| *rule.get_action());
+        assert_eq!(reach, Some(RuleAction::Accept));
+
+        // Addresses are kept across restarts until they are released
+        assert_eq!(
+            assign(config.clone(), "api").await?,
+            Some("10.1.0.1/29".parse()?)
+        );
+        release_sandbox_ip(&pool, "api").await?;
+        let state = lifecycle::get_sandbox_state(&pool, "api").await?.unwrap();
+        assert_eq!(state.get_group_ip(), &None);
+
+        // The /29 has six host addresses, so only four are left with api released
+        for name in ["a", "b", "c", "d"] {
+            assert!(assign(config.clone(), name).await?.is_some());
+        }
+        assert!(matches!(
+            assign(config.clone(), "e").await,
+            Err(MonocoreError::IpAllocation(_))
+        ));
+
+        // The subnet of a group whose sandboxes hold addresses isn't given to another group, even
+        // once the group is gone from the configuration
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            groups:
+              - name: frontend
+                network:
+                  subnet: 10.1.0.0/16
+            sandboxes:
+              - name: web
+                image: alpine
+                groups:
+                  frontend: {}
+            "#,
+        )?;
+        config.validate()?;
+        assert!(matches!(
+            assign(config, "web").await,
+            Err(MonocoreError::IpAllocation(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,605 @@
+use std::{fmt, net::Ipv4Addr, path::Path, time::Duration};
+
+use getset::Getters;
+use nix::{
+    sys::{
+        signal,
+        wait::{waitpid, WaitPidFlag},
+    },
+    unistd::Pid,
+};
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
+
+use crate::{MonocoreError, MonocoreResult};
+
+use super::HealthStatus;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How long a sandbox can be starting without a supervisor before it is considered failed.
+pub const SANDBOX_START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The statuses of a sandbox that has, or is about to have, running processes.
+pub(crate) const ACTIVE_STATUSES: &str = "'starting', 'running', 'stopping'";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Where a sandbox is in its lifecycle.
+///
+/// A sandbox goes `Created` → `Starting` → `Running` → `Stopping` → `Exited` when all goes well,
+/// and can fail at any point after being created. Exited and failed sandboxes go back to
+/// `Created` when they are started again, or straight to `Starting` when their supervisor
+/// restarts them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxStatus {
+    /// The sandbox has a record but no processes yet.
+    Created,
+
+    /// The supervisor of the sandbox is being started.
+    Starting,
+
+    /// The sandbox is running under its supervisor.
+    Running,
+
+    /// The sandbox has been asked to stop.
+    Stopping,
+
+    /// The sandbox exited with the given exit code.
+    Exited(i32),
+
+    /// The sandbox failed to start or stopped for the given reason.
+    Failed(String),
+}
+
+/// The recorded state of a sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct SandboxState {
+    /// The name of the sandbox.
+    name: String,
+
+    /// The current status of the sandbox.
+    status: SandboxStatus,
+
+    /// The PID of the supervisor of the sandbox.
+    supervisor_pid: Option<u32>,
+
+    /// The PID of the process running the sandbox.
+    microvm_pid: Option<u32>,
+
+    /// The exit code of the last run of the sandbox.
+    exit_code: Option<i32>,
+
+    /// The last error the sandbox failed with.
+    last_error: Option<String>,
+
+    /// The number of times the supervisor has restarted the sandbox since it was started.
+    restart_count: u32,
+
+    /// The health of the sandbox, if it has a healthcheck and has been started.
+    health: Option<HealthStatus>,
+
+    /// The address the sandbox holds in its group, if it belongs to one and hasn't been stopped.
+    group_ip: Option<Ipv4Addr>,
+
+    /// When the sandbox was first created, in UTC.
+    created_at: String,
+
+    /// When the sandbox last changed status, in UTC.
+    modified_at: String,
+}
+
+/// A recorded change of status of a sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct SandboxTransition {
+    /// The status the sandbox was in, if it had a record.
+    from: Option<String>,
+
+    /// The status the sandbox moved to.
+    to: SandboxStatus,
+
+    /// When the change happened, in UTC.
+    timestamp: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SandboxStatus {
+    /// Returns the name the status is stored under.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Stopping => "stopping",
+            Self::Exited(_) => "exited",
+            Self::Failed(_) => "failed",
+        }
+    }
+
+    /// Returns whether the sandbox has, or is about to have, running processes.
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Starting | Self::Running | Self::Stopping)
+    }
+
+    /// Returns whether a sandbox in this status can move to the given one.
+    pub fn can_transition_to(&self, next: &SandboxStatus) -> bool {
+        use SandboxStatus::*;
+        matches!(
+            (self, next),
+            (Created, Created | Starting | Failed(_))
+                | (Starting, Running | Stopping | Exited(_) | Failed(_))
+                | (Running, Stopping | Exited(_) | Failed(_))
+                | (Stopping, Exited(_) | Failed(_))
+                | (Exited(_) | Failed(_), Created | Starting)
+        )
+    }
+
+    /// Builds a status from its stored name along with the exit code and error stored next to it.
+    fn from_parts(
+        status: &str,
+        exit_code: Option<i32>,
+        last_error: Option<String>,
+    ) -> MonocoreResult<Self> {
+        match status {
+            "created" => Ok(Self::Created),
+            "starting" => Ok(Self::Starting),
+            "running" => Ok(Self::Running),
+            "stopping" => Ok(Self::Stopping),
+            "exited" => Ok(Self::Exited(exit_code.unwrap_or_default())),
+            "failed" => Ok(Self::Failed(last_error.unwrap_or_default())),
+            _ => Err(MonocoreError::InvalidArgument(format!(
+                "unknown sandbox status '{}'",
+                status
+            ))),
+        }
+    }
+}
+
+impl SandboxState {
+    /// Builds the state of a sandbox from a `sandboxes` row.
+    fn from_row(row: &SqliteRow) -> MonocoreResult<Self> {
+        let exit_code = row.get::<Option<i32>, _>("exit_code");
+        let last_error = row.get::<Option<String>, _>("last_error");
+
+        Ok(Self {
+            name: row.get("name"),
+            status: SandboxStatus::from_parts(row.get("status"), exit_code, last_error.clone())?,
+            supervisor_pid: row.get("supervisor_pid"),
+            microvm_pid: row.get("microvm_pid"),
+            exit_code,
+            last_error,
+            restart_count: row.get("restart_count"),
+            health: row
+                .get::<Option<&str>, _>("health")
+                .map(str::parse)
+                .transpose()?,
+            group_ip: row
+                .get::<Option<&str>, _>("group_ip")
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| MonocoreError::InvalidArgument(format!("invalid group ip: {}", e)))?,
+            created_at: row.get("created_at"),
+            modified_at: row.get("modified_at"),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Records a sandbox as created, ready to be started.
+///
+/// A sandbox without a record gets a new one. A sandbox that has exited or failed is moved back
+/// to `Created`, keeping its history, its last exit code and its last error.
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+/// * `name` - The name of the sandbox
+/// * `rootfs_path` - The root filesystem the sandbox runs in
+/// * `config` - The configuration of the sandbox as JSON
+///
+/// ## Returns
+///
+/// The id of the record of the sandbox.
+pub(crate) async fn create_sandbox_record(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    rootfs_path: &Path,
+    config: &str,
+) -> MonocoreResult<i64> {
+    let existing = get_sandbox_state(pool, name).await?;
+    if existing.is_some() {
+        transition_sandbox(pool, name, SandboxStatus::Created).await?;
+
+        let id = sqlx::query(
+            r#"
+            UPDATE sandboxes
+            SET rootfs_path = ?, config = ?
+            WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+            RETURNING id
+            "#,
+        )
+        .bind(rootfs_path.to_string_lossy())
+        .bind(config)
+        .bind(name)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>("id");
+
+        return Ok(id);
+    }
+
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query(
+        r#"
+        INSERT INTO sandboxes (name, status, rootfs_path, config)
+        VALUES (?, 'created', ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(rootfs_path.to_string_lossy())
+    .bind(config)
+    .fetch_one(&mut *tx)
+    .await?
+    .get::<i64, _>("id");
+
+    sqlx::query("INSERT INTO sandbox_transitions (sandbox_id, to_status) VALUES (?, 'created')")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(id)
+}
+
+/// Moves a sandbox to a new status and records the transition.
+///
+/// Moving to `Exited` records the exit code of the sandbox and moving to `Failed` records the
+/// error. Moving to `Created` clears the PIDs, exit code, restart count and health of the previous
+/// run.
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+/// * `name` - The name of the sandbox
+/// * `next` - The status to move the sandbox to
+///
+/// ## Returns
+///
+/// An error if the sandbox has no record or can't move from its current status to `next`.
+pub(crate) async fn transition_sandbox(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    next: SandboxStatus,
+) -> MonocoreResult<()> {
+    let (exit_code, reason) = match &next {
+        SandboxStatus::Exited(code) => (Some(*code), None),
+        SandboxStatus::Failed(reason) => (None, Some(reason.as_str())),
+        _ => (None, None),
+    };
+
+    // The status is only updated if it is still the one checked, so a concurrent transition
+    // makes this one check again against the new status
+    loop {
+        let current = get_sandbox_state(pool, name)
+            .await?
+            .ok_or_else(|| MonocoreError::ServiceNotFound(name.to_string()))?;
+
+        if !current.status.can_transition_to(&next) {
+            return Err(MonocoreError::InvalidSandboxTransition {
+                name: name.to_string(),
+                from: current.status.to_string(),
+                to: next.to_string(),
+            });
+        }
+
+        let mut tx = pool.begin().await?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE sandboxes
+            SET status = ?1,
+                exit_code = CASE
+                    WHEN ?1 = 'exited' THEN ?2
+                    WHEN ?1 = 'created' THEN NULL
+                    ELSE exit_code
+                END,
+                last_error = COALESCE(?3, last_error),
+                supervisor_pid = CASE WHEN ?1 = 'created' THEN NULL ELSE supervisor_pid END,
+                microvm_pid = CASE WHEN ?1 = 'created' THEN NULL ELSE microvm_pid END,
+                restart_count = CASE WHEN ?1 = 'created' THEN 0 ELSE restart_count END,
+                health = CASE WHEN ?1 = 'created' THEN NULL ELSE health END,
+                modified_at = CURRENT_TIMESTAMP
+            WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?4) AND status = ?5
+            RETURNING id
+            "#,
+        )
+        .bind(next.as_str())
+        .bind(exit_code)
+        .bind(reason)
+        .bind(name)
+        .bind(current.status.as_str())
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(updated) = updated else {
+            tx.rollback().await?;
+            continue;
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO sandbox_transitions (sandbox_id, from_status, to_status, exit_code, reason)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(updated.get::<i64, _>("id"))
+        .bind(current.status.as_str())
+        .bind(next.as_str())
+        .bind(exit_code)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!("sandbox {} is now {}", name, next);
+        return Ok(());
+    }
+}
+
+/// Marks sandboxes whose processes are gone as failed.
+///
+/// A sandbox is left active when its supervisor is killed or crashes, as nothing is there to
+/// record how it ended. Those sandboxes are moved to `Failed`, as are sandboxes that have been
+/// starting without a supervisor for longer than [`SANDBOX_START_TIMEOUT`].
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+///
+/// ## Returns
+///
+/// The names of the sandboxes that were marked as failed.
+pub(crate) async fn reconcile_sandboxes(pool: &Pool<Sqlite>) -> MonocoreResult<Vec<String>> {
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT name, supervisor_pid,
+            CAST((julianday('now') - julianday(modified_at)) * 86400 AS INTEGER) AS age
+        FROM sandboxes
+        WHERE id IN (SELECT MAX(id) FROM sandboxes GROUP BY name) AND status IN ({})
+        "#,
+        ACTIVE_STATUSES
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut reconciled = Vec::new();
+    for row in rows {
+        let name = row.get::<String, _>("name");
+        let reason = match row.get::<Option<i64>, _>("supervisor_pid") {
+            Some(pid) if !is_process_alive(pid) => {
+                format!("supervisor {} is no longer running", pid)
+            }
+            None if row.get::<i64, _>("age") as u64 > SANDBOX_START_TIMEOUT.as_secs() => {
+                "sandbox never got a supervisor".to_string()
+            }
+            _ => continue,
+        };
+
+        // The sandbox may have been moved on since it was read, which is fine
+        match transition_sandbox(pool, &name, SandboxStatus::Failed(reason)).await {
+            Ok(()) => reconciled.push(name),
+            Err(MonocoreError::InvalidSandboxTransition { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(reconciled)
+}
+
+/// Returns the recorded state of a sandbox, if it has a record.
+pub(crate) async fn get_sandbox_state(
+    pool: &Pool<Sqlite>,
+    name: &str,
+) -> MonocoreResult<Option<SandboxState>> {
+    let row = sqlx::query(
+        r#"
+        SELECT name, status, supervisor_pid, microvm_pid, exit_code, last_error, restart_count,
+            health, group_ip, datetime(created_at) AS created_at,
+            datetime(modified_at) AS modified_at
+        FROM sandboxes
+        WHERE name = ?
+        ORDER BY id DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+
+    row.as_ref().map(SandboxState::from_row).transpose()
+}
+
+/// Returns the recorded states of all sandboxes, ordered by name.
+pub(crate) async fn get_sandbox_states(pool: &Pool<Sqlite>) -> MonocoreResult<Vec<SandboxState>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, status, supervisor_pid, microvm_pid, exit_code, last_error, restart_count,
+            health, group_ip, datetime(created_at) AS created_at,
+            datetime(modified_at) AS modified_at
+        FROM sandboxes
+        WHERE id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(SandboxState::from_row).collect()
+}
+
+/// Returns the recorded transitions of a sandbox, oldest first.
+pub async fn get_sandbox_transitions(
+    pool: &Pool<Sqlite>,
+    name: &str,
+) -> MonocoreResult<Vec<SandboxTransition>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT t.from_status, t.to_status, t.exit_code, t.reason,
+            datetime(t.timestamp) AS timestamp
+        FROM sandbox_transitions t
+        JOIN sandboxes s ON s.id = t.sandbox_id
+        WHERE s.name = ?
+        ORDER BY t.id
+        "#,
+    )
+    .bind(name)
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(SandboxTransition {
+                from: row.get("from_status"),
+                to: SandboxStatus::from_parts(
+                    row.get("to_status"),
+                    row.get("exit_code"),
+                    row.get("reason"),
+                )?,
+                timestamp: row.get("timestamp"),
+            })
+        })
+        .collect()
+}
+
+/// Returns whether a process exists. Processes that are children of this one are reaped first
+/// so exited ones don't linger as zombies.
+pub(crate) fn is_process_alive(pid: i64) -> bool {
+    let pid = Pid::from_raw(pid as i32);
+    let _ = waitpid(pid, Some(WaitPidFlag::WNOHANG));
+    signal::kill(pid, None).is_ok()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for SandboxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited ({})", code),
+            Self::Failed(reason) => write!(f, "failed: {}", reason),
+            _ => write!(f, "{}", self.as_str()),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::management::{db, SANDBOX_DB_MIGRATOR};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lifecycle_records_transitions() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+
+        create_sandbox_record(&pool, "api", Path::new("/rootfs/api"), "{}").await?;
+        for status in [
+            SandboxStatus::Starting,
+            SandboxStatus::Running,
+            SandboxStatus::Stopping,
+            SandboxStatus::Exited(143),
+        ] {
+            transition_sandbox(&pool, "api", status).await?;
+        }
+
+        let state = get_sandbox_state(&pool, "api").await?.unwrap();
+        assert_eq!(state.get_status(), &SandboxStatus::Exited(143));
+        assert_eq!(state.get_exit_code(), &Some(143));
+
+        // A running sandbox can't be created again, but an exited one can
+        assert!(matches!(
+            transition_sandbox(&pool, "api", SandboxStatus::Running).await,
+            Err(MonocoreError::InvalidSandboxTransition { .. })
+        ));
+        create_sandbox_record(&pool, "api", Path::new("/rootfs/api"), "{}").await?;
+        transition_sandbox(&pool, "api", SandboxStatus::Failed("no image".to_string())).await?;
+
+        let state = get_sandbox_state(&pool, "api").await?.unwrap();
+        assert_eq!(
+            state.get_status(),
+            &SandboxStatus::Failed("no image".to_string())
+        );
+        assert_eq!(state.get_last_error().as_deref(), Some("no image"));
+        assert_eq!(get_sandbox_states(&pool).await?.len(), 1);
+
+        let transitions: Vec<_> = get_sandbox_transitions(&pool, "api")
+            .await?
+            .into_iter()
+            .map(|t| t.get_to().to_string())
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                "created",
+                "starting",
+                "running",
+                "stopping",
+                "exited (143)",
+                "created",
+                "failed: no image"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_reconciles_dead_sandboxes() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+
+        // A supervisor that has already exited and one that is still running
+        let mut exited = std::process::Command::new("true").spawn()?;
+        exited.wait()?;
+        for (name, pid) in [("dead", exited.id()), ("alive", std::process::id())] {
+            create_sandbox_record(&pool, name, Path::new("/rootfs"), "{}").await?;
+            transition_sandbox(&pool, name, SandboxStatus::Starting).await?;
+            sqlx::query("UPDATE sandboxes SET supervisor_pid = ? WHERE name = ?")
+                .bind(pid)
+                .bind(name)
+                .execute(&pool)
+                .await?;
+            transition_sandbox(&pool, name, SandboxStatus::Running).await?;
+        }
+
+        assert_eq!(reconcile_sandboxes(&pool).await?, ["dead"]);
+
+        let dead = get_sandbox_state(&pool, "dead").await?.unwrap();
+        assert!(
+            matches!(dead.get_status(), SandboxStatus::Failed(reason) if reason.contains("no longer running"))
+        );
+        let alive = get_sandbox_state(&pool, "alive").await?.unwrap();
+        assert_eq!(alive.get_status(), &SandboxStatus::Running);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,695 @@
+use std::{
+    fmt,
+    io::SeekFrom,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use getset::Getters;
+use monoutils::{LogRecord, LogStream, LOG_SUFFIX};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use typed_builder::TypedBuilder;
+
+use crate::{utils::MCRUN_LOG_PREFIX, MonocoreError, MonocoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The extension a log segment gets when the log is rotated.
+pub const ROTATED_LOG_EXTENSION: &str = "old";
+
+/// The number of runs of a sandbox whose logs are kept by default.
+pub const DEFAULT_LOG_MAX_RUNS: usize = 5;
+
+/// How long the logs of a sandbox run are kept by default after they were last written.
+pub const DEFAULT_LOG_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often a followed log is checked for new lines.
+pub const LOG_FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many bytes are read at a time when reading a log backwards.
+const LOG_TAIL_CHUNK_SIZE: u64 = 8 * 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The level of a log line, for lines that have one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Very detailed tracing.
+    Trace,
+
+    /// Debugging information.
+    Debug,
+
+    /// General information.
+    Info,
+
+    /// Something unexpected that didn't stop the sandbox.
+    Warn,
+
+    /// Something that failed.
+    Error,
+}
+
+/// Which records of a log to show.
+#[derive(Debug, Clone, Default, TypedBuilder, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct LogFilter {
+    /// The lowest level to show. Lines without a level are hidden when it is set.
+    #[builder(default)]
+    level: Option<LogLevel>,
+
+    /// The only stream to show.
+    #[builder(default)]
+    stream: Option<LogStream>,
+}
+
+/// How many logs of a sandbox are kept.
+///
+/// Every run of a sandbox, including every restart, writes a log of its own, which is rotated
+/// into a second segment when it grows too big. The logs of the most recent runs are kept, as
+/// long as they were written to recently enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct LogRetention {
+    /// The number of runs whose logs are kept.
+    max_runs: usize,
+
+    /// How long the logs of a run are kept after they were last written.
+    max_age: Duration,
+}
+
+/// Follows the log of a sandbox as it is written, across rotations and restarts.
+pub struct LogFollower {
+    /// The directory the logs are in.
+    log_dir: PathBuf,
+
+    /// The name of the sandbox.
+    name: String,
+
+    /// Which records to return.
+    filter: LogFilter,
+
+    /// The log segment being read, with its path and inode.
+    current: Option<(PathBuf, File, u64)>,
+
+    /// The end of the log that isn't a complete record yet.
+    partial: Vec<u8>,
+}
+
+/// A log segment of a run of a sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct LogSegment {
+    /// When the run started, in seconds since the epoch.
+    timestamp: u64,
+
+    /// The process ID of the run.
+    pid: u32,
+
+    /// Whether this is the current segment rather than a rotated one.
+    current: bool,
+
+    /// The path of the segment.
+    path: PathBuf,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LogLevel {
+    /// Returns the level a line is logged at, looking for a level among its first words.
+    pub fn detect(line: &str) -> Option<Self> {
+        line.split_whitespace().take(3).find_map(|word| {
+            word.trim_matches(|c: char| !c.is_ascii_alphabetic())
+                .parse()
+                .ok()
+        })
+    }
+}
+
+impl LogFilter {
+    /// Returns whether a record should be shown.
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if self
+            .stream
+            .is_some_and(|stream| stream != record.get_stream())
+        {
+            return false;
+        }
+
+        match self.level {
+            Some(level) => {
+                LogLevel::detect(record.get_line()).is_some_and(|line_level| line_level >= level)
+            }
+            None => true,
+        }
+    }
+}
+
+impl LogRetention {
+    /// Creates a retention policy keeping the logs of the last `max_runs` runs of a sandbox that
+    /// were written in the last `max_age`.
+    pub fn new(max_runs: usize, max_age: Duration) -> Self {
+        Self { max_runs, max_age }
+    }
+}
+
+impl LogFollower {
+    /// Starts following the log of a sandbox from its current end.
+    ///
+    /// ## Arguments
+    ///
+    /// * `log_dir` - The directory the logs are in
+    /// * `name` - The name of the sandbox
+    /// * `filter` - Which records to return
+    pub async fn new(
+        log_dir: impl Into<PathBuf>,
+        name: impl Into<String>,
+        filter: LogFilter,
+    ) -> MonocoreResult<Self> {
+        let mut follower = Self {
+            log_dir: log_dir.into(),
+            name: name.into(),
+            filter,
+            current: None,
+            partial: Vec::new(),
+        };
+
+        if let Some(path) = follower.get_latest_path().await? {
+            let mut file = File::open(&path).await?;
+            let inode = file.metadata().await?.ino();
+            file.seek(SeekFrom::End(0)).await?;
+            follower.current = Some((path, file, inode));
+        }
+
+        Ok(follower)
+    }
+
+    /// Returns the records written since the last call.
+    ///
+    /// When the log is rotated or the sandbox is restarted, the rest of the previous log is
+    /// returned before the records of the new one.
+    pub async fn poll(&mut self) -> MonocoreResult<Vec<LogRecord>> {
+        let mut records = self.read_new_records().await?;
+
+        let Some(latest) = self.get_latest_path().await? else {
+            return Ok(records);
+        };
+
+        let switched = match &self.current {
+            Some((path, _, inode)) => {
+                *path != latest || fs::metadata(&latest).await?.ino() != *inode
+            }
+            None => true,
+        };
+        if switched {
+            // Catch what was written just before the switch, then start on the new log
+            records.extend(self.read_new_records().await?);
+            push_record(&self.partial, &self.filter, &mut records);
+            self.partial.clear();
+
+            let file = File::open(&latest).await?;
+            let inode = file.metadata().await?.ino();
+            self.current = Some((latest, file, inode));
+            records.extend(self.read_new_records().await?);
+        }
+
+        Ok(records)
+    }
+
+    /// Reads the complete records appended to the current segment.
+    async fn read_new_records(&mut self) -> MonocoreResult<Vec<LogRecord>> {
+        let Some((_, file, _)) = &mut self.current else {
+            return Ok(Vec::new());
+        };
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await?;
+        self.partial.extend(data);
+
+        let Some(end) = self.partial.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+
+        let mut records = Vec::new();
+        for line in complete[..end].split(|byte| *byte == b'\n') {
+            push_record(line, &self.filter, &mut records);
+        }
+
+        Ok(records)
+    }
+
+    /// Returns the path of the current segment of the latest run of the sandbox.
+    async fn get_latest_path(&self) -> MonocoreResult<Option<PathBuf>> {
+        Ok(get_segments(&self.log_dir, &self.name)
+            .await?
+            .into_iter()
+            .rfind(|segment| segment.current)
+            .map(|segment| segment.path))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the log segments of a sandbox in the order they were written: run by run, with the
+/// rotated segment of a run before its current one.
+///
+/// ## Arguments
+///
+/// * `log_dir` - The directory the logs are in
+/// * `name` - The name of the sandbox
+pub async fn get_log_segments(log_dir: &Path, name: &str) -> MonocoreResult<Vec<PathBuf>> {
+    Ok(get_segments(log_dir, name)
+        .await?
+        .into_iter()
+        .map(|segment| segment.path)
+        .collect())
+}
+
+/// Returns the records of log segments that match a filter.
+///
+/// Lines that aren't records, like those of logs written by older versions, are skipped.
+pub async fn read_log_records(
+    segments: &[PathBuf],
+    filter: &LogFilter,
+) -> MonocoreResult<Vec<LogRecord>> {
+    let mut records = Vec::new();
+    for segment in segments {
+        let data = fs::read(segment).await?;
+        for line in data.split(|byte| *byte == b'\n') {
+            push_record(line, filter, &mut records);
+        }
+    }
+
+    Ok(records)
+}
+
+/// Returns the last `count` records of log segments that match a filter, in order.
+///
+/// The segments are read backwards from their end, so only as much of them is read as the
+/// records need.
+pub async fn tail_log_records(
+    segments: &[PathBuf],
+    count: usize,
+    filter: &LogFilter,
+) -> MonocoreResult<Vec<LogRecord>> {
+    let mut records = Vec::new();
+    for segment in segments.iter().rev() {
+        if records.len() >= count {
+            break;
+        }
+
+        tail_segment(segment, count, filter, LOG_TAIL_CHUNK_SIZE, &mut records).await?;
+    }
+
+    records.reverse();
+    Ok(records)
+}
+
+/// Deletes the logs of a sandbox that the retention policy doesn't keep. The run writing to
+/// `current` is always kept.
+///
+/// ## Arguments
+///
+/// * `log_dir` - The directory the logs are in
+/// * `name` - The name of the sandbox
+/// * `retention` - Which logs to keep
+/// * `current` - The current segment of the running sandbox
+pub(crate) async fn prune_logs(
+    log_dir: &Path,
+    name: &str,
+    retention: &LogRetention,
+    current: &Path,
+) -> MonocoreResult<()> {
+    let segments = get_segments(log_dir, name).await?;
+    let Some(current) = segments.iter().find(|segment| segment.path == current) else {
+        return Ok(());
+    };
+    let current_run = (current.timestamp, current.pid);
+
+    // Runs from the most recent to the oldest
+    let mut runs: Vec<_> = segments
+        .iter()
+        .map(|segment| (segment.timestamp, segment.pid))
+        .collect();
+    runs.dedup();
+    runs.reverse();
+
+    let now = SystemTime::now();
+    for segment in &segments {
+        let run = (segment.timestamp, segment.pid);
+        if run == current_run {
+            continue;
+        }
+
+        let too_many =
+            runs.iter().position(|r| *r == run).unwrap_or_default() >= retention.max_runs;
+        let too_old = fs::metadata(&segment.path)
+            .await?
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > retention.max_age);
+        if too_many || too_old {
+            fs::remove_file(&segment.path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the log segments of a sandbox, ordered as they were written.
+///
+/// Logs are named `{prefix}-{name}-{pid}-{timestamp}.log`, and `.old` once rotated.
+async fn get_segments(log_dir: &Path, name: &str) -> MonocoreResult<Vec<LogSegment>> {
+    let prefix = format!("{}-{}-", MCRUN_LOG_PREFIX, name);
+    let mut segments = Vec::new();
+    let mut entries = match fs::read_dir(log_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(segments),
+        Err(e) => return Err(e.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(rest) = file_name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+
+        // Names of other sandboxes that start with this one fail to parse here
+        let Some((stem, extension)) = rest.split_once('.') else {
+            continue;
+        };
+        let Some((pid, timestamp)) = stem.split_once('-') else {
+            continue;
+        };
+        let (Ok(pid), Ok(timestamp)) = (pid.parse(), timestamp.parse()) else {
+            continue;
+        };
+        let current = match extension {
+            LOG_SUFFIX => true,
+            ROTATED_LOG_EXTENSION => false,
+            _ => continue,
+        };
+
+        segments.push(LogSegment {
+            timestamp,
+            pid,
+            current,
+            path: entry.path(),
+        });
+    }
+
+    segments.sort();
+    Ok(segments)
+}
+
+/// Reads a segment backwards, pushing its records that match the filter onto `records`, last
+/// first, until `records` holds `count` records or the segment is read.
+async fn tail_segment(
+    path: &Path,
+    count: usize,
+    filter: &LogFilter,
+    chunk_size: u64,
+    records: &mut Vec<LogRecord>,
+) -> MonocoreResult<()> {
+    let mut file = File::open(path).await?;
+    let mut position = file.metadata().await?.len();
+
+    // The start of the chunk read last, which may be the end of a line in an earlier chunk
+    let mut carry = Vec::new();
+    let mut at_end = true;
+    while position > 0 && records.len() < count {
+        let size = chunk_size.min(position);
+        position -= size;
+
+        let mut chunk = vec![0; size as usize];
+        file.seek(SeekFrom::Start(position)).await?;
+        file.read_exact(&mut chunk).await?;
+        chunk.append(&mut carry);
+
+        // The newline ending the segment doesn't start another line
+        if at_end && chunk.last() == Some(&b'\n') {
+            chunk.pop();
+        }
+        at_end = false;
+
+        let mut pieces: Vec<_> = chunk.split(|byte| *byte == b'\n').collect();
+        carry = pieces.remove(0).to_vec();
+        for piece in pieces.into_iter().rev() {
+            push_record(piece, filter, records);
+            if records.len() >= count {
+                return Ok(());
+            }
+        }
+    }
+
+    // The first line of the segment
+    if position == 0 && records.len() < count {
+        push_record(&carry, filter, records);
+    }
+
+    Ok(())
+}
+
+/// Pushes the record a line holds onto `records` if it matches the filter. Lines that aren't
+/// records are skipped.
+fn push_record(line: &[u8], filter: &LogFilter, records: &mut Vec<LogRecord>) {
+    let Some(record) = std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.parse::<LogRecord>().ok())
+    else {
+        return;
+    };
+
+    if filter.matches(&record) {
+        records.push(record);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_MAX_RUNS, DEFAULT_LOG_MAX_AGE)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = MonocoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(MonocoreError::InvalidArgument(format!(
+                "unknown log level '{}', expected trace, debug, info, warn or error",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        };
+        write!(f, "{}", level)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_logs_stitch_tail_and_prune_segments() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let log_dir = temp_dir.path();
+        let write = |file: &str, content: &[(LogStream, &str)]| {
+            std::fs::write(log_dir.join(file), to_records(content))
+        };
+
+        write(
+            "mcrun-app-20-100.old",
+            &[
+                (LogStream::Stdout, "INFO one"),
+                (LogStream::Stderr, "WARN two"),
+            ],
+        )?;
+        write(
+            "mcrun-app-20-100.log",
+            &[
+                (LogStream::Stdout, "plain three"),
+                (LogStream::Stderr, "ERROR four"),
+            ],
+        )?;
+        write(
+            "mcrun-app-30-200.log",
+            &[
+                (LogStream::Stdout, "INFO five"),
+                (LogStream::Stdout, "WARN six"),
+            ],
+        )?;
+        write(
+            "mcrun-app-db-40-300.log",
+            &[(LogStream::Stdout, "INFO other sandbox")],
+        )?;
+
+        let segments = get_log_segments(log_dir, "app").await?;
+        let names: Vec<_> = segments
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "mcrun-app-20-100.old",
+                "mcrun-app-20-100.log",
+                "mcrun-app-30-200.log"
+            ]
+        );
+
+        let all = LogFilter::default();
+        let records = read_log_records(&segments, &all).await?;
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[2].get_line(), "plain three");
+
+        // Lines that aren't records, like those of logs written by older versions, are skipped
+        let old_log = log_dir.join("mcrun-app-10-50.log");
+        std::fs::write(&old_log, "plain text\n")?;
+        let with_old_log = get_log_segments(log_dir, "app").await?;
+        assert_eq!(with_old_log.len(), 4);
+        assert_eq!(read_log_records(&with_old_log, &all).await?.len(), 6);
+        std::fs::remove_file(old_log)?;
+
+        // Tailing reads across segments and chunks, counting only records that match
+        assert_eq!(
+            lines(&tail_log_records(&segments, 3, &all).await?),
+            ["ERROR four", "INFO five", "WARN six"]
+        );
+        let warnings = LogFilter::builder().level(Some(LogLevel::Warn)).build();
+        assert_eq!(
+            lines(&tail_log_records(&segments, 3, &warnings).await?),
+            ["WARN two", "ERROR four", "WARN six"]
+        );
+        let stderr = LogFilter::builder().stream(Some(LogStream::Stderr)).build();
+        assert_eq!(
+            lines(&tail_log_records(&segments, 5, &stderr).await?),
+            ["WARN two", "ERROR four"]
+        );
+        let mut records = Vec::new();
+        tail_segment(&segments[1], 10, &all, 3, &mut records).await?;
+        assert_eq!(lines(&records), ["ERROR four", "plain three"]);
+
+        // Only the latest run is kept, along with the running one
+        prune_logs(
+            log_dir,
+            "app",
+            &LogRetention::new(1, DEFAULT_LOG_MAX_AGE),
+            &log_dir.join("mcrun-app-20-100.log"),
+        )
+        .await?;
+        assert_eq!(get_log_segments(log_dir, "app").await?.len(), 3);
+        prune_logs(
+            log_dir,
+            "app",
+            &LogRetention::new(1, DEFAULT_LOG_MAX_AGE),
+            &log_dir.join("mcrun-app-30-200.log"),
+        )
+        .await?;
+        assert_eq!(get_log_segments(log_dir, "app").await?.len(), 1);
+        assert_eq!(get_log_segments(log_dir, "app-db").await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_log_follower_follows_rotations_and_restarts() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let log_dir = temp_dir.path();
+        let log_path = log_dir.join("mcrun-app-20-100.log");
+        std::fs::write(&log_path, to_records(&[(LogStream::Stdout, "before")]))?;
+
+        let append = |path: &Path, content: &[u8]| -> std::io::Result<()> {
+            use std::io::Write;
+            std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)?
+                .write_all(content)
+        };
+
+        let mut follower = LogFollower::new(log_dir, "app", LogFilter::default()).await?;
+        assert!(follower.poll().await?.is_empty());
+
+        // A record written in pieces is only returned once it is complete
+        let two = to_records(&[(LogStream::Stderr, "two")]);
+        let (two_start, two_end) = two.split_at(5);
+        append(&log_path, &to_records(&[(LogStream::Stdout, "one")]))?;
+        append(&log_path, two_start)?;
+        assert_eq!(lines(&follower.poll().await?), ["one"]);
+
+        // Rotation moves the log aside and starts a new one at the same path
+        append(&log_path, two_end)?;
+        std::fs::rename(&log_path, log_dir.join("mcrun-app-20-100.old"))?;
+        append(&log_path, &to_records(&[(LogStream::Stdout, "three")]))?;
+        let records = follower.poll().await?;
+        assert_eq!(lines(&records), ["two", "three"]);
+        assert_eq!(records[0].get_stream(), LogStream::Stderr);
+
+        // A restart starts a new log
+        append(
+            &log_dir.join("mcrun-app-30-200.log"),
+            &to_records(&[(LogStream::Stdout, "four")]),
+        )?;
+        assert_eq!(lines(&follower.poll().await?), ["four"]);
+
+        Ok(())
+    }
+
+    /// Returns records of lines as they are written to a log.
+    fn to_records(lines: &[(LogStream, &str)]) -> Vec<u8> {
+        lines
+            .iter()
+            .flat_map(|(stream, line)| LogRecord::new(*stream, *line).to_json_line())
+            .collect()
+    }
+
+    /// Returns the lines of records.
+    fn lines(records: &[LogRecord]) -> Vec<&str> {
+        records.iter().map(|record| record.get_line()).collect()
+    }
+}
@@ -2,7 +2,9 @@ use crate::{config::DEFAULT_CONFIG, MonocoreResult};
 use std::path::{Path, PathBuf};
 use tokio::{fs, io::AsyncWriteExt};
 
-use crate::utils::path::{LOG_SUBDIR, MONOCORE_ENV_DIR, SANDBOX_DB_FILENAME};
+use crate::utils::path::{
+    LOG_SUBDIR, MONOCORE_CONFIG_FILENAME, MONOCORE_ENV_DIR, SANDBOX_DB_FILENAME,
+};
 
 use super::{db, SANDBOX_DB_MIGRATOR};
 
@@ -51,7 +53,7 @@ pub async fn init_menv(project_path: Option<PathBuf>) -> MonocoreResult<()> {
     create_default_config(&project_path).await?;
     tracing::info!(
         "created default config file at {}",
-        project_path.join("monocore.yaml").display()
+        project_path.join(MONOCORE_CONFIG_FILENAME).display()
     );
 
     Ok(())
@@ -70,7 +72,7 @@ async fn create_menv_dirs(menv_path: &Path) -> MonocoreResult<()> {
 
 /// Create a default monocore.yaml configuration file
 async fn create_default_config(project_path: &Path) -> MonocoreResult<()> {
-    let config_path = project_path.join("monocore.yaml");
+    let config_path = project_path.join(MONOCORE_CONFIG_FILENAME);
 
     // Only create if it doesn't exist
     if !config_path.exists() {
@@ -0,0 +1,281 @@
+use std::time::Duration;
+
+use getset::Getters;
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
+use typed_builder::TypedBuilder;
+
+use crate::{MonocoreError, MonocoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How often the resource usage of a sandbox is sampled by default.
+pub const DEFAULT_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How old samples get before only one is kept for each minute.
+pub const METRICS_DOWNSAMPLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// How old samples get before they are deleted.
+pub const METRICS_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The resources used by a sandbox at a point in time.
+///
+/// Disk and network counters are totals since the sandbox started. They are None where they
+/// aren't available, like network counters for sandboxes sharing the host network.
+#[derive(Debug, Clone, Default, PartialEq, TypedBuilder, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct ResourceUsage {
+    /// The CPU used since the previous sample, as a percentage of one CPU.
+    #[builder(default)]
+    cpu_usage: f64,
+
+    /// The resident memory in bytes.
+    #[builder(default)]
+    memory_usage: u64,
+
+    /// The bytes read from storage.
+    #[builder(default)]
+    disk_read_bytes: Option<u64>,
+
+    /// The bytes written to storage.
+    #[builder(default)]
+    disk_write_bytes: Option<u64>,
+
+    /// The bytes received over the network.
+    #[builder(default)]
+    net_rx_bytes: Option<u64>,
+
+    /// The bytes sent over the network.
+    #[builder(default)]
+    net_tx_bytes: Option<u64>,
+}
+
+/// A recorded sample of the resources used by a sandbox.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct SandboxMetrics {
+    /// The name of the sandbox.
+    name: String,
+
+    /// The resources used.
+    usage: ResourceUsage,
+
+    /// When the sample was taken, in UTC.
+    timestamp: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Records a sample of the resources used by a sandbox.
+pub(crate) async fn record_sandbox_metrics(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    usage: &ResourceUsage,
+) -> MonocoreResult<()> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO sandbox_metrics (
+            sandbox_id, cpu_usage, memory_usage, disk_read_bytes, disk_write_bytes,
+            net_rx_bytes, net_tx_bytes
+        )
+        SELECT MAX(id), ?, ?, ?, ?, ?, ? FROM sandboxes WHERE name = ? HAVING MAX(id) IS NOT NULL
+        "#,
+    )
+    .bind(usage.cpu_usage)
+    .bind(usage.memory_usage as i64)
+    .bind(usage.disk_read_bytes.map(|bytes| bytes as i64))
+    .bind(usage.disk_write_bytes.map(|bytes| bytes as i64))
+    .bind(usage.net_rx_bytes.map(|bytes| bytes as i64))
+    .bind(usage.net_tx_bytes.map(|bytes| bytes as i64))
+    .bind(name)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(MonocoreError::ServiceNotFound(name.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Returns the latest sample of each sandbox, ordered by name.
+pub async fn get_latest_sandbox_metrics(
+    pool: &Pool<Sqlite>,
+) -> MonocoreResult<Vec<SandboxMetrics>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT s.name, m.cpu_usage, m.memory_usage, m.disk_read_bytes, m.disk_write_bytes,
+            m.net_rx_bytes, m.net_tx_bytes, datetime(m.timestamp) AS timestamp
+        FROM sandbox_metrics m
+        JOIN sandboxes s ON s.id = m.sandbox_id
+        WHERE m.id IN (SELECT MAX(id) FROM sandbox_metrics GROUP BY sandbox_id)
+            AND s.id IN (SELECT MAX(id) FROM sandboxes GROUP BY name)
+        ORDER BY s.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(metrics_from_row).collect())
+}
+
+/// Returns the recorded samples of a sandbox, oldest first.
+pub async fn get_sandbox_metrics(
+    pool: &Pool<Sqlite>,
+    name: &str,
+) -> MonocoreResult<Vec<SandboxMetrics>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT s.name, m.cpu_usage, m.memory_usage, m.disk_read_bytes, m.disk_write_bytes,
+            m.net_rx_bytes, m.net_tx_bytes, datetime(m.timestamp) AS timestamp
+        FROM sandbox_metrics m
+        JOIN sandboxes s ON s.id = m.sandbox_id
+        WHERE s.id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        ORDER BY m.id
+        "#,
+    )
+    .bind(name)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(metrics_from_row).collect())
+}
+
+/// Thins out old samples of all sandboxes. Samples older than `downsample_after` are reduced to
+/// the last one of each minute and samples older than `retention` are deleted.
+///
+/// ## Arguments
+///
+/// * `pool` - The sandbox database
+/// * `downsample_after` - How old samples get before they are downsampled
+/// * `retention` - How old samples get before they are deleted
+pub(crate) async fn prune_sandbox_metrics(
+    pool: &Pool<Sqlite>,
+    downsample_after: Duration,
+    retention: Duration,
+) -> MonocoreResult<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM sandbox_metrics WHERE timestamp < datetime('now', ?)")
+        .bind(format!("-{} seconds", retention.as_secs()))
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM sandbox_metrics
+        WHERE timestamp < datetime('now', ?1) AND id NOT IN (
+            SELECT MAX(id) FROM sandbox_metrics
+            WHERE timestamp < datetime('now', ?1)
+            GROUP BY sandbox_id, strftime('%Y-%m-%d %H:%M', timestamp)
+        )
+        "#,
+    )
+    .bind(format!("-{} seconds", downsample_after.as_secs()))
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Reads a sample from a row joining `sandbox_metrics` with the name of its sandbox.
+fn metrics_from_row(row: &SqliteRow) -> SandboxMetrics {
+    let bytes = |column: &str| row.get::<Option<i64>, _>(column).map(|bytes| bytes as u64);
+    SandboxMetrics {
+        name: row.get("name"),
+        usage: ResourceUsage {
+            cpu_usage: row.get("cpu_usage"),
+            memory_usage: row.get::<i64, _>("memory_usage") as u64,
+            disk_read_bytes: bytes("disk_read_bytes"),
+            disk_write_bytes: bytes("disk_write_bytes"),
+            net_rx_bytes: bytes("net_rx_bytes"),
+            net_tx_bytes: bytes("net_tx_bytes"),
+        },
+        timestamp: row.get("timestamp"),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use crate::management::{db, lifecycle, SANDBOX_DB_MIGRATOR};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_record_latest_and_prune() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let pool = db::init_db(temp_dir.path().join("sandbox.db"), &SANDBOX_DB_MIGRATOR).await?;
+
+        for name in ["api", "db"] {
+            lifecycle::create_sandbox_record(&pool, name, Path::new("/rootfs"), "{}").await?;
+        }
+
+        let usage = |cpu_usage| {
+            ResourceUsage::builder()
+                .cpu_usage(cpu_usage)
+                .memory_usage(1024)
+                .disk_read_bytes(Some(10))
+                .build()
+        };
+        record_sandbox_metrics(&pool, "api", &usage(1.0)).await?;
+        record_sandbox_metrics(&pool, "api", &usage(2.0)).await?;
+        record_sandbox_metrics(&pool, "db", &usage(3.0)).await?;
+        assert!(record_sandbox_metrics(&pool, "web", &usage(4.0))
+            .await
+            .is_err());
+
+        let latest = get_latest_sandbox_metrics(&pool).await?;
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].get_name(), "api");
+        assert_eq!(latest[0].get_usage(), &usage(2.0));
+        assert_eq!(latest[0].get_usage().get_net_rx_bytes(), &None);
+
+        // Backdate the samples of api: two in the same old minute and one past retention
+        for (id, age) in [(1, "-2 hours"), (2, "-10 minutes")] {
+            sqlx::query("UPDATE sandbox_metrics SET timestamp = datetime('now', ?) WHERE id = ?")
+                .bind(age)
+                .bind(id)
+                .execute(&pool)
+                .await?;
+        }
+        for _ in 0..2 {
+            record_sandbox_metrics(&pool, "api", &usage(5.0)).await?;
+        }
+        sqlx::query(
+            "UPDATE sandbox_metrics SET timestamp = (SELECT timestamp FROM sandbox_metrics WHERE id = 2) WHERE id = 4",
+        )
+        .execute(&pool)
+        .await?;
+
+        prune_sandbox_metrics(&pool, METRICS_DOWNSAMPLE_AFTER, METRICS_RETENTION).await?;
+        let ids: Vec<i64> = sqlx::query("SELECT id FROM sandbox_metrics ORDER BY id")
+            .fetch_all(&pool)
+            .await?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+        assert_eq!(ids, [3, 4, 5]);
+        assert_eq!(get_sandbox_metrics(&pool, "api").await?.len(), 2);
+
+        Ok(())
+    }
+}
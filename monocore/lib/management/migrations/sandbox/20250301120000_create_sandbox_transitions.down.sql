@@ -0,0 +1,11 @@
+-- Add down migration script here
+
+-- Drop index first
+DROP INDEX IF EXISTS idx_sandbox_transitions_sandbox_id_timestamp;
+
+-- Drop sandbox_transitions table
+DROP TABLE IF EXISTS sandbox_transitions;
+
+-- Drop the exit columns of sandboxes
+ALTER TABLE sandboxes DROP COLUMN last_error;
+ALTER TABLE sandboxes DROP COLUMN exit_code;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+-- Keep how the last run of a sandbox ended
+ALTER TABLE sandboxes ADD COLUMN exit_code INTEGER;
+ALTER TABLE sandboxes ADD COLUMN last_error TEXT;
+
+-- Create sandbox_transitions table
+CREATE TABLE IF NOT EXISTS sandbox_transitions (
+    id INTEGER PRIMARY KEY,
+    sandbox_id INTEGER NOT NULL,
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    exit_code INTEGER,
+    reason TEXT,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(sandbox_id) REFERENCES sandboxes(id) ON DELETE CASCADE
+);
+
+-- Create index
+CREATE INDEX IF NOT EXISTS idx_sandbox_transitions_sandbox_id_timestamp ON sandbox_transitions(sandbox_id, timestamp);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- Drop the restart count of sandboxes
+ALTER TABLE sandboxes DROP COLUMN restart_count;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Count the restarts of the current run of a sandbox
+ALTER TABLE sandboxes ADD COLUMN restart_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- Add down migration script here
+
+-- Drop index first
+DROP INDEX IF EXISTS idx_sandbox_health_checks_sandbox_id;
+
+-- Drop sandbox_health_checks table
+DROP TABLE IF EXISTS sandbox_health_checks;
+
+-- Drop the health of sandboxes
+ALTER TABLE sandboxes DROP COLUMN health;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+-- Keep the health of sandboxes with a healthcheck
+ALTER TABLE sandboxes ADD COLUMN health TEXT;
+
+-- Create sandbox_health_checks table
+CREATE TABLE IF NOT EXISTS sandbox_health_checks (
+    id INTEGER PRIMARY KEY,
+    sandbox_id INTEGER NOT NULL,
+    passed BOOLEAN NOT NULL,
+    output TEXT NOT NULL,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(sandbox_id) REFERENCES sandboxes(id) ON DELETE CASCADE
+);
+
+-- Create index
+CREATE INDEX IF NOT EXISTS idx_sandbox_health_checks_sandbox_id ON sandbox_health_checks(sandbox_id);
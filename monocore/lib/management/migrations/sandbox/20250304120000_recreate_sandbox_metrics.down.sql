@@ -0,0 +1,20 @@
+-- Add down migration script here
+
+-- Drop index first
+DROP INDEX IF EXISTS idx_sandbox_metrics_sandbox_id_timestamp;
+
+-- Drop sandbox_metrics table
+DROP TABLE IF EXISTS sandbox_metrics;
+
+-- Restore the original sandbox_metrics table
+CREATE TABLE IF NOT EXISTS sandbox_metrics (
+    id INTEGER PRIMARY KEY,
+    sandbox_id INTEGER NOT NULL,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    cpu_usage REAL,
+    memory_usage INTEGER,
+    FOREIGN KEY(sandbox_id) REFERENCES sandbox(id)
+);
+
+-- Create index
+CREATE INDEX IF NOT EXISTS idx_sandbox_metrics_sandbox_id_timestamp ON sandbox_metrics(sandbox_id, timestamp);
@@ -0,0 +1,23 @@
+-- Add up migration script here
+
+-- Nothing was ever written to sandbox_metrics and its foreign key names a table that doesn't
+-- exist, so recreate it instead of migrating its rows
+DROP INDEX IF EXISTS idx_sandbox_metrics_sandbox_id_timestamp;
+DROP TABLE IF EXISTS sandbox_metrics;
+
+-- Create sandbox_metrics table
+CREATE TABLE IF NOT EXISTS sandbox_metrics (
+    id INTEGER PRIMARY KEY,
+    sandbox_id INTEGER NOT NULL,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    cpu_usage REAL NOT NULL,
+    memory_usage INTEGER NOT NULL,
+    disk_read_bytes INTEGER,
+    disk_write_bytes INTEGER,
+    net_rx_bytes INTEGER,
+    net_tx_bytes INTEGER,
+    FOREIGN KEY(sandbox_id) REFERENCES sandboxes(id) ON DELETE CASCADE
+);
+
+-- Create index
+CREATE INDEX IF NOT EXISTS idx_sandbox_metrics_sandbox_id_timestamp ON sandbox_metrics(sandbox_id, timestamp);
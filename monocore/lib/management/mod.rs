@@ -1,8 +1,16 @@
 //! Management components for the Monocore runtime.
 
 mod db;
+mod dns;
+mod health;
 mod image;
+mod ipam;
+mod lifecycle;
+mod logs;
 mod menv;
+mod metrics;
+mod orchestration;
+mod proxy;
 mod rootfs;
 
 //--------------------------------------------------------------------------------------------------
@@ -10,6 +18,14 @@ mod rootfs;
 //--------------------------------------------------------------------------------------------------
 
 pub use db::*;
+pub use dns::*;
+pub use health::*;
 pub use image::*;
+pub use ipam::*;
+pub use lifecycle::*;
+pub use logs::*;
 pub use menv::*;
+pub use metrics::*;
+pub use orchestration::*;
+pub use proxy::*;
 pub use rootfs::*;
@@ -0,0 +1,1094 @@
+use std::{
+    collections::HashSet,
+    net::Ipv4Addr,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use sqlx::{Pool, Row, Sqlite};
+use tokio::{fs, time};
+use typed_path::Utf8UnixPathBuf;
+
+use crate::{
+    config::{
+        DependencyCondition, EnvPair, HealthProbe, Monocore, PathPair, Sandbox,
+        SandboxNetworkReach, DEFAULT_SCRIPT, DEFAULT_SHELL,
+    },
+    runtime::{self, NetworkPolicy, SandboxBackendType},
+    utils::{
+        env::{
+            get_metrics_interval, get_monocore_home_path, get_sandbox_backend, MCRUN_EXE_ENV_VAR,
+        },
+        path::{
+            BIN_SUBDIR, LAYERS_SUBDIR, LOG_SUBDIR, MCRUN_EXE_FILENAME, MONOCORE_CONFIG_FILENAME,
+            MONOCORE_ENV_DIR, ROOTS_SUBDIR, SANDBOX_DB_FILENAME,
+        },
+    },
+    MonocoreError, MonocoreResult,
+};
+
+use super::{
+    db::{self, ImageRunConfig},
+    health::{self, HealthCheckRecord},
+    image::{self, EXTRACTED_LAYER_SUFFIX},
+    ipam,
+    lifecycle::{self, is_process_alive, SandboxState, SandboxStatus, ACTIVE_STATUSES},
+    menv,
+    metrics::{self, SandboxMetrics},
+    rootfs, SANDBOX_DB_MIGRATOR,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How long a sandbox is given to stop after SIGTERM before it is sent SIGKILL.
+pub const SANDBOX_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a stopping sandbox is checked for having exited.
+const SANDBOX_STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Loads and validates the `monocore.yaml` configuration of a project.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+pub async fn load_config(project_path: Option<PathBuf>) -> MonocoreResult<Monocore> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+    let config_path = project_path.join(MONOCORE_CONFIG_FILENAME);
+    if !config_path.exists() {
+        return Err(MonocoreError::ConfigNotFound(
+            config_path.display().to_string(),
+        ));
+    }
+
+    let config: Monocore = serde_yaml::from_str(&fs::read_to_string(&config_path).await?)?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// Starts sandboxes of a project along with the sandboxes they depend on.
+///
+/// Sandboxes are started in dependency order. Each one gets its root filesystem prepared under
+/// `.menv/rootfs`, pulling its image if needed, and is run by an `mcrun supervisor` process that
+/// outlives this call. Sandboxes that are already running are left as they are. A sandbox that
+/// depends on another with the `healthy` condition isn't started until that one is healthy.
+/// Sandboxes in a group are given an address in the subnet of the group, which they hold until
+/// they are stopped with [`down`]. Every group in the configuration is given its subnet before
+/// any sandbox is started.
+///
+/// Each sandbox is run with the first backend set among `backend`, the `MONOCORE_BACKEND`
+/// environment variable and the `backend` of the sandbox in the configuration, or with the
+/// backend that suits the host if none is.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+/// * `names` - The sandboxes to start, or the groups to start the sandboxes of if `group` is set.
+///   If empty, all sandboxes are started
+/// * `group` - Whether `names` are group names
+/// * `backend` - Optional backend to run all the sandboxes with
+///
+/// ## Example
+/// ```no_run
+/// use monocore::{management, runtime::SandboxBackendType};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// // Start all sandboxes in the current directory's project
+/// management::up(None, &[], false, None).await?;
+///
+/// // Start the sandboxes of the backend group in containers
+/// management::up(
+///     None,
+///     &["backend".to_string()],
+///     true,
+///     Some(SandboxBackendType::Container),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn up(
+    project_path: Option<PathBuf>,
+    names: &[String],
+    group: bool,
+    backend: Option<SandboxBackendType>,
+) -> MonocoreResult<()> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+    let config = load_config(Some(project_path.clone())).await?;
+    let backend = backend.or(get_sandbox_backend()?);
+
+    let menv_path = project_path.join(MONOCORE_ENV_DIR);
+    let db_path = menv_path.join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        menv::init_menv(Some(project_path.clone())).await?;
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+    lifecycle::reconcile_sandboxes(&pool).await?;
+
+    let selected = select_sandboxes(&config, names, group)?;
+    let selected = get_with_dependencies(&config, selected);
+    let ordered: Vec<_> = order_sandboxes(&config, &selected)
+        .into_iter()
+        .map(|sandbox| {
+            let backend = backend
+                .or(*sandbox.get_backend())
+                .unwrap_or_else(SandboxBackendType::detect);
+            (sandbox, backend)
+        })
+        .collect();
+
+    // Nothing is started if a sandbox can't run on its backend
+    for (sandbox, backend) in &ordered {
+        check_backend_support(sandbox, *backend)?;
+    }
+
+    // The network policies of the sandboxes cover the groups whose sandboxes start after them
+    ipam::assign_group_subnets(&pool, &config).await?;
+
+    for (sandbox, backend) in ordered {
+        if is_sandbox_running(&pool, sandbox.get_name()).await? {
+            tracing::info!("sandbox {} is already running", sandbox.get_name());
+            continue;
+        }
+
+        // Dependencies that have to be healthy were started before this sandbox
+        for dependency in sandbox.get_depends_on().iter().flatten() {
+            if dependency.get_condition() == &DependencyCondition::Healthy {
+                health::wait_for_healthy(&pool, dependency.get_name()).await?;
+            }
+        }
+
+        start_sandbox(&project_path, &pool, &config, sandbox, backend).await?;
+    }
+
+    Ok(())
+}
+
+/// Stops sandboxes of a project along with the sandboxes that depend on them.
+///
+/// Sandboxes are stopped in reverse dependency order. Each supervisor is sent SIGTERM, which it
+/// forwards to its sandbox, and both are sent SIGKILL if they haven't exited after
+/// [`SANDBOX_STOP_TIMEOUT`]. Stopped sandboxes keep their records, with how they exited, and
+/// release their addresses in their groups.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+/// * `names` - The sandboxes to stop, or the groups to stop the sandboxes of if `group` is set.
+///   If empty, all running sandboxes are stopped, including ones no longer in the configuration
+/// * `group` - Whether `names` are group names
+pub async fn down(
+    project_path: Option<PathBuf>,
+    names: &[String],
+    group: bool,
+) -> MonocoreResult<()> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+    let config = load_config(Some(project_path.clone())).await?;
+
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        tracing::info!("no sandboxes are running");
+        return Ok(());
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+
+    let selected = select_sandboxes(&config, names, group)?;
+    let selected = get_with_dependents(&config, selected);
+    for sandbox in order_sandboxes(&config, &selected).into_iter().rev() {
+        stop_sandbox(&pool, sandbox.get_name()).await?;
+        ipam::release_sandbox_ip(&pool, sandbox.get_name()).await?;
+        runtime::remove_network_policy(sandbox.get_name()).await?;
+    }
+
+    // Sandboxes removed from the configuration while running have no known order
+    if names.is_empty() {
+        let running = sqlx::query(&format!(
+            "SELECT DISTINCT name FROM sandboxes WHERE status IN ({})",
+            ACTIVE_STATUSES
+        ))
+        .fetch_all(&pool)
+        .await?;
+        for row in running {
+            let name = row.get::<&str, _>("name");
+            stop_sandbox(&pool, name).await?;
+            ipam::release_sandbox_ip(&pool, name).await?;
+            runtime::remove_network_policy(name).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the recorded states of sandboxes of a project.
+///
+/// Sandboxes whose processes are gone are marked as failed first, so the states reflect what is
+/// actually running.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+/// * `names` - The sandboxes to show, or the groups to show the sandboxes of if `group` is set.
+///   If empty, all sandboxes with a record are shown, including ones no longer in the
+///   configuration
+/// * `group` - Whether `names` are group names
+///
+/// ## Example
+/// ```no_run
+/// use monocore::management;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// for state in management::status(None, &[], false).await? {
+///     println!("{}: {}", state.get_name(), state.get_status());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn status(
+    project_path: Option<PathBuf>,
+    names: &[String],
+    group: bool,
+) -> MonocoreResult<Vec<SandboxState>> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+    lifecycle::reconcile_sandboxes(&pool).await?;
+
+    let mut states = lifecycle::get_sandbox_states(&pool).await?;
+    if !names.is_empty() {
+        let config = load_config(Some(project_path)).await?;
+        let selected: HashSet<_> = select_sandboxes(&config, names, group)?
+            .into_iter()
+            .map(|sandbox| sandbox.get_name())
+            .collect();
+        states.retain(|state| selected.contains(state.get_name()));
+    }
+
+    Ok(states)
+}
+
+/// Returns the recorded health check results of a sandbox of a project, oldest first.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+/// * `name` - The name of the sandbox
+pub async fn health_checks(
+    project_path: Option<PathBuf>,
+    name: &str,
+) -> MonocoreResult<Vec<HealthCheckRecord>> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+    health::get_health_checks(&pool, name).await
+}
+
+/// Returns the latest sample of the resources used by each sandbox of a project, ordered by name.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+pub async fn metrics(project_path: Option<PathBuf>) -> MonocoreResult<Vec<SandboxMetrics>> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+    metrics::get_latest_sandbox_metrics(&pool).await
+}
+
+/// Marks the sandboxes of a project whose processes are gone as failed.
+///
+/// Does nothing if the project has no sandbox database yet.
+///
+/// ## Arguments
+///
+/// * `project_path` - Optional path of the project. If None, uses current directory
+pub async fn reconcile(project_path: Option<PathBuf>) -> MonocoreResult<()> {
+    let project_path = project_path.unwrap_or_else(|| PathBuf::from("."));
+
+    let db_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(SANDBOX_DB_FILENAME);
+    if !db_path.exists() {
+        return Ok(());
+    }
+
+    let pool = db::get_or_create_db_pool(&db_path, &SANDBOX_DB_MIGRATOR).await?;
+    for name in lifecycle::reconcile_sandboxes(&pool).await? {
+        tracing::warn!("sandbox {} stopped without its exit being recorded", name);
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the sandboxes named, or the sandboxes in the groups named. All sandboxes if no names
+/// are given.
+fn select_sandboxes<'a>(
+    config: &'a Monocore,
+    names: &[String],
+    group: bool,
+) -> MonocoreResult<Vec<&'a Sandbox>> {
+    let sandboxes = config.get_sandboxes().as_deref().unwrap_or_default();
+    if names.is_empty() {
+        return Ok(sandboxes.iter().collect());
+    }
+
+    if group {
+        if let Some(name) = names.iter().find(|name| config.get_group(name).is_none()) {
+            return Err(MonocoreError::InvalidArgument(format!(
+                "group '{}' is not in the configuration",
+                name
+            )));
+        }
+
+        return Ok(sandboxes
+            .iter()
+            .filter(|sandbox| {
+                sandbox
+                    .get_groups()
+                    .as_ref()
+                    .is_some_and(|groups| names.iter().any(|name| groups.contains_key(name)))
+            })
+            .collect());
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            config
+                .get_sandbox(name)
+                .ok_or_else(|| MonocoreError::ServiceNotFound(name.clone()))
+        })
+        .collect()
+}
+
+/// Returns the names of the sandboxes and all the sandboxes they depend on.
+fn get_with_dependencies<'a>(config: &'a Monocore, selected: Vec<&'a Sandbox>) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    let mut stack = selected;
+    while let Some(sandbox) = stack.pop() {
+        if names.insert(sandbox.get_name().as_str()) {
+            stack.extend(
+                sandbox
+                    .get_depends_on()
+                    .iter()
+                    .flatten()
+                    .filter_map(|dependency| config.get_sandbox(dependency.get_name())),
+            );
+        }
+    }
+
+    names
+}
+
+/// Returns the names of the sandboxes and all the sandboxes that depend on them.
+fn get_with_dependents<'a>(config: &'a Monocore, selected: Vec<&'a Sandbox>) -> HashSet<&'a str> {
+    let sandboxes = config.get_sandboxes().as_deref().unwrap_or_default();
+    let mut names: HashSet<_> = selected.iter().map(|s| s.get_name().as_str()).collect();
+    loop {
+        let dependents: Vec<_> = sandboxes
+            .iter()
+            .filter(|sandbox| !names.contains(sandbox.get_name().as_str()))
+            .filter(|sandbox| {
+                sandbox
+                    .get_depends_on()
+                    .iter()
+                    .flatten()
+                    .any(|dependency| names.contains(dependency.get_name().as_str()))
+            })
+            .collect();
+
+        if dependents.is_empty() {
+            return names;
+        }
+
+        names.extend(dependents.iter().map(|s| s.get_name().as_str()));
+    }
+}
+
+/// Returns the named sandboxes ordered so every sandbox comes after the sandboxes it depends on.
+/// Sandboxes keep their configuration order otherwise.
+///
+/// The configuration must have been validated so its dependencies have no cycles.
+fn order_sandboxes<'a>(config: &'a Monocore, names: &HashSet<&str>) -> Vec<&'a Sandbox> {
+    fn visit<'a>(
+        config: &'a Monocore,
+        sandbox: &'a Sandbox,
+        names: &HashSet<&str>,
+        visited: &mut HashSet<&'a str>,
+        ordered: &mut Vec<&'a Sandbox>,
+    ) {
+        if !visited.insert(sandbox.get_name()) {
+            return;
+        }
+
+        for dependency in sandbox.get_depends_on().iter().flatten() {
+            if let Some(dependency) = config.get_sandbox(dependency.get_name()) {
+                visit(config, dependency, names, visited, ordered);
+            }
+        }
+
+        if names.contains(sandbox.get_name().as_str()) {
+            ordered.push(sandbox);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut ordered = Vec::new();
+    for sandbox in config.get_sandboxes().iter().flatten() {
+        if names.contains(sandbox.get_name().as_str()) {
+            visit(config, sandbox, names, &mut visited, &mut ordered);
+        }
+    }
+
+    ordered
+}
+
+/// Records a sandbox as created, assigns it an address in its group, applies its network policy
+/// and starts a supervisor running it with the given backend.
+///
+/// The sandbox is marked as failed, with the error, if it can't be given an address, its network
+/// policy can't be applied, it can't be prepared or its supervisor can't be started.
+async fn start_sandbox(
+    project_path: &Path,
+    pool: &Pool<Sqlite>,
+    config: &Monocore,
+    sandbox: &Sandbox,
+    backend: SandboxBackendType,
+) -> MonocoreResult<()> {
+    let name = sandbox.get_name();
+    let rootfs_path = project_path
+        .join(MONOCORE_ENV_DIR)
+        .join(ROOTS_SUBDIR)
+        .join(name);
+
+    // The record goes in before the supervisor starts so its monitor always finds it
+    lifecycle::create_sandbox_record(pool, name, &rootfs_path, &serde_json::to_string(sandbox)?)
+        .await?;
+
+    let assigned_ip = match ipam::assign_sandbox_ip(pool, config, sandbox).await {
+        Ok(assigned_ip) => assigned_ip,
+        Err(e) => return Err(fail_sandbox(pool, name, e).await),
+    };
+
+    // Sandboxes outside groups have no address of their own to filter the traffic of
+    if let Some(address) = assigned_ip {
+        let reach = sandbox
+            .get_network()
+            .as_ref()
+            .and_then(|network| network.get_reach().clone())
+            .unwrap_or(SandboxNetworkReach::Local);
+        let group_subnets = match ipam::get_group_subnets(pool).await {
+            Ok(group_subnets) => group_subnets,
+            Err(e) => return Err(fail_sandbox(pool, name, e).await),
+        };
+        let policy = NetworkPolicy::compile(&reach, address, &group_subnets);
+        if let Err(e) = runtime::apply_network_policy(name, &policy).await {
+            return Err(fail_sandbox(pool, name, e).await);
+        }
+    }
+
+    let child_args = match get_supervisor_args(
+        project_path,
+        sandbox,
+        &rootfs_path,
+        assigned_ip.map(|address| address.ip()),
+        backend,
+    )
+    .await
+    {
+        Ok(child_args) => child_args,
+        Err(e) => return Err(fail_sandbox(pool, name, e).await),
+    };
+
+    lifecycle::transition_sandbox(pool, name, SandboxStatus::Starting).await?;
+
+    // The supervisor gets its own process group so it outlives the terminal that started it
+    let supervisor = find_mcrun_exe().and_then(|mcrun_exe| {
+        Ok(Command::new(mcrun_exe)
+            .args(&child_args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?)
+    });
+
+    let supervisor = match supervisor {
+        Ok(supervisor) => supervisor,
+        Err(e) => return Err(fail_sandbox(pool, name, e).await),
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE sandboxes
+        SET supervisor_pid = ?, modified_at = CURRENT_TIMESTAMP
+        WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
+        "#,
+    )
+    .bind(supervisor.id())
+    .bind(name)
+    .execute(pool)
+    .await?;
+
+    tracing::info!(
+        "started sandbox {} with supervisor pid {}",
+        name,
+        supervisor.id()
+    );
+
+    Ok(())
+}
+
+/// Prepares the root filesystem of a sandbox, pulling its image if needed, and returns the
+/// arguments of the `mcrun supervisor` process that runs it.
+async fn get_supervisor_args(
+    project_path: &Path,
+    sandbox: &Sandbox,
+    rootfs_path: &Path,
+    assigned_ip: Option<Ipv4Addr>,
+    backend: SandboxBackendType,
+) -> MonocoreResult<Vec<String>> {
+    let menv_path = project_path.join(MONOCORE_ENV_DIR);
+    let image = image::get_or_pull_image_config(sandbox.get_image()).await?;
+
+    if !rootfs_path.exists() {
+        prepare_rootfs(&image, rootfs_path).await?;
+    }
+
+    let (exec_path, args) = get_exec_command(sandbox, &image)?;
+    let env = get_env(project_path, sandbox, &image)?;
+
+    let mut child_args = vec![
+        "supervisor".to_string(),
+        format!("--log-dir={}", menv_path.join(LOG_SUBDIR).display()),
+        format!("--child-name={}", sandbox.get_name()),
+        format!(
+            "--sandbox-db-path={}",
+            menv_path.join(SANDBOX_DB_FILENAME).display()
+        ),
+        format!(
+            "--root-path={}",
+            fs::canonicalize(rootfs_path).await?.display()
+        ),
+        format!("--num-vcpus={}", sandbox.get_cpus()),
+        format!("--ram-mib={}", sandbox.get_ram()),
+        format!("--exec-path={}", exec_path),
+        format!("--backend={}", backend),
+    ];
+    if let Some(assigned_ip) = assigned_ip {
+        child_args.push(format!("--assigned-ip={}", assigned_ip));
+    }
+    if let Some(restart) = sandbox.get_restart() {
+        child_args.push(format!("--restart={}", restart));
+    }
+    if let Some(interval) = get_metrics_interval()? {
+        child_args.push(format!("--metrics-interval={}", interval));
+    }
+    if let Some(healthcheck) = sandbox.get_healthcheck() {
+        child_args.push(format!(
+            "--healthcheck={}",
+            serde_json::to_string(healthcheck)?
+        ));
+    }
+    child_args.extend(args.iter().map(|arg| format!("--args={}", arg)));
+    child_args.extend(env.iter().map(|env| format!("--env={}", env)));
+    child_args.extend(
+        sandbox
+            .get_ports()
+            .iter()
+            .map(|port| format!("--port-map={}", port)),
+    );
+    for volume in sandbox.get_volumes() {
+        let host = project_path.join(volume.get_host().as_str());
+        let host = fs::canonicalize(&host)
+            .await
+            .map_err(|_| MonocoreError::PathNotFound(host.display().to_string()))?;
+        let volume = PathPair::with_distinct(
+            Utf8UnixPathBuf::from(host.to_string_lossy().into_owned()),
+            volume.get_guest().clone(),
+        );
+        child_args.push(format!("--mapped-dirs={}", volume));
+    }
+    if let Some(workdir) = sandbox
+        .get_workdir()
+        .as_ref()
+        .map(|workdir| workdir.to_string())
+        .or_else(|| image.working_dir.clone())
+    {
+        child_args.push(format!("--workdir-path={}", workdir));
+    }
+
+    Ok(child_args)
+}
+
+/// Checks that a sandbox only uses what its backend supports, like exec healthchecks, which
+/// need the backend to run commands in the sandbox.
+fn check_backend_support(sandbox: &Sandbox, backend: SandboxBackendType) -> MonocoreResult<()> {
+    let has_exec_healthcheck = sandbox
+        .get_healthcheck()
+        .as_ref()
+        .is_some_and(|healthcheck| matches!(healthcheck.get_probe(), HealthProbe::Exec(_)));
+    if has_exec_healthcheck && !backend.supports_exec() {
+        return Err(MonocoreError::ConfigValidation(format!(
+            "sandbox '{}' has an exec healthcheck, which the {} backend can't run",
+            sandbox.get_name(),
+            backend
+        )));
+    }
+
+    Ok(())
+}
+
+/// Marks a sandbox as failed with an error and returns the error.
+async fn fail_sandbox(pool: &Pool<Sqlite>, name: &str, error: MonocoreError) -> MonocoreError {
+    let status = SandboxStatus::Failed(error.to_string());
+    if let Err(e) = lifecycle::transition_sandbox(pool, name, status).await {
+        tracing::warn!("failed to record the failure of sandbox {}: {}", name, e);
+    }
+
+    error
+}
+
+/// Copies the layers of an image into a new root filesystem. The layers are copied next to the
+/// root filesystem first, so a failed copy doesn't leave a partial root filesystem behind.
+async fn prepare_rootfs(image: &ImageRunConfig, rootfs_path: &Path) -> MonocoreResult<()> {
+    let layers_dir = get_monocore_home_path().join(LAYERS_SUBDIR);
+    let layer_paths: Vec<_> = image
+        .layer_digests
+        .iter()
+        .map(|digest| layers_dir.join(format!("{}.{}", digest, EXTRACTED_LAYER_SUFFIX)))
+        .collect();
+
+    if let Some(missing) = layer_paths.iter().find(|path| !path.exists()) {
+        return Err(MonocoreError::LayerExtraction(format!(
+            "extracted layer not found: {}",
+            missing.display()
+        )));
+    }
+
+    let partial_path = rootfs_path.with_extension("partial");
+    if partial_path.exists() {
+        fs::remove_dir_all(&partial_path).await?;
+    }
+
+    tracing::info!("preparing rootfs at {}", rootfs_path.display());
+    let copy_path = partial_path.clone();
+    tokio::task::spawn_blocking(move || rootfs::copy_oci_layers_to_dir(&layer_paths, copy_path))
+        .await??;
+
+    fs::rename(&partial_path, rootfs_path).await?;
+
+    Ok(())
+}
+
+/// Returns the executable and arguments a sandbox runs: its start script in its shell, or the
+/// entrypoint and command of its image.
+fn get_exec_command(
+    sandbox: &Sandbox,
+    image: &ImageRunConfig,
+) -> MonocoreResult<(String, Vec<String>)> {
+    if let Some(script) = sandbox
+        .get_scripts()
+        .as_ref()
+        .and_then(|scripts| scripts.get(DEFAULT_SCRIPT))
+    {
+        // Command lines can't contain newlines, so the lines of the script are chained instead
+        let shell = sandbox.get_shell().as_deref().unwrap_or(DEFAULT_SHELL);
+        return Ok((
+            shell.to_string(),
+            vec!["-c".to_string(), script.join(" && ")],
+        ));
+    }
+
+    let mut command = image.entrypoint.iter().chain(&image.cmd).cloned();
+    let exec_path = command.next().ok_or_else(|| {
+        MonocoreError::ConfigValidation(format!(
+            "sandbox '{}' has no '{}' script and its image has no entrypoint or command",
+            sandbox.get_name(),
+            DEFAULT_SCRIPT
+        ))
+    })?;
+
+    Ok((exec_path, command.collect()))
+}
+
+/// Returns the environment of a sandbox: the environment of its image, overridden by its
+/// environment file, overridden by its environment variables.
+fn get_env(
+    project_path: &Path,
+    sandbox: &Sandbox,
+    image: &ImageRunConfig,
+) -> MonocoreResult<Vec<EnvPair>> {
+    let mut env = image
+        .env
+        .iter()
+        .map(|pair| pair.parse())
+        .collect::<MonocoreResult<Vec<EnvPair>>>()?;
+
+    if let Some(env_file) = sandbox.get_env_file() {
+        let env_file = project_path.join(env_file.as_str());
+        let pairs = dotenvy::from_path_iter(&env_file).map_err(|e| {
+            MonocoreError::ConfigParseError(format!("{}: {}", env_file.display(), e))
+        })?;
+        for pair in pairs {
+            let (name, value) = pair.map_err(|e| {
+                MonocoreError::ConfigParseError(format!("{}: {}", env_file.display(), e))
+            })?;
+            env.push(EnvPair::new(name, value));
+        }
+    }
+
+    env.extend(sandbox.get_envs().iter().flatten().cloned());
+
+    // Keep only the last value of each variable
+    let mut seen = HashSet::new();
+    let mut deduped: Vec<_> = env
+        .into_iter()
+        .rev()
+        .filter(|pair| seen.insert(pair.get_name().clone()))
+        .collect();
+    deduped.reverse();
+
+    Ok(deduped)
+}
+
+/// Returns whether a sandbox is active and its supervisor is still running.
+async fn is_sandbox_running(pool: &Pool<Sqlite>, name: &str) -> MonocoreResult<bool> {
+    let Some(state) = lifecycle::get_sandbox_state(pool, name).await? else {
+        return Ok(false);
+    };
+
+    Ok(state.get_status().is_active()
+        && state
+            .get_supervisor_pid()
+            .is_some_and(|pid| is_process_alive(pid as i64)))
+}
+
+/// Stops the processes of a sandbox, keeping its record.
+///
+/// The supervisor records how the sandbox exited. If it couldn't, because it had to be killed
+/// or exited first, the sandbox is marked as failed instead.
+async fn stop_sandbox(pool: &Pool<Sqlite>, name: &str) -> MonocoreResult<()> {
+    let Some(state) = lifecycle::get_sandbox_state(pool, name).await? else {
+        return Ok(());
+    };
+
+    if !state.get_status().is_active() {
+        return Ok(());
+    }
+
+    if state.get_status() != &SandboxStatus::Stopping {
+        match lifecycle::transition_sandbox(pool, name, SandboxStatus::Stopping).await {
+            Ok(()) => {}
+            // The sandbox exited while it was being read
+            Err(MonocoreError::InvalidSandboxTransition { .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let pids: Vec<_> = [state.get_supervisor_pid(), state.get_microvm_pid()]
+        .into_iter()
+        .flatten()
+        .map(|pid| *pid as i64)
+        .collect();
+
+    let mut killed = false;
+    if !pids.is_empty() {
+        tracing::info!("stopping sandbox {}", name);
+        killed = stop_processes(&pids).await?;
+    }
+
+    let stopped = lifecycle::get_sandbox_state(pool, name).await?;
+    if stopped.is_some_and(|state| state.get_status() == &SandboxStatus::Stopping) {
+        let reason = if killed {
+            format!(
+                "killed after not stopping within {}s",
+                SANDBOX_STOP_TIMEOUT.as_secs()
+            )
+        } else {
+            "supervisor exited without recording the exit of the sandbox".to_string()
+        };
+        lifecycle::transition_sandbox(pool, name, SandboxStatus::Failed(reason)).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends SIGTERM to the first process, the supervisor, and SIGKILL to all the processes if they
+/// haven't exited after [`SANDBOX_STOP_TIMEOUT`].
+///
+/// ## Returns
+///
+/// Whether the processes had to be killed.
+async fn stop_processes(pids: &[i64]) -> MonocoreResult<bool> {
+    send_signal(pids[0], Signal::SIGTERM)?;
+
+    let deadline = time::Instant::now() + SANDBOX_STOP_TIMEOUT;
+    while pids.iter().any(|pid| is_process_alive(*pid)) {
+        if time::Instant::now() >= deadline {
+            tracing::warn!("processes {:?} did not stop in time, killing them", pids);
+            for pid in pids {
+                send_signal(*pid, Signal::SIGKILL)?;
+            }
+            return Ok(true);
+        }
+
+        time::sleep(SANDBOX_STOP_POLL_INTERVAL).await;
+    }
+
+    Ok(false)
+}
+
+/// Sends a signal to a process, ignoring processes that have already exited.
+fn send_signal(pid: i64, signal: Signal) -> MonocoreResult<()> {
+    match signal::kill(Pid::from_raw(pid as i32), signal) {
+        Err(nix::errno::Errno::ESRCH) => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Finds the mcrun binary: the path in `MCRUN_EXE`, next to the current executable, or in the
+/// monocore home `bin` directory.
+fn find_mcrun_exe() -> MonocoreResult<PathBuf> {
+    if let Ok(path) = std::env::var(MCRUN_EXE_ENV_VAR) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let sibling = std::env::current_exe()?.with_file_name(MCRUN_EXE_FILENAME);
+    let installed = get_monocore_home_path()
+        .join(BIN_SUBDIR)
+        .join(MCRUN_EXE_FILENAME);
+
+    [sibling, installed]
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or_else(|| MonocoreError::SupervisorBinaryNotFound(MCRUN_EXE_FILENAME.to_string()))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_config() -> anyhow::Result<Monocore> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: web
+                image: alpine
+                depends_on: [api]
+                groups:
+                  frontend: {}
+              - name: api
+                image: alpine
+                depends_on: [db, cache]
+                groups:
+                  backend: {}
+              - name: cache
+                image: alpine
+                groups:
+                  backend: {}
+              - name: db
+                image: alpine
+              - name: worker
+                image: alpine
+                depends_on: [db]
+            groups:
+              - name: frontend
+              - name: backend
+            "#,
+        )?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn get_names(sandboxes: &[&Sandbox]) -> Vec<String> {
+        sandboxes.iter().map(|s| s.get_name().clone()).collect()
+    }
+
+    #[test]
+    fn test_orchestration_orders_sandboxes_by_dependencies() -> anyhow::Result<()> {
+        let config = get_test_config()?;
+
+        let all = select_sandboxes(&config, &[], false)?;
+        let ordered = order_sandboxes(&config, &get_with_dependencies(&config, all));
+        assert_eq!(get_names(&ordered), ["db", "cache", "api", "web", "worker"]);
+
+        // Starting a sandbox starts what it depends on
+        let api = select_sandboxes(&config, &["api".to_string()], false)?;
+        let ordered = order_sandboxes(&config, &get_with_dependencies(&config, api));
+        assert_eq!(get_names(&ordered), ["db", "cache", "api"]);
+
+        // Stopping a sandbox stops what depends on it, dependents first
+        let db = select_sandboxes(&config, &["db".to_string()], false)?;
+        let mut ordered = order_sandboxes(&config, &get_with_dependents(&config, db));
+        ordered.reverse();
+        assert_eq!(get_names(&ordered), ["worker", "web", "api", "db"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_orchestration_selects_sandboxes_by_name_or_group() -> anyhow::Result<()> {
+        let config = get_test_config()?;
+
+        let backend = select_sandboxes(&config, &["backend".to_string()], true)?;
+        assert_eq!(get_names(&backend), ["api", "cache"]);
+
+        assert!(matches!(
+            select_sandboxes(&config, &["missing".to_string()], false),
+            Err(MonocoreError::ServiceNotFound(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            select_sandboxes(&config, &["missing".to_string()], true),
+            Err(MonocoreError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_orchestration_checks_backend_support() -> anyhow::Result<()> {
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: db
+                image: alpine
+                healthcheck:
+                  exec: ["pg_isready"]
+              - name: api
+                image: alpine
+                healthcheck:
+                  tcp: 80
+            "#,
+        )?;
+        config.validate()?;
+        let db = config.get_sandbox("db").unwrap();
+        let api = config.get_sandbox("api").unwrap();
+
+        // The backend isn't known until the sandbox is started, where it defaults to a microvm
+        check_backend_support(db, SandboxBackendType::Container)?;
+        check_backend_support(db, SandboxBackendType::Process)?;
+        check_backend_support(api, SandboxBackendType::MicroVm)?;
+        assert!(matches!(
+            check_backend_support(db, SandboxBackendType::MicroVm),
+            Err(MonocoreError::ConfigValidation(e))
+                if e == "sandbox 'db' has an exec healthcheck, which the microvm backend can't run"
+        ));
+
+        // A backend set in the configuration is checked when it is validated
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: db
+                image: alpine
+                backend: microvm
+                healthcheck:
+                  exec: ["pg_isready"]
+            "#,
+        )?;
+        assert!(matches!(
+            config.validate(),
+            Err(MonocoreError::ConfigValidation(e))
+                if e == "sandbox 'db' has an exec healthcheck, which the microvm backend can't run"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_orchestration_exec_command_and_env() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join(".env"),
+            "FROM_FILE=file\nSHARED=file\n",
+        )?;
+
+        let config: Monocore = serde_yaml::from_str(
+            r#"
+            sandboxes:
+              - name: scripted
+                image: alpine
+                shell: /bin/bash
+                env_file: .env
+                envs: ["SHARED=sandbox"]
+                scripts:
+                  start: ["cd /app", "exec ./server"]
+              - name: plain
+                image: alpine
+            "#,
+        )?;
+        let image = ImageRunConfig {
+            entrypoint: vec!["/entrypoint.sh".to_string()],
+            cmd: vec!["serve".to_string()],
+            env: vec!["PATH=/bin".to_string(), "SHARED=image".to_string()],
+            ..Default::default()
+        };
+
+        let scripted = config.get_sandbox("scripted").unwrap();
+        assert_eq!(
+            get_exec_command(scripted, &image)?,
+            (
+                "/bin/bash".to_string(),
+                vec!["-c".to_string(), "cd /app && exec ./server".to_string()]
+            )
+        );
+        let env = get_env(temp_dir.path(), scripted, &image)?;
+        assert_eq!(
+            env.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["PATH=/bin", "FROM_FILE=file", "SHARED=sandbox"]
+        );
+
+        let plain = config.get_sandbox("plain").unwrap();
+        assert_eq!(
+            get_exec_command(plain, &image)?,
+            ("/entrypoint.sh".to_string(), vec!["serve".to_string()])
+        );
+        assert!(get_exec_command(plain, &ImageRunConfig::default()).is_err());
+
+        Ok(())
+    }
+}
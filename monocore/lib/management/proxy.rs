@@ -0,0 +1,661 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use futures::StreamExt;
+use getset::Getters;
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use tokio::{net::TcpListener, sync::Semaphore};
+use typed_builder::TypedBuilder;
+
+use crate::{
+    config::{ProxyConfig, Sandbox},
+    MonocoreResult,
+};
+
+use super::orchestration;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The port the reverse proxy listens on by default.
+pub const DEFAULT_PROXY_PORT: u16 = 8080;
+
+/// The headers that only apply to a single connection, which aren't passed on.
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// The header holding the addresses of the clients a request was forwarded for.
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// The header holding the host a request was originally sent to.
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+/// The header holding the protocol a request was originally sent with.
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// The header holding the path prefix stripped from a request.
+const X_FORWARDED_PREFIX: HeaderName = HeaderName::from_static("x-forwarded-prefix");
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A route of the reverse proxy, forwarding the requests under a path prefix to a sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, TypedBuilder, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct ProxyRoute {
+    /// The name of the sandbox.
+    #[builder(setter(into))]
+    sandbox: String,
+
+    /// The path prefix, without leading or trailing slashes. Routes with an empty prefix get all
+    /// the requests no other route gets.
+    #[builder(setter(into))]
+    prefix: String,
+
+    /// The address the sandbox serves on.
+    upstream: SocketAddr,
+
+    /// How long connections to the sandbox are kept open while idle. If None, the default of the
+    /// HTTP client is used.
+    #[builder(default)]
+    keep_alive: Option<Duration>,
+
+    /// How many requests can be forwarded to the sandbox at once. Other requests wait for one of
+    /// them to finish. If None, there is no limit.
+    #[builder(default)]
+    concurrency: Option<usize>,
+}
+
+/// A route along with the client and the concurrency limit requests are forwarded with.
+#[derive(Debug)]
+struct RouteState {
+    /// The route.
+    route: ProxyRoute,
+
+    /// The client keeping the connections to the sandbox.
+    client: reqwest::Client,
+
+    /// The permits to forward a request, if the concurrency is limited.
+    permits: Option<Arc<Semaphore>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ProxyRoute {
+    /// Returns the route of the legacy proxy of a sandbox, or None if it has none or no port to
+    /// forward to.
+    ///
+    /// Requests go to the host side of the proxy port, or of the first port of the sandbox if the
+    /// proxy has none.
+    pub fn from_sandbox(sandbox: &Sandbox) -> Option<Self> {
+        let Some(ProxyConfig::Legacy {
+            keep_alive,
+            concurrency,
+            port,
+            ..
+        }) = sandbox.get_proxy()
+        else {
+            return None;
+        };
+
+        let port = port.as_ref().or(sandbox.get_ports().first())?;
+        Some(Self {
+            sandbox: sandbox.get_name().clone(),
+            prefix: sandbox.get_proxy_prefix()?,
+            upstream: (Ipv4Addr::LOCALHOST, port.get_host()).into(),
+            keep_alive: keep_alive
+                .as_deref()
+                .and_then(ProxyConfig::parse_keep_alive),
+            concurrency: concurrency.map(|concurrency| concurrency as usize),
+        })
+    }
+
+    /// Returns the path a request is forwarded to the sandbox with, or None if the request path
+    /// isn't under the prefix of the route.
+    pub fn get_upstream_path<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if self.prefix.is_empty() {
+            return Some(path);
+        }
+
+        match path.strip_prefix('/')?.strip_prefix(self.prefix.as_str())? {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Runs a reverse proxy for the sandboxes of a project with a legacy proxy until interrupted.
+///
+/// ## Arguments
+///
+/// * `project_path` - The path to the project, or the current directory if None
+/// * `addr` - The address to listen on
+pub async fn serve_proxy(project_path: Option<PathBuf>, addr: SocketAddr) -> MonocoreResult<()> {
+    let config = orchestration::load_config(project_path).await?;
+    let routes: Vec<_> = config
+        .get_sandboxes()
+        .iter()
+        .flatten()
+        .filter_map(ProxyRoute::from_sandbox)
+        .collect();
+    if routes.is_empty() {
+        tracing::warn!("no sandboxes have a legacy proxy");
+    }
+
+    for route in &routes {
+        tracing::info!(
+            "proxying /{} to sandbox {} on {}",
+            route.prefix,
+            route.sandbox,
+            route.upstream
+        );
+    }
+
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("proxy listening on {}", listener.local_addr()?);
+
+    axum::serve(
+        listener,
+        proxy_router(routes)?.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Creates a router forwarding requests to sandboxes along the given routes.
+///
+/// Requests are sent to the route with the longest prefix they are under, with the prefix
+/// stripped from their path. They are streamed both ways, and connections upgraded by the sandbox,
+/// like websockets, are tunnelled through. The `X-Forwarded-For`, `X-Forwarded-Host`,
+/// `X-Forwarded-Proto` and `X-Forwarded-Prefix` headers tell the sandbox where requests come from.
+///
+/// The router needs the address of clients, so it has to be served with
+/// [`Router::into_make_service_with_connect_info`] for [`SocketAddr`].
+///
+/// ## Example
+///
+/// ```no_run
+/// use std::net::SocketAddr;
+///
+/// use monocore::management::{self, ProxyRoute};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let route = ProxyRoute::builder()
+///     .sandbox("api")
+///     .prefix("api")
+///     .upstream("127.0.0.1:3000".parse()?)
+///     .concurrency(Some(16))
+///     .build();
+///
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
+/// let router = management::proxy_router(vec![route])?;
+/// axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn proxy_router(mut routes: Vec<ProxyRoute>) -> MonocoreResult<Router> {
+    routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+
+    let routes = routes
+        .into_iter()
+        .map(|route| {
+            // Redirects are for the client to follow, not the proxy
+            let mut client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .no_proxy();
+            if let Some(keep_alive) = route.keep_alive {
+                client = client.pool_idle_timeout(keep_alive);
+            }
+
+            Ok(RouteState {
+                client: client.build()?,
+                permits: route
+                    .concurrency
+                    .map(|concurrency| Arc::new(Semaphore::new(concurrency))),
+                route,
+            })
+        })
+        .collect::<MonocoreResult<Vec<_>>>()?;
+
+    Ok(Router::new()
+        .fallback(proxy_request)
+        .with_state(Arc::new(routes)))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Forwards a request to the sandbox of the route it is under.
+async fn proxy_request(
+    State(routes): State<Arc<Vec<RouteState>>>,
+    ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+) -> Response {
+    let path = request.uri().path();
+    let Some((state, upstream_path)) = routes
+        .iter()
+        .find_map(|state| Some((state, state.route.get_upstream_path(path)?)))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            "no sandbox is proxied under this path",
+        )
+            .into_response();
+    };
+
+    let url = match request.uri().query() {
+        Some(query) => format!("http://{}{}?{}", state.route.upstream, upstream_path, query),
+        None => format!("http://{}{}", state.route.upstream, upstream_path),
+    };
+
+    // Requests over the concurrency limit wait for a permit, which is held until the response
+    // body or the upgraded connection is done with
+    let permit = match &state.permits {
+        Some(permits) => Some(
+            permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("proxy permits are never closed"),
+        ),
+        None => None,
+    };
+
+    let mut headers = get_forwarded_headers(request.headers(), client_addr, &state.route.prefix);
+    let protocol = get_upgrade_protocol(request.headers());
+    let client_upgrade = match &protocol {
+        Some(protocol) => {
+            headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+            headers.insert(header::UPGRADE, protocol.clone());
+            Some(hyper::upgrade::on(&mut request))
+        }
+        None => None,
+    };
+
+    // Requests without a length or chunked encoding have no body
+    let has_body = request.headers().contains_key(header::TRANSFER_ENCODING)
+        || request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .is_some_and(|length| length != "0");
+
+    let mut upstream_request = state
+        .client
+        .request(request.method().clone(), url)
+        .headers(headers);
+    if has_body {
+        upstream_request = upstream_request.body(reqwest::Body::wrap_stream(
+            request.into_body().into_data_stream(),
+        ));
+    }
+
+    let response = match upstream_request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!(
+                "failed to forward request to sandbox {}: {}",
+                state.route.sandbox,
+                e
+            );
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("sandbox '{}' is unreachable", state.route.sandbox),
+            )
+                .into_response();
+        }
+    };
+
+    let status = response.status();
+    let mut response_headers = strip_hop_by_hop_headers(response.headers());
+
+    if status == StatusCode::SWITCHING_PROTOCOLS {
+        let upstream_protocol = response.headers().get(header::UPGRADE).cloned();
+        let (Some(client_upgrade), Some(upstream_protocol)) = (client_upgrade, upstream_protocol)
+        else {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!(
+                    "sandbox '{}' switched protocols unasked",
+                    state.route.sandbox
+                ),
+            )
+                .into_response();
+        };
+
+        response_headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        response_headers.insert(header::UPGRADE, upstream_protocol);
+
+        let sandbox = state.route.sandbox.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            tunnel(&sandbox, client_upgrade, response).await;
+        });
+
+        return (status, response_headers).into_response();
+    }
+
+    let body = response.bytes_stream().map(move |chunk| {
+        let _ = &permit;
+        chunk
+    });
+
+    (status, response_headers, Body::from_stream(body)).into_response()
+}
+
+/// Copies data both ways between an upgraded client connection and an upgraded connection to a
+/// sandbox until either is closed.
+async fn tunnel(sandbox: &str, client: OnUpgrade, upstream: reqwest::Response) {
+    let upgraded = async {
+        let client = client.await.map_err(|e| e.to_string())?;
+        let upstream = upstream.upgrade().await.map_err(|e| e.to_string())?;
+        Ok::<_, String>((client, upstream))
+    };
+
+    match upgraded.await {
+        Ok((client, mut upstream)) => {
+            if let Err(e) =
+                tokio::io::copy_bidirectional(&mut TokioIo::new(client), &mut upstream).await
+            {
+                tracing::debug!("upgraded connection to sandbox {} closed: {}", sandbox, e);
+            }
+        }
+        Err(e) => tracing::warn!("failed to upgrade connection to sandbox {}: {}", sandbox, e),
+    }
+}
+
+/// Returns the headers a request is forwarded with: its own without the hop-by-hop ones and
+/// `Host`, along with the forwarding headers.
+fn get_forwarded_headers(headers: &HeaderMap, client_addr: SocketAddr, prefix: &str) -> HeaderMap {
+    let mut forwarded = strip_hop_by_hop_headers(headers);
+    forwarded.remove(header::HOST);
+
+    let forwarded_for = match headers
+        .get(X_FORWARDED_FOR)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(previous) => format!("{}, {}", previous, client_addr.ip()),
+        None => client_addr.ip().to_string(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        forwarded.insert(X_FORWARDED_FOR, value);
+    }
+
+    if let Some(host) = headers.get(header::HOST) {
+        forwarded.insert(X_FORWARDED_HOST, host.clone());
+    }
+
+    forwarded.insert(X_FORWARDED_PROTO, HeaderValue::from_static("http"));
+    if let Ok(value) = HeaderValue::from_str(&format!("/{}", prefix)) {
+        forwarded.insert(X_FORWARDED_PREFIX, value);
+    }
+
+    forwarded
+}
+
+/// Returns the headers without the hop-by-hop ones, including those listed in `Connection`.
+fn strip_hop_by_hop_headers(headers: &HeaderMap) -> HeaderMap {
+    let listed = get_connection_options(headers);
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            !HOP_BY_HOP_HEADERS.contains(&name.as_str())
+                && !listed.iter().any(|option| option == name.as_str())
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Returns the protocol a request asks to upgrade its connection to, if any.
+fn get_upgrade_protocol(headers: &HeaderMap) -> Option<HeaderValue> {
+    get_connection_options(headers)
+        .iter()
+        .any(|option| option == "upgrade")
+        .then(|| headers.get(header::UPGRADE).cloned())
+        .flatten()
+}
+
+/// Returns the lowercase options listed in the `Connection` headers.
+fn get_connection_options(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|option| option.trim().to_ascii_lowercase())
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::routing::{any, get, post};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_proxy_forwards_requests() -> anyhow::Result<()> {
+        let upstream = start_server(
+            Router::new()
+                .route(
+                    "/echo",
+                    get(|request: Request| async move {
+                        let header = |name| {
+                            request
+                                .headers()
+                                .get(name)
+                                .map_or("", |value: &HeaderValue| {
+                                    value.to_str().unwrap_or_default()
+                                })
+                        };
+                        format!(
+                            "{} {} {} {}",
+                            request.uri(),
+                            header(X_FORWARDED_PREFIX),
+                            header(X_FORWARDED_FOR),
+                            header(X_FORWARDED_HOST)
+                        )
+                    }),
+                )
+                .route("/upload", post(|body: Body| async move { body }))
+                .route("/upgrade", any(echo_upgrade)),
+        )
+        .await?;
+
+        // A port nothing listens on
+        let down = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+
+        let proxy = start_server(proxy_router(vec![
+            ProxyRoute::builder()
+                .sandbox("api")
+                .prefix("api")
+                .upstream(upstream)
+                .build(),
+            ProxyRoute::builder()
+                .sandbox("api-v2")
+                .prefix("api/v2")
+                .upstream(upstream)
+                .keep_alive(Some(Duration::from_secs(1)))
+                .build(),
+            ProxyRoute::builder()
+                .sandbox("down")
+                .prefix("down")
+                .upstream(down)
+                .build(),
+        ])?)
+        .await?;
+
+        // Requests go to the route with the longest prefix, with the prefix stripped
+        let client = reqwest::Client::new();
+        let body = client
+            .get(format!("http://{}/api/echo?name=monocore", proxy))
+            .header(X_FORWARDED_FOR, "10.0.0.9")
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(
+            body,
+            format!("/echo?name=monocore /api 10.0.0.9, 127.0.0.1 {}", proxy)
+        );
+
+        let body = client
+            .get(format!("http://{}/api/v2/echo", proxy))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(body, format!("/echo /api/v2 127.0.0.1 {}", proxy));
+
+        // Bodies are streamed both ways
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("hello "), Ok("sandbox")];
+        let body = client
+            .post(format!("http://{}/api/upload", proxy))
+            .body(reqwest::Body::wrap_stream(futures::stream::iter(chunks)))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(body, "hello sandbox");
+
+        let response = client.get(format!("http://{}/other", proxy)).send().await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = client.get(format!("http://{}/down/", proxy)).send().await?;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        // Upgraded connections are tunnelled through
+        let mut stream = tokio::net::TcpStream::connect(proxy).await?;
+        stream
+            .write_all(
+                b"GET /api/upgrade HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+                  Upgrade: echo\r\n\r\n",
+            )
+            .await?;
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await?);
+        }
+        assert!(head.starts_with(b"HTTP/1.1 101"), "{:?}", head);
+
+        stream.write_all(b"ping").await?;
+        let mut echoed = [0; 4];
+        stream.read_exact(&mut echoed).await?;
+        assert_eq!(&echoed, b"ping");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proxy_limits_concurrency() -> anyhow::Result<()> {
+        let active = Arc::new(AtomicUsize::new(0));
+        let most_active = Arc::new(AtomicUsize::new(0));
+        let upstream = start_server(Router::new().route(
+            "/slow",
+            get({
+                let active = active.clone();
+                let most_active = most_active.clone();
+                move || async move {
+                    let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_active.fetch_max(now_active, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    "done"
+                }
+            }),
+        ))
+        .await?;
+
+        let proxy = start_server(proxy_router(vec![ProxyRoute::builder()
+            .sandbox("api")
+            .prefix("")
+            .upstream(upstream)
+            .concurrency(Some(2))
+            .build()])?)
+        .await?;
+
+        // Requests over the limit are queued rather than refused
+        let client = reqwest::Client::new();
+        let responses = futures::future::join_all(
+            (0..6).map(|_| client.get(format!("http://{}/slow", proxy)).send()),
+        )
+        .await;
+        for response in responses {
+            assert_eq!(response?.text().await?, "done");
+        }
+        assert_eq!(most_active.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    /// Serves a router on a free port of localhost and returns its address.
+    async fn start_server(router: Router) -> anyhow::Result<SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        Ok(addr)
+    }
+
+    /// Switches to a protocol echoing back what it receives.
+    async fn echo_upgrade(mut request: Request) -> Response {
+        let on_upgrade = hyper::upgrade::on(&mut request);
+        tokio::spawn(async move {
+            let upgraded = TokioIo::new(on_upgrade.await?);
+            let (mut reader, mut writer) = tokio::io::split(upgraded);
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            anyhow::Ok(())
+        });
+
+        (
+            StatusCode::SWITCHING_PROTOCOLS,
+            [(header::CONNECTION, "upgrade"), (header::UPGRADE, "echo")],
+        )
+            .into_response()
+    }
+}
@@ -9,9 +9,10 @@ use monofs::filesystem::{
     UNIX_MTIME_KEY, UNIX_UID_KEY,
 };
 use std::{
+    collections::BTreeMap,
     fs,
-    os::unix::fs::{MetadataExt, PermissionsExt},
-    path::Path,
+    os::unix::fs::{symlink, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
 };
 use tokio::{fs::File as TokioFile, io::BufReader};
 
@@ -220,6 +221,41 @@ where
     Ok((cid, merged))
 }
 
+/// Copies OCI layers into a directory, applying them bottom-up with OCI layer semantics.
+///
+/// This is the copy-based counterpart of [`merge_oci_based_monofs_layers`] for runtimes that need
+/// the root filesystem as a plain host directory. Regular files, directories and symlinks are
+/// copied with their permissions; other file types and ownership are skipped since creating them
+/// needs privileges.
+///
+/// ## Arguments
+///
+/// * `layer_paths` - The extracted layer directories, ordered from the base layer up
+/// * `rootfs_path` - The directory to copy the layers into, created if it doesn't exist
+pub fn copy_oci_layers_to_dir(
+    layer_paths: &[impl AsRef<Path>],
+    rootfs_path: impl AsRef<Path>,
+) -> MonocoreResult<()> {
+    let rootfs_path = rootfs_path.as_ref();
+    fs::create_dir_all(rootfs_path)?;
+
+    // Directory modes are applied last so upper layers can still write into read-only directories
+    let mut dir_modes = BTreeMap::new();
+    for layer_path in layer_paths {
+        copy_layer_entries(layer_path.as_ref(), rootfs_path, &mut dir_modes)?;
+    }
+
+    // Children sort after their parents, so reversing sets a directory's mode after its contents'
+    for (path, mode) in dir_modes.into_iter().rev() {
+        match fs::set_permissions(&path, fs::Permissions::from_mode(mode)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
@@ -317,6 +353,89 @@ where
     Ok(())
 }
 
+fn copy_layer_entries(
+    src: &Path,
+    dest: &Path,
+    dir_modes: &mut BTreeMap<PathBuf, u32>,
+) -> MonocoreResult<()> {
+    let entries = match fs::read_dir(src) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            let _guard = PermissionGuard::new(src, 0o700)?;
+            fs::read_dir(src)?
+        }
+        Err(e) => return Err(e.into()),
+    }
+    .collect::<Result<Vec<_>, _>>()?;
+
+    // Whiteouts only hide entries of lower layers, so they go before this layer's entries
+    for entry in &entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name == OPAQUE_WHITEOUT_MARKER {
+            for existing in fs::read_dir(dest)? {
+                remove_path(&existing?.path())?;
+            }
+        } else if let Some(target) = name.strip_prefix(WHITEOUT_PREFIX) {
+            remove_path(&dest.join(target))?;
+        }
+    }
+
+    for entry in entries {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(WHITEOUT_PREFIX) {
+            continue;
+        }
+
+        let src_path = entry.path();
+        let dest_path = dest.join(&name);
+        let metadata = fs::symlink_metadata(&src_path)?;
+        let file_type = metadata.file_type();
+
+        if file_type.is_dir() {
+            if !fs::symlink_metadata(&dest_path).is_ok_and(|m| m.is_dir()) {
+                remove_path(&dest_path)?;
+                fs::create_dir(&dest_path)?;
+            }
+
+            dir_modes.insert(dest_path.clone(), metadata.mode() & 0o7777);
+            copy_layer_entries(&src_path, &dest_path, dir_modes)?;
+        } else if file_type.is_file() {
+            remove_path(&dest_path)?;
+            match fs::copy(&src_path, &dest_path) {
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    let _guard = PermissionGuard::new(&src_path, 0o400)?;
+                    fs::copy(&src_path, &dest_path)?;
+                }
+                result => {
+                    result?;
+                }
+            }
+        } else if file_type.is_symlink() {
+            remove_path(&dest_path)?;
+            symlink(fs::read_link(&src_path)?, &dest_path)?;
+        } else {
+            tracing::debug!("skipping special file {}", src_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a file, symlink or directory tree if it exists.
+fn remove_path(path: &Path) -> MonocoreResult<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
+    }
+}
+
 async fn set_metadata<S>(
     metadata: &mut Metadata<S>,
     fs_metadata: &fs::Metadata,
@@ -525,6 +644,72 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_rootfs_copy_oci_layers_to_dir() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let base = temp_dir.path().join("base");
+        let top = temp_dir.path().join("top");
+        let rootfs = temp_dir.path().join("rootfs");
+
+        // Base layer
+        fs::create_dir_all(base.join("app"))?;
+        fs::create_dir_all(base.join("opaque"))?;
+        fs::create_dir_all(base.join("bin"))?;
+        fs::write(base.join("app/keep.txt"), "base keep")?;
+        fs::write(base.join("app/replaced.txt"), "base")?;
+        fs::write(base.join("app/deleted.txt"), "deleted")?;
+        fs::write(base.join("opaque/hidden.txt"), "hidden")?;
+        fs::write(base.join("bin/tool"), "#!/bin/sh")?;
+        fs::set_permissions(base.join("bin/tool"), fs::Permissions::from_mode(0o755))?;
+        fs::set_permissions(base.join("bin"), fs::Permissions::from_mode(0o555))?;
+
+        // Top layer
+        fs::create_dir_all(top.join("app"))?;
+        fs::create_dir_all(top.join("opaque"))?;
+        fs::create_dir_all(top.join("bin"))?;
+        fs::write(top.join("app/replaced.txt"), "top")?;
+        fs::write(top.join(format!("app/{WHITEOUT_PREFIX}deleted.txt")), "")?;
+        fs::write(top.join(format!("opaque/{OPAQUE_WHITEOUT_MARKER}")), "")?;
+        fs::write(top.join("opaque/visible.txt"), "visible")?;
+        fs::write(top.join("bin/other"), "other")?;
+        symlink("tool", top.join("bin/link"))?;
+        fs::set_permissions(top.join("bin"), fs::Permissions::from_mode(0o555))?;
+
+        copy_oci_layers_to_dir(&[&base, &top], &rootfs)?;
+
+        assert_eq!(
+            fs::read_to_string(rootfs.join("app/keep.txt"))?,
+            "base keep"
+        );
+        assert_eq!(fs::read_to_string(rootfs.join("app/replaced.txt"))?, "top");
+        assert!(!rootfs.join("app/deleted.txt").exists());
+        assert!(!rootfs
+            .join(format!("app/{WHITEOUT_PREFIX}deleted.txt"))
+            .exists());
+        assert!(!rootfs.join("opaque/hidden.txt").exists());
+        assert_eq!(
+            fs::read_to_string(rootfs.join("opaque/visible.txt"))?,
+            "visible"
+        );
+        assert_eq!(fs::read_link(rootfs.join("bin/link"))?, Path::new("tool"));
+        assert_eq!(fs::read_to_string(rootfs.join("bin/other"))?, "other");
+        assert_eq!(
+            fs::metadata(rootfs.join("bin/tool"))?.permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(
+            fs::metadata(rootfs.join("bin"))?.permissions().mode() & 0o777,
+            0o555
+        );
+
+        // Let the temp dir be cleaned up
+        for dir in [base.join("bin"), top.join("bin"), rootfs.join("bin")] {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o755))?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,170 @@
+use std::{fmt, fs::OpenOptions, str::FromStr};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{vm::MicroVmConfig, MonocoreError, MonocoreResult};
+
+use super::{ContainerBackend, MicroVmBackend, ProcessBackend};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A runtime a sandbox can be run with.
+///
+/// A backend runs the process described by a [`MicroVmConfig`] in an isolated environment rooted
+/// at its root path. It is created from the configuration, then driven through its lifecycle:
+/// [`create`][Self::create], [`start`][Self::start], and [`wait`][Self::wait] or
+/// [`stop`][Self::stop].
+///
+/// ## Examples
+///
+/// ```no_run
+/// use monocore::{runtime::SandboxBackendType, vm::MicroVmConfig};
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let config = MicroVmConfig::builder()
+///     .root_path("/path/to/rootfs")
+///     .ram_mib(1024)
+///     .exec_path("/bin/echo")
+///     .args(["Hello, World!"])
+///     .build();
+///
+/// let mut backend = SandboxBackendType::Process.new_backend(config);
+/// backend.create().await?;
+/// backend.start().await?;
+/// let status = backend.wait().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait SandboxBackend: Send {
+    /// Prepares the sandbox from its configuration without running anything yet.
+    async fn create(&mut self) -> MonocoreResult<()>;
+
+    /// Starts the main process of the sandbox.
+    async fn start(&mut self) -> MonocoreResult<()>;
+
+    /// Stops the main process of the sandbox, gracefully if it exits in time.
+    async fn stop(&mut self) -> MonocoreResult<()>;
+
+    /// Waits for the main process of the sandbox to exit and returns its exit status.
+    ///
+    /// Waiting is cancel-safe, so it can be raced against other work like health checks.
+    async fn wait(&mut self) -> MonocoreResult<i32>;
+
+    /// Runs another process in the sandbox, waits for it to exit and returns its exit status.
+    async fn exec(&mut self, exec_path: &str, args: &[String]) -> MonocoreResult<i32>;
+}
+
+/// The sandbox backends available.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxBackendType {
+    /// Runs the sandbox in a libkrun MicroVm. Needs KVM or Hypervisor.framework.
+    #[default]
+    MicroVm,
+
+    /// Runs the sandbox in a Linux container made of unprivileged namespaces. For Linux hosts
+    /// without virtualization.
+    Container,
+
+    /// Runs the sandbox as a host process rooted at its root filesystem. Doesn't isolate the
+    /// sandbox like a MicroVm does, but runs anywhere, which makes it useful for testing.
+    Process,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl SandboxBackendType {
+    /// Returns the backend that suits this host: a MicroVm, unless this is a Linux host that
+    /// can't use KVM, in which case a container.
+    pub fn detect() -> Self {
+        let has_kvm = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/kvm")
+            .is_ok();
+
+        if cfg!(target_os = "linux") && !has_kvm {
+            Self::Container
+        } else {
+            Self::MicroVm
+        }
+    }
+
+    /// Returns whether backends of this type can run commands in their sandbox, which exec
+    /// healthchecks need.
+    pub fn supports_exec(&self) -> bool {
+        !matches!(self, Self::MicroVm)
+    }
+
+    /// Creates a backend of this type for the given configuration.
+    pub fn new_backend(&self, config: MicroVmConfig) -> Box<dyn SandboxBackend> {
+        match self {
+            Self::MicroVm => Box::new(MicroVmBackend::new(config)),
+            Self::Container => Box::new(ContainerBackend::new(config)),
+            Self::Process => Box::new(ProcessBackend::new(config)),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for SandboxBackendType {
+    type Err = MonocoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "microvm" => Ok(Self::MicroVm),
+            "container" => Ok(Self::Container),
+            "process" => Ok(Self::Process),
+            _ => Err(MonocoreError::InvalidArgument(format!(
+                "unknown sandbox backend '{}', expected 'microvm', 'container' or 'process'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for SandboxBackendType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MicroVm => write!(f, "microvm"),
+            Self::Container => write!(f, "container"),
+            Self::Process => write!(f, "process"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_backend_type_from_str_and_display() -> anyhow::Result<()> {
+        for backend in [
+            SandboxBackendType::MicroVm,
+            SandboxBackendType::Container,
+            SandboxBackendType::Process,
+        ] {
+            assert_eq!(backend.to_string().parse::<SandboxBackendType>()?, backend);
+        }
+
+        let backend: SandboxBackendType = serde_yaml::from_str("container")?;
+        assert_eq!(backend, SandboxBackendType::Container);
+        assert!("docker".parse::<SandboxBackendType>().is_err());
+
+        Ok(())
+    }
+}
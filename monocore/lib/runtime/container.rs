@@ -0,0 +1,1041 @@
+use std::{
+    ffi::{CStr, CString, OsStr},
+    fs::File,
+    io::{IoSliceMut, Read, Write},
+    mem,
+    net::TcpStream as StdTcpStream,
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{ffi::OsStrExt, net::UnixStream, process::ExitStatusExt},
+    },
+    path::Path,
+    process::ExitStatus,
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    mount::{self, MntFlags, MsFlags},
+    sched::{self, CloneFlags},
+    sys::{
+        signal::{self, SigSet, SigmaskHow, Signal},
+        signalfd::{SfdFlags, SignalFd},
+        socket::{
+            self, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockType, SockaddrIn,
+        },
+        wait::{self, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, ForkResult, Pid},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    process::Command,
+    task::JoinHandle,
+};
+
+use crate::{
+    config::PortPair,
+    management::SANDBOX_STOP_TIMEOUT,
+    vm::{LinuxRlimit, MicroVmConfig},
+    MonocoreError, MonocoreResult,
+};
+
+use super::{
+    create_mount_points, get_exit_code, get_mount_point, set_rlimit, to_cstring, SandboxBackend,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The size of the stack the container init starts on.
+const INIT_STACK_SIZE: usize = 1024 * 1024;
+
+/// The device nodes bind-mounted from the host into the container.
+const DEVICES: [&str; 5] = [
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+];
+
+/// The signals the container init forwards to the exec command.
+const FORWARDED_SIGNALS: [Signal; 6] = [
+    Signal::SIGTERM,
+    Signal::SIGINT,
+    Signal::SIGHUP,
+    Signal::SIGQUIT,
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+];
+
+/// The namespaces joined by processes executed in a running container, the user namespace first
+/// so the others can be joined with its capabilities.
+const JOINED_NAMESPACES: [(&str, CloneFlags); 5] = [
+    ("user", CloneFlags::CLONE_NEWUSER),
+    ("mnt", CloneFlags::CLONE_NEWNS),
+    ("uts", CloneFlags::CLONE_NEWUTS),
+    ("net", CloneFlags::CLONE_NEWNET),
+    ("pid", CloneFlags::CLONE_NEWPID),
+];
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A sandbox backend that runs the sandbox in a Linux container.
+///
+/// The container gets its own user, mount, PID, UTS and network namespaces, which don't need any
+/// privileges to create, so this backend works on hosts without virtualization and without root.
+/// The current user is mapped to root in the container and the root filesystem of the container
+/// is pivoted to the root path of the sandbox. The mapped directories are bind-mounted into it
+/// and the resource limits are applied to its processes.
+///
+/// A minimal init runs as PID 1 of the container. It forwards signals to the exec command, reaps
+/// orphaned processes and exits with the exit status of the exec command, which takes the rest of
+/// the container down with it.
+///
+/// The network of the container only has a loopback interface. Each host port of the port map is
+/// forwarded to its guest port on the loopback interface of the container.
+pub struct ContainerBackend {
+    /// The configuration of the sandbox.
+    config: MicroVmConfig,
+
+    /// The init of the container, once started.
+    init: Option<Pid>,
+
+    /// The task waiting for the init of the container to exit.
+    exit: Option<JoinHandle<MonocoreResult<i32>>>,
+
+    /// The exit status of the container, once it has exited.
+    status: Option<i32>,
+
+    /// The tasks forwarding the host ports into the container.
+    forwarders: Vec<JoinHandle<()>>,
+}
+
+/// Everything the container init needs to set up the container, prepared before it is cloned.
+struct ContainerInit {
+    /// The root path of the sandbox.
+    root_path: CString,
+
+    /// The proc mount point in the root path.
+    proc_path: CString,
+
+    /// The hostname of the container.
+    hostname: CString,
+
+    /// The working directory of the exec command in the container.
+    workdir_path: CString,
+
+    /// The executable path of the exec command.
+    exec_path: CString,
+
+    /// The arguments of the exec command, starting with the executable path.
+    argv: Vec<CString>,
+
+    /// The environment of the exec command.
+    envp: Vec<CString>,
+
+    /// The host paths and root path mount points of the directories and devices to bind-mount.
+    mounts: Vec<(CString, CString)>,
+
+    /// The resource limits to apply.
+    rlimits: Vec<LinuxRlimit>,
+}
+
+/// The file descriptors the container init gets from the backend.
+#[derive(Clone, Copy)]
+struct InitFds {
+    /// Reaches end of file once the backend has mapped the user and group IDs of the init.
+    sync: RawFd,
+
+    /// Gets the step that failed and its errno if setting up the container fails.
+    error: RawFd,
+
+    /// Gets requests for sockets in the network namespace of the container.
+    control: RawFd,
+
+    /// The ends of the above the backend keeps, closed by the init.
+    parent: [RawFd; 3],
+}
+
+/// The steps of setting up a container, as reported when one fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum SetupStep {
+    WaitForIdMap,
+    SetHostname,
+    MakeMountsPrivate,
+    BindMount,
+    MountProc,
+    PivotRoot,
+    ChangeDir,
+    BringUpLoopback,
+    SetRlimit,
+    Fork,
+    Exec,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ContainerBackend {
+    /// Creates a new container backend for the given configuration.
+    pub fn new(config: MicroVmConfig) -> Self {
+        Self {
+            config,
+            init: None,
+            exit: None,
+            status: None,
+            forwarders: Vec::new(),
+        }
+    }
+
+    /// Waits for the init of the container to exit and returns its exit status.
+    async fn wait_exit(&mut self) -> MonocoreResult<i32> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+
+        let exit = self.exit.as_mut().ok_or_else(|| {
+            MonocoreError::InvalidArgument("the container has not been started".to_string())
+        })?;
+
+        let status = exit.await??;
+        self.exit = None;
+        self.status = Some(status);
+        for forwarder in self.forwarders.drain(..) {
+            forwarder.abort();
+        }
+
+        Ok(status)
+    }
+
+    /// Returns the running init of the container.
+    fn get_running_init(&self) -> MonocoreResult<Pid> {
+        match self.init {
+            Some(pid) if self.status.is_none() => Ok(pid),
+            _ => Err(MonocoreError::InvalidArgument(
+                "the container is not running".to_string(),
+            )),
+        }
+    }
+}
+
+impl ContainerInit {
+    /// Prepares the container init for the given configuration.
+    fn new(config: &MicroVmConfig) -> MonocoreResult<Self> {
+        let exec_path = config.exec_path.as_ref().ok_or_else(|| {
+            MonocoreError::InvalidArgument("the container backend needs an exec path".to_string())
+        })?;
+
+        let root_path = &config.root_path;
+        let hostname = root_path
+            .file_name()
+            .map(|name| name.as_bytes().to_vec())
+            .unwrap_or_else(|| b"sandbox".to_vec());
+
+        let mut mounts = Vec::new();
+        for dir in &config.mapped_dirs {
+            mounts.push((
+                to_cstring(dir.get_host().as_str())?,
+                to_cstring(get_mount_point(root_path, dir.get_guest().as_str())?)?,
+            ));
+        }
+        for device in DEVICES {
+            mounts.push((
+                to_cstring(device)?,
+                to_cstring(get_mount_point(root_path, device)?)?,
+            ));
+        }
+
+        Ok(Self {
+            root_path: to_cstring(root_path)?,
+            proc_path: to_cstring(get_mount_point(root_path, "/proc")?)?,
+            hostname: to_cstring(OsStr::from_bytes(&hostname))?,
+            workdir_path: to_cstring(
+                config
+                    .workdir_path
+                    .as_ref()
+                    .map(|path| path.as_str())
+                    .unwrap_or("/"),
+            )?,
+            exec_path: to_cstring(exec_path.as_str())?,
+            argv: std::iter::once(exec_path.as_str())
+                .chain(config.args.iter().map(String::as_str))
+                .map(to_cstring)
+                .collect::<MonocoreResult<_>>()?,
+            envp: config
+                .env
+                .iter()
+                .map(|pair| to_cstring(pair.to_string()))
+                .collect::<MonocoreResult<_>>()?,
+            mounts,
+            rlimits: config.rlimits.clone(),
+        })
+    }
+
+    /// Clones the container init into new namespaces, maps its user and group IDs and waits for
+    /// it to start the exec command.
+    ///
+    /// ## Returns
+    /// The PID of the init and the socket to request sockets in the container's network
+    /// namespace from.
+    fn spawn(&self) -> MonocoreResult<(Pid, UnixStream)> {
+        let (sync_read, sync_write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (error_read, error_write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (control, init_control) = UnixStream::pair()?;
+
+        let fds = InitFds {
+            sync: sync_read.as_raw_fd(),
+            error: error_write.as_raw_fd(),
+            control: init_control.as_raw_fd(),
+            parent: [
+                sync_write.as_raw_fd(),
+                error_read.as_raw_fd(),
+                control.as_raw_fd(),
+            ],
+        };
+
+        // The pointer arrays are built here because the init must not allocate
+        let argv = to_pointer_array(&self.argv);
+        let envp = to_pointer_array(&self.envp);
+
+        let mut stack = vec![0u8; INIT_STACK_SIZE];
+        let flags = CloneFlags::CLONE_NEWUSER
+            | CloneFlags::CLONE_NEWNS
+            | CloneFlags::CLONE_NEWPID
+            | CloneFlags::CLONE_NEWUTS
+            | CloneFlags::CLONE_NEWNET;
+
+        // SAFETY: The init runs on its own copy of the address space and only makes system calls
+        // on data prepared above before it execs or exits.
+        let pid = unsafe {
+            sched::clone(
+                Box::new(|| self.run(fds, &argv, &envp)),
+                &mut stack,
+                flags,
+                Some(Signal::SIGCHLD as i32),
+            )?
+        };
+
+        drop((sync_read, error_write, init_control));
+
+        if let Err(e) = write_id_maps(pid) {
+            let _ = signal::kill(pid, Signal::SIGKILL);
+            let _ = wait::waitpid(pid, None);
+            return Err(e);
+        }
+
+        // Let the init go on, then wait for it to exec the command or report what failed
+        drop(sync_write);
+
+        let mut report = Vec::new();
+        File::from(error_read).read_to_end(&mut report)?;
+        if let [step, errno @ ..] = report.as_slice() {
+            let _ = wait::waitpid(pid, None);
+            let errno = Errno::from_raw(i32::from_ne_bytes(errno.try_into().unwrap_or_default()));
+            return Err(std::io::Error::other(format!(
+                "failed to set up container: {}: {}",
+                SetupStep::from_u8(*step),
+                errno
+            ))
+            .into());
+        }
+
+        Ok((pid, control))
+    }
+
+    /// Sets up the container and runs the exec command under the init. Runs as PID 1 of the
+    /// container and only returns if setting up the container fails.
+    fn run(
+        &self,
+        fds: InitFds,
+        argv: &[*const libc::c_char],
+        envp: &[*const libc::c_char],
+    ) -> isize {
+        for fd in fds.parent {
+            let _ = unistd::close(fd);
+        }
+
+        if let Err((step, errno)) = self.setup(fds.sync) {
+            report_failure(fds.error, step, errno);
+            return 127;
+        }
+
+        let mut signals = SigSet::empty();
+        for sig in FORWARDED_SIGNALS {
+            signals.add(sig);
+        }
+        signals.add(Signal::SIGCHLD);
+
+        let mut old_signals = SigSet::empty();
+        if let Err(errno) = signal::sigprocmask(
+            SigmaskHow::SIG_BLOCK,
+            Some(&signals),
+            Some(&mut old_signals),
+        ) {
+            report_failure(fds.error, SetupStep::Fork, errno);
+            return 127;
+        }
+
+        // SAFETY: The init is single-threaded.
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                let _ = signal::sigprocmask(SigmaskHow::SIG_SETMASK, Some(&old_signals), None);
+                let _ = unistd::close(fds.control);
+
+                // SAFETY: The arrays are null-terminated and point into strings that outlive the
+                // call.
+                unsafe { libc::execve(self.exec_path.as_ptr(), argv.as_ptr(), envp.as_ptr()) };
+                report_failure(fds.error, SetupStep::Exec, Errno::last());
+
+                // SAFETY: Exiting without running anything inherited from the init.
+                unsafe { libc::_exit(127) }
+            }
+            Ok(ForkResult::Parent { child }) => {
+                let _ = unistd::close(fds.error);
+                supervise(child, &signals, fds.control)
+            }
+            Err(errno) => {
+                report_failure(fds.error, SetupStep::Fork, errno);
+                127
+            }
+        }
+    }
+
+    /// Sets up the container from inside its namespaces once its IDs are mapped.
+    fn setup(&self, sync: RawFd) -> Result<(), (SetupStep, Errno)> {
+        let mut byte = [0u8];
+        loop {
+            match unistd::read(sync, &mut byte) {
+                Ok(_) => break,
+                Err(Errno::EINTR) => continue,
+                Err(errno) => return Err((SetupStep::WaitForIdMap, errno)),
+            }
+        }
+        let _ = unistd::close(sync);
+
+        unistd::sethostname(OsStr::from_bytes(self.hostname.as_bytes()))
+            .map_err(|errno| (SetupStep::SetHostname, errno))?;
+
+        // Keep the mounts below from propagating back to the host
+        mount::mount(
+            None::<&CStr>,
+            c"/",
+            None::<&CStr>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&CStr>,
+        )
+        .map_err(|errno| (SetupStep::MakeMountsPrivate, errno))?;
+
+        // The new root must be a mount point to pivot to it
+        let root_mount = (self.root_path.as_c_str(), self.root_path.as_c_str());
+        for (source, target) in std::iter::once(root_mount).chain(
+            self.mounts
+                .iter()
+                .map(|(source, target)| (source.as_c_str(), target.as_c_str())),
+        ) {
+            mount::mount(
+                Some(source),
+                target,
+                None::<&CStr>,
+                MsFlags::MS_BIND | MsFlags::MS_REC,
+                None::<&CStr>,
+            )
+            .map_err(|errno| (SetupStep::BindMount, errno))?;
+        }
+
+        mount::mount(
+            Some(c"proc"),
+            self.proc_path.as_c_str(),
+            Some(c"proc"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+            None::<&CStr>,
+        )
+        .map_err(|errno| (SetupStep::MountProc, errno))?;
+
+        // Stack the old root under the new one and detach it
+        unistd::chdir(self.root_path.as_c_str()).map_err(|errno| (SetupStep::ChangeDir, errno))?;
+        unistd::pivot_root(c".", c".").map_err(|errno| (SetupStep::PivotRoot, errno))?;
+        mount::umount2(c".", MntFlags::MNT_DETACH)
+            .map_err(|errno| (SetupStep::PivotRoot, errno))?;
+        unistd::chdir(self.workdir_path.as_c_str())
+            .map_err(|errno| (SetupStep::ChangeDir, errno))?;
+
+        bring_up_loopback().map_err(|errno| (SetupStep::BringUpLoopback, errno))?;
+
+        for rlimit in &self.rlimits {
+            set_rlimit(rlimit).map_err(|errno| (SetupStep::SetRlimit, errno))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SetupStep {
+    /// All the steps, in the order of their discriminants.
+    const ALL: [SetupStep; 11] = [
+        Self::WaitForIdMap,
+        Self::SetHostname,
+        Self::MakeMountsPrivate,
+        Self::BindMount,
+        Self::MountProc,
+        Self::PivotRoot,
+        Self::ChangeDir,
+        Self::BringUpLoopback,
+        Self::SetRlimit,
+        Self::Fork,
+        Self::Exec,
+    ];
+
+    /// Returns the step with the given discriminant.
+    fn from_u8(step: u8) -> Self {
+        Self::ALL
+            .get(step as usize)
+            .copied()
+            .unwrap_or(Self::WaitForIdMap)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns a null-terminated array of pointers to the given strings.
+fn to_pointer_array(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(std::iter::once(ptr::null()))
+        .collect()
+}
+
+/// Maps the current user and group to root in the user namespace of a cloned process.
+fn write_id_maps(pid: Pid) -> MonocoreResult<()> {
+    let proc_path = Path::new("/proc").join(pid.to_string());
+    std::fs::write(proc_path.join("setgroups"), "deny")?;
+    std::fs::write(
+        proc_path.join("uid_map"),
+        format!("0 {} 1\n", unistd::geteuid()),
+    )?;
+    std::fs::write(
+        proc_path.join("gid_map"),
+        format!("0 {} 1\n", unistd::getegid()),
+    )?;
+
+    Ok(())
+}
+
+/// Reports the step that failed to the backend.
+fn report_failure(error: RawFd, step: SetupStep, errno: Errno) {
+    let mut report = [0u8; 5];
+    report[0] = step as u8;
+    report[1..].copy_from_slice(&(errno as i32).to_ne_bytes());
+
+    // SAFETY: `error` stays open until the init exits.
+    let error = unsafe { std::os::fd::BorrowedFd::borrow_raw(error) };
+    let _ = unistd::write(error, &report);
+}
+
+/// Brings up the loopback interface of the current network namespace.
+fn bring_up_loopback() -> Result<(), Errno> {
+    let sock = socket::socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+
+    // SAFETY: `ifreq` is plain data and the ioctls only read and write it.
+    unsafe {
+        let mut ifreq: libc::ifreq = mem::zeroed();
+        for (dst, src) in ifreq.ifr_name.iter_mut().zip(b"lo\0") {
+            *dst = *src as libc::c_char;
+        }
+
+        if libc::ioctl(sock.as_raw_fd(), libc::SIOCGIFFLAGS as _, &mut ifreq) < 0 {
+            return Err(Errno::last());
+        }
+
+        ifreq.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+        if libc::ioctl(sock.as_raw_fd(), libc::SIOCSIFFLAGS as _, &ifreq) < 0 {
+            return Err(Errno::last());
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the init of the container until the exec command exits, then exits with its status.
+///
+/// The init forwards the signals it gets to the exec command, reaps every process orphaned in the
+/// container and serves requests for sockets in the network namespace of the container.
+fn supervise(workload: Pid, signals: &SigSet, control: RawFd) -> ! {
+    let exit = |code: i32| -> ! {
+        // SAFETY: Exiting the init, which kills every other process in the container.
+        unsafe { libc::_exit(code) }
+    };
+
+    let Ok(signal_fd) = SignalFd::with_flags(signals, SfdFlags::SFD_CLOEXEC) else {
+        let _ = signal::kill(workload, Signal::SIGKILL);
+        exit(127);
+    };
+
+    let mut poll_fds = [
+        libc::pollfd {
+            fd: signal_fd.as_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: control,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+
+    loop {
+        // SAFETY: `poll_fds` is a valid array of pollfds for the duration of the call.
+        if unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as _, -1) } < 0 {
+            continue;
+        }
+
+        if poll_fds[0].revents != 0 {
+            if let Ok(Some(info)) = signal_fd.read_signal() {
+                match Signal::try_from(info.ssi_signo as i32) {
+                    Ok(Signal::SIGCHLD) => {
+                        if let Some(code) = reap_children(workload) {
+                            exit(code);
+                        }
+                    }
+                    Ok(sig) => {
+                        let _ = signal::kill(workload, sig);
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        if poll_fds[1].revents != 0 && !serve_socket_request(control) {
+            // The backend has gone away, stop polling its end
+            poll_fds[1].fd = -1;
+        }
+    }
+}
+
+/// Reaps the exited children of the init and returns the exit status of the exec command if it
+/// is among them.
+fn reap_children(workload: Pid) -> Option<i32> {
+    loop {
+        match wait::waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::Exited(pid, code)) if pid == workload => return Some(code),
+            Ok(WaitStatus::Signaled(pid, sig, _)) if pid == workload => {
+                return Some(128 + sig as i32)
+            }
+            Ok(WaitStatus::StillAlive) | Err(_) => return None,
+            Ok(_) => continue,
+        }
+    }
+}
+
+/// Serves a request for a socket in the network namespace of the container by sending a new TCP
+/// socket back. Returns false once the backend has closed its end.
+fn serve_socket_request(control: RawFd) -> bool {
+    let mut byte = [0u8];
+    match unistd::read(control, &mut byte) {
+        Ok(1) => {}
+        Err(Errno::EINTR) => return true,
+        _ => return false,
+    }
+
+    // SAFETY: A socket is created and only handed out if it is valid.
+    let sock = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+
+    // Room for a single control message carrying one file descriptor
+    let mut cmsg_buf = [0u64; 4];
+    let data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    // SAFETY: The message only points to buffers on this stack frame.
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        if sock >= 0 {
+            msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<RawFd>() as _) as _;
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as _) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, sock);
+        }
+
+        libc::sendmsg(control, &msg, 0);
+        if sock >= 0 {
+            libc::close(sock);
+        }
+    }
+
+    true
+}
+
+/// Requests a socket in the network namespace of the container and connects it to a port on
+/// the loopback interface of the container.
+fn connect_in_container(control: &Mutex<UnixStream>, port: u16) -> MonocoreResult<StdTcpStream> {
+    let sock = {
+        let mut control = control
+            .lock()
+            .map_err(|_| std::io::Error::other("container control socket is poisoned"))?;
+        control.write_all(&[0])?;
+
+        let mut byte = [0u8];
+        let mut iov = [IoSliceMut::new(&mut byte)];
+        let mut cmsg_buf = nix::cmsg_space!(RawFd);
+        let msg = socket::recvmsg::<()>(
+            control.as_raw_fd(),
+            &mut iov,
+            Some(&mut cmsg_buf),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )?;
+
+        msg.cmsgs()?
+            .find_map(|cmsg| match cmsg {
+                ControlMessageOwned::ScmRights(fds) => fds.first().copied(),
+                _ => None,
+            })
+            .ok_or_else(|| std::io::Error::other("container did not hand out a socket"))?
+    };
+
+    // SAFETY: The socket was just received and nothing else owns it.
+    let sock = unsafe { OwnedFd::from_raw_fd(sock) };
+    socket::connect(sock.as_raw_fd(), &SockaddrIn::new(127, 0, 0, 1, port))?;
+
+    let stream = StdTcpStream::from(sock);
+    stream.set_nonblocking(true)?;
+
+    Ok(stream)
+}
+
+/// Forwards connections to a host port to the guest port of a mapping in the container.
+async fn forward_port(
+    port: PortPair,
+    control: Arc<Mutex<UnixStream>>,
+) -> MonocoreResult<JoinHandle<()>> {
+    let listener = TcpListener::bind(("0.0.0.0", port.get_host())).await?;
+    let guest = port.get_guest();
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let (mut inbound, _) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("failed to accept connection on port {}: {}", port, e);
+                    continue;
+                }
+            };
+
+            let control = control.clone();
+            tokio::spawn(async move {
+                let outbound =
+                    tokio::task::spawn_blocking(move || connect_in_container(&control, guest))
+                        .await;
+
+                match outbound {
+                    Ok(Ok(outbound)) => match TcpStream::from_std(outbound) {
+                        Ok(mut outbound) => {
+                            let _ =
+                                tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                        }
+                        Err(e) => tracing::warn!("failed to connect to port {}: {}", guest, e),
+                    },
+                    Ok(Err(e)) => tracing::warn!("failed to connect to port {}: {}", guest, e),
+                    Err(e) => tracing::warn!("failed to connect to port {}: {}", guest, e),
+                }
+            });
+        }
+    }))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl SandboxBackend for ContainerBackend {
+    async fn create(&mut self) -> MonocoreResult<()> {
+        self.config.validate()?;
+        create_mount_points(
+            &self.config.root_path,
+            self.config
+                .mapped_dirs
+                .iter()
+                .map(|dir| dir.get_guest().as_str())
+                .chain(["/proc"]),
+            DEVICES,
+        )?;
+        Ok(())
+    }
+
+    async fn start(&mut self) -> MonocoreResult<()> {
+        if self.init.is_some() {
+            return Err(MonocoreError::InvalidArgument(
+                "the container has already been started".to_string(),
+            ));
+        }
+
+        let init = ContainerInit::new(&self.config)?;
+        let (pid, control) = tokio::task::spawn_blocking(move || init.spawn()).await??;
+        tracing::info!("started container with init {}", pid);
+
+        self.init = Some(pid);
+        self.exit = Some(tokio::task::spawn_blocking(move || {
+            let mut status = 0;
+            Errno::result(unsafe { libc::waitpid(pid.as_raw(), &mut status, 0) })?;
+            Ok(get_exit_code(ExitStatus::from_raw(status)))
+        }));
+
+        let control = Arc::new(Mutex::new(control));
+        for port in &self.config.port_map {
+            self.forwarders
+                .push(forward_port(port.clone(), control.clone()).await?);
+        }
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> MonocoreResult<()> {
+        let pid = self.get_running_init()?;
+
+        tracing::info!("stopping container with init {}", pid);
+        match signal::kill(pid, Signal::SIGTERM) {
+            Ok(()) | Err(Errno::ESRCH) => {}
+            Err(e) => return Err(std::io::Error::from(e).into()),
+        }
+
+        if tokio::time::timeout(SANDBOX_STOP_TIMEOUT, self.wait_exit())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "container with init {} did not stop in time, killing it",
+                pid
+            );
+            match signal::kill(pid, Signal::SIGKILL) {
+                Ok(()) | Err(Errno::ESRCH) => {}
+                Err(e) => return Err(std::io::Error::from(e).into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn wait(&mut self) -> MonocoreResult<i32> {
+        let status = self.wait_exit().await?;
+        tracing::info!("container exited with status {}", status);
+        Ok(status)
+    }
+
+    async fn exec(&mut self, exec_path: &str, args: &[String]) -> MonocoreResult<i32> {
+        let pid = self.get_running_init()?;
+
+        let namespaces = JOINED_NAMESPACES
+            .iter()
+            .map(|(name, flag)| {
+                let file = File::open(format!("/proc/{}/ns/{}", pid, name))?;
+                Ok((OwnedFd::from(file), *flag))
+            })
+            .collect::<MonocoreResult<Vec<_>>>()?;
+        let workdir_path = to_cstring(
+            self.config
+                .workdir_path
+                .as_ref()
+                .map(|path| path.as_str())
+                .unwrap_or("/"),
+        )?;
+        let rlimits = self.config.rlimits.clone();
+
+        let mut command = Command::new(exec_path);
+        command
+            .args(args)
+            .env_clear()
+            .envs(
+                self.config
+                    .env
+                    .iter()
+                    .map(|pair| (pair.get_name(), pair.get_value())),
+            )
+            .kill_on_drop(true);
+
+        // SAFETY: Only system calls are made on data prepared before the fork.
+        unsafe {
+            command.pre_exec(move || {
+                for (fd, flag) in &namespaces {
+                    sched::setns(fd, *flag)?;
+                }
+
+                unistd::chdir(workdir_path.as_c_str())?;
+                for rlimit in &rlimits {
+                    set_rlimit(rlimit)?;
+                }
+
+                Ok(())
+            });
+        }
+
+        Ok(get_exit_code(command.status().await?))
+    }
+}
+
+impl std::fmt::Display for SetupStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WaitForIdMap => write!(f, "waiting for id maps"),
+            Self::SetHostname => write!(f, "setting hostname"),
+            Self::MakeMountsPrivate => write!(f, "making mounts private"),
+            Self::BindMount => write!(f, "bind-mounting"),
+            Self::MountProc => write!(f, "mounting proc"),
+            Self::PivotRoot => write!(f, "pivoting root"),
+            Self::ChangeDir => write!(f, "changing directory"),
+            Self::BringUpLoopback => write!(f, "bringing up loopback"),
+            Self::SetRlimit => write!(f, "setting resource limits"),
+            Self::Fork => write!(f, "forking"),
+            Self::Exec => write!(f, "executing"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command as StdCommand;
+
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use typed_path::Utf8UnixPathBuf;
+
+    use crate::{
+        config::{EnvPair, PathPair},
+        runtime::get_sandbox_path,
+    };
+
+    use super::*;
+
+    /// Builds a root filesystem with a shell, `sleep` and the libraries they load.
+    fn create_rootfs() -> anyhow::Result<TempDir> {
+        let rootfs = TempDir::new()?;
+        std::fs::create_dir_all(rootfs.path().join("app"))?;
+
+        for exe in ["/bin/sh", "/bin/sleep"] {
+            let output = StdCommand::new("ldd").arg(exe).output()?;
+            let libraries = String::from_utf8(output.stdout)?;
+            let paths = std::iter::once(exe).chain(
+                libraries
+                    .split_whitespace()
+                    .filter(|word| word.starts_with('/')),
+            );
+            for path in paths {
+                let target = get_sandbox_path(rootfs.path(), path);
+                std::fs::create_dir_all(target.parent().unwrap())?;
+                std::fs::copy(path, target)?;
+            }
+        }
+
+        Ok(rootfs)
+    }
+
+    #[tokio::test]
+    async fn test_container_backend_runs_exec_command_in_namespaces() -> anyhow::Result<()> {
+        let rootfs = create_rootfs()?;
+        let data_dir = TempDir::new()?;
+
+        let config = MicroVmConfig::builder()
+            .root_path(rootfs.path())
+            .ram_mib(256)
+            .mapped_dirs([PathPair::with_distinct(
+                Utf8UnixPathBuf::from(data_dir.path().to_str().unwrap()),
+                Utf8UnixPathBuf::from("/data"),
+            )])
+            .workdir_path("/app")
+            .exec_path("/bin/sh")
+            .args([
+                "-c",
+                "echo $$ $GREETING $(pwd) > /data/out.txt; test -e /proc/1 && exit 3",
+            ])
+            .env([EnvPair::new("GREETING", "hello")])
+            .build();
+
+        let mut backend = ContainerBackend::new(config);
+        backend.create().await?;
+        backend.start().await?;
+        assert_eq!(backend.wait().await?, 3);
+
+        // The exec command runs as PID 2 under the init, in the working directory
+        let output = std::fs::read_to_string(data_dir.path().join("out.txt"))?;
+        assert_eq!(output, "2 hello /app\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_container_backend_stop_and_exec() -> anyhow::Result<()> {
+        let rootfs = create_rootfs()?;
+        let host = TcpListener::bind("127.0.0.1:0").await?;
+        let host_port = host.local_addr()?.port();
+        drop(host);
+
+        // Only reachable from the host network
+        let guest = TcpListener::bind("127.0.0.1:0").await?;
+        let guest_port = guest.local_addr()?.port();
+
+        let config = MicroVmConfig::builder()
+            .root_path(rootfs.path())
+            .ram_mib(256)
+            .port_map([PortPair::with_distinct(host_port, guest_port)])
+            .exec_path("/bin/sleep")
+            .args(["60"])
+            .build();
+
+        let mut backend = ContainerBackend::new(config);
+        backend.create().await?;
+        backend.start().await?;
+
+        // Processes executed in the container see its root filesystem
+        let status = backend
+            .exec("/bin/sh", &["-c".to_string(), "test -d /app".to_string()])
+            .await?;
+        assert_eq!(status, 0);
+
+        // The host port is forwarded into the network of the container, not to the host network
+        let mut client = TcpStream::connect(("127.0.0.1", host_port)).await?;
+        let mut buf = Vec::new();
+        client.write_all(b"ping").await.ok();
+        client.read_to_end(&mut buf).await.ok();
+        assert!(buf.is_empty());
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), guest.accept())
+                .await
+                .is_err()
+        );
+
+        backend.stop().await?;
+        assert_eq!(backend.wait().await?, 128 + Signal::SIGTERM as i32);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use sqlx::{Pool, Sqlite};
+use tokio::{
+    net::TcpStream,
+    time::{self, Instant},
+};
+
+use crate::{
+    config::{HealthProbe, Healthcheck, PortPair},
+    management::{self, HealthStatus},
+    MonocoreResult,
+};
+
+use super::SandboxBackend;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Runs the healthcheck of a sandbox and records the results in the sandbox database.
+///
+/// Checks run every interval, starting one interval after the sandbox starts. Exec probes are
+/// run through the backend of the sandbox, so the checker lives next to it.
+pub struct HealthChecker {
+    /// The healthcheck to run.
+    healthcheck: Healthcheck,
+
+    /// The port mappings of the sandbox, used to reach guest ports.
+    port_map: Vec<PortPair>,
+
+    /// The database the results are recorded in.
+    sandbox_db: Pool<Sqlite>,
+
+    /// The name of the sandbox.
+    name: String,
+
+    /// When the sandbox started.
+    started_at: Instant,
+
+    /// When the next check is due.
+    next_check: Instant,
+
+    /// The number of checks in a row that failed and counted.
+    failures: u32,
+
+    /// The current health of the sandbox.
+    health: HealthStatus,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl HealthChecker {
+    /// Creates a checker for a sandbox that has just started and marks the sandbox as starting.
+    ///
+    /// ## Arguments
+    ///
+    /// * `healthcheck` - The healthcheck to run
+    /// * `port_map` - The port mappings of the sandbox
+    /// * `sandbox_db_path` - The path of the sandbox database
+    /// * `name` - The name of the sandbox
+    pub async fn new(
+        healthcheck: Healthcheck,
+        port_map: Vec<PortPair>,
+        sandbox_db_path: impl AsRef<Path>,
+        name: impl Into<String>,
+    ) -> MonocoreResult<Self> {
+        let name = name.into();
+        let sandbox_db = management::get_db_pool(sandbox_db_path.as_ref()).await?;
+        management::set_sandbox_health(&sandbox_db, &name, HealthStatus::Starting).await?;
+
+        let started_at = Instant::now();
+        Ok(Self {
+            next_check: started_at + healthcheck.get_interval_duration(),
+            healthcheck,
+            port_map,
+            sandbox_db,
+            name,
+            started_at,
+            failures: 0,
+            health: HealthStatus::Starting,
+        })
+    }
+
+    /// Returns when the next check is due.
+    pub fn get_next_check(&self) -> Instant {
+        self.next_check
+    }
+
+    /// Runs a check against the sandbox, records the result and schedules the next check.
+    ///
+    /// ## Returns
+    ///
+    /// The health of the sandbox after the check.
+    pub async fn check(
+        &mut self,
+        backend: &mut dyn SandboxBackend,
+    ) -> MonocoreResult<HealthStatus> {
+        let timeout = self.healthcheck.get_timeout_duration();
+        let result = time::timeout(timeout, self.probe(backend))
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {}s", timeout.as_secs())));
+
+        let in_start_period =
+            self.started_at.elapsed() < self.healthcheck.get_start_period_duration();
+        let (passed, output) = match result {
+            Ok(output) => {
+                self.failures = 0;
+                self.health = HealthStatus::Healthy;
+                (true, output)
+            }
+            Err(output) => {
+                if !in_start_period {
+                    self.failures += 1;
+                    if self.failures >= *self.healthcheck.get_retries() {
+                        self.health = HealthStatus::Unhealthy;
+                    }
+                }
+                (false, output)
+            }
+        };
+
+        if !passed {
+            tracing::warn!("health check of sandbox {} failed: {}", self.name, output);
+        }
+
+        management::record_health_check(&self.sandbox_db, &self.name, passed, &output, self.health)
+            .await?;
+
+        self.next_check = Instant::now() + self.healthcheck.get_interval_duration();
+        Ok(self.health)
+    }
+
+    /// Runs the probe of the healthcheck, returning what it reported on success or why it failed.
+    async fn probe(&self, backend: &mut dyn SandboxBackend) -> Result<String, String> {
+        match self.healthcheck.get_probe() {
+            HealthProbe::Tcp(port) => {
+                let port = self.get_host_port(*port);
+                TcpStream::connect(("127.0.0.1", port))
+                    .await
+                    .map(|_| format!("connected to port {}", port))
+                    .map_err(|e| format!("failed to connect to port {}: {}", port, e))
+            }
+            HealthProbe::Http { port, path } => {
+                let url = format!("http://127.0.0.1:{}{}", self.get_host_port(*port), path);
+                let response = reqwest::get(&url)
+                    .await
+                    .map_err(|e| format!("GET {} failed: {}", url, e))?;
+
+                let status = response.status();
+                if status.is_success() {
+                    Ok(format!("GET {} returned {}", url, status))
+                } else {
+                    Err(format!("GET {} returned {}", url, status))
+                }
+            }
+            HealthProbe::Exec(command) => {
+                let (exec_path, args) = command
+                    .split_first()
+                    .ok_or_else(|| "the exec probe has no command".to_string())?;
+
+                match backend.exec(exec_path, args).await {
+                    Ok(0) => Ok(format!("{} exited with 0", exec_path)),
+                    Ok(code) => Err(format!("{} exited with {}", exec_path, code)),
+                    Err(e) => Err(format!("failed to run {}: {}", exec_path, e)),
+                }
+            }
+        }
+    }
+
+    /// Returns the host port a guest port is mapped to, or the same port if it isn't mapped.
+    fn get_host_port(&self, guest_port: u16) -> u16 {
+        self.port_map
+            .iter()
+            .find(|pair| pair.get_guest() == guest_port)
+            .map_or(guest_port, PortPair::get_host)
+    }
+}
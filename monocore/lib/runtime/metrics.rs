@@ -0,0 +1,271 @@
+use std::{fs, path::PathBuf, time::Instant};
+
+use crate::{management::ResourceUsage, MonocoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Samples the resources used by a process and all its descendants from `/proc`.
+///
+/// CPU usage is measured between samples, so the first sample is measured from when the sampler
+/// was created. Network counters are only reported for processes in a network namespace of their
+/// own, as the counters of a shared namespace include traffic of other processes.
+pub struct ResourceSampler {
+    /// The root process of the sandbox.
+    pid: u32,
+
+    /// The CPU time of the process tree in clock ticks and when it was read.
+    last_cpu: Option<(u64, Instant)>,
+
+    /// The number of clock ticks per second.
+    clock_ticks: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ResourceSampler {
+    /// Creates a sampler for the process tree rooted at `pid`.
+    pub fn new(pid: u32) -> Self {
+        let clock_ticks = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+            ticks if ticks > 0 => ticks as u64,
+            _ => 100,
+        };
+
+        let mut sampler = Self {
+            pid,
+            last_cpu: None,
+            clock_ticks,
+        };
+        sampler.last_cpu = Some((
+            sampler.get_cpu_ticks(&sampler.get_process_tree()),
+            Instant::now(),
+        ));
+        sampler
+    }
+
+    /// Returns the resources used by the process tree, with the CPU usage since the last sample.
+    ///
+    /// ## Returns
+    ///
+    /// An error if the root process is gone.
+    pub fn sample(&mut self) -> MonocoreResult<ResourceUsage> {
+        // Fail early rather than report an empty tree
+        fs::metadata(proc_path(self.pid, "stat"))?;
+
+        let pids = self.get_process_tree();
+        let cpu_ticks = self.get_cpu_ticks(&pids);
+        let now = Instant::now();
+
+        let cpu_usage = match self.last_cpu.replace((cpu_ticks, now)) {
+            Some((last_ticks, last_time)) => {
+                let elapsed = now.duration_since(last_time).as_secs_f64();
+                let ticks = cpu_ticks.saturating_sub(last_ticks) as f64;
+                if elapsed > 0.0 {
+                    ticks / self.clock_ticks as f64 / elapsed * 100.0
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+
+        let memory_usage = pids
+            .iter()
+            .filter_map(|pid| read_status_field(*pid, "VmRSS:"))
+            .sum::<u64>()
+            * 1024;
+
+        let io: Vec<_> = pids.iter().filter_map(|pid| read_io(*pid)).collect();
+        let (disk_read_bytes, disk_write_bytes) = if io.is_empty() {
+            (None, None)
+        } else {
+            (
+                Some(io.iter().map(|(read, _)| read).sum()),
+                Some(io.iter().map(|(_, write)| write).sum()),
+            )
+        };
+
+        let (net_rx_bytes, net_tx_bytes) = match read_net_dev(self.pid) {
+            Some((rx, tx)) => (Some(rx), Some(tx)),
+            None => (None, None),
+        };
+
+        Ok(ResourceUsage::builder()
+            .cpu_usage(cpu_usage)
+            .memory_usage(memory_usage)
+            .disk_read_bytes(disk_read_bytes)
+            .disk_write_bytes(disk_write_bytes)
+            .net_rx_bytes(net_rx_bytes)
+            .net_tx_bytes(net_tx_bytes)
+            .build())
+    }
+
+    /// Returns the root process and all its descendants that are still running.
+    pub fn get_process_tree(&self) -> Vec<u32> {
+        let mut pids = Vec::new();
+        let mut stack = vec![self.pid];
+        while let Some(pid) = stack.pop() {
+            let Ok(tasks) = fs::read_dir(proc_path(pid, "task")) else {
+                continue;
+            };
+
+            pids.push(pid);
+            for task in tasks.flatten() {
+                let Ok(children) = fs::read_to_string(task.path().join("children")) else {
+                    continue;
+                };
+
+                stack.extend(
+                    children
+                        .split_whitespace()
+                        .filter_map(|child| child.parse::<u32>().ok()),
+                );
+            }
+        }
+
+        pids
+    }
+
+    /// Returns the CPU time used by the processes, including children they have waited for, in
+    /// clock ticks.
+    fn get_cpu_ticks(&self, pids: &[u32]) -> u64 {
+        pids.iter().filter_map(|pid| read_cpu_ticks(*pid)).sum()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the path of a file in the `/proc` directory of a process.
+fn proc_path(pid: u32, file: &str) -> PathBuf {
+    PathBuf::from(format!("/proc/{}/{}", pid, file))
+}
+
+/// Reads the user and system CPU time of a process and its waited-for children from its `stat`
+/// file.
+fn read_cpu_ticks(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(proc_path(pid, "stat")).ok()?;
+
+    // The command name can contain spaces, so fields are counted from after it. `utime`,
+    // `stime`, `cutime` and `cstime` are fields 14 to 17, with the state being field 3.
+    let (_, fields) = stat.rsplit_once(')')?;
+    let fields: Vec<_> = fields.split_whitespace().collect();
+    fields
+        .get(11..15)?
+        .iter()
+        .map(|field| field.parse::<u64>().ok())
+        .sum()
+}
+
+/// Reads a field of a process' `status` file, in the units the file uses.
+fn read_status_field(pid: u32, field: &str) -> Option<u64> {
+    let status = fs::read_to_string(proc_path(pid, "status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Reads the bytes a process has read from and written to storage from its `io` file.
+fn read_io(pid: u32) -> Option<(u64, u64)> {
+    let io = fs::read_to_string(proc_path(pid, "io")).ok()?;
+    let field = |name: &str| {
+        io.lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+
+    Some((field("read_bytes:")?, field("write_bytes:")?))
+}
+
+/// Reads the bytes received and sent by all interfaces but loopback in the network namespace of
+/// a process. Returns None if the process shares the network namespace of this one.
+fn read_net_dev(pid: u32) -> Option<(u64, u64)> {
+    let namespace = fs::read_link(proc_path(pid, "ns/net")).ok()?;
+    if fs::read_link("/proc/self/ns/net").ok()? == namespace {
+        return None;
+    }
+
+    // Each interface line is `name: rx_bytes ... (8 receive fields) tx_bytes ...`
+    let net_dev = fs::read_to_string(proc_path(pid, "net/dev")).ok()?;
+    let mut totals = (0, 0);
+    for line in net_dev.lines().skip(2) {
+        let Some((interface, counters)) = line.split_once(':') else {
+            continue;
+        };
+        if interface.trim() == "lo" {
+            continue;
+        }
+
+        let counters: Vec<u64> = counters
+            .split_whitespace()
+            .filter_map(|counter| counter.parse().ok())
+            .collect();
+        if let (Some(rx), Some(tx)) = (counters.first(), counters.get(8)) {
+            totals.0 += rx;
+            totals.1 += tx;
+        }
+    }
+
+    Some(totals)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Command, time::Duration};
+
+    use nix::{
+        sys::signal::{self, Signal},
+        unistd::Pid,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_resource_sampler_covers_process_tree() -> anyhow::Result<()> {
+        let mut child = Command::new("/bin/sh")
+            .args(["-c", "sleep 10 & sleep 10; wait"])
+            .spawn()?;
+
+        let mut sampler = ResourceSampler::new(child.id());
+        let mut pids = Vec::new();
+        for _ in 0..50 {
+            pids = sampler.get_process_tree();
+            if pids.len() == 3 {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(pids.len(), 3, "{:?}", pids);
+        assert_eq!(pids[0], child.id());
+
+        let usage = sampler.sample()?;
+        assert!(usage.get_memory_usage() > &0);
+        assert!(usage.get_cpu_usage() >= &0.0);
+
+        // The sampled processes share the network namespace of the test
+        assert_eq!(usage.get_net_rx_bytes(), &None);
+
+        for pid in pids {
+            signal::kill(Pid::from_raw(pid as i32), Signal::SIGKILL)?;
+        }
+        child.wait()?;
+        assert!(sampler.sample().is_err());
+
+        Ok(())
+    }
+}
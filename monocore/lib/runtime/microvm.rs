@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use nix::sys::signal::Signal;
+use tokio::task::JoinHandle;
+
+use crate::{
+    vm::{MicroVm, MicroVmConfig},
+    MonocoreError, MonocoreResult,
+};
+
+use super::SandboxBackend;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A sandbox backend that runs the sandbox in a libkrun [`MicroVm`].
+///
+/// libkrun takes over the process that starts a MicroVm and exits it when the MicroVm shuts
+/// down, so this backend is meant to run in a process of its own, like `mcrun microvm`.
+pub struct MicroVmBackend {
+    /// The configuration, until the MicroVm is created from it.
+    config: Option<MicroVmConfig>,
+
+    /// The MicroVm, until it is started.
+    vm: Option<MicroVm>,
+
+    /// The task running the MicroVm.
+    handle: Option<JoinHandle<MonocoreResult<i32>>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl MicroVmBackend {
+    /// Creates a new MicroVm backend for the given configuration.
+    pub fn new(config: MicroVmConfig) -> Self {
+        Self {
+            config: Some(config),
+            vm: None,
+            handle: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl SandboxBackend for MicroVmBackend {
+    async fn create(&mut self) -> MonocoreResult<()> {
+        let config = self.config.take().ok_or_else(|| {
+            MonocoreError::InvalidArgument("microvm has already been created".to_string())
+        })?;
+
+        self.vm = Some(MicroVm::from_config(config)?);
+        Ok(())
+    }
+
+    async fn start(&mut self) -> MonocoreResult<()> {
+        let vm = self.vm.take().ok_or_else(|| {
+            MonocoreError::InvalidArgument("microvm has not been created".to_string())
+        })?;
+
+        self.handle = Some(tokio::task::spawn_blocking(move || vm.start()));
+        Ok(())
+    }
+
+    /// The MicroVm owns this process, so stopping it exits the process the way SIGTERM would.
+    async fn stop(&mut self) -> MonocoreResult<()> {
+        tracing::info!("stopping microvm");
+        std::process::exit(128 + Signal::SIGTERM as i32);
+    }
+
+    async fn wait(&mut self) -> MonocoreResult<i32> {
+        let handle = self.handle.as_mut().ok_or_else(|| {
+            MonocoreError::InvalidArgument("microvm has not been started".to_string())
+        })?;
+
+        // Only let go of the handle once the MicroVm has exited, so waiting can be cancelled
+        let result = handle.await;
+        self.handle = None;
+        result?
+    }
+
+    async fn exec(&mut self, _exec_path: &str, _args: &[String]) -> MonocoreResult<i32> {
+        Err(MonocoreError::NotImplemented(
+            "exec is not supported by the microvm backend".to_string(),
+        ))
+    }
+}
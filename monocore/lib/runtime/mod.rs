@@ -1,9 +1,25 @@
 //! Runtime components for the Monocore runtime.
 
+mod backend;
+mod container;
+mod health;
+mod metrics;
+mod microvm;
 mod monitor;
+mod policy;
+mod process;
+mod utils;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub use backend::*;
+pub use container::*;
+pub use health::*;
+pub use metrics::*;
+pub use microvm::*;
 pub use monitor::*;
+pub use policy::*;
+pub use process::*;
+pub(crate) use utils::*;
@@ -1,18 +1,32 @@
 use std::{
-    io::Write,
+    os::unix::process::ExitStatusExt,
     path::{Path, PathBuf},
-    time::{SystemTime, UNIX_EPOCH},
+    process::ExitStatus,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
-use monoutils::{MonoutilsError, MonoutilsResult, ProcessMonitor, RotatingLog, LOG_SUFFIX};
+use monoutils::{
+    copy_log_records, LogStream, MonoutilsError, MonoutilsResult, ProcessMonitor, RotatingLog,
+    LOG_SUFFIX,
+};
 use sqlx::{Pool, Sqlite};
 use tokio::{
-    io::AsyncReadExt,
     process::{ChildStderr, ChildStdout},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use crate::{
+    management::{
+        self, LogRetention, SandboxStatus, DEFAULT_METRICS_INTERVAL, METRICS_DOWNSAMPLE_AFTER,
+        METRICS_RETENTION,
+    },
+    utils::MCRUN_LOG_PREFIX,
+    MonocoreError, MonocoreResult,
 };
 
-use crate::{management, utils::MCRUN_LOG_PREFIX, MonocoreResult};
+use super::ResourceSampler;
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -31,6 +45,18 @@ pub struct MicroVmMonitor {
 
     /// The log directory
     log_dir: PathBuf,
+
+    /// The name of the sandbox being monitored
+    name: Option<String>,
+
+    /// How often the resource usage of the MicroVM is sampled
+    metrics_interval: Duration,
+
+    /// The task sampling the resource usage of the MicroVM
+    metrics_task: Option<JoinHandle<()>>,
+
+    /// How many logs of the sandbox are kept
+    log_retention: LogRetention,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -49,9 +75,85 @@ impl MicroVmMonitor {
             sandbox_db: management::get_db_pool(sandbox_db_path.as_ref()).await?,
             log_path: None,
             log_dir: log_dir.into(),
+            name: None,
+            metrics_interval: DEFAULT_METRICS_INTERVAL,
+            metrics_task: None,
+            log_retention: LogRetention::default(),
         })
     }
 
+    /// Sets how many logs of previous runs of the sandbox are kept
+    pub fn with_log_retention(mut self, log_retention: LogRetention) -> Self {
+        self.log_retention = log_retention;
+        self
+    }
+
+    /// Sets how often the resource usage of the MicroVM and its child processes is sampled
+    pub fn with_metrics_interval(mut self, metrics_interval: Duration) -> Self {
+        self.metrics_interval = metrics_interval;
+        self
+    }
+
+    /// Starts sampling the resource usage of the MicroVM process tree into the sandbox database,
+    /// thinning out old samples every minute.
+    fn start_metrics(&mut self, microvm_pid: u32, name: String) {
+        let sandbox_db = self.sandbox_db.clone();
+        let interval = self.metrics_interval;
+        self.stop_metrics();
+        self.metrics_task = Some(tokio::spawn(async move {
+            let mut sampler = ResourceSampler::new(microvm_pid);
+            let mut last_prune = Instant::now();
+            loop {
+                time::sleep(interval).await;
+                let usage = match sampler.sample() {
+                    Ok(usage) => usage,
+                    Err(_) => break, // The MicroVM is gone
+                };
+
+                if let Err(e) = management::record_sandbox_metrics(&sandbox_db, &name, &usage).await
+                {
+                    tracing::warn!(error = %e, "failed to record sandbox metrics");
+                }
+
+                if last_prune.elapsed() >= Duration::from_secs(60) {
+                    last_prune = Instant::now();
+                    if let Err(e) = management::prune_sandbox_metrics(
+                        &sandbox_db,
+                        METRICS_DOWNSAMPLE_AFTER,
+                        METRICS_RETENTION,
+                    )
+                    .await
+                    {
+                        tracing::warn!(error = %e, "failed to prune sandbox metrics");
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Stops sampling the resource usage of the MicroVM.
+    fn stop_metrics(&mut self) {
+        if let Some(task) = self.metrics_task.take() {
+            task.abort();
+        }
+    }
+
+    /// Records a new status for the monitored sandbox. A status the sandbox can't move to, like
+    /// `Running` after it has been asked to stop, is only logged.
+    async fn record_status(&self, status: SandboxStatus) -> MonoutilsResult<()> {
+        let Some(name) = &self.name else {
+            return Ok(());
+        };
+
+        match management::transition_sandbox(&self.sandbox_db, name, status).await {
+            Err(e @ MonocoreError::InvalidSandboxTransition { .. }) => {
+                tracing::warn!(error = %e, "skipping sandbox status update");
+                Ok(())
+            }
+            result => result.map_err(MonoutilsError::custom),
+        }
+    }
+
     /// Generates a unique log name using name, process ID, and current timestamp.
     ///
     /// The ID format is: "{name}-{pid}-{timestamp}.{suffix}"
@@ -82,91 +184,102 @@ impl ProcessMonitor for MicroVmMonitor {
         &mut self,
         pid: u32,
         name: String,
-        mut stdout: ChildStdout,
-        mut stderr: ChildStderr,
+        stdout: ChildStdout,
+        stderr: ChildStderr,
     ) -> MonoutilsResult<()> {
-        let log_name = self.generate_log_name(pid, name);
+        let log_name = self.generate_log_name(pid, &name);
         let log_path = self.log_dir.join(&log_name);
 
         let microvm_log = RotatingLog::new(&log_path).await?;
-        let mut stdout_writer = microvm_log.get_sync_writer();
-        let mut stderr_writer = microvm_log.get_sync_writer();
+        let stdout_writer = microvm_log.get_sync_writer();
+        let stderr_writer = microvm_log.get_sync_writer();
         let microvm_pid = pid;
 
+        // Make room for this run's log among the kept logs of previous runs
+        if let Err(e) =
+            management::prune_logs(&self.log_dir, &name, &self.log_retention, &log_path).await
+        {
+            tracing::warn!(error = %e, "failed to prune old microvm logs");
+        }
+
         self.log_path = Some(log_path);
 
-        // Insert sandbox entry into database
+        // Record the processes on the sandbox entry added when the sandbox was started
         sqlx::query(
             r#"
-            INSERT INTO sandboxes (supervisor_pid, microvm_pid)
-            VALUES (?, ?)
+            UPDATE sandboxes
+            SET supervisor_pid = ?, microvm_pid = ?, modified_at = CURRENT_TIMESTAMP
+            WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
             "#,
         )
         .bind(self.supervisor_pid)
         .bind(microvm_pid)
+        .bind(&name)
         .execute(&self.sandbox_db)
         .await
         .map_err(MonoutilsError::custom)?;
 
-        // Spawn tasks to handle stdout/stderr
-        tokio::spawn(async move {
-            let mut buf = [0u8; 1024];
+        self.start_metrics(microvm_pid, name.clone());
+        self.name = Some(name);
+        self.record_status(SandboxStatus::Running).await?;
 
-            while let Ok(n) = stdout.read(&mut buf).await {
-                if n == 0 {
-                    break;
-                }
-                if let Err(e) = stdout_writer.write_all(&buf[..n]) {
-                    tracing::error!(microvm_pid = microvm_pid, error = %e, "Failed to write to microvm stdout log");
-                }
-                if let Err(e) = stdout_writer.flush() {
-                    tracing::error!(microvm_pid = microvm_pid, error = %e, "Failed to flush microvm stdout log");
-                }
+        // Spawn tasks to copy stdout/stderr into the log as records
+        tokio::spawn(async move {
+            if let Err(e) = copy_log_records(stdout, LogStream::Stdout, stdout_writer).await {
+                tracing::error!(microvm_pid = microvm_pid, error = %e, "failed to write microvm stdout log");
             }
         });
 
         tokio::spawn(async move {
-            let mut buf = [0u8; 1024];
-
-            while let Ok(n) = stderr.read(&mut buf).await {
-                if n == 0 {
-                    break;
-                }
-                if let Err(e) = stderr_writer.write_all(&buf[..n]) {
-                    tracing::error!(microvm_pid = microvm_pid, error = %e, "Failed to write to microvm stderr log");
-                }
-                if let Err(e) = stderr_writer.flush() {
-                    tracing::error!(microvm_pid = microvm_pid, error = %e, "Failed to flush microvm stderr log");
-                }
+            if let Err(e) = copy_log_records(stderr, LogStream::Stderr, stderr_writer).await {
+                tracing::error!(microvm_pid = microvm_pid, error = %e, "failed to write microvm stderr log");
             }
         });
 
         Ok(())
     }
 
-    async fn stop(&mut self) -> MonoutilsResult<()> {
-        // Remove sandbox entry from database
+    async fn stop(&mut self, exit_status: Option<ExitStatus>) -> MonoutilsResult<()> {
+        self.stop_metrics();
+
+        // Record how the sandbox ended, keeping its entry. Processes killed by a signal get the
+        // exit code a shell would report for them.
+        let status = match exit_status {
+            Some(status) => SandboxStatus::Exited(
+                status
+                    .code()
+                    .or_else(|| status.signal().map(|signal| 128 + signal))
+                    .unwrap_or_default(),
+            ),
+            None => SandboxStatus::Failed("failed to wait for the sandbox process".to_string()),
+        };
+        self.record_status(status).await?;
+
+        // The log is kept for `monocore log` and pruned when a later run starts
+        self.log_path = None;
+
+        Ok(())
+    }
+
+    async fn restart(&mut self, restart_count: u32) -> MonoutilsResult<()> {
+        let Some(name) = &self.name else {
+            return Ok(());
+        };
+
+        // The previous process is gone, so only the supervisor is left running
         sqlx::query(
             r#"
-            DELETE FROM sandboxes
-            WHERE supervisor_pid = ?
+            UPDATE sandboxes
+            SET microvm_pid = NULL, restart_count = ?, modified_at = CURRENT_TIMESTAMP
+            WHERE id = (SELECT MAX(id) FROM sandboxes WHERE name = ?)
             "#,
         )
-        .bind(self.supervisor_pid)
+        .bind(restart_count)
+        .bind(name)
         .execute(&self.sandbox_db)
         .await
         .map_err(MonoutilsError::custom)?;
 
-        // Delete the log file if it exists
-        if let Some(log_path) = &self.log_path {
-            if let Err(e) = tokio::fs::remove_file(log_path).await {
-                tracing::warn!(error = %e, "Failed to delete microvm log file");
-            }
-        }
-
-        // Reset the log path
-        self.log_path = None;
-
-        Ok(())
+        self.record_status(SandboxStatus::Starting).await
     }
 }
@@ -0,0 +1,376 @@
+use std::{
+    fmt::{self, Write as _},
+    io,
+    net::Ipv4Addr,
+    process::Stdio,
+};
+
+use getset::Getters;
+use ipnetwork::Ipv4Network as Ipv4Net;
+use nix::unistd;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{config::SandboxNetworkReach, MonocoreError, MonocoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The nftables command network policies are applied with.
+pub const NFT_EXE: &str = "nft";
+
+/// The prefix of the nftables table a sandbox's network policy is applied in.
+pub const NETWORK_POLICY_TABLE_PREFIX: &str = "monocore";
+
+/// The private ranges of RFC 1918, other than the one `public` sandboxes can reach.
+const PUBLIC_DENIED_RANGES: [(Ipv4Addr, u8); 2] = [
+    (Ipv4Addr::new(10, 0, 0, 0), 8),
+    (Ipv4Addr::new(192, 168, 0, 0), 16),
+];
+
+/// A private range `public` sandboxes can reach, along with the subnets of groups.
+const PUBLIC_GROUP_RANGE: (Ipv4Addr, u8) = (Ipv4Addr::new(172, 16, 0, 0), 12);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// What happens to traffic a rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    /// The traffic is let through.
+    Accept,
+
+    /// The traffic is dropped.
+    Drop,
+}
+
+/// A rule for traffic from a sandbox to a range of addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct NetworkRule {
+    /// The addresses the rule applies to.
+    destination: Ipv4Net,
+
+    /// What happens to the traffic.
+    action: RuleAction,
+}
+
+/// The addresses a sandbox can reach, compiled from its network reach and its address in its
+/// group.
+///
+/// Rules apply to traffic from the address of the sandbox and are checked in order, with the
+/// first one matching the destination deciding. Traffic no rule matches gets the default action.
+/// Replies to connections made to the sandbox are always let through.
+///
+/// ## Example
+///
+/// ```
+/// use monocore::{config::SandboxNetworkReach, runtime::{NetworkPolicy, RuleAction}};
+///
+/// let policy =
+///     NetworkPolicy::compile(&SandboxNetworkReach::Local, "10.0.0.2/24".parse().unwrap(), &[]);
+/// assert_eq!(policy.get_rules()[0].get_destination().to_string(), "10.0.0.0/24");
+/// assert_eq!(policy.get_default_action(), &RuleAction::Drop);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct NetworkPolicy {
+    /// The address of the sandbox.
+    source: Ipv4Addr,
+
+    /// The rules, checked in order.
+    rules: Vec<NetworkRule>,
+
+    /// What happens to traffic no rule matches.
+    default_action: RuleAction,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl NetworkRule {
+    /// Creates a rule for traffic to `destination`.
+    pub fn new(destination: Ipv4Net, action: RuleAction) -> Self {
+        Self {
+            destination,
+            action,
+        }
+    }
+}
+
+impl NetworkPolicy {
+    /// Compiles the network reach of a sandbox into rules.
+    ///
+    /// - `local` sandboxes can only reach the subnet of their group.
+    /// - `public` sandboxes can reach the subnet of their group, the subnets of other groups,
+    ///   172.16.0.0/12 and any address outside the other private ranges, 10.0.0.0/8 and
+    ///   192.168.0.0/16.
+    /// - `any` sandboxes can reach any address.
+    /// - `none` sandboxes can't reach any address, not even in their group.
+    ///
+    /// ## Arguments
+    ///
+    /// * `reach` - The network reach of the sandbox
+    /// * `address` - The address of the sandbox along with the prefix of the subnet of its group,
+    ///   like `10.0.0.2/24`
+    /// * `group_subnets` - The subnets of the groups recorded in the sandbox database
+    pub fn compile(
+        reach: &SandboxNetworkReach,
+        address: Ipv4Net,
+        group_subnets: &[Ipv4Net],
+    ) -> Self {
+        let subnet = Ipv4Net::new(address.network(), address.prefix())
+            .expect("the prefix comes from a valid network");
+        let range = |(ip, prefix): (Ipv4Addr, u8)| {
+            Ipv4Net::new(ip, prefix).expect("private ranges have valid prefixes")
+        };
+
+        let (rules, default_action) = match reach {
+            SandboxNetworkReach::Local => (
+                vec![NetworkRule::new(subnet, RuleAction::Accept)],
+                RuleAction::Drop,
+            ),
+            SandboxNetworkReach::Public => {
+                let mut rules = vec![NetworkRule::new(subnet, RuleAction::Accept)];
+
+                // Groups get subnets in the private ranges, so they have to be let through
+                // before those are dropped
+                for other in group_subnets {
+                    let other = Ipv4Net::new(other.network(), other.prefix())
+                        .expect("the prefix comes from a valid network");
+                    if !rules.iter().any(|rule| rule.destination == other) {
+                        rules.push(NetworkRule::new(other, RuleAction::Accept));
+                    }
+                }
+
+                rules.push(NetworkRule::new(
+                    range(PUBLIC_GROUP_RANGE),
+                    RuleAction::Accept,
+                ));
+                rules.extend(
+                    PUBLIC_DENIED_RANGES
+                        .into_iter()
+                        .map(|denied| NetworkRule::new(range(denied), RuleAction::Drop)),
+                );
+                (rules, RuleAction::Accept)
+            }
+            SandboxNetworkReach::Any => (Vec::new(), RuleAction::Accept),
+            SandboxNetworkReach::None => (Vec::new(), RuleAction::Drop),
+        };
+
+        Self {
+            source: address.ip(),
+            rules,
+            default_action,
+        }
+    }
+
+    /// Renders the policy as an nftables script that replaces `table` with one holding the
+    /// rules. The rules filter the traffic from the sandbox that is sent from or forwarded by
+    /// the host.
+    pub fn to_nftables(&self, table: &str) -> String {
+        let mut script = String::new();
+
+        // Declaring the table first lets it be deleted whether it exists or not
+        let _ = writeln!(script, "table ip {}", table);
+        let _ = writeln!(script, "delete table ip {}", table);
+        let _ = writeln!(script, "table ip {} {{", table);
+        let _ = writeln!(script, "    chain sandbox {{");
+        let _ = writeln!(script, "        ct state established,related accept");
+        for rule in &self.rules {
+            let _ = writeln!(
+                script,
+                "        ip daddr {} {}",
+                rule.destination, rule.action
+            );
+        }
+        let _ = writeln!(script, "        {}", self.default_action);
+        let _ = writeln!(script, "    }}");
+        for hook in ["forward", "output"] {
+            let _ = writeln!(script, "    chain {} {{", hook);
+            let _ = writeln!(
+                script,
+                "        type filter hook {} priority filter; policy accept;",
+                hook
+            );
+            let _ = writeln!(script, "        ip saddr {} jump sandbox", self.source);
+            let _ = writeln!(script, "    }}");
+        }
+        let _ = writeln!(script, "}}");
+
+        script
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the nftables table the network policy of a sandbox is applied in.
+pub fn get_network_policy_table(name: &str) -> String {
+    format!("{}-{}", NETWORK_POLICY_TABLE_PREFIX, name)
+}
+
+/// Applies the network policy of a sandbox with nftables, replacing the one applied before.
+///
+/// Changing the firewall needs privileges, so the policy is only applied when running as root
+/// with `nft` installed. Otherwise the sandbox runs without it and a warning is logged.
+///
+/// ## Returns
+///
+/// Whether the policy was applied.
+pub async fn apply_network_policy(name: &str, policy: &NetworkPolicy) -> MonocoreResult<bool> {
+    if !unistd::geteuid().is_root() {
+        tracing::warn!(
+            "not applying the network policy of sandbox {} without root privileges",
+            name
+        );
+        return Ok(false);
+    }
+
+    let mut child = match Command::new(NFT_EXE)
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            tracing::warn!(
+                "not applying the network policy of sandbox {} as {} is not installed",
+                name,
+                NFT_EXE
+            );
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let script = policy.to_nftables(&get_network_policy_table(name));
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(MonocoreError::NetworkPolicy(format!(
+            "failed to apply the network policy of sandbox '{}': {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(true)
+}
+
+/// Removes the network policy of a sandbox, if one was applied.
+pub async fn remove_network_policy(name: &str) -> MonocoreResult<()> {
+    if !unistd::geteuid().is_root() {
+        return Ok(());
+    }
+
+    // The table is missing if the policy was never applied, which is fine
+    match Command::new(NFT_EXE)
+        .args(["delete", "table", "ip", &get_network_policy_table(name)])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accept => write!(f, "accept"),
+            Self::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_policy_compiles_reach() -> anyhow::Result<()> {
+        let address: Ipv4Net = "10.0.3.7/24".parse()?;
+        let group_subnets: Vec<Ipv4Net> = vec!["10.0.3.0/24".parse()?, "10.0.4.0/24".parse()?];
+        let rules = |reach| {
+            let policy = NetworkPolicy::compile(&reach, address, &group_subnets);
+            assert_eq!(policy.get_source(), &Ipv4Addr::new(10, 0, 3, 7));
+            let rules: Vec<_> = policy
+                .rules
+                .iter()
+                .map(|rule| format!("{} {}", rule.destination, rule.action))
+                .collect();
+            (rules, policy.default_action)
+        };
+
+        assert_eq!(
+            rules(SandboxNetworkReach::Local),
+            (vec!["10.0.3.0/24 accept".to_string()], RuleAction::Drop)
+        );
+        assert_eq!(
+            rules(SandboxNetworkReach::Public),
+            (
+                vec![
+                    "10.0.3.0/24 accept".to_string(),
+                    "10.0.4.0/24 accept".to_string(),
+                    "172.16.0.0/12 accept".to_string(),
+                    "10.0.0.0/8 drop".to_string(),
+                    "192.168.0.0/16 drop".to_string(),
+                ],
+                RuleAction::Accept
+            )
+        );
+        assert_eq!(
+            rules(SandboxNetworkReach::Any),
+            (Vec::new(), RuleAction::Accept)
+        );
+        assert_eq!(
+            rules(SandboxNetworkReach::None),
+            (Vec::new(), RuleAction::Drop)
+        );
+
+        let script = NetworkPolicy::compile(&SandboxNetworkReach::Local, address, &group_subnets)
+            .to_nftables(&get_network_policy_table("api"));
+        assert_eq!(
+            script,
+            "table ip monocore-api\n\
+             delete table ip monocore-api\n\
+             table ip monocore-api {\n\
+             \x20   chain sandbox {\n\
+             \x20       ct state established,related accept\n\
+             \x20       ip daddr 10.0.3.0/24 accept\n\
+             \x20       drop\n\
+             \x20   }\n\
+             \x20   chain forward {\n\
+             \x20       type filter hook forward priority filter; policy accept;\n\
+             \x20       ip saddr 10.0.3.7 jump sandbox\n\
+             \x20   }\n\
+             \x20   chain output {\n\
+             \x20       type filter hook output priority filter; policy accept;\n\
+             \x20       ip saddr 10.0.3.7 jump sandbox\n\
+             \x20   }\n\
+             }\n"
+        );
+
+        Ok(())
+    }
+}
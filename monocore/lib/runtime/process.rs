@@ -0,0 +1,396 @@
+use std::{ffi::CString, process::Stdio};
+
+use async_trait::async_trait;
+use nix::{
+    mount::{self, MsFlags},
+    sched::{self, CloneFlags},
+    sys::signal::{self, Signal},
+    unistd::{self, Pid},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+
+use crate::{
+    config::PortPair,
+    management::SANDBOX_STOP_TIMEOUT,
+    vm::{LinuxRlimit, MicroVmConfig},
+    MonocoreError, MonocoreResult,
+};
+
+use super::{
+    create_mount_points, get_exit_code, get_mount_point, get_sandbox_path, set_rlimit, to_cstring,
+    SandboxBackend,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A sandbox backend that runs the sandbox's exec command as a host process.
+///
+/// When running as root, the process is chrooted into the root path of the sandbox in a mount
+/// namespace of its own, with the mapped directories bind-mounted into it and the resource limits
+/// applied. Otherwise, it runs on the host with its working directory in the root path, and the
+/// mapped directories are not available to it.
+///
+/// The process shares the host network, so the host side of each port mapping is forwarded to
+/// its guest side on localhost.
+///
+/// This backend doesn't isolate the sandbox the way a MicroVm does. It exists so sandboxes can be
+/// run where there is no virtualization, like on CI machines.
+pub struct ProcessBackend {
+    /// The configuration of the sandbox.
+    config: MicroVmConfig,
+
+    /// Whether to chroot the process into the root path.
+    chroot: bool,
+
+    /// The main process of the sandbox.
+    child: Option<Child>,
+
+    /// The tasks forwarding the host ports to the guest ports.
+    forwarders: Vec<JoinHandle<()>>,
+}
+
+/// Everything the child process needs between fork and exec, prepared ahead of time so nothing
+/// gets allocated in the child.
+struct Isolation {
+    /// The root path of the sandbox.
+    root_path: CString,
+
+    /// The working directory of the process in the sandbox.
+    workdir_path: CString,
+
+    /// The host and sandbox paths of the directories to bind-mount.
+    mounts: Vec<(CString, CString)>,
+
+    /// The resource limits to apply.
+    rlimits: Vec<LinuxRlimit>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ProcessBackend {
+    /// Creates a new process backend for the given configuration.
+    ///
+    /// The process is chrooted if the current user is root.
+    pub fn new(config: MicroVmConfig) -> Self {
+        Self {
+            config,
+            chroot: unistd::geteuid().is_root(),
+            child: None,
+            forwarders: Vec::new(),
+        }
+    }
+
+    /// Sets whether to chroot the process into the root path. Chrooting needs root.
+    pub fn with_chroot(mut self, chroot: bool) -> Self {
+        self.chroot = chroot;
+        self
+    }
+
+    /// Builds the command running the given executable in the sandbox.
+    fn command(&self, exec_path: &str, args: &[String]) -> MonocoreResult<Command> {
+        let mut command = Command::new(exec_path);
+        command
+            .args(args)
+            .env_clear()
+            .envs(
+                self.config
+                    .env
+                    .iter()
+                    .map(|pair| (pair.get_name(), pair.get_value())),
+            )
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true);
+
+        let workdir_path = self
+            .config
+            .workdir_path
+            .as_ref()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+
+        if !self.chroot {
+            command.current_dir(get_sandbox_path(&self.config.root_path, workdir_path));
+            return Ok(command);
+        }
+
+        let isolation = Isolation {
+            root_path: to_cstring(&self.config.root_path)?,
+            workdir_path: to_cstring(workdir_path)?,
+            mounts: self
+                .config
+                .mapped_dirs
+                .iter()
+                .map(|dir| {
+                    let target = get_mount_point(&self.config.root_path, dir.get_guest().as_str())?;
+                    Ok((to_cstring(dir.get_host().as_str())?, to_cstring(target)?))
+                })
+                .collect::<MonocoreResult<_>>()?,
+            rlimits: self.config.rlimits.clone(),
+        };
+
+        // SAFETY: `isolate` only makes system calls on data prepared before the fork.
+        unsafe {
+            command.pre_exec(move || isolation.isolate());
+        }
+
+        Ok(command)
+    }
+}
+
+impl Isolation {
+    /// Moves the calling process into a mount namespace of its own, mounts the mapped directories,
+    /// chroots into the root path and applies the resource limits.
+    fn isolate(&self) -> std::io::Result<()> {
+        sched::unshare(CloneFlags::CLONE_NEWNS)?;
+
+        // Keep the mounts below from propagating back to the host
+        mount::mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&str>,
+        )?;
+
+        for (source, target) in &self.mounts {
+            mount::mount(
+                Some(source.as_c_str()),
+                target.as_c_str(),
+                None::<&str>,
+                MsFlags::MS_BIND | MsFlags::MS_REC,
+                None::<&str>,
+            )?;
+        }
+
+        unistd::chroot(self.root_path.as_c_str())?;
+        unistd::chdir(self.workdir_path.as_c_str())?;
+
+        for rlimit in &self.rlimits {
+            set_rlimit(rlimit)?;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Forwards connections to a host port to the guest port of a mapping on localhost.
+async fn forward_port(port: PortPair) -> MonocoreResult<JoinHandle<()>> {
+    let listener = TcpListener::bind(("0.0.0.0", port.get_host())).await?;
+    let guest = port.get_guest();
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let (mut inbound, _) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("failed to accept connection on port {}: {}", port, e);
+                    continue;
+                }
+            };
+
+            tokio::spawn(async move {
+                match TcpStream::connect(("127.0.0.1", guest)).await {
+                    Ok(mut outbound) => {
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    }
+                    Err(e) => tracing::warn!("failed to connect to port {}: {}", guest, e),
+                }
+            });
+        }
+    }))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl SandboxBackend for ProcessBackend {
+    async fn create(&mut self) -> MonocoreResult<()> {
+        self.config.validate()?;
+
+        if self.config.exec_path.is_none() {
+            return Err(MonocoreError::InvalidArgument(
+                "the process backend needs an exec path".to_string(),
+            ));
+        }
+
+        if self.chroot {
+            create_mount_points(
+                &self.config.root_path,
+                self.config
+                    .mapped_dirs
+                    .iter()
+                    .map(|dir| dir.get_guest().as_str()),
+                [],
+            )?;
+        } else if !self.config.mapped_dirs.is_empty() {
+            tracing::warn!("mapped directories are only available to chrooted processes");
+        }
+
+        Ok(())
+    }
+
+    async fn start(&mut self) -> MonocoreResult<()> {
+        let exec_path = self.config.exec_path.clone().ok_or_else(|| {
+            MonocoreError::InvalidArgument("the process backend needs an exec path".to_string())
+        })?;
+
+        for port in &self.config.port_map {
+            if port.get_host() != port.get_guest() {
+                self.forwarders.push(forward_port(port.clone()).await?);
+            }
+        }
+
+        let child = self
+            .command(exec_path.as_str(), &self.config.args)?
+            .spawn()?;
+
+        tracing::info!("started sandbox process {:?}", child.id());
+        self.child = Some(child);
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> MonocoreResult<()> {
+        let child = self.child.as_mut().ok_or_else(|| {
+            MonocoreError::InvalidArgument("the sandbox process has not been started".to_string())
+        })?;
+
+        if let Some(pid) = child.id() {
+            tracing::info!("stopping sandbox process {}", pid);
+            match signal::kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+                Ok(()) | Err(nix::errno::Errno::ESRCH) => {}
+                Err(e) => return Err(std::io::Error::from(e).into()),
+            }
+
+            if tokio::time::timeout(SANDBOX_STOP_TIMEOUT, child.wait())
+                .await
+                .is_err()
+            {
+                tracing::warn!("sandbox process {} did not stop in time, killing it", pid);
+                child.kill().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn wait(&mut self) -> MonocoreResult<i32> {
+        let child = self.child.as_mut().ok_or_else(|| {
+            MonocoreError::InvalidArgument("the sandbox process has not been started".to_string())
+        })?;
+
+        let status = child.wait().await?;
+        for forwarder in self.forwarders.drain(..) {
+            forwarder.abort();
+        }
+
+        tracing::info!("sandbox process exited with {}", status);
+        Ok(get_exit_code(status))
+    }
+
+    async fn exec(&mut self, exec_path: &str, args: &[String]) -> MonocoreResult<i32> {
+        let status = self.command(exec_path, args)?.status().await?;
+        Ok(get_exit_code(status))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::config::EnvPair;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process_backend_runs_exec_command() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        tokio::fs::create_dir_all(temp_dir.path().join("app")).await?;
+
+        let config = MicroVmConfig::builder()
+            .root_path(temp_dir.path())
+            .ram_mib(256)
+            .workdir_path("/app")
+            .exec_path("/bin/sh")
+            .args(["-c", "echo $GREETING > out.txt; exit 3"])
+            .env([EnvPair::new("GREETING", "hello")])
+            .build();
+
+        let mut backend = ProcessBackend::new(config).with_chroot(false);
+        backend.create().await?;
+        backend.start().await?;
+        assert_eq!(backend.wait().await?, 3);
+
+        let output = tokio::fs::read_to_string(temp_dir.path().join("app/out.txt")).await?;
+        assert_eq!(output, "hello\n");
+
+        let status = backend
+            .exec(
+                "/bin/sh",
+                &["-c".to_string(), "test -f out.txt".to_string()],
+            )
+            .await?;
+        assert_eq!(status, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_backend_stop_terminates_process() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config = MicroVmConfig::builder()
+            .root_path(temp_dir.path())
+            .ram_mib(256)
+            .exec_path("/bin/sh")
+            .args(["-c", "exec sleep 60"])
+            .build();
+
+        let mut backend = ProcessBackend::new(config).with_chroot(false);
+        backend.create().await?;
+        backend.start().await?;
+        backend.stop().await?;
+        assert_eq!(backend.wait().await?, 128 + Signal::SIGTERM as i32);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_backend_forward_port() -> anyhow::Result<()> {
+        let guest = TcpListener::bind("127.0.0.1:0").await?;
+        let guest_port = guest.local_addr()?.port();
+        let host_port = TcpListener::bind("0.0.0.0:0").await?.local_addr()?.port();
+
+        let forwarder = forward_port(PortPair::with_distinct(host_port, guest_port)).await?;
+        let mut client = TcpStream::connect(("127.0.0.1", host_port)).await?;
+        let (mut server, _) = guest.accept().await?;
+
+        client.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+
+        forwarder.abort();
+        Ok(())
+    }
+}
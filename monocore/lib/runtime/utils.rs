@@ -0,0 +1,284 @@
+use std::{
+    ffi::{CString, OsStr},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{ffi::OsStrExt, process::ExitStatusExt},
+    },
+    path::{Component, Path, PathBuf},
+    process::ExitStatus,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{self, OFlag},
+    sys::stat::{self, Mode, SFlag},
+    unistd,
+};
+
+use crate::{vm::LinuxRlimit, MonocoreError, MonocoreResult};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The kind of the last component of a mount point, created if it is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MountPointKind {
+    /// A directory, for mounting directories on.
+    Dir,
+
+    /// An empty file, for mounting files such as device nodes on.
+    File,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the host path of a path in the sandbox.
+///
+/// The path is resolved against the root path the way it would be inside the sandbox, so `..`
+/// never goes above the root path.
+pub(crate) fn get_sandbox_path(root_path: &Path, path: &str) -> PathBuf {
+    let mut components = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            Component::ParentDir => {
+                components.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+
+    let mut host_path = root_path.to_path_buf();
+    host_path.extend(components);
+    host_path
+}
+
+/// Creates the mount points of a sandbox in its root path.
+///
+/// The root path comes from an untrusted image, so no component of a mount point is allowed to be a
+/// symlink. Otherwise a link like `dev -> /home/user/.ssh` would have the mount points created, and
+/// later mounted, outside of the root path.
+///
+/// ## Arguments
+/// * `root_path` - The root path of the sandbox
+/// * `dirs` - The directories to mount other directories on
+/// * `files` - The files to mount other files, such as device nodes, on
+pub(crate) fn create_mount_points<'a>(
+    root_path: &Path,
+    dirs: impl IntoIterator<Item = &'a str>,
+    files: impl IntoIterator<Item = &'a str>,
+) -> MonocoreResult<()> {
+    for dir in dirs {
+        open_mount_point(root_path, dir, Some(MountPointKind::Dir))?;
+    }
+
+    for file in files {
+        open_mount_point(root_path, file, Some(MountPointKind::File))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the host path of a mount point in the sandbox after checking that none of its
+/// components is a symlink.
+///
+/// The check must be made right before mounting, while nothing runs in the sandbox that could swap
+/// a component for a symlink.
+pub(crate) fn get_mount_point(root_path: &Path, path: &str) -> MonocoreResult<PathBuf> {
+    open_mount_point(root_path, path, None)?;
+    Ok(get_sandbox_path(root_path, path))
+}
+
+/// Applies a resource limit to the current process.
+pub(crate) fn set_rlimit(rlimit: &LinuxRlimit) -> Result<(), Errno> {
+    let limit = libc::rlimit {
+        rlim_cur: *rlimit.get_soft(),
+        rlim_max: *rlimit.get_hard(),
+    };
+
+    // SAFETY: `limit` is a valid rlimit for the duration of the call.
+    if unsafe { libc::setrlimit(rlimit.get_resource().as_int() as _, &limit) } != 0 {
+        return Err(Errno::last());
+    }
+
+    Ok(())
+}
+
+/// Converts a path or string to a C string.
+pub(crate) fn to_cstring(s: impl AsRef<OsStr>) -> MonocoreResult<CString> {
+    CString::new(s.as_ref().as_bytes()).map_err(|_| {
+        MonocoreError::InvalidArgument(format!(
+            "contains a nul byte: {}",
+            s.as_ref().to_string_lossy()
+        ))
+    })
+}
+
+/// Returns the exit status of a process the way a shell reports it.
+pub(crate) fn get_exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Opens a mount point in the root path one component at a time without following symlinks.
+///
+/// Missing components are created if `create` is given, the last one as the given kind. Fails if
+/// a component is a symlink, a component other than the last isn't a directory, or a component is
+/// missing and `create` isn't given.
+fn open_mount_point(
+    root_path: &Path,
+    path: &str,
+    create: Option<MountPointKind>,
+) -> MonocoreResult<OwnedFd> {
+    let host_path = get_sandbox_path(root_path, path);
+    let names = host_path
+        .strip_prefix(root_path)
+        .unwrap_or(Path::new(""))
+        .components()
+        .collect::<Vec<_>>();
+
+    let mut current = open_fd(
+        None,
+        &root_path,
+        OFlag::O_PATH | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+    )?;
+
+    for (i, name) in names.iter().enumerate() {
+        let dirfd = Some(current.as_raw_fd());
+        let kind = match i + 1 == names.len() {
+            true => create,
+            false => Some(MountPointKind::Dir),
+        };
+
+        let flags = OFlag::O_PATH | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC;
+        let next = match (open_fd(dirfd, name, flags), create.and(kind)) {
+            (Ok(fd), _) => fd,
+            (Err(Errno::ENOENT), Some(kind)) => {
+                create_component(dirfd, name, kind)?;
+                open_fd(dirfd, name, flags)?
+            }
+            (Err(errno), _) => {
+                return Err(MonocoreError::InvalidArgument(format!(
+                    "failed to open mount point {}: {}",
+                    path, errno
+                )))
+            }
+        };
+
+        let file_type =
+            SFlag::from_bits_truncate(stat::fstat(next.as_raw_fd())?.st_mode) & SFlag::S_IFMT;
+        if file_type == SFlag::S_IFLNK {
+            return Err(MonocoreError::InvalidArgument(format!(
+                "mount point {} goes through a symlink: {}",
+                path,
+                Path::new(name.as_os_str()).display()
+            )));
+        }
+
+        if kind == Some(MountPointKind::Dir) && file_type != SFlag::S_IFDIR {
+            return Err(MonocoreError::InvalidArgument(format!(
+                "mount point {} goes through a file that is not a directory: {}",
+                path,
+                Path::new(name.as_os_str()).display()
+            )));
+        }
+
+        current = next;
+    }
+
+    Ok(current)
+}
+
+/// Creates a missing component of a mount point in the directory it belongs to.
+fn create_component(
+    dirfd: Option<RawFd>,
+    name: &impl AsRef<Path>,
+    kind: MountPointKind,
+) -> Result<(), Errno> {
+    let result = match kind {
+        MountPointKind::Dir => stat::mkdirat(dirfd, name.as_ref(), Mode::from_bits_truncate(0o755)),
+        MountPointKind::File => fcntl::openat(
+            dirfd,
+            name.as_ref(),
+            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            Mode::from_bits_truncate(0o644),
+        )
+        .map(|fd| {
+            let _ = unistd::close(fd);
+        }),
+    };
+
+    // Whatever created it in the meantime is checked once it is opened
+    match result {
+        Ok(()) | Err(Errno::EEXIST) => Ok(()),
+        Err(errno) => Err(errno),
+    }
+}
+
+/// Opens a path relative to a directory file descriptor.
+fn open_fd(dirfd: Option<RawFd>, path: &impl AsRef<Path>, flags: OFlag) -> Result<OwnedFd, Errno> {
+    let fd = fcntl::openat(dirfd, path.as_ref(), flags, Mode::empty())?;
+
+    // SAFETY: The file descriptor was just opened and isn't owned elsewhere.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_sandbox_path_stays_in_root_path() {
+        let root_path = Path::new("/var/lib/monocore/rootfs");
+
+        assert_eq!(
+            get_sandbox_path(root_path, "/usr/bin/env"),
+            root_path.join("usr/bin/env")
+        );
+        assert_eq!(
+            get_sandbox_path(root_path, "./data/../logs/"),
+            root_path.join("logs")
+        );
+        assert_eq!(
+            get_sandbox_path(root_path, "/../../etc/passwd"),
+            root_path.join("etc/passwd")
+        );
+        assert_eq!(get_sandbox_path(root_path, "/"), root_path);
+    }
+
+    #[test]
+    fn test_create_mount_points_rejects_symlinks() -> anyhow::Result<()> {
+        let rootfs = tempfile::TempDir::new()?;
+        let outside = tempfile::TempDir::new()?;
+        std::os::unix::fs::symlink(outside.path(), rootfs.path().join("dev"))?;
+        std::os::unix::fs::symlink("/", rootfs.path().join("data"))?;
+
+        create_mount_points(rootfs.path(), ["/proc", "/app/data"], [])?;
+        assert!(rootfs.path().join("proc").is_dir());
+        assert!(rootfs.path().join("app/data").is_dir());
+        assert!(get_mount_point(rootfs.path(), "/app/data").is_ok());
+
+        // Neither the mount points nor their parents are created through the symlinks
+        assert!(create_mount_points(rootfs.path(), [], ["/dev/null"]).is_err());
+        assert!(create_mount_points(rootfs.path(), ["/data/home"], []).is_err());
+        assert!(create_mount_points(rootfs.path(), ["/data"], []).is_err());
+        assert_eq!(std::fs::read_dir(outside.path())?.count(), 0);
+
+        assert!(get_mount_point(rootfs.path(), "/dev/null").is_err());
+        assert!(get_mount_point(rootfs.path(), "/missing").is_err());
+
+        Ok(())
+    }
+}
@@ -6,11 +6,24 @@
 
 use std::path::PathBuf;
 
-use crate::config::{DEFAULT_MONOCORE_HOME, DEFAULT_OCI_REGISTRY};
+use crate::{
+    config::{DEFAULT_MONOCORE_HOME, DEFAULT_OCI_REGISTRY},
+    runtime::SandboxBackendType,
+    MonocoreError, MonocoreResult,
+};
 
 /// Environment variable for the monocore home directory
 pub const MONOCORE_HOME_ENV_VAR: &str = "MONOCORE_HOME";
 
+/// Environment variable for the path to the mcrun binary
+pub const MCRUN_EXE_ENV_VAR: &str = "MCRUN_EXE";
+
+/// Environment variable for the backend sandboxes are run with
+pub const MONOCORE_BACKEND_ENV_VAR: &str = "MONOCORE_BACKEND";
+
+/// Environment variable for the seconds between samples of the resource usage of sandboxes
+pub const MONOCORE_METRICS_INTERVAL_ENV_VAR: &str = "MONOCORE_METRICS_INTERVAL";
+
 /// Environment variable for the OCI registry domain
 pub const OCI_REGISTRY_ENV_VAR: &str = "OCI_REGISTRY_DOMAIN";
 
@@ -39,3 +52,30 @@ pub fn get_oci_registry() -> String {
         DEFAULT_OCI_REGISTRY.to_string()
     }
 }
+
+/// Returns the backend sandboxes are run with if it is set.
+/// If the MONOCORE_BACKEND environment variable is set, returns the backend it names.
+/// Otherwise, returns None so the backend can be chosen from the configuration or the host.
+pub fn get_sandbox_backend() -> MonocoreResult<Option<SandboxBackendType>> {
+    if let Ok(backend) = std::env::var(MONOCORE_BACKEND_ENV_VAR) {
+        backend.parse().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the seconds between samples of the resource usage of sandboxes if it is set.
+/// If the MONOCORE_METRICS_INTERVAL environment variable is set, returns the number it holds.
+/// Otherwise, returns None so sandboxes are sampled at the default interval.
+pub fn get_metrics_interval() -> MonocoreResult<Option<u64>> {
+    if let Ok(interval) = std::env::var(MONOCORE_METRICS_INTERVAL_ENV_VAR) {
+        interval.parse().map(Some).map_err(|_| {
+            MonocoreError::InvalidArgument(format!(
+                "{} must be a number of seconds, got '{}'",
+                MONOCORE_METRICS_INTERVAL_ENV_VAR, interval
+            ))
+        })
+    } else {
+        Ok(None)
+    }
+}
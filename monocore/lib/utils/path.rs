@@ -29,6 +29,12 @@ pub const LAYERS_SUBDIR: &str = "layers";
 /// The directory where monocore's installed binaries are stored
 pub const BIN_SUBDIR: &str = "bin";
 
+/// The filename for the project configuration
+pub const MONOCORE_CONFIG_FILENAME: &str = "monocore.yaml";
+
+/// The filename for the mcrun binary
+pub const MCRUN_EXE_FILENAME: &str = "mcrun";
+
 /// The filename for the project active sandbox database
 pub const SANDBOX_DB_FILENAME: &str = "sandbox.db";
 
@@ -0,0 +1,434 @@
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use monocore::{
+    management::{self, HealthStatus, SandboxStatus, OCI_DB_MIGRATOR},
+    oci::Reference,
+    runtime::SandboxBackendType,
+    utils::{
+        env::{MCRUN_EXE_ENV_VAR, MONOCORE_HOME_ENV_VAR, MONOCORE_METRICS_INTERVAL_ENV_VAR},
+        path::{LAYERS_SUBDIR, LOG_SUBDIR, MONOCORE_ENV_DIR, OCI_DB_FILENAME, SANDBOX_DB_FILENAME},
+    },
+};
+use sqlx::Row;
+use tempfile::TempDir;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The digest of the only layer of the test image.
+const LAYER_DIGEST: &str =
+    "sha256:0000000000000000000000000000000000000000000000000000000000000001";
+
+/// The diff ID of the only layer of the test image.
+const LAYER_DIFF_ID: &str =
+    "sha256:0000000000000000000000000000000000000000000000000000000000000002";
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_up_and_down_with_host_backends() -> anyhow::Result<()> {
+    let home_dir = TempDir::new()?;
+    std::env::set_var(MONOCORE_HOME_ENV_VAR, home_dir.path());
+    std::env::set_var(MCRUN_EXE_ENV_VAR, env!("CARGO_BIN_EXE_mcrun"));
+    std::env::set_var(MONOCORE_METRICS_INTERVAL_ENV_VAR, "1");
+
+    seed_image(home_dir.path(), "test:latest").await?;
+
+    for backend in [SandboxBackendType::Process, SandboxBackendType::Container] {
+        let project_dir = TempDir::new()?;
+        up_and_down(project_dir.path(), backend).await?;
+    }
+
+    let project_dir = TempDir::new()?;
+    restart_on_failure(project_dir.path()).await?;
+
+    let project_dir = TempDir::new()?;
+    wait_for_healthy_dependency(project_dir.path()).await?;
+
+    let project_dir = TempDir::new()?;
+    record_subnets_before_start(project_dir.path()).await?;
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Starts the sandbox of a test project with the given backend, checks that it runs its exec
+/// command and that its port is wired up, then stops it.
+async fn up_and_down(project_path: &Path, backend: SandboxBackendType) -> anyhow::Result<()> {
+    let host_port = get_free_port()?;
+    tokio::fs::write(
+        project_path.join("monocore.yaml"),
+        format!(
+            r#"
+            sandboxes:
+              - name: app
+                image: test:latest
+                workdir: /app
+                ports: ["{}:{}"]
+                envs: ["GREETING=hello"]
+                scripts:
+                  start: ["echo $GREETING > greeting.txt", "echo started", "exec sleep 60"]
+                groups:
+                  web: {{}}
+            groups:
+              - name: web
+            "#,
+            host_port,
+            get_free_port()?
+        ),
+    )
+    .await?;
+
+    let project = Some(project_path.to_path_buf());
+    management::up(project.clone(), &[], false, Some(backend)).await?;
+
+    // The supervisor marks the sandbox as running once it has started it
+    let menv_path = project_path.join(MONOCORE_ENV_DIR);
+    let pool = management::get_or_create_db_pool(
+        &menv_path.join(SANDBOX_DB_FILENAME),
+        &management::SANDBOX_DB_MIGRATOR,
+    )
+    .await?;
+    let mut status = String::new();
+    for _ in 0..100 {
+        if let Some(row) = sqlx::query("SELECT status FROM sandboxes WHERE name = 'app'")
+            .fetch_optional(&pool)
+            .await?
+        {
+            status = row.get("status");
+            if status == "running" {
+                break;
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status, "running", "{} backend", backend);
+
+    // The sandbox got the first address of the subnet its group was given
+    let states = management::status(project.clone(), &[], false).await?;
+    assert_eq!(states[0].get_group_ip(), &Some([10, 0, 0, 1].into()));
+
+    // The exec command ran in the rootfs and its output went to the sandbox log
+    let greeting_path = menv_path.join("rootfs/app/app/greeting.txt");
+    let log_dir = menv_path.join(LOG_SUBDIR);
+    let mut started = false;
+    for _ in 0..100 {
+        started = greeting_path.exists() && read_logs(&log_dir)?.contains("started");
+        if started {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        started,
+        "{} backend did not start the exec command",
+        backend
+    );
+    assert_eq!(std::fs::read_to_string(&greeting_path)?, "hello\n");
+
+    // The host side of the port mapping is wired up
+    tokio::net::TcpStream::connect(("127.0.0.1", host_port)).await?;
+
+    // The supervisor samples the resource usage of the sandbox
+    let mut metrics = Vec::new();
+    for _ in 0..50 {
+        metrics = management::metrics(project.clone()).await?;
+        if !metrics.is_empty() {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(metrics.len(), 1, "{} backend", backend);
+    assert!(*metrics[0].get_usage().get_memory_usage() > 0);
+
+    management::down(project.clone(), &[], false).await?;
+
+    // The sandbox keeps its record, with the exit code of being stopped by SIGTERM
+    let states = management::status(project, &[], false).await?;
+    assert_eq!(states.len(), 1, "{} backend", backend);
+    assert_eq!(
+        states[0].get_status(),
+        &SandboxStatus::Exited(143),
+        "{} backend",
+        backend
+    );
+    assert_eq!(states[0].get_group_ip(), &None);
+
+    // The log of the sandbox is kept after it stops
+    let segments = management::get_log_segments(&log_dir, "app").await?;
+    let records = management::read_log_records(&segments, &Default::default()).await?;
+    assert!(
+        records.iter().any(|record| record.get_line() == "started"),
+        "{:?}",
+        records
+    );
+
+    Ok(())
+}
+
+/// Starts a sandbox that keeps failing with an `on-failure` restart policy and checks that it is
+/// restarted as many times as the policy allows.
+async fn restart_on_failure(project_path: &Path) -> anyhow::Result<()> {
+    tokio::fs::write(
+        project_path.join("monocore.yaml"),
+        r#"
+        sandboxes:
+          - name: flaky
+            image: test:latest
+            restart: on-failure:2
+            scripts:
+              start: ["exit 3"]
+        "#,
+    )
+    .await?;
+
+    let project = Some(project_path.to_path_buf());
+    management::up(
+        project.clone(),
+        &[],
+        false,
+        Some(SandboxBackendType::Process),
+    )
+    .await?;
+
+    // Two restarts wait at most a second and two seconds
+    let mut states = Vec::new();
+    for _ in 0..100 {
+        states = management::status(project.clone(), &[], false).await?;
+        if states[0].get_status() == &SandboxStatus::Exited(3)
+            && *states[0].get_restart_count() == 2
+        {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(states[0].get_status(), &SandboxStatus::Exited(3));
+    assert_eq!(*states[0].get_restart_count(), 2);
+
+    Ok(())
+}
+
+/// Starts a sandbox that depends on another being healthy and checks that it is only started
+/// once the health check of the other one passes.
+async fn wait_for_healthy_dependency(project_path: &Path) -> anyhow::Result<()> {
+    tokio::fs::write(
+        project_path.join("monocore.yaml"),
+        r#"
+        sandboxes:
+          - name: db
+            image: test:latest
+            workdir: /app
+            healthcheck:
+              exec: ["/bin/sh", "-c", "test -f /app/ready"]
+              interval: 1
+            scripts:
+              start: ["echo > ready", "exec sleep 60"]
+          - name: app
+            image: test:latest
+            depends_on:
+              - name: db
+                condition: healthy
+            scripts:
+              start: ["exec sleep 60"]
+        "#,
+    )
+    .await?;
+
+    let project = Some(project_path.to_path_buf());
+    management::up(
+        project.clone(),
+        &["app".to_string()],
+        false,
+        Some(SandboxBackendType::Process),
+    )
+    .await?;
+
+    // The dependency was healthy before the sandbox depending on it was started
+    let states = management::status(project.clone(), &[], false).await?;
+    let [app, db] = states.as_slice() else {
+        panic!("expected two sandboxes, got {:?}", states);
+    };
+    assert_eq!(db.get_health(), &Some(HealthStatus::Healthy));
+    assert!(app.get_status().is_active());
+
+    let checks = management::health_checks(project.clone(), "db").await?;
+    assert!(checks.last().is_some_and(|check| *check.get_passed()));
+
+    management::down(project, &[], false).await?;
+
+    Ok(())
+}
+
+/// Starts a public sandbox before the sandbox of another group and checks that the subnet of the
+/// other group was recorded, and so covered by the network policy of the public sandbox, before
+/// the public sandbox was started.
+async fn record_subnets_before_start(project_path: &Path) -> anyhow::Result<()> {
+    tokio::fs::write(
+        project_path.join("monocore.yaml"),
+        r#"
+        sandboxes:
+          - name: web
+            image: test:latest
+            network:
+              reach: public
+            scripts:
+              start: ["exec sleep 60"]
+            groups:
+              frontend: {}
+          - name: db
+            image: test:latest
+            scripts:
+              start: ["exec sleep 60"]
+            groups:
+              backend: {}
+        groups:
+          - name: frontend
+          - name: backend
+            network:
+              subnet: 10.0.0.0/24
+        "#,
+    )
+    .await?;
+
+    let project = Some(project_path.to_path_buf());
+    management::up(
+        project.clone(),
+        &["web".to_string()],
+        false,
+        Some(SandboxBackendType::Process),
+    )
+    .await?;
+
+    let pool = management::get_or_create_db_pool(
+        &project_path
+            .join(MONOCORE_ENV_DIR)
+            .join(SANDBOX_DB_FILENAME),
+        &management::SANDBOX_DB_MIGRATOR,
+    )
+    .await?;
+    let subnets: Vec<(String, String)> =
+        sqlx::query_as("SELECT name, subnet FROM groups ORDER BY name")
+            .fetch_all(&pool)
+            .await?;
+    assert_eq!(
+        subnets,
+        vec![
+            ("backend".to_string(), "10.0.0.0/24".to_string()),
+            ("frontend".to_string(), "10.0.1.0/24".to_string()),
+        ]
+    );
+
+    // The sandbox of the other group is given an address in the subnet recorded before
+    management::up(
+        project.clone(),
+        &["db".to_string()],
+        false,
+        Some(SandboxBackendType::Process),
+    )
+    .await?;
+    let states = management::status(project.clone(), &[], false).await?;
+    let [db, web] = states.as_slice() else {
+        panic!("expected two sandboxes, got {:?}", states);
+    };
+    assert_eq!(web.get_group_ip(), &Some([10, 0, 1, 1].into()));
+    assert_eq!(db.get_group_ip(), &Some([10, 0, 0, 1].into()));
+
+    management::down(project, &[], false).await?;
+
+    Ok(())
+}
+
+/// Records an image in the OCI database of the monocore home, with a single extracted layer
+/// holding a shell and `sleep` along with the libraries they load.
+async fn seed_image(home_path: &Path, reference: &str) -> anyhow::Result<()> {
+    let layer_path = home_path
+        .join(LAYERS_SUBDIR)
+        .join(format!("{}.extracted", LAYER_DIGEST));
+    std::fs::create_dir_all(layer_path.join("app"))?;
+    for exe in ["/bin/sh", "/bin/sleep"] {
+        for path in std::iter::once(PathBuf::from(exe)).chain(get_libraries(exe)?) {
+            let target = layer_path.join(path.strip_prefix("/")?);
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            std::fs::copy(&path, &target)?;
+        }
+    }
+
+    let reference: Reference = reference.parse()?;
+    let pool =
+        management::get_or_create_db_pool(&home_path.join(OCI_DB_FILENAME), &OCI_DB_MIGRATOR)
+            .await?;
+
+    let image_id: i64 =
+        sqlx::query("INSERT INTO images (reference, size_bytes) VALUES (?, 0) RETURNING id")
+            .bind(reference.to_string())
+            .fetch_one(&pool)
+            .await?
+            .get("id");
+
+    let manifest_id: i64 = sqlx::query(
+        "INSERT INTO manifests (image_id, schema_version, media_type) VALUES (?, 2, '') RETURNING id",
+    )
+    .bind(image_id)
+    .fetch_one(&pool)
+    .await?
+    .get("id");
+
+    sqlx::query(
+        "INSERT INTO configs (manifest_id, media_type, rootfs_diff_ids_json) VALUES (?, '', ?)",
+    )
+    .bind(manifest_id)
+    .bind(LAYER_DIFF_ID)
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO layers (manifest_id, media_type, digest, diff_id, size_bytes) VALUES (?, '', ?, ?, 0)",
+    )
+    .bind(manifest_id)
+    .bind(LAYER_DIGEST)
+    .bind(LAYER_DIFF_ID)
+    .execute(&pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the shared libraries an executable loads, as listed by `ldd`.
+fn get_libraries(exe: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let output = Command::new("ldd").arg(exe).output()?;
+    Ok(String::from_utf8(output.stdout)?
+        .split_whitespace()
+        .filter(|word| word.starts_with('/'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Returns the contents of all the logs in a directory.
+fn read_logs(log_dir: &Path) -> anyhow::Result<String> {
+    let mut logs = String::new();
+    for entry in std::fs::read_dir(log_dir)? {
+        logs.push_str(&std::fs::read_to_string(entry?.path())?);
+    }
+
+    Ok(logs)
+}
+
+/// Returns a port nothing is listening on.
+fn get_free_port() -> anyhow::Result<u16> {
+    Ok(TcpListener::bind("0.0.0.0:0")?.local_addr()?.port())
+}
@@ -23,6 +23,13 @@
 //! - `--host`: The address to bind to (default: "127.0.0.1")
 //! - `--port`: The port to listen on (default: 2049)
 //! - `--store-dir`: Directory path where the monofs store will be located
+//! - `--rev`: Optional CID of a directory revision to serve in read-only mode
+//! - `--memory`: Keep the filesystem in memory instead of `--store-dir`
+//! - `--fs-db-path`: Optional path to the database the head of the filesystem is tracked in
+//! - `--mount-dir`: Directory the filesystem is mounted at, required with `--fs-db-path`
+//!
+//! The store is encrypted if the `MONOFS_ENCRYPTION_KEYS` environment variable holds the keys of
+//! an encrypted filesystem, which `monofs` sets when it mounts one.
 //!
 //! ### Supervisor Mode
 //!
@@ -46,6 +53,11 @@
 //! - `--port`: The port for the NFS server to listen on (default: 2049)
 //! - `--store-dir`: Directory path where the monofs store will be located
 //! - `--db-path`: Path to the metrics database file
+//! - `--rev`: Optional CID of a directory revision the NFS server serves in read-only mode
+//! - `--memory`: Have the NFS server keep the filesystem in memory instead of `--store-dir`
+//! - `--ephemeral`: Remove the mount point and all filesystem data when the supervisor exits
+//! - `--restart`: When to restart the NFS server after it exits: `never`, `always`, `on-failure`
+//!   or `on-failure:<max retries>` (default: "on-failure", so a crashed server comes back)
 //!
 //! ## Examples
 //!
@@ -57,6 +69,15 @@
 //!     --store-path=/mnt/monofs/store
 //! ```
 //!
+//! ### Serving a Historical Revision Read-Only
+//! ```bash
+//! mfsrun nfsserver \
+//!     --host=127.0.0.1 \
+//!     --port=2050 \
+//!     --store-dir=/mnt/monofs/store \
+//!     --rev=bafyr4i...
+//! ```
+//!
 //! ### Supervising an NFS Server Process
 //! ```bash
 //! mfsrun supervisor \
@@ -78,8 +99,10 @@ use anyhow::Result;
 use clap::Parser;
 use monofs::{
     cli::{MfsRuntimeArgs, MfsRuntimeSubcommand},
+    management::{self, Keyring},
     runtime::NfsServerMonitor,
     server::MonofsServer,
+    utils::ENCRYPTION_KEYS_ENV_VAR,
 };
 use monoutils::runtime::Supervisor;
 
@@ -100,15 +123,37 @@ async fn main() -> Result<()> {
             host,
             port,
             store_dir,
+            rev,
+            fs_db_path,
+            mount_dir,
+            ..
         } => {
             // Create and start NFS server
-            let server = MonofsServer::new(store_dir, host, port);
+            let mut server = match (store_dir, rev) {
+                (Some(store_dir), Some(cid)) => MonofsServer::read_only(store_dir, host, port, cid),
+                (Some(store_dir), None) => MonofsServer::new(store_dir, host, port),
+                (None, _) => MonofsServer::in_memory(host, port),
+            };
+            if let (Some(fs_db_path), Some(mount_dir)) = (fs_db_path, mount_dir) {
+                server = server.with_head_tracking(fs_db_path, mount_dir);
+            }
+            if let Ok(keys) = env::var(ENCRYPTION_KEYS_ENV_VAR) {
+                let keyring: Keyring = keys.parse()?;
+                tracing::info!("Encrypting store with key {}", keyring.get_key().get_id());
+                server = server.with_keyring(keyring);
+            }
             tracing::info!(
                 "Starting NFS server on {}:{}",
                 server.get_host(),
                 server.get_port()
             );
-            tracing::info!("Using store at: {}", server.get_store_dir().display());
+            match server.get_store_dir() {
+                Some(store_dir) => tracing::info!("Using store at: {}", store_dir.display()),
+                None => tracing::info!("Using in-memory store"),
+            }
+            if let Some(cid) = server.get_root_cid() {
+                tracing::info!("Serving revision {} in read-only mode", cid);
+            }
 
             server.start().await?;
         }
@@ -120,6 +165,10 @@ async fn main() -> Result<()> {
             store_dir,
             fs_db_path,
             mount_dir,
+            rev,
+            memory,
+            ephemeral,
+            restart,
         } => {
             // Get current executable path
             let child_exe = env::current_exe()?;
@@ -128,18 +177,39 @@ async fn main() -> Result<()> {
             let supervisor_pid = std::process::id();
 
             // Create nfs server monitor
-            let process_monitor =
-                NfsServerMonitor::new(supervisor_pid, fs_db_path, mount_dir, log_dir.clone())
-                    .await?;
+            let process_monitor = NfsServerMonitor::new(
+                supervisor_pid,
+                &fs_db_path,
+                mount_dir.clone(),
+                log_dir.clone(),
+            )
+            .await?;
 
             // Compose child arguments
-            let child_args = vec![
+            let mut child_args = vec![
                 "nfsserver".to_string(),
                 format!("--host={}", host),
                 format!("--port={}", port),
-                format!("--store-dir={}", store_dir.display()),
             ];
 
+            if let Some(store_dir) = &store_dir {
+                child_args.push(format!("--store-dir={}", store_dir.display()));
+            }
+
+            if let Some(cid) = rev {
+                child_args.push(format!("--rev={}", cid));
+            }
+
+            // Have the NFS server track the head of writable, persistent filesystems
+            if store_dir.is_some() && rev.is_none() {
+                child_args.push(format!("--fs-db-path={}", fs_db_path.display()));
+                child_args.push(format!("--mount-dir={}", mount_dir.display()));
+            }
+
+            if memory {
+                child_args.push("--memory".to_string());
+            }
+
             // Compose child environment variables
             let child_envs = vec![("RUST_LOG", "info")];
 
@@ -151,9 +221,17 @@ async fn main() -> Result<()> {
                 child_name,
                 log_dir,
                 process_monitor,
-            );
+            )
+            .with_restart_policy(restart);
+
+            let result = supervisor.start().await;
+
+            // Tear down ephemeral filesystems however the supervisor exits
+            if ephemeral {
+                management::remove_tmp_mfs(&mount_dir).await?;
+            }
 
-            supervisor.start().await?;
+            result?;
         }
     }
 
@@ -1,7 +1,11 @@
+use std::env;
+
 use clap::{CommandFactory, Parser};
 use monofs::{
     cli::{MonofsArgs, MonofsSubcommand},
-    management,
+    management::{self, KeySource, Revision},
+    utils::NEW_PASSPHRASE_ENV_VAR,
+    FsError,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -16,16 +20,154 @@ async fn main() -> anyhow::Result<()> {
     // Parse command line arguments
     let args = MonofsArgs::parse();
     match args.subcommand {
-        Some(MonofsSubcommand::Init { mount_dir }) => {
+        Some(MonofsSubcommand::Init {
+            mount_dir,
+            encrypted: false,
+            ..
+        }) => {
             tracing::info!("initializing monofs...");
             management::init_mfs(mount_dir).await?;
             tracing::info!("successfully initialized monofs");
         }
+        Some(MonofsSubcommand::Init {
+            mount_dir,
+            encrypted: true,
+            keyfile,
+        }) => {
+            let source = keyfile
+                .map(KeySource::Keyfile)
+                .or_else(KeySource::from_env)
+                .ok_or_else(|| {
+                    FsError::EncryptionKeyRequired("set MONOFS_PASSPHRASE or --keyfile".into())
+                })?;
+
+            tracing::info!("initializing encrypted monofs...");
+            management::init_encrypted_mfs(mount_dir, source).await?;
+            tracing::info!("successfully initialized encrypted monofs");
+        }
+        Some(MonofsSubcommand::Tmp { memory }) => {
+            tracing::info!("creating temporary monofs...");
+            let mount_dir = management::init_tmp_mfs(memory).await?;
+            println!("{}", mount_dir.display());
+        }
+        Some(MonofsSubcommand::Mount {
+            rev,
+            mount_dir,
+            source,
+        }) => {
+            tracing::info!("mounting revision {}...", rev);
+            management::mount_rev(rev, mount_dir, source).await?;
+            tracing::info!("successfully mounted revision");
+        }
+        Some(MonofsSubcommand::Clone {
+            source,
+            mount_dir,
+            lazy,
+        }) => {
+            tracing::info!("cloning {}...", source);
+            management::clone_mfs(source, mount_dir, lazy).await?;
+            tracing::info!("successfully cloned monofs");
+        }
+        Some(MonofsSubcommand::Sync { uri, mode, source }) => {
+            tracing::info!("syncing monofs...");
+            let stats = management::sync_mfs(uri, mode, source).await?;
+            tracing::info!(
+                "successfully synced to {} ({} blocks, {} bytes copied)",
+                stats.get_head(),
+                stats.get_blocks_copied(),
+                stats.get_bytes_copied()
+            );
+        }
+        Some(MonofsSubcommand::Merge { uri, resolve, path }) => {
+            tracing::info!("merging monofs...");
+            let outcome = management::merge_mfs(uri, resolve, path).await?;
+            for (conflict, resolution) in outcome.get_conflicts() {
+                println!(
+                    "{}: {}, resolved with {}",
+                    conflict.get_path(),
+                    conflict.get_kind(),
+                    resolution
+                );
+            }
+            tracing::info!(
+                "successfully merged to {} ({} conflicts)",
+                outcome.get_head(),
+                outcome.get_conflicts().len()
+            );
+        }
+        Some(MonofsSubcommand::Serve { path, host, port }) => {
+            management::serve_mfs(path, &host, port).await?;
+        }
+        Some(MonofsSubcommand::Raft { id, members, path }) => {
+            management::configure_raft_mfs(path, id, &members).await?;
+            tracing::info!(
+                "successfully configured raft node {}, remount to join the group",
+                id
+            );
+        }
         Some(MonofsSubcommand::Detach { mount_dir, force }) => {
             tracing::info!("detaching monofs...");
             management::detach_mfs(mount_dir, force).await?;
             tracing::info!("successfully detached monofs");
         }
+        Some(MonofsSubcommand::RotateKey {
+            mount_dir,
+            keyfile,
+            new_keyfile,
+        }) => {
+            let old_source = keyfile
+                .map(KeySource::Keyfile)
+                .or_else(KeySource::from_env)
+                .ok_or_else(|| {
+                    FsError::EncryptionKeyRequired("set MONOFS_PASSPHRASE or --keyfile".into())
+                })?;
+            let new_source = new_keyfile
+                .map(KeySource::Keyfile)
+                .or_else(|| {
+                    env::var(NEW_PASSPHRASE_ENV_VAR)
+                        .ok()
+                        .map(KeySource::Passphrase)
+                })
+                .ok_or_else(|| {
+                    FsError::EncryptionKeyRequired(
+                        "set MONOFS_NEW_PASSPHRASE or --new-keyfile".into(),
+                    )
+                })?;
+
+            tracing::info!("rotating encryption key...");
+            let head = management::rotate_key_mfs(mount_dir, old_source, new_source).await?;
+            match head {
+                Some(cid) => tracing::info!("successfully rotated key, head is now {}", cid),
+                None => tracing::info!("successfully rotated key"),
+            }
+        }
+        Some(MonofsSubcommand::Rev { path: None }) => {
+            for revision in management::get_revisions(None).await? {
+                print_revision(&revision);
+            }
+        }
+        Some(MonofsSubcommand::Verify { path }) => {
+            let revisions = management::get_revisions(path).await?;
+            let flagged = revisions
+                .iter()
+                .filter(|revision| revision.get_status().is_flagged())
+                .inspect(|revision| print_revision(revision))
+                .count();
+            if flagged > 0 {
+                return Err(FsError::UnverifiedRevisions(flagged).into());
+            }
+
+            tracing::info!("successfully verified {} revisions", revisions.len());
+        }
+        Some(MonofsSubcommand::Sign {
+            keyfile,
+            generate,
+            source,
+        }) => {
+            let did = management::configure_signer_mfs(source, keyfile, generate).await?;
+            println!("{}", did);
+            tracing::info!("new revisions will be signed by {}, remount to apply", did);
+        }
         Some(_) => (), // TODO: implement other subcommands
         None => {
             MonofsArgs::command().print_help()?;
@@ -38,3 +180,18 @@ async fn main() -> anyhow::Result<()> {
 //--------------------------------------------------------------------------------------------------
 // Functions: *
 //--------------------------------------------------------------------------------------------------
+
+/// Prints a revision with its verification status and signer
+fn print_revision(revision: &Revision) {
+    let signer = revision
+        .get_signer()
+        .as_ref()
+        .map(|did| did.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    println!(
+        "{}  {}  {}",
+        revision.get_cid(),
+        revision.get_status(),
+        signer
+    );
+}
@@ -57,13 +57,13 @@ async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     // Create and start the server
+    tracing::info!("Using store at: {}", args.store_dir.display());
     let server = MonofsServer::new(args.store_dir, args.host, args.port);
     tracing::info!(
         "Starting NFS server on {}:{}",
         server.get_host(),
         server.get_port()
     );
-    tracing::info!("Using store at: {}", server.get_store_dir().display());
 
     server.start().await?;
     Ok(())
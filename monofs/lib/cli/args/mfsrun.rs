@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
+use ipldstore::ipld::cid::Cid;
+use monoutils::RestartPolicy;
 
 use crate::{
     cli::styles,
@@ -34,8 +36,24 @@ pub enum MfsRuntimeSubcommand {
         port: u32,
 
         /// The directory to store the filesystem data
+        #[arg(long, required_unless_present = "memory")]
+        store_dir: Option<PathBuf>,
+
+        /// CID of a directory revision to serve in read-only mode
         #[arg(long)]
-        store_dir: PathBuf,
+        rev: Option<Cid>,
+
+        /// Keep the filesystem data in memory instead of a store directory
+        #[arg(long, conflicts_with_all = ["store_dir", "rev"])]
+        memory: bool,
+
+        /// Path to the filesystem database the head of the filesystem is tracked in
+        #[arg(long, requires = "mount_dir", conflicts_with_all = ["rev", "memory"])]
+        fs_db_path: Option<PathBuf>,
+
+        /// Directory the filesystem is mounted at, used to look up its head
+        #[arg(long, requires = "fs_db_path")]
+        mount_dir: Option<PathBuf>,
     },
     /// Run as supervisor
     Supervisor {
@@ -56,8 +74,8 @@ pub enum MfsRuntimeSubcommand {
         port: u32,
 
         /// The directory to store the filesystem data
-        #[arg(long)]
-        store_dir: PathBuf,
+        #[arg(long, required_unless_present = "memory")]
+        store_dir: Option<PathBuf>,
 
         /// Path to the filesystem metrics and metadata database file
         #[arg(long)]
@@ -66,5 +84,22 @@ pub enum MfsRuntimeSubcommand {
         /// Directory where the filesystem is mounted
         #[arg(long)]
         mount_dir: PathBuf,
+
+        /// CID of a directory revision to serve in read-only mode
+        #[arg(long)]
+        rev: Option<Cid>,
+
+        /// Keep the filesystem data in memory instead of a store directory
+        #[arg(long, conflicts_with_all = ["store_dir", "rev"])]
+        memory: bool,
+
+        /// Remove the mount point and all filesystem data, blocks included, on exit
+        #[arg(long)]
+        ephemeral: bool,
+
+        /// When to restart the NFS server after it exits (never, always, on-failure or
+        /// on-failure:<max retries>)
+        #[arg(long, default_value = "on-failure")]
+        restart: RestartPolicy,
     },
 }
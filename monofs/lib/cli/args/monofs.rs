@@ -1,7 +1,13 @@
 use std::path::PathBuf;
 
-use crate::cli::styles;
+use crate::{
+    cli::styles,
+    config::{RaftMember, DEFAULT_HOST, DEFAULT_SYNC_PORT},
+    filesystem::ConflictResolution,
+    sync::SyncMode,
+};
 use clap::Parser;
+use monoutils_did::KeyType;
 use typed_path::Utf8UnixPathBuf;
 
 //-------------------------------------------------------------------------------------------------
@@ -33,17 +39,52 @@ pub enum MonofsSubcommand {
     Init {
         /// Directory where the filesystem will be mounted
         mount_dir: Option<PathBuf>,
+
+        /// Encrypt the filesystem with a key derived from the `MONOFS_PASSPHRASE` environment
+        /// variable, or from `--keyfile`
+        #[arg(short = 'e', long)]
+        encrypted: bool,
+
+        /// File to derive the encryption key from
+        #[arg(short = 'k', long, requires = "encrypted")]
+        keyfile: Option<PathBuf>,
     },
 
-    /// Create a temporary filesystem
+    /// Mount a revision of a filesystem in read-only mode
+    #[command(name = "mount")]
+    Mount {
+        /// CID or tag of the revision to mount
+        #[arg(short = 'r', long)]
+        rev: String,
+
+        /// Directory where the revision will be mounted
+        mount_dir: PathBuf,
+
+        /// Path within the filesystem the revision belongs to. Defaults to the current directory
+        #[arg(short = 's', long)]
+        source: Option<PathBuf>,
+    },
+
+    /// Create a temporary filesystem that is removed when detached
     #[command(name = "tmp")]
-    Tmp,
+    Tmp {
+        /// Keep the filesystem in memory instead of a temporary store directory
+        #[arg(short = 'm', long)]
+        memory: bool,
+    },
 
     /// Clone an existing filesystem
     #[command(name = "clone")]
     Clone {
-        /// Remote or local path to clone from
-        uri: String,
+        /// URL of a served filesystem or local path within a filesystem to clone from
+        source: String,
+
+        /// Directory where the clone will be mounted
+        mount_dir: PathBuf,
+
+        /// Fetch blocks from the source on first read instead of copying them all up front
+        #[arg(long)]
+        lazy: bool,
     },
 
     /// Sync a filesystem with another filesystem
@@ -52,9 +93,57 @@ pub enum MonofsSubcommand {
         /// Remote or local path to sync with
         uri: String,
 
-        /// Type of sync (e.g. backup, raft, crdt)
-        #[arg(short = 't', long)]
-        r#type: String,
+        /// Mode of sync (backup or crdt). Raft replication is set up with `monofs raft`
+        #[arg(short = 't', long = "type")]
+        mode: SyncMode,
+
+        /// Path within the filesystem to sync. Defaults to the current directory
+        #[arg(short = 's', long)]
+        source: Option<PathBuf>,
+    },
+
+    /// Merge the head of another filesystem into a filesystem
+    #[command(name = "merge")]
+    Merge {
+        /// Remote or local path of the filesystem to merge from
+        uri: String,
+
+        /// How to resolve conflicting changes (ours, theirs, keep-both)
+        #[arg(short = 'r', long, default_value_t = ConflictResolution::Ours)]
+        resolve: ConflictResolution,
+
+        /// Path within the filesystem to merge into. Defaults to the current directory
+        path: Option<PathBuf>,
+    },
+
+    /// Serve a filesystem to remote peers so they can sync with it
+    #[command(name = "serve")]
+    Serve {
+        /// Path within the filesystem to serve. Defaults to the current directory
+        path: Option<PathBuf>,
+
+        /// Host address to bind to
+        #[arg(long, default_value = DEFAULT_HOST)]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short = 'p', long, default_value_t = DEFAULT_SYNC_PORT)]
+        port: u32,
+    },
+
+    /// Replicate the head of a filesystem with a Raft group, from the next time it is mounted
+    #[command(name = "raft")]
+    Raft {
+        /// ID of this filesystem's node in the group
+        #[arg(long)]
+        id: u64,
+
+        /// Members of the group as `<id>=<url>`, this node included
+        #[arg(short = 'm', long = "member", required = true)]
+        members: Vec<RaftMember>,
+
+        /// Path within the filesystem to replicate. Defaults to the current directory
+        path: Option<PathBuf>,
     },
 
     /// Show the revisions of a filesystem
@@ -65,6 +154,28 @@ pub enum MonofsSubcommand {
         path: Option<Utf8UnixPathBuf>,
     },
 
+    /// Verify every revision of a filesystem, flagging unsigned and tampered ones
+    #[command(name = "verify")]
+    Verify {
+        /// Path within the filesystem to verify. Defaults to the current directory
+        path: Option<PathBuf>,
+    },
+
+    /// Sign new revisions of a filesystem with a DID key
+    #[command(name = "sign")]
+    Sign {
+        /// File holding the multibase encoded private key to sign with
+        keyfile: PathBuf,
+
+        /// Generate a new key of the given type (ed25519, p256) into the keyfile
+        #[arg(short = 'g', long)]
+        generate: Option<KeyType>,
+
+        /// Path within the filesystem. Defaults to the current directory
+        #[arg(short = 's', long)]
+        source: Option<PathBuf>,
+    },
+
     /// Tag a revision of a file entity
     #[command(name = "tag")]
     Tag {
@@ -120,6 +231,23 @@ pub enum MonofsSubcommand {
         force: bool,
     },
 
+    /// Rotate the encryption key of a detached encrypted filesystem
+    #[command(name = "rotate-key")]
+    RotateKey {
+        /// Directory where the filesystem is mounted
+        mount_dir: PathBuf,
+
+        /// File the current key is derived from. Defaults to the `MONOFS_KEYFILE` or
+        /// `MONOFS_PASSPHRASE` environment variable
+        #[arg(short = 'k', long)]
+        keyfile: Option<PathBuf>,
+
+        /// File to derive the new key from. Defaults to the `MONOFS_NEW_PASSPHRASE` environment
+        /// variable
+        #[arg(short = 'n', long)]
+        new_keyfile: Option<PathBuf>,
+    },
+
     /// Show version information
     #[command(name = "version")]
     Version,
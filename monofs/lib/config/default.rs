@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 //--------------------------------------------------------------------------------------------------
 // Constants
 //--------------------------------------------------------------------------------------------------
@@ -11,5 +13,21 @@ pub const DEFAULT_HOST: &str = "127.0.0.1";
 /// The default NFS port number to use.
 pub const DEFAULT_NFS_PORT: u32 = 2049;
 
+/// The default port to serve a filesystem to remote sync peers on.
+pub const DEFAULT_SYNC_PORT: u32 = 3049;
+
 /// The default path for the mfsrun binary.
 pub const DEFAULT_MFSRUN_BIN_PATH: &str = "./mfsrun";
+
+/// How often a mounted filesystem checkpoints its changes and moves its head.
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the leader of a Raft group sends heartbeats to its followers.
+pub const DEFAULT_RAFT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a follower waits without hearing from a leader before it starts an election. The
+/// actual timeout is randomized between this and twice this, so that elections rarely split.
+pub const DEFAULT_RAFT_ELECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long a Raft leader waits for a proposed head to be committed.
+pub const DEFAULT_RAFT_COMMIT_TIMEOUT: Duration = Duration::from_secs(10);
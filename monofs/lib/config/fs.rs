@@ -0,0 +1,169 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use getset::{Getters, Setters};
+use serde::{Deserialize, Serialize};
+
+use crate::FsError;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The configuration of a filesystem, stored in the `config` column of its entry in the
+/// filesystem database.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[getset(get = "pub with_prefix")]
+pub struct FsConfig {
+    /// The filesystem this one was cloned from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<Origin>,
+
+    /// The ID of this filesystem's node in the Raft group its head is replicated with, if any.
+    /// The members of the group are stored in the `raft_members` table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[getset(set = "pub")]
+    raft_node_id: Option<u64>,
+
+    /// How the blocks of this filesystem are encrypted, if they are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[getset(set = "pub")]
+    encryption: Option<Encryption>,
+
+    /// The file holding the multibase private key new revisions are signed with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[getset(set = "pub")]
+    signing_key: Option<PathBuf>,
+}
+
+/// The filesystem a clone was made from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct Origin {
+    /// The URL of the origin peer, or the absolute path of the origin filesystem.
+    uri: String,
+
+    /// Whether blocks are fetched from the origin on first read instead of at clone time.
+    #[serde(default)]
+    lazy: bool,
+}
+
+/// How the blocks of an encrypted filesystem are encrypted.
+///
+/// The key itself is never stored. It is derived from a passphrase or keyfile and the salt, and
+/// checked against the key ID. Keys retired by rotation are kept, encrypted with the current key,
+/// so blocks written before a rotation can still be read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct Encryption {
+    /// The hex encoded salt the key is derived with.
+    salt: String,
+
+    /// The ID of the current key.
+    key_id: String,
+
+    /// The hex encoded retired keys, each encrypted with the current key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    retired_keys: Vec<String>,
+}
+
+/// A member of the Raft group the head of a filesystem is replicated with.
+///
+/// Members are written as `<id>=<url>` on the command line, e.g. `1=http://10.0.0.1:3050`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RaftMember {
+    /// The ID of the member, unique within the group.
+    id: u64,
+
+    /// The base URL the member serves Raft and block requests on.
+    url: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl FsConfig {
+    /// Creates a new filesystem configuration.
+    pub fn new(origin: Option<Origin>) -> Self {
+        Self {
+            origin,
+            raft_node_id: None,
+            encryption: None,
+            signing_key: None,
+        }
+    }
+}
+
+impl Encryption {
+    /// Creates a new encryption configuration.
+    ///
+    /// ## Arguments
+    /// * `salt` - The hex encoded salt the key is derived with
+    /// * `key_id` - The ID of the key
+    /// * `retired_keys` - The hex encoded retired keys, each encrypted with the key
+    pub fn new(
+        salt: impl Into<String>,
+        key_id: impl Into<String>,
+        retired_keys: Vec<String>,
+    ) -> Self {
+        Self {
+            salt: salt.into(),
+            key_id: key_id.into(),
+            retired_keys,
+        }
+    }
+}
+
+impl Origin {
+    /// Creates a new origin.
+    ///
+    /// ## Arguments
+    /// * `uri` - The URL of the origin peer, or the absolute path of the origin filesystem
+    /// * `lazy` - Whether blocks are fetched from the origin on first read
+    pub fn new(uri: impl Into<String>, lazy: bool) -> Self {
+        Self {
+            uri: uri.into(),
+            lazy,
+        }
+    }
+}
+
+impl RaftMember {
+    /// Creates a new Raft group member.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of the member, unique within the group
+    /// * `url` - The base URL the member serves Raft and block requests on
+    pub fn new(id: u64, url: impl Into<String>) -> Self {
+        Self {
+            id,
+            url: url.into().trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for RaftMember {
+    type Err = FsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, url) = s
+            .split_once('=')
+            .ok_or_else(|| FsError::InvalidOperation(format!("expected <id>=<url>: {}", s)))?;
+        let id = id
+            .parse()
+            .map_err(|_| FsError::InvalidOperation(format!("invalid raft member id: {}", id)))?;
+
+        Ok(Self::new(id, url))
+    }
+}
+
+impl fmt::Display for RaftMember {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.id, self.url)
+    }
+}
@@ -1,9 +1,11 @@
 //! Configuration types and helpers.
 
 mod default;
+mod fs;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
 pub use default::*;
+pub use fs::*;
@@ -49,9 +49,10 @@ pub enum FsError {
     #[error(transparent)]
     Custom(#[from] AnyError),
 
-    // /// DID related error.
-    // #[error("DID error: {0}")]
-    // Did(#[from] monoutils_did_wk::DidError),
+    /// DID related error.
+    #[error("DID error: {0}")]
+    Did(#[from] monoutils_did::DidError),
+
     /// IPLD Store error.
     #[error("IPLD Store error: {0}")]
     IpldStore(#[from] ipldstore::StoreError),
@@ -185,6 +186,71 @@ pub enum FsError {
     #[error("No MFS root found in path hierarchy starting from {0}")]
     NoMfsRootFound(String),
 
+    /// Revision not found
+    #[error("Revision not found: {0}")]
+    RevisionNotFound(String),
+
+    /// The head of a filesystem was moved by someone else. Holds the head that was found instead
+    #[error("Head conflict: head was moved to {0:?}")]
+    HeadConflict(Option<Cid>),
+
+    /// The filesystem has no head revision yet
+    #[error("Filesystem has no head revision: {0}")]
+    NoHead(String),
+
+    /// Unsupported sync mode
+    #[error("Unsupported sync mode: {0}")]
+    UnsupportedSyncMode(String),
+
+    /// Unsupported merge conflict resolution
+    #[error("Unsupported conflict resolution: {0}")]
+    UnsupportedConflictResolution(String),
+
+    /// An error returned by a remote peer
+    #[error("Remote peer error: {0}")]
+    RemotePeer(String),
+
+    /// A write was sent to a Raft node that is not the leader of its group. Holds the URL of the
+    /// leader if it is known
+    #[error("Not the Raft leader, the leader is {0:?}")]
+    NotLeader(Option<String>),
+
+    /// An error in the Raft replication of a head
+    #[error("Raft error: {0}")]
+    Raft(String),
+
+    /// The filesystem is encrypted and no key was given to unlock it
+    #[error("Encryption key required to unlock {0}")]
+    EncryptionKeyRequired(String),
+
+    /// The key given to unlock an encrypted filesystem is not its key
+    #[error("Invalid encryption key: {0}")]
+    InvalidEncryptionKey(String),
+
+    /// The operation is not supported on encrypted filesystems
+    #[error("Operation not supported on encrypted filesystem: {0}")]
+    EncryptedFilesystem(String),
+
+    /// An error that occurred when encrypting or decrypting data
+    #[error("Encryption error: {0}")]
+    Crypt(#[from] monoutils_cryptdag::CryptError),
+
+    /// Revisions of a filesystem are unsigned or were tampered with. Holds how many
+    #[error("{0} revisions failed verification")]
+    UnverifiedRevisions(usize),
+
+    /// The signature stored in a revision can't be decoded
+    #[error("Malformed revision signature")]
+    MalformedRevisionSignature,
+
+    /// HTTP error
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// JSON error
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
     /// An error that occurred when a migration error occurred
     #[error("migration error: {0}")]
     MigrationError(#[from] sqlx::migrate::MigrateError),
@@ -10,13 +10,17 @@ use std::{
 };
 
 use chrono::Utc;
-use ipldstore::{ipld::cid::Cid, IpldReferences, IpldStore, Storable, StoreError, StoreResult};
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld, serde as ipld_serde},
+    IpldReferences, IpldStore, Storable, StoreError, StoreResult,
+};
+use monoutils_did::KeyPair;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     filesystem::{
-        kind::EntityType, Entity, EntityCidLink, File, Link, Metadata, MetadataSerializable,
-        SymCidLink,
+        kind::EntityType, sign_fields, Entity, EntityCidLink, File, Link, Metadata,
+        MetadataSerializable, SymCidLink,
     },
     FsError, FsResult,
 };
@@ -194,6 +198,54 @@ where
         Ok(cid)
     }
 
+    /// Creates a checkpoint of the current directory state and signs the new revision with the
+    /// given key.
+    ///
+    /// The signature is stored in the revision, covering its CID without the signature and the
+    /// CID of the revision it was made on top of. See [`RevisionSignature`].
+    ///
+    /// [`RevisionSignature`]: crate::filesystem::RevisionSignature
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use monofs::filesystem::{self, Dir};
+    /// use monoutils_did::{KeyPair, KeyType};
+    /// use ipldstore::MemoryStore;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let keypair = KeyPair::generate(KeyType::Ed25519);
+    /// let mut dir = Dir::new(MemoryStore::default());
+    /// let first = dir.checkpoint().await?;
+    ///
+    /// let cid = dir.checkpoint_signed(&keypair).await?;
+    /// assert_eq!(dir.get_initial_load_cid(), Some(&cid));
+    ///
+    /// let signature = filesystem::get_revision_signature(dir.get_store(), &cid).await?.unwrap();
+    /// assert_eq!(signature.get_previous(), &Some(first));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn checkpoint_signed(&mut self, keypair: &KeyPair) -> StoreResult<Cid>
+    where
+        S: Send + Sync,
+    {
+        let serializable = self.get_serializable().await.map_err(StoreError::custom)?;
+        let Ipld::Map(mut fields) =
+            ipld_serde::to_ipld(&serializable).map_err(StoreError::custom)?
+        else {
+            unreachable!("directories serialize to maps");
+        };
+        sign_fields(keypair, &mut fields).map_err(StoreError::custom)?;
+
+        let store = self.inner.store.clone();
+        let cid = store.put_node(&Ipld::Map(fields)).await?;
+        let loaded = Self::load(&cid, store).await?;
+        self.inner = loaded.inner;
+        Ok(cid)
+    }
+
     /// Returns the CID of the previous version of the directory if there is one.
     ///
     /// ## Examples
@@ -0,0 +1,122 @@
+use std::fmt;
+
+use chrono::Utc;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A [hybrid logical clock][hlc] timestamp.
+///
+/// Timestamps are ordered by wall-clock time first, then by the logical counter that breaks ties
+/// between events in the same millisecond, then by the node that issued them. No two nodes issue
+/// the same timestamp, so the order is total and every replica picks the same last writer.
+///
+/// [hlc]: https://cse.buffalo.edu/tech-reports/2014-04.pdf
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Getters,
+)]
+#[getset(get = "pub with_prefix")]
+pub struct Hlc {
+    /// Milliseconds since the Unix epoch.
+    time: u64,
+
+    /// Orders events with the same wall-clock time.
+    counter: u32,
+
+    /// The node that issued the timestamp.
+    node: u64,
+}
+
+/// A hybrid logical clock that issues [`Hlc`] timestamps for one node.
+///
+/// Timestamps issued by a clock always increase, even if the wall clock goes backwards, and stay
+/// ahead of every timestamp the clock has [observed][HybridClock::observe] from other nodes.
+///
+/// ## Examples
+///
+/// ```
+/// use monofs::filesystem::HybridClock;
+///
+/// let mut clock = HybridClock::new(1);
+/// let first = clock.tick();
+/// let second = clock.tick();
+/// assert!(second > first);
+///
+/// // Timestamps from other nodes push the clock forward
+/// let mut other = HybridClock::new(2);
+/// other.observe(&second);
+/// assert!(other.tick() > second);
+/// ```
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct HybridClock {
+    /// The node the clock issues timestamps for.
+    node: u64,
+
+    /// The last timestamp issued or observed.
+    last: Hlc,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl Hlc {
+    /// Creates a new timestamp.
+    pub fn new(time: u64, counter: u32, node: u64) -> Self {
+        Self {
+            time,
+            counter,
+            node,
+        }
+    }
+}
+
+impl HybridClock {
+    /// Creates a new clock for the given node.
+    pub fn new(node: u64) -> Self {
+        Self {
+            node,
+            last: Hlc::new(0, 0, node),
+        }
+    }
+
+    /// Issues a timestamp greater than every timestamp issued or observed so far.
+    pub fn tick(&mut self) -> Hlc {
+        let now = Self::wall_time();
+        self.last = if now > self.last.time {
+            Hlc::new(now, 0, self.node)
+        } else {
+            Hlc::new(self.last.time, self.last.counter + 1, self.node)
+        };
+
+        self.last
+    }
+
+    /// Moves the clock past a timestamp received from another node.
+    pub fn observe(&mut self, remote: &Hlc) {
+        if remote.time > self.last.time
+            || (remote.time == self.last.time && remote.counter > self.last.counter)
+        {
+            self.last = Hlc::new(remote.time, remote.counter, self.node);
+        }
+    }
+
+    /// Returns the current wall-clock time in milliseconds since the Unix epoch.
+    fn wall_time() -> u64 {
+        Utc::now().timestamp_millis().max(0) as u64
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}@{}", self.time, self.counter, self.node)
+    }
+}
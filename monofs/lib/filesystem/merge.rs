@@ -0,0 +1,742 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+};
+
+use async_recursion::async_recursion;
+use getset::Getters;
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld, serde as ipld_serde},
+    IpldStore,
+};
+
+use crate::{
+    filesystem::{
+        get_dir_entries, get_parents, is_ancestor, is_dir_fields, load_fields, Entries,
+        SIGNATURE_FIELD,
+    },
+    FsError, FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The suffix our version of a conflicting entry is renamed with when both versions are kept.
+pub const OURS_SUFFIX: &str = "~ours";
+
+/// The suffix their version of a conflicting entry is renamed with when both versions are kept.
+pub const THEIRS_SUFFIX: &str = "~theirs";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// How a conflict between two versions of an entry is resolved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Keep our version of the entry, or its removal.
+    #[default]
+    Ours,
+
+    /// Keep their version of the entry, or its removal.
+    Theirs,
+
+    /// Keep both versions, renamed with [`OURS_SUFFIX`] and [`THEIRS_SUFFIX`]. If one side
+    /// removed the entry, the version that is left is kept under its own name.
+    KeepBoth,
+}
+
+/// The way both sides of a merge changed the same entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides changed the entry differently.
+    BothModified,
+
+    /// Both sides added a different entry under the same name.
+    BothAdded,
+
+    /// We changed the entry and they removed it.
+    ModifiedDeleted,
+
+    /// We removed the entry and they changed it.
+    DeletedModified,
+}
+
+/// An entry that was changed on both sides of a merge in ways that cannot be merged
+/// automatically.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct MergeConflict {
+    /// The path of the entry, relative to the root of the merged directories.
+    path: String,
+
+    /// The way both sides changed the entry.
+    kind: ConflictKind,
+
+    /// The version of the entry in the common ancestor, if it had one.
+    base: Option<Cid>,
+
+    /// Our version of the entry, or None if we removed it.
+    ours: Option<Cid>,
+
+    /// Their version of the entry, or None if they removed it.
+    theirs: Option<Cid>,
+}
+
+/// The result of a [`merge`].
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct MergeOutcome {
+    /// The merged directory.
+    head: Cid,
+
+    /// The common ancestor the merge was based on, if there is one.
+    base: Option<Cid>,
+
+    /// The conflicts that were found, along with how they were resolved.
+    conflicts: Vec<(MergeConflict, ConflictResolution)>,
+}
+
+/// Decides how conflicts found by a [`merge`] are resolved.
+///
+/// A [`ConflictResolution`] resolves every conflict the same way, and closures taking a
+/// [`MergeConflict`] can pick a resolution per conflict.
+pub trait ConflictResolver: Send + Sync {
+    /// Returns how `conflict` is resolved.
+    fn resolve(&self, conflict: &MergeConflict) -> ConflictResolution;
+}
+
+/// The state shared by the steps of a merge.
+struct MergeContext<'a, S> {
+    /// The store holding both versions.
+    store: &'a S,
+
+    /// The resolver conflicts are handed to.
+    resolver: &'a dyn ConflictResolver,
+
+    /// The conflicts found so far.
+    conflicts: Vec<(MergeConflict, ConflictResolution)>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl MergeOutcome {
+    /// Returns true if the merge made a new version of the directory, rather than returning
+    /// `ours` or `theirs` as is.
+    pub fn is_new(&self, ours: &Cid, theirs: &Cid) -> bool {
+        self.head != *ours && self.head != *theirs
+    }
+
+    /// Replaces the merged directory, e.g. with a signed version of it.
+    pub(crate) fn set_head(&mut self, head: Cid) {
+        self.head = head;
+    }
+}
+
+impl MergeConflict {
+    /// Creates a new conflict.
+    fn new(
+        path: String,
+        kind: ConflictKind,
+        base: Option<Cid>,
+        ours: Option<Cid>,
+        theirs: Option<Cid>,
+    ) -> Self {
+        Self {
+            path,
+            kind,
+            base,
+            ours,
+            theirs,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Merges two divergent versions of a directory, `ours` and `theirs`, into one.
+///
+/// The common ancestor of both versions is found through their `previous` and `merged` links, and
+/// every entry is compared against it:
+///
+/// - Entries changed on one side only take that side's version.
+/// - Directories changed on both sides are merged recursively, with the ancestor's version of the
+///   directory as their base.
+/// - Entities changed on both sides where one version descends from the other take the newer one.
+/// - Anything else is a [`MergeConflict`], which `resolver` decides the outcome of.
+///
+/// Without a common ancestor, every entry both sides have differently is a conflict.
+///
+/// The merged directory keeps our metadata and follows `ours` in the version history, with a
+/// `merged` link to `theirs`, so later merges find a common ancestor at least as recent as
+/// `theirs`. If one version is an ancestor of the other, the newer one is returned as is.
+///
+/// ## Arguments
+/// * `store` - A store holding every block reachable from both versions
+/// * `ours` - Our version of the directory
+/// * `theirs` - Their version of the directory
+/// * `resolver` - Decides how conflicts are resolved
+///
+/// ## Examples
+///
+/// ```
+/// use ipldstore::MemoryStore;
+/// use monofs::filesystem::{self, ConflictResolution, Dir, File};
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let store = MemoryStore::default();
+/// let mut base = Dir::new(store.clone());
+/// let base_cid = base.checkpoint().await?;
+///
+/// // Both sides add a different file
+/// let mut ours = base.clone();
+/// ours.put_adapted_file("a.txt", File::new(store.clone())).await?;
+/// let ours_cid = ours.checkpoint().await?;
+///
+/// let mut theirs = base.clone();
+/// theirs.put_adapted_file("b.txt", File::new(store.clone())).await?;
+/// let theirs_cid = theirs.checkpoint().await?;
+///
+/// let outcome = filesystem::merge(&store, &ours_cid, &theirs_cid, &ConflictResolution::Ours).await?;
+/// assert_eq!(outcome.get_base(), &Some(base_cid));
+/// assert!(outcome.get_conflicts().is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn merge<S>(
+    store: &S,
+    ours: &Cid,
+    theirs: &Cid,
+    resolver: &impl ConflictResolver,
+) -> FsResult<MergeOutcome>
+where
+    S: IpldStore + Send + Sync,
+{
+    let base = find_common_ancestor(store, ours, theirs).await?;
+    let mut cx = MergeContext {
+        store,
+        resolver,
+        conflicts: Vec::new(),
+    };
+
+    let head = if base == Some(*theirs) {
+        *ours
+    } else if base == Some(*ours) {
+        *theirs
+    } else {
+        merge_dirs(&mut cx, "", base, *ours, *theirs).await?
+    };
+
+    Ok(MergeOutcome {
+        head,
+        base,
+        conflicts: cx.conflicts,
+    })
+}
+
+/// Finds the latest version both `a` and `b` descend from through their `previous` and `merged`
+/// links.
+///
+/// A version counts as its own ancestor, so if one version descends from the other, the older one
+/// is returned. Versions whose blocks are not in the store end a history.
+///
+/// ## Arguments
+/// * `store` - The store holding both versions
+/// * `a` - The first version
+/// * `b` - The second version
+pub async fn find_common_ancestor<S>(store: &S, a: &Cid, b: &Cid) -> FsResult<Option<Cid>>
+where
+    S: IpldStore + Send + Sync,
+{
+    let ancestors = get_history(store, a)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    Ok(get_history(store, b)
+        .await?
+        .into_iter()
+        .find(|cid| ancestors.contains(cid)))
+}
+
+/// Merges two versions of a directory against their common ancestor.
+#[async_recursion]
+async fn merge_dirs<S>(
+    cx: &mut MergeContext<'_, S>,
+    path: &str,
+    base: Option<Cid>,
+    ours: Cid,
+    theirs: Cid,
+) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    let mut fields = load_dir(cx.store, &ours, path).await?;
+    let ours_entries = get_dir_entries(&fields)?;
+    let theirs_entries = get_dir_entries(&load_dir(cx.store, &theirs, path).await?)?;
+    let base_entries = match base {
+        Some(base) => match load_dir(cx.store, &base, path).await {
+            Ok(fields) => get_dir_entries(&fields)?,
+            Err(FsError::NotADirectory(_)) => Entries::new(),
+            Err(e) => return Err(e),
+        },
+        None => Entries::new(),
+    };
+
+    let (b, o, t) = (
+        live(&base_entries),
+        live(&ours_entries),
+        live(&theirs_entries),
+    );
+
+    let mut entries = ours_entries.clone();
+    let names = b.keys().chain(o.keys()).chain(t.keys()).cloned();
+    for name in names.collect::<BTreeSet<_>>() {
+        let (bc, oc, tc) = (b.get(&name), o.get(&name), t.get(&name));
+        if oc == tc || tc == bc {
+            continue;
+        }
+
+        if oc == bc {
+            set_entry(&mut entries, &name, tc.copied());
+            continue;
+        }
+
+        let child = match path {
+            "" => name.clone(),
+            _ => format!("{}/{}", path, name),
+        };
+
+        let (Some(&oc), Some(&tc)) = (oc, tc) else {
+            let kind = match oc {
+                Some(_) => ConflictKind::ModifiedDeleted,
+                None => ConflictKind::DeletedModified,
+            };
+
+            let conflict = MergeConflict::new(child, kind, bc.copied(), oc.copied(), tc.copied());
+            resolve(cx, &mut entries, &o, &t, &name, conflict);
+            continue;
+        };
+
+        if is_ancestor(cx.store, &oc, &tc).await? {
+            set_entry(&mut entries, &name, Some(tc));
+            continue;
+        }
+
+        if is_ancestor(cx.store, &tc, &oc).await? {
+            continue;
+        }
+
+        if is_dir(cx.store, &oc).await? && is_dir(cx.store, &tc).await? {
+            let merged = merge_dirs(cx, &child, bc.copied(), oc, tc).await?;
+            set_entry(&mut entries, &name, Some(merged));
+            continue;
+        }
+
+        let kind = match bc {
+            Some(_) => ConflictKind::BothModified,
+            None => ConflictKind::BothAdded,
+        };
+
+        let conflict = MergeConflict::new(child, kind, bc.copied(), Some(oc), Some(tc));
+        resolve(cx, &mut entries, &o, &t, &name, conflict);
+    }
+
+    if entries == ours_entries {
+        return Ok(ours);
+    }
+
+    fields.insert(
+        "entries".to_string(),
+        ipld_serde::to_ipld(&entries).map_err(FsError::custom)?,
+    );
+    fields.insert("previous".to_string(), Ipld::Link(ours));
+    fields.insert("merged".to_string(), Ipld::Link(theirs));
+
+    // The signature of our version doesn't cover the merged one
+    fields.remove(SIGNATURE_FIELD);
+
+    Ok(cx.store.put_node(&Ipld::Map(fields)).await?)
+}
+
+/// Hands a conflict to the resolver and applies its resolution to the merged entries.
+fn resolve<S>(
+    cx: &mut MergeContext<'_, S>,
+    entries: &mut Entries,
+    ours: &BTreeMap<String, Cid>,
+    theirs: &BTreeMap<String, Cid>,
+    name: &str,
+    conflict: MergeConflict,
+) {
+    let resolution = cx.resolver.resolve(&conflict);
+    tracing::debug!(
+        "resolving {} conflict on {} with {}",
+        conflict.kind,
+        conflict.path,
+        resolution
+    );
+
+    match (resolution, conflict.ours, conflict.theirs) {
+        (ConflictResolution::Ours, version, _) | (ConflictResolution::Theirs, _, version) => {
+            set_entry(entries, name, version)
+        }
+        (ConflictResolution::KeepBoth, Some(ours_cid), Some(theirs_cid)) => {
+            set_entry(entries, name, None);
+            for (suffix, cid) in [(OURS_SUFFIX, ours_cid), (THEIRS_SUFFIX, theirs_cid)] {
+                let name = get_unused_name(entries, ours, theirs, &format!("{}{}", name, suffix));
+                set_entry(entries, &name, Some(cid));
+            }
+        }
+        (ConflictResolution::KeepBoth, version, None)
+        | (ConflictResolution::KeepBoth, None, version) => set_entry(entries, name, version),
+    }
+
+    cx.conflicts.push((conflict, resolution));
+}
+
+/// Puts an entity under `name`, or marks the entry as deleted if there is no entity.
+fn set_entry(entries: &mut Entries, name: &str, cid: Option<Cid>) {
+    match cid {
+        Some(cid) => {
+            entries.insert(name.to_string(), (false, cid));
+        }
+        None => {
+            if let Some(entry) = entries.get_mut(name) {
+                entry.0 = true;
+            }
+        }
+    }
+}
+
+/// Returns `name`, or `name` followed by a number if it is already taken on either side.
+fn get_unused_name(
+    entries: &Entries,
+    ours: &BTreeMap<String, Cid>,
+    theirs: &BTreeMap<String, Cid>,
+    name: &str,
+) -> String {
+    let taken = |name: &String| {
+        ours.contains_key(name)
+            || theirs.contains_key(name)
+            || entries.get(name).is_some_and(|(deleted, _)| !deleted)
+    };
+
+    let mut candidate = name.to_string();
+    let mut n = 1;
+    while taken(&candidate) {
+        n += 1;
+        candidate = format!("{}{}", name, n);
+    }
+
+    candidate
+}
+
+/// Returns the entries that are not deleted.
+fn live(entries: &Entries) -> BTreeMap<String, Cid> {
+    entries
+        .iter()
+        .filter(|(_, (deleted, _))| !deleted)
+        .map(|(name, (_, cid))| (name.clone(), *cid))
+        .collect()
+}
+
+/// Returns the versions of an entity, from `cid` back through its `previous` and `merged` links,
+/// breadth first so nearer versions come first.
+async fn get_history<S>(store: &S, cid: &Cid) -> FsResult<Vec<Cid>>
+where
+    S: IpldStore + Send + Sync,
+{
+    let mut history = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([*cid]);
+
+    while let Some(cid) = queue.pop_front() {
+        if !visited.insert(cid) || !store.has(&cid).await {
+            continue;
+        }
+
+        history.push(cid);
+        queue.extend(get_parents(&load_fields(store, &cid).await?));
+    }
+
+    Ok(history)
+}
+
+/// Returns true if the entity is a directory.
+async fn is_dir<S>(store: &S, cid: &Cid) -> FsResult<bool>
+where
+    S: IpldStore + Send + Sync,
+{
+    Ok(is_dir_fields(&load_fields(store, cid).await?))
+}
+
+/// Loads the fields of a directory node, failing if the entity is not a directory.
+async fn load_dir<S>(store: &S, cid: &Cid, path: &str) -> FsResult<BTreeMap<String, Ipld>>
+where
+    S: IpldStore + Send + Sync,
+{
+    let fields = load_fields(store, cid).await?;
+    if !is_dir_fields(&fields) {
+        return Err(FsError::NotADirectory(format!("{} ({})", path, cid)));
+    }
+
+    Ok(fields)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl ConflictResolver for ConflictResolution {
+    fn resolve(&self, _conflict: &MergeConflict) -> ConflictResolution {
+        *self
+    }
+}
+
+impl<F> ConflictResolver for F
+where
+    F: Fn(&MergeConflict) -> ConflictResolution + Send + Sync,
+{
+    fn resolve(&self, conflict: &MergeConflict) -> ConflictResolution {
+        self(conflict)
+    }
+}
+
+impl FromStr for ConflictResolution {
+    type Err = FsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ours" => Ok(ConflictResolution::Ours),
+            "theirs" => Ok(ConflictResolution::Theirs),
+            "keep-both" => Ok(ConflictResolution::KeepBoth),
+            _ => Err(FsError::UnsupportedConflictResolution(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ConflictResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictResolution::Ours => write!(f, "ours"),
+            ConflictResolution::Theirs => write!(f, "theirs"),
+            ConflictResolution::KeepBoth => write!(f, "keep-both"),
+        }
+    }
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictKind::BothModified => write!(f, "both modified"),
+            ConflictKind::BothAdded => write!(f, "both added"),
+            ConflictKind::ModifiedDeleted => write!(f, "modified by us, deleted by them"),
+            ConflictKind::DeletedModified => write!(f, "deleted by us, modified by them"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{MemoryStore, Storable};
+    use tokio::io::AsyncReadExt;
+
+    use crate::filesystem::{Dir, File};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_merge_combines_non_conflicting_changes() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut base = Dir::new(store.clone());
+        write(&mut base, "a.txt", "a").await?;
+        write(&mut base, "b.txt", "b").await?;
+        write(base.create_dir("sub").await?, "c.txt", "c").await?;
+        let base_cid = base.checkpoint().await?;
+
+        // We change a file and add one to the subdirectory
+        let mut ours = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut ours, "a.txt", "a ours").await?;
+        write(ours.get_dir_mut("sub").await?.unwrap(), "d.txt", "d").await?;
+        let ours_cid = ours.checkpoint().await?;
+
+        // They change another file and remove one from the subdirectory
+        let mut theirs = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut theirs, "b.txt", "b theirs").await?;
+        theirs
+            .get_dir_mut("sub")
+            .await?
+            .unwrap()
+            .remove_entry("c.txt")?;
+        let theirs_cid = theirs.checkpoint().await?;
+
+        let outcome = merge(&store, &ours_cid, &theirs_cid, &ConflictResolution::Ours).await?;
+        assert_eq!(outcome.get_base(), &Some(base_cid));
+        assert!(outcome.get_conflicts().is_empty());
+
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert_eq!(merged.get_previous(), Some(&ours_cid));
+        assert_eq!(read(&merged, "a.txt").await?.as_deref(), Some("a ours"));
+        assert_eq!(read(&merged, "b.txt").await?.as_deref(), Some("b theirs"));
+
+        let sub = merged.get_dir("sub").await?.unwrap();
+        assert!(!sub.has_entry("c.txt")?);
+        assert_eq!(read(sub, "d.txt").await?.as_deref(), Some("d"));
+
+        // Merging a version with one of its ancestors fast-forwards
+        let outcome = merge(&store, &base_cid, &ours_cid, &ConflictResolution::Ours).await?;
+        assert_eq!(outcome.get_head(), &ours_cid);
+        let outcome = merge(&store, &ours_cid, &base_cid, &ConflictResolution::Ours).await?;
+        assert_eq!(outcome.get_head(), &ours_cid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_resolves_conflicts() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut base = Dir::new(store.clone());
+        write(&mut base, "a.txt", "a").await?;
+        write(&mut base, "b.txt", "b").await?;
+        let base_cid = base.checkpoint().await?;
+
+        let mut ours = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut ours, "a.txt", "a ours").await?;
+        ours.remove_entry("b.txt")?;
+        let ours_cid = ours.checkpoint().await?;
+
+        let mut theirs = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut theirs, "a.txt", "a theirs").await?;
+        write(&mut theirs, "b.txt", "b theirs").await?;
+        let theirs_cid = theirs.checkpoint().await?;
+
+        // Conflicts are reported in path order
+        let outcome = merge(&store, &ours_cid, &theirs_cid, &ConflictResolution::Ours).await?;
+        let kinds = outcome
+            .get_conflicts()
+            .iter()
+            .map(|(conflict, _)| (conflict.get_path().as_str(), *conflict.get_kind()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                ("a.txt", ConflictKind::BothModified),
+                ("b.txt", ConflictKind::DeletedModified)
+            ]
+        );
+
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert_eq!(read(&merged, "a.txt").await?.as_deref(), Some("a ours"));
+        assert!(!merged.has_entry("b.txt")?);
+
+        let outcome = merge(&store, &ours_cid, &theirs_cid, &ConflictResolution::Theirs).await?;
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert_eq!(read(&merged, "a.txt").await?.as_deref(), Some("a theirs"));
+        assert_eq!(read(&merged, "b.txt").await?.as_deref(), Some("b theirs"));
+
+        let outcome = merge(
+            &store,
+            &ours_cid,
+            &theirs_cid,
+            &ConflictResolution::KeepBoth,
+        )
+        .await?;
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert!(!merged.has_entry("a.txt")?);
+        assert_eq!(
+            read(&merged, "a.txt~ours").await?.as_deref(),
+            Some("a ours")
+        );
+        assert_eq!(
+            read(&merged, "a.txt~theirs").await?.as_deref(),
+            Some("a theirs")
+        );
+        assert_eq!(read(&merged, "b.txt").await?.as_deref(), Some("b theirs"));
+
+        // A resolver can pick a resolution per conflict
+        let resolver = |conflict: &MergeConflict| match conflict.get_kind() {
+            ConflictKind::BothModified => ConflictResolution::Theirs,
+            _ => ConflictResolution::Ours,
+        };
+        let outcome = merge(&store, &ours_cid, &theirs_cid, &resolver).await?;
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert_eq!(read(&merged, "a.txt").await?.as_deref(), Some("a theirs"));
+        assert!(!merged.has_entry("b.txt")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_twice_uses_the_last_merge_as_base() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut base = Dir::new(store.clone());
+        write(&mut base, "a.txt", "a").await?;
+        let base_cid = base.checkpoint().await?;
+
+        let mut ours = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut ours, "b.txt", "b").await?;
+        let ours_cid = ours.checkpoint().await?;
+
+        let mut theirs = Dir::load(&base_cid, store.clone()).await?;
+        write(&mut theirs, "c.txt", "c").await?;
+        let theirs_cid = theirs.checkpoint().await?;
+
+        // The merge links both versions it was made from
+        let outcome = merge(&store, &ours_cid, &theirs_cid, &ConflictResolution::Ours).await?;
+        let first_merge = *outcome.get_head();
+        let fields = load_fields(&store, &first_merge).await?;
+        assert_eq!(get_parents(&fields), [ours_cid, theirs_cid]);
+        assert!(is_ancestor(&store, &theirs_cid, &first_merge).await?);
+
+        // We remove the file we merged in, while they keep working on top of their version
+        let mut ours = Dir::load(&first_merge, store.clone()).await?;
+        ours.remove_entry("c.txt")?;
+        let ours_cid = ours.checkpoint().await?;
+
+        write(&mut theirs, "d.txt", "d").await?;
+        let theirs_cid_2 = theirs.checkpoint().await?;
+
+        // The second merge is based on their version we already merged, so the removal sticks
+        let outcome = merge(&store, &ours_cid, &theirs_cid_2, &ConflictResolution::Ours).await?;
+        assert_eq!(outcome.get_base(), &Some(theirs_cid));
+        assert!(outcome.get_conflicts().is_empty());
+
+        let merged = Dir::load(outcome.get_head(), store.clone()).await?;
+        assert!(!merged.has_entry("c.txt")?);
+        assert_eq!(read(&merged, "b.txt").await?.as_deref(), Some("b"));
+        assert_eq!(read(&merged, "d.txt").await?.as_deref(), Some("d"));
+
+        Ok(())
+    }
+
+    /// Writes a file with the given content into the directory.
+    async fn write(dir: &mut Dir<MemoryStore>, name: &str, content: &str) -> anyhow::Result<()> {
+        let file = File::with_content(dir.get_store().clone(), content.as_bytes()).await?;
+        dir.put_adapted_file(name, file).await?;
+        Ok(())
+    }
+
+    /// Reads the content of a file in the directory.
+    async fn read(dir: &Dir<MemoryStore>, name: &str) -> anyhow::Result<Option<String>> {
+        let Some(file) = dir.get_file(name).await? else {
+            return Ok(None);
+        };
+
+        let mut content = String::new();
+        file.get_input_stream()
+            .await?
+            .read_to_string(&mut content)
+            .await?;
+        Ok(Some(content))
+    }
+}
@@ -16,7 +16,7 @@ use tokio::sync::RwLock;
 
 use crate::FsResult;
 
-use super::{kind::EntityType, AttributesCidLink};
+use super::{kind::EntityType, AttributesCidLink, Hlc};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -78,6 +78,10 @@ where
     /// The sync type of the entity.
     sync_type: SyncType,
 
+    /// The hybrid logical clock timestamp of the last change to the entity, as stamped by a
+    /// [`CrdtReplica`][crate::sync::CrdtReplica].
+    clock: Option<Hlc>,
+
     /// Extended attributes.
     extended_attrs: Option<AttributesCidLink<S>>,
 
@@ -152,12 +156,26 @@ struct ExtendedAttributesInner<S> {
 //--------------------------------------------------------------------------------------------------
 
 /// A serializable representation of [`Metadata`].
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
 pub struct MetadataSerializable {
+    /// The type of the entity.
     entity_type: EntityType,
+
+    /// The creation time of the entity.
     created_at: DateTime<Utc>,
+
+    /// The last modification time of the entity.
     modified_at: DateTime<Utc>,
+
+    /// The method used to sync the entity.
     sync_type: SyncType,
+
+    /// The hybrid logical clock timestamp of the last change, if the entity was ever stamped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock: Option<Hlc>,
+
+    /// The CID of the extended attributes.
     extended_attrs: Option<Cid>,
 }
 
@@ -181,6 +199,7 @@ where
             created_at: now,
             modified_at: now,
             sync_type: SyncType::default(),
+            clock: None,
             extended_attrs: None,
             store,
         }
@@ -193,6 +212,7 @@ where
             created_at: serializable.created_at,
             modified_at: serializable.modified_at,
             sync_type: serializable.sync_type,
+            clock: serializable.clock,
             extended_attrs: serializable
                 .extended_attrs
                 .map(|cid| AttributesCidLink::from(cid)),
@@ -216,6 +236,7 @@ where
             created_at: self.created_at,
             modified_at: self.modified_at,
             sync_type: self.sync_type,
+            clock: self.clock,
             extended_attrs,
         })
     }
@@ -324,6 +345,11 @@ where
         self.sync_type = sync_type;
     }
 
+    /// Sets the hybrid logical clock timestamp of the last change to the entity.
+    pub fn set_clock(&mut self, clock: Option<Hlc>) {
+        self.clock = clock;
+    }
+
     /// Sets the modified timestamp.
     pub fn set_modified_at(&mut self, modified_at: DateTime<Utc>) {
         self.modified_at = modified_at;
@@ -408,6 +434,7 @@ where
             .field("created_at", &self.created_at)
             .field("modified_at", &self.modified_at)
             .field("sync_type", &self.sync_type)
+            .field("clock", &self.clock)
             .field(
                 "extended_attrs",
                 &self.extended_attrs.as_ref().map(|link| link.get_cid()),
@@ -5,8 +5,12 @@ mod dir;
 mod entity;
 mod eq;
 mod file;
+mod hlc;
 mod kind;
+mod merge;
 mod metadata;
+mod node;
+mod signature;
 mod symcidlink;
 mod sympathlink;
 
@@ -19,7 +23,11 @@ pub use dir::*;
 pub use entity::*;
 pub use eq::*;
 pub use file::*;
+pub use hlc::*;
 pub use kind::*;
+pub use merge::*;
 pub use metadata::*;
+pub(crate) use node::*;
+pub use signature::*;
 pub use symcidlink::*;
 pub use sympathlink::*;
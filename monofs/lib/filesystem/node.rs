@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld, serde as ipld_serde},
+    IpldStore,
+};
+
+use crate::{filesystem::DIR_TYPE_TAG, FsError, FsResult};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The entries of a directory node: name to deleted flag and entity.
+pub(crate) type Entries = BTreeMap<String, (bool, Cid)>;
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Loads the fields of an entity node.
+pub(crate) async fn load_fields<S>(store: &S, cid: &Cid) -> FsResult<BTreeMap<String, Ipld>>
+where
+    S: IpldStore + Send + Sync,
+{
+    match store.get_node(cid).await? {
+        Ipld::Map(fields) => Ok(fields),
+        _ => Err(FsError::UnableToLoadEntity(*cid)),
+    }
+}
+
+/// Returns true if the fields are those of a directory node.
+pub(crate) fn is_dir_fields(fields: &BTreeMap<String, Ipld>) -> bool {
+    matches!(fields.get("type"), Some(Ipld::String(tag)) if tag == DIR_TYPE_TAG)
+}
+
+/// Decodes the entries of a directory node.
+pub(crate) fn get_dir_entries(fields: &BTreeMap<String, Ipld>) -> FsResult<Entries> {
+    match fields.get("entries") {
+        Some(entries) => ipld_serde::from_ipld(entries.clone()).map_err(FsError::custom),
+        None => Ok(Entries::new()),
+    }
+}
+
+/// Returns the version an entity node follows, if it has one.
+pub(crate) fn get_previous(fields: &BTreeMap<String, Ipld>) -> Option<Cid> {
+    match fields.get("previous") {
+        Some(Ipld::Link(previous)) => Some(*previous),
+        _ => None,
+    }
+}
+
+/// Returns the versions an entity node follows: the one it was made on top of, then the one that
+/// was merged into it if it is the result of a merge.
+pub(crate) fn get_parents(fields: &BTreeMap<String, Ipld>) -> Vec<Cid> {
+    ["previous", "merged"]
+        .into_iter()
+        .filter_map(|key| match fields.get(key) {
+            Some(Ipld::Link(parent)) => Some(*parent),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns true if `ancestor` is among the versions `descendant` follows through their
+/// `previous` and `merged` links.
+///
+/// Versions whose blocks are not in the store end the history.
+pub(crate) async fn is_ancestor<S>(store: &S, ancestor: &Cid, descendant: &Cid) -> FsResult<bool>
+where
+    S: IpldStore + Send + Sync,
+{
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([*descendant]);
+
+    while let Some(current) = queue.pop_front() {
+        if !visited.insert(current) || !store.has(&current).await {
+            continue;
+        }
+
+        for parent in get_parents(&load_fields(store, &current).await?) {
+            if parent == *ancestor {
+                return Ok(true);
+            }
+
+            queue.push_back(parent);
+        }
+    }
+
+    Ok(false)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::MemoryStore;
+
+    use crate::filesystem::Dir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_is_ancestor_stops_at_missing_blocks() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut dir = Dir::new(store.clone());
+        let first = dir.checkpoint().await?;
+        dir.create_dir("docs").await?;
+        let second = dir.checkpoint().await?;
+
+        let fields = load_fields(&store, &second).await?;
+        assert!(is_dir_fields(&fields));
+        assert_eq!(get_previous(&fields), Some(first));
+        assert!(get_dir_entries(&fields)?.contains_key("docs"));
+
+        assert!(is_ancestor(&store, &first, &second).await?);
+        assert!(!is_ancestor(&store, &second, &first).await?);
+        assert!(!is_ancestor(&store, &second, &second).await?);
+
+        // A version whose blocks are missing ends the chain rather than failing
+        let other = MemoryStore::default();
+        let mut dir = Dir::new(other.clone());
+        let missing = dir.checkpoint().await?;
+        assert!(!is_ancestor(&store, &first, &missing).await?);
+
+        Ok(())
+    }
+}
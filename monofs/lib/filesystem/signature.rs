@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+
+use getset::Getters;
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld},
+    utils, Codec, IpldStore,
+};
+use monoutils_did::{Did, KeyPair};
+
+use crate::{
+    filesystem::{get_previous, load_fields},
+    FsError, FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The prefix of the bytes a revision signature signs, so it can't be mistaken for a signature
+/// over anything else.
+pub const REVISION_SIGNATURE_DOMAIN: &[u8] = b"monofs.revision.v1";
+
+/// The field of a directory node that holds the signature of the revision.
+pub(crate) const SIGNATURE_FIELD: &str = "signature";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A signature over a revision of a root directory, proving who produced it.
+///
+/// The signature covers the CID of the revision and the CID of the revision it was made on top
+/// of, so a signed revision can neither be swapped for another nor moved to another history.
+///
+/// Signatures are stored in the `signature` field of the root directory node they sign, so they
+/// travel with the revision wherever its blocks are copied. A signature can't cover the node that
+/// holds it, so the signed CID is that of the node without its `signature` field.
+///
+/// ## Examples
+///
+/// ```
+/// use monofs::filesystem::{self, Dir};
+/// use monoutils_did::{KeyPair, KeyType};
+/// use ipldstore::MemoryStore;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let keypair = KeyPair::generate(KeyType::Ed25519);
+/// let mut dir = Dir::new(MemoryStore::default());
+///
+/// let cid = dir.checkpoint_signed(&keypair).await?;
+/// let signature = filesystem::get_revision_signature(dir.get_store(), &cid).await?;
+/// assert!(signature.unwrap().verify().is_ok());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RevisionSignature {
+    /// The CID of the signed revision, without its signature.
+    root: Cid,
+
+    /// The CID of the revision the signed revision was made on top of, if any.
+    previous: Option<Cid>,
+
+    /// The DID of the key the revision was signed with.
+    signer: Did,
+
+    /// The signature over the revision and its previous revision.
+    signature: Vec<u8>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RevisionSignature {
+    /// Signs a revision with the given key.
+    ///
+    /// ## Arguments
+    /// * `keypair` - The key to sign with, the signer is its `did:key`
+    /// * `root` - The CID of the revision
+    /// * `previous` - The CID of the revision it was made on top of, if any
+    pub fn sign(keypair: &KeyPair, root: Cid, previous: Option<Cid>) -> Self {
+        let signature = keypair.sign(&get_signing_input(&root, previous.as_ref()));
+
+        Self {
+            root,
+            previous,
+            signer: Did::key(keypair.get_public_key()),
+            signature,
+        }
+    }
+
+    /// Creates a revision signature from its parts, e.g. as they were stored. The signature is
+    /// not checked until [`verify`][Self::verify] is called.
+    pub fn from_parts(root: Cid, previous: Option<Cid>, signer: Did, signature: Vec<u8>) -> Self {
+        Self {
+            root,
+            previous,
+            signer,
+            signature,
+        }
+    }
+
+    /// Verifies that the signer signed the revision on top of its previous revision.
+    pub fn verify(&self) -> FsResult<()> {
+        let input = get_signing_input(&self.root, self.previous.as_ref());
+        self.signer.verify(&input, &self.signature)?;
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Signs a stored revision of a root directory with the given key.
+///
+/// The revision is stored again with the signature in its `signature` field, replacing any
+/// signature it had. Its `previous` revision stays the same.
+///
+/// ## Arguments
+/// * `store` - The store the revision is in
+/// * `keypair` - The key to sign with
+/// * `cid` - The CID of the revision
+///
+/// ## Returns
+/// The CID of the signed revision
+pub async fn sign_revision<S>(store: &S, keypair: &KeyPair, cid: &Cid) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    let mut fields = load_fields(store, cid).await?;
+    sign_fields(keypair, &mut fields)?;
+    Ok(store.put_node(&Ipld::Map(fields)).await?)
+}
+
+/// Returns the signature stored in a revision of a root directory.
+///
+/// ## Returns
+/// The signature as it was stored, not yet verified, or None if the revision is unsigned
+pub async fn get_revision_signature<S>(store: &S, cid: &Cid) -> FsResult<Option<RevisionSignature>>
+where
+    S: IpldStore + Send + Sync,
+{
+    get_fields_signature(&load_fields(store, cid).await?)
+}
+
+/// Signs the fields of a root directory node, replacing the signature they had.
+pub(crate) fn sign_fields(
+    keypair: &KeyPair,
+    fields: &mut BTreeMap<String, Ipld>,
+) -> FsResult<RevisionSignature> {
+    fields.remove(SIGNATURE_FIELD);
+    let signature =
+        RevisionSignature::sign(keypair, get_unsigned_cid(fields)?, get_previous(fields));
+
+    let mut field = BTreeMap::new();
+    field.insert(
+        "signer".to_string(),
+        Ipld::String(signature.signer.to_string()),
+    );
+    field.insert(
+        "signature".to_string(),
+        Ipld::Bytes(signature.signature.clone()),
+    );
+    fields.insert(SIGNATURE_FIELD.to_string(), Ipld::Map(field));
+
+    Ok(signature)
+}
+
+/// Decodes the signature stored in the fields of a root directory node, if there is one.
+pub(crate) fn get_fields_signature(
+    fields: &BTreeMap<String, Ipld>,
+) -> FsResult<Option<RevisionSignature>> {
+    let Some(field) = fields.get(SIGNATURE_FIELD) else {
+        return Ok(None);
+    };
+
+    let Ipld::Map(field) = field else {
+        return Err(FsError::MalformedRevisionSignature);
+    };
+    let (Some(Ipld::String(signer)), Some(Ipld::Bytes(signature))) =
+        (field.get("signer"), field.get("signature"))
+    else {
+        return Err(FsError::MalformedRevisionSignature);
+    };
+
+    let mut unsigned = fields.clone();
+    unsigned.remove(SIGNATURE_FIELD);
+
+    Ok(Some(RevisionSignature::from_parts(
+        get_unsigned_cid(&unsigned)?,
+        get_previous(fields),
+        signer.parse()?,
+        signature.clone(),
+    )))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the CID the fields of a node without a signature are stored under.
+fn get_unsigned_cid(fields: &BTreeMap<String, Ipld>) -> FsResult<Cid> {
+    let bytes = serde_ipld_dagcbor::to_vec(&Ipld::Map(fields.clone())).map_err(FsError::custom)?;
+    Ok(utils::generate_cid(Codec::DagCbor, &bytes))
+}
+
+/// Returns the bytes a revision signature signs: the domain, the revision CID, and a flag
+/// followed by the previous revision CID if there is one.
+fn get_signing_input(root: &Cid, previous: Option<&Cid>) -> Vec<u8> {
+    let mut input = REVISION_SIGNATURE_DOMAIN.to_vec();
+    input.extend(root.to_bytes());
+    match previous {
+        Some(previous) => {
+            input.push(1);
+            input.extend(previous.to_bytes());
+        }
+        None => input.push(0),
+    }
+
+    input
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{utils, Codec};
+    use monoutils_did::KeyType;
+
+    use super::*;
+
+    #[test]
+    fn test_revision_signature_covers_root_and_previous() -> anyhow::Result<()> {
+        let keypair = KeyPair::generate(KeyType::P256);
+        let root = utils::generate_cid(Codec::DagCbor, b"root");
+        let previous = utils::generate_cid(Codec::DagCbor, b"previous");
+
+        let signature = RevisionSignature::sign(&keypair, root, Some(previous));
+        signature.verify()?;
+        assert_eq!(signature.get_signer(), &Did::key(keypair.get_public_key()));
+
+        // Moving the signature to another revision or history breaks it
+        let other = utils::generate_cid(Codec::DagCbor, b"other");
+        for (root, previous) in [(other, Some(previous)), (root, Some(other)), (root, None)] {
+            let moved = RevisionSignature::from_parts(
+                root,
+                previous,
+                signature.signer.clone(),
+                signature.signature.clone(),
+            );
+            assert!(moved.verify().is_err());
+        }
+
+        // So does claiming another signer
+        let impostor = RevisionSignature::from_parts(
+            root,
+            Some(previous),
+            Did::key(KeyPair::generate(KeyType::P256).get_public_key()),
+            signature.signature.clone(),
+        );
+        assert!(impostor.verify().is_err());
+
+        Ok(())
+    }
+}
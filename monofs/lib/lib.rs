@@ -15,6 +15,7 @@ pub mod management;
 pub mod runtime;
 pub mod server;
 pub mod store;
+pub mod sync;
 pub mod utils;
 
 pub use error::*;
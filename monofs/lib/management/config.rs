@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use sqlx::Row;
+
+use crate::{config::FsConfig, management::db, FsResult};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Get the configuration of the filesystem mounted at `mount_dir`
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+///
+/// ## Returns
+/// The stored configuration, or the default one if none has been stored yet
+pub async fn get_fs_config(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+) -> FsResult<FsConfig> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref().to_string_lossy().to_string();
+
+    let record =
+        sqlx::query("SELECT config FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1")
+            .bind(mount_dir)
+            .fetch_optional(&pool)
+            .await?;
+
+    match record.and_then(|row| row.get::<Option<String>, _>("config")) {
+        Some(config) => Ok(serde_json::from_str(&config)?),
+        None => Ok(FsConfig::default()),
+    }
+}
+
+/// Store the configuration of the filesystem mounted at `mount_dir`
+///
+/// A filesystem entry is created if there is none yet.
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+/// * `config` - The configuration to store
+pub async fn set_fs_config(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    config: &FsConfig,
+) -> FsResult<()> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref();
+    let mount_dir_str = mount_dir.to_string_lossy().to_string();
+    let config = serde_json::to_string(config)?;
+
+    let result = sqlx::query(
+        "UPDATE filesystems SET config = ?, modified_at = CURRENT_TIMESTAMP WHERE mount_dir = ?",
+    )
+    .bind(&config)
+    .bind(&mount_dir_str)
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let name = mount_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| mount_dir_str.clone());
+
+        sqlx::query("INSERT INTO filesystems (name, mount_dir, config) VALUES (?, ?, ?)")
+            .bind(name)
+            .bind(&mount_dir_str)
+            .bind(&config)
+            .execute(&pool)
+            .await?;
+    }
+
+    Ok(())
+}
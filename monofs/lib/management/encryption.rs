@@ -0,0 +1,550 @@
+use std::{
+    env,
+    fmt::{self, Debug},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use argon2::Argon2;
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld},
+    IpldStore,
+};
+use monoutils_cryptdag::{self as cryptdag, CryptStore, SecretKey, KEY_SIZE};
+use tokio::{fs, task};
+
+use crate::{
+    config::Encryption,
+    filesystem::{sign_fields, SIGNATURE_FIELD},
+    management::{config, head, mfs, RevisionSigner},
+    store::FlatFsStore,
+    utils::{
+        path::{BLOCKS_SUBDIR, FS_DB_FILENAME},
+        KEYFILE_ENV_VAR, PASSPHRASE_ENV_VAR,
+    },
+    FsError, FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The size of the salt encryption keys are derived with, in bytes
+const SALT_SIZE: usize = 16;
+
+/// The separator of the keys of a keyring in its encoded form
+const KEYRING_SEPARATOR: char = ',';
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// What the key of an encrypted filesystem is derived from.
+///
+/// Keys are derived with Argon2id from the passphrase, or from the contents of the keyfile, and
+/// the salt stored in the filesystem's configuration.
+#[derive(Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// A passphrase.
+    Passphrase(String),
+
+    /// A file whose contents are used as the passphrase.
+    Keyfile(PathBuf),
+}
+
+/// The keys an encrypted filesystem is read and written with: the current key, and the keys it
+/// replaced that blocks written before a rotation are still encrypted with.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    /// The key new blocks are encrypted with.
+    key: SecretKey,
+
+    /// The keys the current key replaced.
+    retired_keys: Vec<SecretKey>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl KeySource {
+    /// Gets the key source from the `MONOFS_KEYFILE` or `MONOFS_PASSPHRASE` environment
+    /// variable, the keyfile taking precedence.
+    pub fn from_env() -> Option<Self> {
+        if let Some(path) = env::var_os(KEYFILE_ENV_VAR) {
+            return Some(Self::Keyfile(path.into()));
+        }
+
+        env::var(PASSPHRASE_ENV_VAR).ok().map(Self::Passphrase)
+    }
+
+    /// Derives a key from this source and the given salt.
+    pub async fn derive_key(&self, salt: &[u8]) -> FsResult<SecretKey> {
+        let secret = match self {
+            Self::Passphrase(passphrase) => passphrase.as_bytes().to_vec(),
+            Self::Keyfile(path) => fs::read(path).await?,
+        };
+
+        if secret.is_empty() {
+            return Err(FsError::InvalidEncryptionKey(
+                "passphrase or keyfile is empty".to_string(),
+            ));
+        }
+
+        // Argon2 is deliberately slow, so keep it off the async workers
+        let salt = salt.to_vec();
+        task::spawn_blocking(move || {
+            let mut key = [0; KEY_SIZE];
+            Argon2::default()
+                .hash_password_into(&secret, &salt, &mut key)
+                .map_err(|e| FsError::InvalidEncryptionKey(e.to_string()))?;
+            Ok(SecretKey::from_bytes(key))
+        })
+        .await
+        .map_err(FsError::custom)?
+    }
+}
+
+impl Keyring {
+    /// Creates a new keyring.
+    ///
+    /// ## Arguments
+    /// * `key` - The key new blocks are encrypted with
+    /// * `retired_keys` - The keys the current key replaced
+    pub fn new(key: SecretKey, retired_keys: Vec<SecretKey>) -> Self {
+        Self { key, retired_keys }
+    }
+
+    /// Returns the key new blocks are encrypted with.
+    pub fn get_key(&self) -> &SecretKey {
+        &self.key
+    }
+
+    /// Returns the keys the current key replaced.
+    pub fn get_retired_keys(&self) -> &[SecretKey] {
+        &self.retired_keys
+    }
+
+    /// Wraps the given store in a store that encrypts with the current key and decrypts with any
+    /// key of the keyring.
+    pub fn open_store<S>(&self, store: S) -> CryptStore<S>
+    where
+        S: IpldStore,
+    {
+        CryptStore::new(store, self.key.clone()).with_retired_keys(self.retired_keys.clone())
+    }
+
+    /// Encodes the keyring as hex encoded keys separated by commas, the current key first.
+    ///
+    /// This is how keyrings are handed to the NFS server, and is parsed back with [`FromStr`].
+    pub fn encode(&self) -> String {
+        std::iter::once(&self.key)
+            .chain(&self.retired_keys)
+            .map(|key| hex::encode(key.as_bytes()))
+            .collect::<Vec<_>>()
+            .join(&KEYRING_SEPARATOR.to_string())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Initialize a new encrypted monofs filesystem at the specified path and mount it
+///
+/// Every block of the filesystem is encrypted before it is written to disk: directory entries,
+/// and with them the names of files, file contents, and metadata including extended attributes.
+/// The key is derived from `source`, which is needed again to mount the filesystem or any of its
+/// revisions.
+///
+/// ## Arguments
+/// * `mount_dir` - The path where the filesystem will be initialized and mounted. If None, uses current directory
+/// * `source` - The passphrase or keyfile the key is derived from
+///
+/// ## Returns
+/// The port number that was successfully used for mounting
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management::{self, KeySource};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let source = KeySource::Keyfile("mfstest.key".into());
+/// management::init_encrypted_mfs(Some("mfstest".into()), source).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn init_encrypted_mfs(mount_dir: Option<PathBuf>, source: KeySource) -> FsResult<u32> {
+    let (mount_dir, mfs_data_dir) = mfs::init_mfs_data_dir(mount_dir).await?;
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+
+    // Encrypted filesystems are unlocked, existing plain ones are never encrypted in place
+    let keyring = match unlock_mfs(&fs_db_path, &mount_dir, Some(source.clone())).await? {
+        Some(keyring) => keyring,
+        None if head::get_head(&fs_db_path, &mount_dir).await?.is_some() => {
+            return Err(FsError::InvalidOperation(format!(
+                "cannot encrypt existing filesystem at {}",
+                mount_dir.display()
+            )));
+        }
+        None => setup_encryption(&fs_db_path, &mount_dir, &source).await?,
+    };
+
+    mfs::start_mfs(&mount_dir, &mfs_data_dir, Some(&keyring)).await
+}
+
+/// Rotate the key of the encrypted filesystem mounted at `mount_dir`
+///
+/// Only the root directory of the head is re-encrypted, as a new revision on top of the current
+/// head. Everything else stays encrypted with the keys it was written with, which are kept,
+/// encrypted with the new key, so the new key alone still reads every revision. Blocks written
+/// from then on are encrypted with the new key. The new head is signed with the filesystem's
+/// signing key, if it has one.
+///
+/// The filesystem must not be mounted while its key is rotated.
+///
+/// ## Arguments
+/// * `mount_dir` - The mount directory of the filesystem
+/// * `old_source` - The passphrase or keyfile the current key is derived from
+/// * `new_source` - The passphrase or keyfile to derive the new key from
+///
+/// ## Returns
+/// The new head of the filesystem, if it has one
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management::{self, KeySource};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// management::rotate_key_mfs(
+///     "mfstest",
+///     KeySource::Keyfile("mfstest.key".into()),
+///     KeySource::Keyfile("mfstest.new.key".into()),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn rotate_key_mfs(
+    mount_dir: impl AsRef<Path>,
+    old_source: KeySource,
+    new_source: KeySource,
+) -> FsResult<Option<Cid>> {
+    let mount_dir = fs::canonicalize(mount_dir.as_ref()).await?;
+    let mfs_data_dir = mfs::get_mfs_data_dir(&mount_dir).await?;
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+
+    if mfs::is_mounted(&fs_db_path, &mount_dir).await? {
+        return Err(FsError::InvalidOperation(format!(
+            "cannot rotate the key of {} while it is mounted",
+            mount_dir.display()
+        )));
+    }
+
+    let old_keyring = unlock_mfs(&fs_db_path, &mount_dir, Some(old_source))
+        .await?
+        .ok_or_else(|| {
+            FsError::InvalidOperation(format!("{} is not encrypted", mount_dir.display()))
+        })?;
+
+    // The new key reads everything the old keys did
+    let mut retired_keys = vec![old_keyring.key.clone()];
+    retired_keys.extend(old_keyring.retired_keys.iter().cloned());
+    let salt: [u8; SALT_SIZE] = rand::random();
+    let new_keyring = Keyring::new(new_source.derive_key(&salt).await?, retired_keys);
+    if new_keyring
+        .get_retired_keys()
+        .contains(new_keyring.get_key())
+    {
+        return Err(FsError::InvalidEncryptionKey(
+            "new key is the same as a current or retired key".to_string(),
+        ));
+    }
+
+    let mut fs_config = config::get_fs_config(&fs_db_path, &mount_dir).await?;
+    fs_config.set_encryption(Some(get_encryption(&new_keyring, &salt)));
+    config::set_fs_config(&fs_db_path, &mount_dir, &fs_config).await?;
+    tracing::info!(
+        "rotated key of {} to {}",
+        mount_dir.display(),
+        new_keyring.get_key().get_id()
+    );
+
+    let Some(old_head) = head::get_head(&fs_db_path, &mount_dir).await? else {
+        return Ok(None);
+    };
+
+    // Re-encrypt the root of the head as a revision on top of it
+    let store = new_keyring.open_store(FlatFsStore::new(mfs_data_dir.join(BLOCKS_SUBDIR)));
+    let Ipld::Map(mut root) = store.get_node::<Ipld>(&old_head).await? else {
+        return Err(FsError::NotADirectory(old_head.to_string()));
+    };
+    root.insert("previous".to_string(), Ipld::Link(old_head));
+    root.remove(SIGNATURE_FIELD);
+    if let Some(signer) = RevisionSigner::open(&fs_db_path, &mount_dir).await? {
+        sign_fields(signer.get_keypair(), &mut root)?;
+    }
+    let new_head = store.put_node(&Ipld::Map(root)).await?;
+
+    head::set_head(&fs_db_path, &mount_dir, &new_head, Some(&old_head)).await?;
+    tracing::info!("re-encrypted head {} as {}", old_head, new_head);
+
+    Ok(Some(new_head))
+}
+
+/// Get the keyring of the filesystem mounted at `mount_dir`, if it is encrypted
+///
+/// The key is derived from `source`, or from the environment if no source is given.
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+/// * `source` - The passphrase or keyfile the key is derived from
+///
+/// ## Returns
+/// The keyring of the filesystem, or `None` if it is not encrypted
+pub async fn unlock_mfs(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    source: Option<KeySource>,
+) -> FsResult<Option<Keyring>> {
+    let mount_dir = mount_dir.as_ref();
+    let fs_config = config::get_fs_config(fs_db_path, mount_dir).await?;
+    let Some(encryption) = fs_config.get_encryption() else {
+        return Ok(None);
+    };
+
+    let source = source
+        .or_else(KeySource::from_env)
+        .ok_or_else(|| FsError::EncryptionKeyRequired(mount_dir.display().to_string()))?;
+
+    let salt = hex::decode(encryption.get_salt()).map_err(FsError::custom)?;
+    let key = source.derive_key(&salt).await?;
+    if key.get_id().to_string() != *encryption.get_key_id() {
+        return Err(FsError::InvalidEncryptionKey(format!(
+            "key {} does not unlock {}",
+            key.get_id(),
+            mount_dir.display()
+        )));
+    }
+
+    let retired_keys = encryption
+        .get_retired_keys()
+        .iter()
+        .map(|wrapped| {
+            let payload = hex::decode(wrapped).map_err(FsError::custom)?;
+            let bytes = cryptdag::decrypt(&key, &payload)?;
+            let bytes = bytes
+                .try_into()
+                .map_err(|_| FsError::InvalidEncryptionKey("malformed retired key".to_string()))?;
+            Ok(SecretKey::from_bytes(bytes))
+        })
+        .collect::<FsResult<_>>()?;
+
+    Ok(Some(Keyring::new(key, retired_keys)))
+}
+
+/// Fail if the filesystem mounted at `mount_dir` is encrypted
+///
+/// Used by operations that exchange blocks with other filesystems, which can't follow the links
+/// of encrypted blocks.
+pub(super) async fn ensure_unencrypted(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    operation: &str,
+) -> FsResult<()> {
+    let fs_config = config::get_fs_config(fs_db_path, mount_dir.as_ref()).await?;
+    if fs_config.get_encryption().is_some() {
+        return Err(FsError::EncryptedFilesystem(format!(
+            "cannot {} {}",
+            operation,
+            mount_dir.as_ref().display()
+        )));
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Derive a new key from `source` with a fresh salt and store the encryption configuration of
+/// the filesystem mounted at `mount_dir`
+async fn setup_encryption(
+    fs_db_path: &Path,
+    mount_dir: &Path,
+    source: &KeySource,
+) -> FsResult<Keyring> {
+    let salt: [u8; SALT_SIZE] = rand::random();
+    let keyring = Keyring::new(source.derive_key(&salt).await?, Vec::new());
+
+    let mut fs_config = config::get_fs_config(fs_db_path, mount_dir).await?;
+    fs_config.set_encryption(Some(get_encryption(&keyring, &salt)));
+    config::set_fs_config(fs_db_path, mount_dir, &fs_config).await?;
+    tracing::info!(
+        "encrypting {} with key {}",
+        mount_dir.display(),
+        keyring.get_key().get_id()
+    );
+
+    Ok(keyring)
+}
+
+/// Get the encryption configuration of a keyring whose key was derived with `salt`
+fn get_encryption(keyring: &Keyring, salt: &[u8]) -> Encryption {
+    let retired_keys = keyring
+        .retired_keys
+        .iter()
+        .map(|retired| hex::encode(cryptdag::encrypt(&keyring.key, retired.as_bytes())))
+        .collect();
+
+    Encryption::new(
+        hex::encode(salt),
+        keyring.key.get_id().to_string(),
+        retired_keys,
+    )
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Debug for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passphrase(_) => f.write_str("Passphrase(..)"),
+            Self::Keyfile(path) => f.debug_tuple("Keyfile").field(path).finish(),
+        }
+    }
+}
+
+impl FromStr for Keyring {
+    type Err = FsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = s
+            .split(KEYRING_SEPARATOR)
+            .map(|key| key.parse::<SecretKey>().map_err(FsError::from));
+        let key = keys
+            .next()
+            .ok_or_else(|| FsError::InvalidEncryptionKey("empty keyring".to_string()))??;
+
+        Ok(Self::new(key, keys.collect::<FsResult<_>>()?))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::Storable;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    use crate::{
+        filesystem::{Dir, File},
+        management::{db, FS_DB_MIGRATOR},
+        utils::path,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encrypted_mfs_unlock_and_rotate_key() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mount_dir = temp_dir.path().join("mfs");
+        fs::create_dir_all(&mount_dir).await?;
+        let mount_dir = fs::canonicalize(&mount_dir).await?;
+        let mfs_data_dir = path::get_mfs_data_dir(&mount_dir);
+        let blocks_dir = mfs_data_dir.join(BLOCKS_SUBDIR);
+        fs::create_dir_all(&blocks_dir).await?;
+        let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+        db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+        let old_source = KeySource::Passphrase("correct horse".to_string());
+        let keyring = setup_encryption(&fs_db_path, &mount_dir, &old_source).await?;
+
+        // Names, contents and extended attributes only ever reach the disk encrypted
+        let store = keyring.open_store(FlatFsStore::new(&blocks_dir));
+        let mut root = Dir::new(store.clone());
+        let mut file = File::with_content(store.clone(), &b"top secret contents"[..]).await?;
+        file.get_metadata_mut()
+            .set_attribute("user.label", "classified label")
+            .await?;
+        root.put_adapted_file("secret-name.txt", file).await?;
+        let old_head = root.store().await?;
+        head::set_head(&fs_db_path, &mount_dir, &old_head, None).await?;
+
+        let mut dirs = vec![blocks_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+
+                let block = fs::read(entry.path()).await?;
+                for plaintext in [&b"secret-name"[..], b"top secret", b"classified"] {
+                    assert!(!block.windows(plaintext.len()).any(|w| w == plaintext));
+                }
+            }
+        }
+
+        // Only the right passphrase unlocks the filesystem
+        let wrong = KeySource::Passphrase("wrong horse".to_string());
+        assert!(matches!(
+            unlock_mfs(&fs_db_path, &mount_dir, Some(wrong)).await,
+            Err(FsError::InvalidEncryptionKey(_))
+        ));
+        let unlocked = unlock_mfs(&fs_db_path, &mount_dir, Some(old_source.clone()))
+            .await?
+            .unwrap();
+        assert_eq!(unlocked.get_key(), keyring.get_key());
+        assert_eq!(
+            unlocked.encode().parse::<Keyring>()?.get_key(),
+            keyring.get_key()
+        );
+
+        // Rotation re-encrypts the head root on top of the old head
+        let new_source = KeySource::Passphrase("battery staple".to_string());
+        let new_head = rotate_key_mfs(&mount_dir, old_source.clone(), new_source.clone())
+            .await?
+            .unwrap();
+        assert_eq!(
+            head::get_head(&fs_db_path, &mount_dir).await?,
+            Some(new_head)
+        );
+        assert!(unlock_mfs(&fs_db_path, &mount_dir, Some(old_source))
+            .await
+            .is_err());
+
+        // The new key alone reads the new head and every revision before it
+        let keyring = unlock_mfs(&fs_db_path, &mount_dir, Some(new_source))
+            .await?
+            .unwrap();
+        assert_eq!(keyring.get_retired_keys(), [unlocked.get_key().clone()]);
+        let store = keyring.open_store(FlatFsStore::new(&blocks_dir));
+        let root = Dir::load(&new_head, store.clone()).await?;
+        assert_eq!(root.get_previous(), Some(&old_head));
+
+        let file = root.get_file("secret-name.txt").await?.unwrap();
+        let mut content = String::new();
+        file.get_input_stream()
+            .await?
+            .read_to_string(&mut content)
+            .await?;
+        assert_eq!(content, "top secret contents");
+        assert!(file
+            .get_metadata()
+            .get_attribute("user.label")
+            .await?
+            .is_some());
+        assert!(Dir::load(&old_head, store).await.is_ok());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use std::path::{Path, PathBuf};
 use tokio::{fs, net::TcpListener};
 
-use crate::{utils::path::MFS_LINK_FILENAME, FsError, FsResult};
+use crate::{
+    utils::path::{get_mfs_data_dir, MFS_LINK_FILENAME},
+    FsError, FsResult,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Constants
@@ -17,8 +20,9 @@ const MAX_MFS_ROOT_SEARCH_DEPTH: u32 = 10;
 /// Find the MFS root directory by searching up the directory tree for the MFS link file.
 ///
 /// This function starts from the given path and traverses up the directory hierarchy
-/// looking for the MFS link file (`.mfs_link`). It will search up to [`MAX_MFS_ROOT_SEARCH_DEPTH`]
-/// parent directories before giving up.
+/// looking for the MFS link file (`.mfs_link`) or an adjacent `.mfs` data directory. The latter
+/// is how read-only mounts, which cannot hold a link file, are found. It will search up to
+/// [`MAX_MFS_ROOT_SEARCH_DEPTH`] parent directories before giving up.
 pub async fn find_mfs_root(start_path: impl AsRef<Path>) -> FsResult<PathBuf> {
     let start_path = start_path.as_ref();
     let canonical_start = fs::canonicalize(start_path).await?;
@@ -31,6 +35,11 @@ pub async fn find_mfs_root(start_path: impl AsRef<Path>) -> FsResult<PathBuf> {
             return Ok(current);
         }
 
+        let mfs_data_dir = get_mfs_data_dir(&current);
+        if fs::metadata(&mfs_data_dir).await.is_ok() {
+            return Ok(current);
+        }
+
         if let Some(parent) = current.parent() {
             current = parent.to_path_buf();
             depth += 1;
@@ -126,6 +135,24 @@ mod tests {
         temp.close().unwrap();
     }
 
+    #[test]
+    async fn test_find_mfs_root_with_adjacent_data_dir() {
+        let (temp, mut path) = helper::setup_test_dir(3).await;
+
+        // Create the data directory next to the middle directory
+        path.pop();
+        fs::create_dir(get_mfs_data_dir(&path)).await.unwrap();
+
+        // Search from the deepest directory
+        path.push("dir_2");
+        let result = find_mfs_root(&path).await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().file_name().unwrap(), "dir_1");
+
+        temp.close().unwrap();
+    }
+
     #[test]
     async fn test_find_mfs_root_from_nonexistent_path() {
         let result = find_mfs_root("/nonexistent/path").await;
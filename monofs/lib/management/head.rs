@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use ipldstore::ipld::cid::Cid;
+use sqlx::Row;
+
+use crate::{management::db, FsError, FsResult};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Get the head revision of the filesystem mounted at `mount_dir`
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+///
+/// ## Returns
+/// The CID of the current root directory, or None if nothing has been checkpointed yet
+pub async fn get_head(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+) -> FsResult<Option<Cid>> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref().to_string_lossy().to_string();
+
+    let record =
+        sqlx::query("SELECT head FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1")
+            .bind(mount_dir)
+            .fetch_optional(&pool)
+            .await?;
+
+    parse_head(record.and_then(|row| row.get::<Option<String>, _>("head")))
+}
+
+/// Atomically move the head of the filesystem mounted at `mount_dir` from `expected` to `head`
+///
+/// The head is only moved if it is still `expected`, otherwise [`FsError::HeadConflict`] is
+/// returned and nothing is changed. A filesystem entry is created if there is none yet.
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+/// * `head` - The CID of the new root directory
+/// * `expected` - The head the caller last saw, or None if it saw no head
+pub async fn set_head(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    head: &Cid,
+    expected: Option<&Cid>,
+) -> FsResult<()> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref();
+    let mount_dir_str = mount_dir.to_string_lossy().to_string();
+
+    let mut tx = pool.begin().await?;
+    let record = sqlx::query(
+        "SELECT id, head FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(&mount_dir_str)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (id, found) = match record {
+        Some(row) => (
+            Some(row.get::<i64, _>("id")),
+            parse_head(row.get::<Option<String>, _>("head"))?,
+        ),
+        None => (None, None),
+    };
+
+    if found.as_ref() != expected {
+        return Err(FsError::HeadConflict(found));
+    }
+
+    match id {
+        Some(id) => {
+            sqlx::query(
+                "UPDATE filesystems SET head = ?, modified_at = CURRENT_TIMESTAMP WHERE id = ?",
+            )
+            .bind(head.to_string())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        None => {
+            let name = mount_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| mount_dir_str.clone());
+
+            sqlx::query("INSERT INTO filesystems (name, mount_dir, head) VALUES (?, ?, ?)")
+                .bind(name)
+                .bind(&mount_dir_str)
+                .bind(head.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+    tracing::info!("moved head of {} to {}", mount_dir.display(), head);
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Parse a head column value into a CID
+fn parse_head(head: Option<String>) -> FsResult<Option<Cid>> {
+    head.map(|head| Cid::try_from(head.as_str()).map_err(FsError::from))
+        .transpose()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{utils, Codec};
+    use tempfile::TempDir;
+
+    use crate::management::FS_DB_MIGRATOR;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_head_compare_and_swap() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let fs_db_path = temp_dir.path().join("fs.db");
+        let mount_dir = temp_dir.path().join("mfs");
+        db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+        let first = utils::generate_cid(Codec::DagCbor, b"first");
+        let second = utils::generate_cid(Codec::DagCbor, b"second");
+
+        // No head until something is set
+        assert_eq!(get_head(&fs_db_path, &mount_dir).await?, None);
+
+        set_head(&fs_db_path, &mount_dir, &first, None).await?;
+        assert_eq!(get_head(&fs_db_path, &mount_dir).await?, Some(first));
+
+        // A stale expectation is rejected and leaves the head alone
+        let result = set_head(&fs_db_path, &mount_dir, &second, None).await;
+        assert!(matches!(
+            result,
+            Err(FsError::HeadConflict(Some(found))) if found == first
+        ));
+        assert_eq!(get_head(&fs_db_path, &mount_dir).await?, Some(first));
+
+        set_head(&fs_db_path, &mount_dir, &second, Some(&first)).await?;
+        assert_eq!(get_head(&fs_db_path, &mount_dir).await?, Some(second));
+
+        Ok(())
+    }
+}
@@ -1,26 +1,42 @@
 use crate::{
     config::{DEFAULT_HOST, DEFAULT_MFSRUN_BIN_PATH, DEFAULT_NFS_PORT},
-    management::{db, find, FS_DB_MIGRATOR},
+    filesystem::Dir,
+    management::{db, encryption, find, Keyring, FS_DB_MIGRATOR},
+    store::FlatFsStore,
     utils::{
-        path::{BLOCKS_SUBDIR, FS_DB_FILENAME, LOG_SUBDIR, MFS_DIR_SUFFIX, MFS_LINK_FILENAME},
-        MFSRUN_BIN_PATH_ENV_VAR,
+        path::{self, BLOCKS_SUBDIR, FS_DB_FILENAME, LOG_SUBDIR, MFS_LINK_FILENAME},
+        ENCRYPTION_KEYS_ENV_VAR, KEYFILE_ENV_VAR, MFSRUN_BIN_PATH_ENV_VAR, PASSPHRASE_ENV_VAR,
     },
     FsError, FsResult,
 };
+use ipldstore::{ipld::cid::Cid, Storable};
 use nix::{
     sys::signal::{self, Signal},
     unistd::Pid,
 };
 use sqlx::Row;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
 use tokio::{fs, net::TcpStream, process::Command, time, time::Instant};
 
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The prefix of the generated mount directories of temporary filesystems
+const TMP_MOUNT_DIR_PREFIX: &str = "monofs-tmp-";
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
 
 /// Initialize a new monofs filesystem at the specified path and mount it
 ///
+/// If the path holds an encrypted filesystem, it is unlocked with the key derived from the
+/// `MONOFS_KEYFILE` or `MONOFS_PASSPHRASE` environment variable.
+///
 /// ## Arguments
 /// * `mount_dir` - The path where the filesystem will be initialized and mounted. If None, uses current directory
 ///
@@ -37,89 +53,266 @@ use tokio::{fs, net::TcpStream, process::Command, time, time::Instant};
 /// # }
 /// ```
 pub async fn init_mfs(mount_dir: Option<PathBuf>) -> FsResult<u32> {
-    // Default to current directory if no path specified
-    let mount_dir = mount_dir.unwrap_or_else(|| PathBuf::from("."));
-    fs::create_dir_all(&mount_dir).await?;
+    let (mount_dir, mfs_data_dir) = init_mfs_data_dir(mount_dir).await?;
+    let keyring =
+        encryption::unlock_mfs(mfs_data_dir.join(FS_DB_FILENAME), &mount_dir, None).await?;
+    start_mfs(&mount_dir, &mfs_data_dir, keyring.as_ref()).await
+}
 
-    // Ensure the mount directory is absolute
+/// Create a temporary monofs filesystem and mount it at a generated path
+///
+/// The filesystem is backed by a store in a temporary data directory, or kept entirely in memory
+/// if `in_memory` is set. Everything, blocks included, is removed when the filesystem is detached
+/// or its supervisor exits.
+///
+/// ## Arguments
+/// * `in_memory` - Whether to keep the filesystem in memory instead of a temporary store directory
+///
+/// ## Returns
+/// The path where the filesystem is mounted
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let mount_dir = management::init_tmp_mfs(false).await?;
+/// println!("scratch space available at {}", mount_dir.display());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn init_tmp_mfs(in_memory: bool) -> FsResult<PathBuf> {
+    // Generate the mount directory
+    let mount_dir = tempfile::Builder::new()
+        .prefix(TMP_MOUNT_DIR_PREFIX)
+        .tempdir()?
+        .into_path();
     let mount_dir = fs::canonicalize(&mount_dir).await?;
     tracing::info!("mount point available at {}", mount_dir.display());
 
     // Create the .mfs directory adjacent to the mount point
-    let mfs_data_dir = PathBuf::from(format!("{}.{}", mount_dir.display(), MFS_DIR_SUFFIX));
-    fs::create_dir_all(&mfs_data_dir).await?;
+    let mfs_data_dir = path::get_mfs_data_dir(&mount_dir);
+    let log_dir = mfs_data_dir.join(LOG_SUBDIR);
+    fs::create_dir_all(&log_dir).await?;
     tracing::info!(".mfs directory available at {}", mfs_data_dir.display());
 
+    // Initialize the filesystem database schema
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+    db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+    tracing::info!("initialized fs database schema");
+
+    // Create the blocks directory unless the filesystem lives in memory
+    let blocks_dir = mfs_data_dir.join(BLOCKS_SUBDIR);
+    if !in_memory {
+        fs::create_dir_all(&blocks_dir).await?;
+        tracing::info!("blocks directory available at {}", blocks_dir.display());
+    }
+
     // Find an available port
     let port = super::find_available_port(DEFAULT_HOST, DEFAULT_NFS_PORT).await?;
     tracing::info!("found available port: {}", port);
 
-    // Create required directories
-    let log_dir = mfs_data_dir.join(LOG_SUBDIR);
-    fs::create_dir_all(&log_dir).await?;
-    tracing::info!("log directory available at {}", log_dir.display());
+    // Start the supervisor process
+    tracing::info!("mounting the temporary filesystem...");
+    let supervisor_pid = spawn_supervisor(
+        &mount_dir,
+        &mfs_data_dir,
+        (!in_memory).then_some(blocks_dir.as_path()),
+        port,
+        None,
+        true,
+        None,
+    )?;
 
-    // Create the fs database file
-    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
-    if !fs_db_path.exists() {
-        fs::File::create(&fs_db_path).await?;
-        tracing::info!("created fs database at {}", fs_db_path.display());
+    // Mount the filesystem
+    if let Err(e) = mount_fs(&mount_dir, DEFAULT_HOST, port).await {
+        // Stop the supervisor, which removes the temporary filesystem on exit
+        if let Err(e) = signal::kill(Pid::from_raw(supervisor_pid as i32), Signal::SIGTERM) {
+            tracing::warn!(
+                "failed to send SIGTERM to supervisor process {}: {}",
+                supervisor_pid,
+                e
+            );
+        }
+
+        return Err(e);
     }
+    tracing::info!("mounted temporary filesystem at {}", mount_dir.display());
 
-    // Initialize the filesystem database schema
-    db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
-    tracing::info!("initialized fs database schema");
+    // Create symbolic link to mfs_data_dir in mount directory
+    let link_path = mount_dir.join(MFS_LINK_FILENAME);
+    fs::symlink(&mfs_data_dir, &link_path).await?;
+    tracing::info!("created symbolic link at {}", link_path.display());
 
-    // Create the blocks directory
-    let blocks_dir = mfs_data_dir.join(BLOCKS_SUBDIR);
-    fs::create_dir_all(&blocks_dir).await?;
-    tracing::info!("blocks directory available at {}", blocks_dir.display());
+    Ok(mount_dir)
+}
 
-    // Start the supervisor process
-    let child_name = mount_dir
-        .file_name()
-        .map(|name| name.to_string_lossy().to_string())
-        .expect("failed to get file name for mount point");
+/// Remove a temporary monofs filesystem along with all its data
+///
+/// This force-unmounts the filesystem if it is still mounted and then deletes the `.mfs` data
+/// directory, blocks included, and the mount point. It is run by the supervisor of a temporary
+/// filesystem when it exits.
+///
+/// ## Arguments
+/// * `mount_dir` - The path where the temporary filesystem is mounted
+pub async fn remove_tmp_mfs(mount_dir: impl AsRef<Path>) -> FsResult<()> {
+    let mount_dir = mount_dir.as_ref();
 
-    let mfsrun_path =
-        monoutils::path::resolve_binary_path(MFSRUN_BIN_PATH_ENV_VAR, DEFAULT_MFSRUN_BIN_PATH)?;
+    // Unmount the filesystem in case the supervisor exited on its own
+    let status = Command::new("umount")
+        .arg("-f")
+        .arg(mount_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await?;
 
-    tracing::info!("mounting the filesystem...");
-    let status = Command::new(mfsrun_path)
-        .arg("supervisor")
-        .arg("--log-dir")
-        .arg(&log_dir)
-        .arg("--child-name")
-        .arg(child_name)
-        .arg("--host")
-        .arg(DEFAULT_HOST)
-        .arg("--port")
-        .arg(port.to_string())
-        .arg("--store-dir")
-        .arg(&blocks_dir)
-        .arg("--fs-db-path")
-        .arg(&fs_db_path)
-        .arg("--mount-dir")
-        .arg(&mount_dir)
-        .spawn()?;
+    if status.success() {
+        tracing::info!("unmounted filesystem at {}", mount_dir.display());
+    }
 
-    tracing::info!(
-        "started supervisor process with PID: {}",
-        status.id().unwrap_or(0)
-    );
+    // Remove the data directory, blocks included
+    let mfs_data_dir = path::get_mfs_data_dir(mount_dir);
+    if fs::metadata(&mfs_data_dir).await.is_ok() {
+        fs::remove_dir_all(&mfs_data_dir).await?;
+        tracing::info!("removed .mfs directory at {}", mfs_data_dir.display());
+    }
+
+    // Remove the mount point
+    if let Err(e) = fs::remove_dir(mount_dir).await {
+        tracing::warn!(
+            "failed to remove mount point {}: {}",
+            mount_dir.display(),
+            e
+        );
+    }
+
+    Ok(())
+}
+
+/// Mount a revision of a monofs filesystem in read-only mode
+///
+/// The revision is served straight from the blocks of the filesystem it belongs to, so nothing is
+/// copied. Every mutating operation on the mount fails with a read-only filesystem error. The
+/// mount gets its own `.mfs` data directory next to the mount point, which is where it is tracked
+/// so that [`detach_mfs`] works as usual.
+///
+/// Revisions of encrypted filesystems are unlocked with the key derived from the
+/// `MONOFS_KEYFILE` or `MONOFS_PASSPHRASE` environment variable.
+///
+/// ## Arguments
+/// * `rev` - The CID of a directory revision or the name of a tag
+/// * `mount_dir` - The path where the revision will be mounted
+/// * `source` - A path within the filesystem the revision belongs to. If None, uses current directory
+///
+/// ## Returns
+/// The port number that was successfully used for mounting
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// management::mount_rev("v1.0", "mfstest-v1.0", Some("mfstest".into())).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn mount_rev(
+    rev: impl AsRef<str>,
+    mount_dir: impl Into<PathBuf>,
+    source: Option<PathBuf>,
+) -> FsResult<u32> {
+    // Find the filesystem the revision belongs to
+    let source = source.unwrap_or_else(|| PathBuf::from("."));
+    let source_root = find::find_mfs_root(&source).await?;
+    let source_data_dir = get_mfs_data_dir(&source_root).await?;
+    let source_blocks_dir = source_data_dir.join(BLOCKS_SUBDIR);
+    tracing::info!("found source filesystem at {}", source_root.display());
+
+    // Resolve the revision and make sure it is a directory in the source store
+    let source_db_path = source_data_dir.join(FS_DB_FILENAME);
+    let root_cid = resolve_revision(&source_db_path, rev).await?;
+    let keyring = encryption::unlock_mfs(&source_db_path, &source_root, None).await?;
+    let store = FlatFsStore::new(&source_blocks_dir);
+    let loaded = match &keyring {
+        Some(keyring) => Dir::load(&root_cid, keyring.open_store(store))
+            .await
+            .map(|_| ()),
+        None => Dir::load(&root_cid, store).await.map(|_| ()),
+    };
+    loaded.map_err(|e| FsError::RevisionNotFound(format!("{root_cid}: {e}")))?;
+    tracing::info!("resolved revision to {}", root_cid);
+
+    // Ensure the mount directory is absolute
+    let mount_dir = mount_dir.into();
+    fs::create_dir_all(&mount_dir).await?;
+    let mount_dir = fs::canonicalize(&mount_dir).await?;
+
+    // Create the .mfs directory adjacent to the mount point
+    let mfs_data_dir = path::get_mfs_data_dir(&mount_dir);
+    fs::create_dir_all(mfs_data_dir.join(LOG_SUBDIR)).await?;
+    tracing::info!(".mfs directory available at {}", mfs_data_dir.display());
+
+    // Initialize the filesystem database used to track the mount
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+    db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+    // Find an available port
+    let port = super::find_available_port(DEFAULT_HOST, DEFAULT_NFS_PORT).await?;
+    tracing::info!("found available port: {}", port);
+
+    // Start the supervisor process serving the revision from the source blocks
+    tracing::info!("mounting revision {}...", root_cid);
+    spawn_supervisor(
+        &mount_dir,
+        &mfs_data_dir,
+        Some(&source_blocks_dir),
+        port,
+        Some(&root_cid),
+        false,
+        keyring.as_ref(),
+    )?;
 
     // Mount the filesystem
     mount_fs(&mount_dir, DEFAULT_HOST, port).await?;
-    tracing::info!("mounted filesystem at {}", mount_dir.display());
+    tracing::info!("mounted revision {} at {}", root_cid, mount_dir.display());
 
-    // Create symbolic link to mfs_data_dir in mount directory
-    let link_path = mount_dir.join(MFS_LINK_FILENAME);
-    if !link_path.exists() {
-        fs::symlink(&mfs_data_dir, &link_path).await?;
-        tracing::info!("created symbolic link at {}", link_path.display());
+    Ok(port)
+}
+
+/// Resolve a revision given as a CID or a tag name to the CID of its root directory
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the database of the filesystem the tags belong to
+/// * `rev` - The CID of a revision or the name of a tag
+pub async fn resolve_revision(fs_db_path: impl AsRef<Path>, rev: impl AsRef<str>) -> FsResult<Cid> {
+    let rev = rev.as_ref();
+
+    // A valid CID is used as is
+    if let Ok(cid) = Cid::try_from(rev) {
+        return Ok(cid);
     }
 
-    Ok(port)
+    // Otherwise look up the most recent tag with that name
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let record = sqlx::query(
+        r#"
+        SELECT root_revision FROM tags
+        WHERE name = ?
+        ORDER BY created_at DESC, id DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(rev)
+    .fetch_optional(&pool)
+    .await?;
+
+    match record {
+        Some(row) => Ok(Cid::try_from(
+            row.get::<String, _>("root_revision").as_str(),
+        )?),
+        None => Err(FsError::RevisionNotFound(rev.to_string())),
+    }
 }
 
 /// Detach a monofs filesystem by finding its root and unmounting it
@@ -202,9 +395,109 @@ pub async fn detach_mfs(mount_dir: Option<PathBuf>, force: bool) -> FsResult<()>
     Ok(())
 }
 
-/// Get the filesystem database path from the MFS root directory
-async fn get_fs_db_path(mfs_root: impl AsRef<Path>) -> FsResult<PathBuf> {
+/// Create the mount point and the `.mfs` data directory of a new filesystem
+///
+/// ## Arguments
+/// * `mount_dir` - The path where the filesystem will be mounted. If None, uses current directory
+///
+/// ## Returns
+/// The absolute mount directory and the data directory next to it
+pub(super) async fn init_mfs_data_dir(mount_dir: Option<PathBuf>) -> FsResult<(PathBuf, PathBuf)> {
+    // Default to current directory if no path specified
+    let mount_dir = mount_dir.unwrap_or_else(|| PathBuf::from("."));
+    fs::create_dir_all(&mount_dir).await?;
+
+    // Ensure the mount directory is absolute
+    let mount_dir = fs::canonicalize(&mount_dir).await?;
+    tracing::info!("mount point available at {}", mount_dir.display());
+
+    // Create the .mfs directory adjacent to the mount point
+    let mfs_data_dir = path::get_mfs_data_dir(&mount_dir);
+    fs::create_dir_all(&mfs_data_dir).await?;
+    tracing::info!(".mfs directory available at {}", mfs_data_dir.display());
+
+    // Create required directories
+    let log_dir = mfs_data_dir.join(LOG_SUBDIR);
+    fs::create_dir_all(&log_dir).await?;
+    tracing::info!("log directory available at {}", log_dir.display());
+
+    // Create the fs database file
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+    if !fs_db_path.exists() {
+        fs::File::create(&fs_db_path).await?;
+        tracing::info!("created fs database at {}", fs_db_path.display());
+    }
+
+    // Initialize the filesystem database schema
+    db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+    tracing::info!("initialized fs database schema");
+
+    // Create the blocks directory
+    let blocks_dir = mfs_data_dir.join(BLOCKS_SUBDIR);
+    fs::create_dir_all(&blocks_dir).await?;
+    tracing::info!("blocks directory available at {}", blocks_dir.display());
+
+    Ok((mount_dir, mfs_data_dir))
+}
+
+/// Start the supervisor of a filesystem initialized with [`init_mfs_data_dir`] and mount it
+///
+/// ## Arguments
+/// * `mount_dir` - The absolute path where the filesystem will be mounted
+/// * `mfs_data_dir` - The `.mfs` data directory of the filesystem
+/// * `keyring` - The keys of the filesystem, if it is encrypted
+///
+/// ## Returns
+/// The port number that was successfully used for mounting
+pub(super) async fn start_mfs(
+    mount_dir: &Path,
+    mfs_data_dir: &Path,
+    keyring: Option<&Keyring>,
+) -> FsResult<u32> {
+    // Find an available port
+    let port = super::find_available_port(DEFAULT_HOST, DEFAULT_NFS_PORT).await?;
+    tracing::info!("found available port: {}", port);
+
+    // Start the supervisor process
+    tracing::info!("mounting the filesystem...");
+    spawn_supervisor(
+        mount_dir,
+        mfs_data_dir,
+        Some(&mfs_data_dir.join(BLOCKS_SUBDIR)),
+        port,
+        None,
+        false,
+        keyring,
+    )?;
+
+    // Mount the filesystem
+    mount_fs(mount_dir, DEFAULT_HOST, port).await?;
+    tracing::info!("mounted filesystem at {}", mount_dir.display());
+
+    // Create symbolic link to mfs_data_dir in mount directory
+    let link_path = mount_dir.join(MFS_LINK_FILENAME);
+    if !link_path.exists() {
+        fs::symlink(mfs_data_dir, &link_path).await?;
+        tracing::info!("created symbolic link at {}", link_path.display());
+    }
+
+    Ok(port)
+}
+
+/// Get the MFS data directory of the MFS root directory
+///
+/// The data directory adjacent to the mount point is preferred over the one the MFS link points
+/// to, since a mounted revision may carry the link of the filesystem it was taken from.
+pub(super) async fn get_mfs_data_dir(mfs_root: impl AsRef<Path>) -> FsResult<PathBuf> {
     let mfs_root = mfs_root.as_ref();
+
+    // Check for the data directory next to the mount point
+    let mfs_data_dir = path::get_mfs_data_dir(mfs_root);
+    if fs::metadata(&mfs_data_dir).await.is_ok() {
+        tracing::info!("MFS data dir: {}", mfs_data_dir.display());
+        return Ok(mfs_data_dir);
+    }
+
     let mfs_link = mfs_root.join(MFS_LINK_FILENAME);
 
     tracing::info!("MFS link path: {}", mfs_link.display());
@@ -214,6 +507,12 @@ async fn get_fs_db_path(mfs_root: impl AsRef<Path>) -> FsResult<PathBuf> {
 
     tracing::info!("MFS data dir: {}", mfs_data_dir.display());
 
+    Ok(mfs_data_dir)
+}
+
+/// Get the filesystem database path from the MFS root directory
+async fn get_fs_db_path(mfs_root: impl AsRef<Path>) -> FsResult<PathBuf> {
+    let mfs_data_dir = get_mfs_data_dir(mfs_root).await?;
     let db_path = mfs_data_dir.join(FS_DB_FILENAME);
 
     tracing::info!("DB path: {}", db_path.display());
@@ -221,6 +520,19 @@ async fn get_fs_db_path(mfs_root: impl AsRef<Path>) -> FsResult<PathBuf> {
     Ok(db_path)
 }
 
+/// Check whether the filesystem mounted at `mount_dir` is being served, i.e. whether its
+/// supervisor process is running
+pub(super) async fn is_mounted(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+) -> FsResult<bool> {
+    let Some(supervisor_pid) = get_supervisor_pid(fs_db_path, mount_dir).await? else {
+        return Ok(false);
+    };
+
+    Ok(nix::unistd::getpgid(Some(Pid::from_raw(supervisor_pid))).is_ok())
+}
+
 /// Get the supervisor PID for a mount directory from the filesystem database
 async fn get_supervisor_pid(
     fs_db_path: impl AsRef<Path>,
@@ -337,6 +649,77 @@ async fn mount_fs(mount_dir: impl AsRef<Path>, host: &str, port: u32) -> FsResul
 // Functions: Helpers
 //--------------------------------------------------------------------------------------------------
 
+/// Spawn an `mfsrun` supervisor that serves a store over NFS for the given mount directory.
+///
+/// If `store_dir` is not set, the NFS server keeps the filesystem in memory. If `rev` is set, the
+/// NFS server serves that directory revision in read-only mode. If `ephemeral` is set, the
+/// supervisor removes the mount point and all filesystem data when it exits. If `keyring` is set,
+/// the NFS server encrypts the store with it.
+///
+/// Returns the PID of the supervisor process.
+fn spawn_supervisor(
+    mount_dir: &Path,
+    mfs_data_dir: &Path,
+    store_dir: Option<&Path>,
+    port: u32,
+    rev: Option<&Cid>,
+    ephemeral: bool,
+    keyring: Option<&Keyring>,
+) -> FsResult<u32> {
+    let child_name = mount_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .expect("failed to get file name for mount point");
+
+    let mfsrun_path =
+        monoutils::path::resolve_binary_path(MFSRUN_BIN_PATH_ENV_VAR, DEFAULT_MFSRUN_BIN_PATH)?;
+
+    let mut command = Command::new(mfsrun_path);
+    command
+        .arg("supervisor")
+        .arg("--log-dir")
+        .arg(mfs_data_dir.join(LOG_SUBDIR))
+        .arg("--child-name")
+        .arg(child_name)
+        .arg("--host")
+        .arg(DEFAULT_HOST)
+        .arg("--port")
+        .arg(port.to_string())
+        .arg("--fs-db-path")
+        .arg(mfs_data_dir.join(FS_DB_FILENAME))
+        .arg("--mount-dir")
+        .arg(mount_dir);
+
+    match store_dir {
+        Some(store_dir) => command.arg("--store-dir").arg(store_dir),
+        None => command.arg("--memory"),
+    };
+
+    if let Some(cid) = rev {
+        command.arg("--rev").arg(cid.to_string());
+    }
+
+    if ephemeral {
+        command.arg("--ephemeral");
+    }
+
+    // Hand the derived keys down through the environment rather than the command line, and keep
+    // what they were derived from out of the long-running processes
+    if let Some(keyring) = keyring {
+        command.env(ENCRYPTION_KEYS_ENV_VAR, keyring.encode());
+    }
+    command
+        .env_remove(PASSPHRASE_ENV_VAR)
+        .env_remove(KEYFILE_ENV_VAR);
+
+    let child = command.spawn()?;
+    let supervisor_pid = child.id().unwrap_or(0);
+
+    tracing::info!("started supervisor process with PID: {}", supervisor_pid);
+
+    Ok(supervisor_pid)
+}
+
 /// Wait for the given host and port to become available.
 ///
 /// This function tries to open a TCP connection to the address. If it fails,
@@ -363,3 +746,80 @@ async fn wait_for_port(host: &str, port: u32) {
         }
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipldstore::MemoryStore;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_resolve_revision() -> anyhow::Result<()> {
+        let temp_dir = tempdir()?;
+        let db_path = temp_dir.path().join(FS_DB_FILENAME);
+        db::init_db(&db_path, &FS_DB_MIGRATOR).await?;
+
+        // Create a revision to tag
+        let mut root = Dir::new(MemoryStore::default());
+        root.create_file("hello.txt").await?;
+        let cid = root.checkpoint().await?;
+
+        // A CID resolves to itself
+        assert_eq!(resolve_revision(&db_path, cid.to_string()).await?, cid);
+
+        // A tag resolves to its root revision
+        let pool = db::get_db_pool(&db_path).await?;
+        let fs_id = sqlx::query("INSERT INTO filesystems (name, mount_dir) VALUES (?, ?)")
+            .bind("mfstest")
+            .bind("/mfstest")
+            .execute(&pool)
+            .await?
+            .last_insert_rowid();
+        sqlx::query("INSERT INTO tags (fs_id, root_revision, path, name) VALUES (?, ?, ?, ?)")
+            .bind(fs_id)
+            .bind(cid.to_string())
+            .bind("")
+            .bind("v1.0")
+            .execute(&pool)
+            .await?;
+
+        assert_eq!(resolve_revision(&db_path, "v1.0").await?, cid);
+
+        // Unknown tags are reported
+        assert!(matches!(
+            resolve_revision(&db_path, "v2.0").await,
+            Err(FsError::RevisionNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remove_tmp_mfs() -> anyhow::Result<()> {
+        let temp_dir = tempdir()?;
+
+        // Lay out an unmounted temporary filesystem
+        let mount_dir = temp_dir.path().join("monofs-tmp-test");
+        let mfs_data_dir = path::get_mfs_data_dir(&mount_dir);
+        fs::create_dir_all(&mount_dir).await?;
+        fs::create_dir_all(mfs_data_dir.join(BLOCKS_SUBDIR)).await?;
+        fs::create_dir_all(mfs_data_dir.join(LOG_SUBDIR)).await?;
+        fs::write(mfs_data_dir.join(BLOCKS_SUBDIR).join("block"), b"data").await?;
+        db::init_db(mfs_data_dir.join(FS_DB_FILENAME), &FS_DB_MIGRATOR).await?;
+
+        remove_tmp_mfs(&mount_dir).await?;
+
+        // Everything is gone, blocks included
+        assert!(!mfs_data_dir.exists());
+        assert!(!mount_dir.exists());
+
+        // Removing it again is harmless
+        remove_tmp_mfs(&mount_dir).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+-- Add down migration script here
+
+-- Drop indexes
+DROP INDEX IF EXISTS idx_raft_members_fs_id;
+
+-- Drop table
+DROP TABLE IF EXISTS raft_members;
+
+-- Drop columns
+ALTER TABLE filesystems DROP COLUMN raft_state;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- Persist the Raft term, vote and log of filesystems whose head is replicated with Raft
+ALTER TABLE filesystems ADD COLUMN raft_state TEXT;
+
+-- Create raft members table
+CREATE TABLE IF NOT EXISTS raft_members (
+    id INTEGER PRIMARY KEY,
+    fs_id INTEGER NOT NULL,
+    node_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (fs_id) REFERENCES filesystems(id) ON DELETE CASCADE,
+    UNIQUE (fs_id, node_id)
+);
+
+-- Create indexes for common queries
+CREATE INDEX idx_raft_members_fs_id ON raft_members(fs_id);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+-- Drop indexes
+DROP INDEX IF EXISTS idx_revision_signatures_fs_id;
+
+-- Drop table
+DROP TABLE IF EXISTS revision_signatures;
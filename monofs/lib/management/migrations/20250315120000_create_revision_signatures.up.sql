@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+-- Create revision signatures table
+CREATE TABLE IF NOT EXISTS revision_signatures (
+    id INTEGER PRIMARY KEY,
+    fs_id INTEGER NOT NULL,
+    root_revision TEXT NOT NULL,
+    previous_revision TEXT,
+    signer TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (fs_id) REFERENCES filesystems(id) ON DELETE CASCADE,
+    UNIQUE (fs_id, root_revision)
+);
+
+-- Create indexes for common queries
+CREATE INDEX idx_revision_signatures_fs_id ON revision_signatures(fs_id);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- Revision signatures are stored in the revisions themselves
+
+-- Drop indexes
+DROP INDEX IF EXISTS idx_revision_signatures_fs_id;
+
+-- Drop table
+DROP TABLE IF EXISTS revision_signatures;
@@ -1,13 +1,25 @@
 //! Management functions.
 
+mod config;
 mod db;
+mod encryption;
 mod find;
+mod head;
 mod mfs;
+mod raft;
+mod signature;
+mod sync;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub use config::*;
 pub use db::*;
+pub use encryption::*;
 pub use find::*;
+pub use head::*;
 pub use mfs::*;
+pub use raft::*;
+pub use signature::*;
+pub use sync::*;
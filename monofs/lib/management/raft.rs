@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+
+use sqlx::{Row, Sqlite, Transaction};
+use tokio::fs;
+
+use crate::{
+    config::RaftMember,
+    management::{config, db, encryption, find, mfs},
+    sync::RaftLog,
+    utils::path::FS_DB_FILENAME,
+    FsError, FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Replicate the head of the filesystem containing `path` with a Raft group
+///
+/// The members of the group and the ID of this filesystem's node are stored in its filesystem
+/// database. The node joins the group the next time the filesystem is mounted, serving Raft and
+/// block requests on the URL of its member entry. Every member of the group has to be configured
+/// with the same members.
+///
+/// ## Arguments
+/// * `path` - Path within the filesystem. Defaults to the current directory
+/// * `node_id` - The ID of this filesystem's node, which must be one of the members
+/// * `members` - The members of the group, this node included
+///
+/// ## Example
+/// ```no_run
+/// use monofs::{config::RaftMember, management};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let members = vec![
+///     RaftMember::new(1, "http://10.0.0.1:3050"),
+///     RaftMember::new(2, "http://10.0.0.2:3050"),
+///     RaftMember::new(3, "http://10.0.0.3:3050"),
+/// ];
+/// management::configure_raft_mfs(None, 1, &members).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn configure_raft_mfs(
+    path: Option<PathBuf>,
+    node_id: u64,
+    members: &[RaftMember],
+) -> FsResult<()> {
+    if !members.iter().any(|member| *member.get_id() == node_id) {
+        return Err(FsError::InvalidOperation(format!(
+            "node {} is not one of the members",
+            node_id
+        )));
+    }
+
+    let path = fs::canonicalize(path.unwrap_or_else(|| PathBuf::from("."))).await?;
+    let mfs_root = find::find_mfs_root(&path).await?;
+    let fs_db_path = mfs::get_mfs_data_dir(&mfs_root).await?.join(FS_DB_FILENAME);
+    encryption::ensure_unencrypted(&fs_db_path, &mfs_root, "replicate").await?;
+
+    set_raft_members(&fs_db_path, &mfs_root, members).await?;
+
+    let mut fs_config = config::get_fs_config(&fs_db_path, &mfs_root).await?;
+    fs_config.set_raft_node_id(Some(node_id));
+    config::set_fs_config(&fs_db_path, &mfs_root, &fs_config).await?;
+
+    tracing::info!(
+        "configured {} as raft node {} of {} members",
+        mfs_root.display(),
+        node_id,
+        members.len()
+    );
+
+    Ok(())
+}
+
+/// Get the members of the Raft group the head of the filesystem mounted at `mount_dir` is
+/// replicated with
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+pub async fn get_raft_members(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+) -> FsResult<Vec<RaftMember>> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref().to_string_lossy().to_string();
+
+    let records = sqlx::query(
+        r#"
+        SELECT node_id, url FROM raft_members
+        WHERE fs_id = (SELECT id FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1)
+        ORDER BY node_id
+        "#,
+    )
+    .bind(mount_dir)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|row| {
+            RaftMember::new(
+                row.get::<i64, _>("node_id") as u64,
+                row.get::<String, _>("url"),
+            )
+        })
+        .collect())
+}
+
+/// Replace the members of the Raft group the head of the filesystem mounted at `mount_dir` is
+/// replicated with
+///
+/// A filesystem entry is created if there is none yet.
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+/// * `members` - The members of the group
+pub async fn set_raft_members(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    members: &[RaftMember],
+) -> FsResult<()> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+
+    let mut tx = pool.begin().await?;
+    let fs_id = get_or_create_fs_id(&mut tx, mount_dir.as_ref()).await?;
+
+    sqlx::query("DELETE FROM raft_members WHERE fs_id = ?")
+        .bind(fs_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for member in members {
+        sqlx::query("INSERT INTO raft_members (fs_id, node_id, url) VALUES (?, ?, ?)")
+            .bind(fs_id)
+            .bind(*member.get_id() as i64)
+            .bind(member.get_url())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Get the persisted Raft state of the filesystem mounted at `mount_dir`
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+///
+/// ## Returns
+/// The term, vote and log of the filesystem's node, or None if it has never run
+pub async fn get_raft_state(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+) -> FsResult<Option<RaftLog>> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let mount_dir = mount_dir.as_ref().to_string_lossy().to_string();
+
+    let record = sqlx::query(
+        "SELECT raft_state FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(mount_dir)
+    .fetch_optional(&pool)
+    .await?;
+
+    match record.and_then(|row| row.get::<Option<String>, _>("raft_state")) {
+        Some(state) => Ok(Some(serde_json::from_str(&state)?)),
+        None => Ok(None),
+    }
+}
+
+/// Persist the Raft state of the filesystem mounted at `mount_dir`
+///
+/// A filesystem entry is created if there is none yet.
+///
+/// ## Arguments
+/// * `fs_db_path` - Path to the filesystem database
+/// * `mount_dir` - The mount directory the filesystem is registered under
+/// * `state` - The term, vote and log of the filesystem's node
+pub async fn set_raft_state(
+    fs_db_path: impl AsRef<Path>,
+    mount_dir: impl AsRef<Path>,
+    state: &RaftLog,
+) -> FsResult<()> {
+    let pool = db::get_db_pool(fs_db_path.as_ref()).await?;
+    let state = serde_json::to_string(state)?;
+
+    let mut tx = pool.begin().await?;
+    let fs_id = get_or_create_fs_id(&mut tx, mount_dir.as_ref()).await?;
+
+    sqlx::query("UPDATE filesystems SET raft_state = ? WHERE id = ?")
+        .bind(state)
+        .bind(fs_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Get the ID of the filesystem entry for `mount_dir`, creating one if there is none yet
+pub(super) async fn get_or_create_fs_id(
+    tx: &mut Transaction<'_, Sqlite>,
+    mount_dir: &Path,
+) -> FsResult<i64> {
+    let mount_dir_str = mount_dir.to_string_lossy().to_string();
+
+    let record =
+        sqlx::query("SELECT id FROM filesystems WHERE mount_dir = ? ORDER BY id DESC LIMIT 1")
+            .bind(&mount_dir_str)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+    if let Some(row) = record {
+        return Ok(row.get("id"));
+    }
+
+    let name = mount_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| mount_dir_str.clone());
+
+    let result = sqlx::query("INSERT INTO filesystems (name, mount_dir) VALUES (?, ?)")
+        .bind(name)
+        .bind(&mount_dir_str)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::management::{self, FS_DB_MIGRATOR};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_raft_members_and_state_round_trip() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let fs_db_path = temp_dir.path().join("fs.db");
+        let mount_dir = temp_dir.path().join("mfs");
+        management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+        assert!(get_raft_members(&fs_db_path, &mount_dir).await?.is_empty());
+        assert_eq!(get_raft_state(&fs_db_path, &mount_dir).await?, None);
+
+        let members = vec![
+            RaftMember::new(2, "http://127.0.0.1:3052"),
+            RaftMember::new(1, "http://127.0.0.1:3051/"),
+        ];
+        set_raft_members(&fs_db_path, &mount_dir, &members).await?;
+        set_raft_members(&fs_db_path, &mount_dir, &members).await?;
+        assert_eq!(
+            get_raft_members(&fs_db_path, &mount_dir).await?,
+            vec![
+                RaftMember::new(1, "http://127.0.0.1:3051"),
+                RaftMember::new(2, "http://127.0.0.1:3052"),
+            ]
+        );
+
+        let state = RaftLog::default();
+        set_raft_state(&fs_db_path, &mount_dir, &state).await?;
+        assert_eq!(get_raft_state(&fs_db_path, &mount_dir).await?, Some(state));
+
+        // Everything lives in the one filesystem entry
+        let pool = db::get_db_pool(&fs_db_path).await?;
+        let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM filesystems")
+            .fetch_one(&pool)
+            .await?
+            .get("count");
+        assert_eq!(count, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,466 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use getset::Getters;
+use ipldstore::{ipld::cid::Cid, utils, IpldStore, IpldStoreExt};
+use monoutils_did::{Did, KeyPair, KeyType};
+use tokio::{fs, io::AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use crate::{
+    filesystem::{self, get_fields_signature, get_previous, load_fields},
+    management::{config, encryption, find, head, mfs},
+    store::FlatFsStore,
+    utils::path::{BLOCKS_SUBDIR, FS_DB_FILENAME},
+    FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// Signs the revisions of a filesystem with its configured signing key.
+///
+/// Signatures are stored in the revisions themselves, see
+/// [`RevisionSignature`][crate::filesystem::RevisionSignature].
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RevisionSigner {
+    /// The key revisions are signed with.
+    keypair: KeyPair,
+}
+
+/// A revision of the root directory of a filesystem, as found when walking back from its head.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct Revision {
+    /// The CID of the revision.
+    cid: Cid,
+
+    /// The CID of the revision it was made on top of, if any.
+    previous: Option<Cid>,
+
+    /// The DID the revision is signed by, if it is signed.
+    signer: Option<Did>,
+
+    /// Whether the revision checks out.
+    status: RevisionStatus,
+}
+
+/// The outcome of verifying a revision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevisionStatus {
+    /// The revision is intact and carries a valid signature.
+    Verified,
+
+    /// The revision is intact but nobody signed it.
+    Unsigned,
+
+    /// The revision doesn't match its CID or its signature. Holds what doesn't match.
+    Tampered(String),
+
+    /// The block of the revision is not in the store, so nothing before it can be checked
+    /// either.
+    Missing,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RevisionSigner {
+    /// Opens the signer of the filesystem mounted at `mount_dir`.
+    ///
+    /// ## Arguments
+    /// * `fs_db_path` - Path to the filesystem database
+    /// * `mount_dir` - The mount directory the filesystem is registered under
+    ///
+    /// ## Returns
+    /// The signer, or None if the filesystem has no signing key configured
+    pub async fn open(
+        fs_db_path: impl AsRef<Path>,
+        mount_dir: impl AsRef<Path>,
+    ) -> FsResult<Option<Self>> {
+        let fs_config = config::get_fs_config(fs_db_path.as_ref(), mount_dir.as_ref()).await?;
+        let Some(keyfile) = fs_config.get_signing_key() else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            keypair: read_signing_key(keyfile).await?,
+        }))
+    }
+
+    /// Returns the DID revisions are signed by.
+    pub fn get_did(&self) -> Did {
+        Did::key(self.keypair.get_public_key())
+    }
+
+    /// Signs a stored revision, storing it again with the signature.
+    ///
+    /// ## Arguments
+    /// * `store` - The store the revision is in
+    /// * `root` - The CID of the revision
+    ///
+    /// ## Returns
+    /// The CID of the signed revision
+    pub async fn sign<S>(&self, store: &S, root: &Cid) -> FsResult<Cid>
+    where
+        S: IpldStore + Send + Sync,
+    {
+        let signed = filesystem::sign_revision(store, &self.keypair, root).await?;
+        tracing::info!(
+            "signed revision {} as {} into {}",
+            root,
+            self.get_did(),
+            signed
+        );
+
+        Ok(signed)
+    }
+}
+
+impl RevisionStatus {
+    /// Returns true if the revision is unsigned, tampered with or missing.
+    pub fn is_flagged(&self) -> bool {
+        *self != Self::Verified
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Sign the revisions of the filesystem containing `path` with the key in `keyfile`
+///
+/// The keyfile holds a multibase encoded private key. With `generate`, a new key of that type is
+/// written to the keyfile first, which must not exist yet. Revisions the filesystem checkpoints
+/// from the next time it is mounted are signed with the key, as are merges and key rotations.
+///
+/// ## Arguments
+/// * `path` - Path within the filesystem. Defaults to the current directory
+/// * `keyfile` - The file holding the signing key
+/// * `generate` - The type of key to generate into the keyfile, if any
+///
+/// ## Returns
+/// The DID revisions are signed by
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management;
+/// use monoutils_did::KeyType;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let did = management::configure_signer_mfs(None, "signing.key", Some(KeyType::Ed25519)).await?;
+/// println!("revisions are signed by {}", did);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn configure_signer_mfs(
+    path: Option<PathBuf>,
+    keyfile: impl AsRef<Path>,
+    generate: Option<KeyType>,
+) -> FsResult<Did> {
+    let (fs_db_path, mfs_root) = find_fs_db(path).await?;
+    let keyfile = keyfile.as_ref();
+
+    if let Some(key_type) = generate {
+        let keypair = KeyPair::generate(key_type);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(keyfile)
+            .await?;
+        file.write_all(keypair.to_multibase().as_bytes()).await?;
+        tracing::info!(
+            "generated {} signing key in {}",
+            key_type,
+            keyfile.display()
+        );
+    }
+
+    // The keyfile is recorded by its absolute path so it is found from anywhere
+    let keyfile = fs::canonicalize(keyfile).await?;
+    let keypair = read_signing_key(&keyfile).await?;
+
+    let mut fs_config = config::get_fs_config(&fs_db_path, &mfs_root).await?;
+    fs_config.set_signing_key(Some(keyfile));
+    config::set_fs_config(&fs_db_path, &mfs_root, &fs_config).await?;
+
+    Ok(Did::key(keypair.get_public_key()))
+}
+
+/// Get the revisions of the root directory of the filesystem containing `path`, from its head
+/// back through their `previous` chain, and verify each of them
+///
+/// Every revision is checked against its CID and, if it is signed, against the signature stored
+/// in it. Revisions of encrypted filesystems are read with the key derived from the `MONOFS_KEYFILE` or
+/// `MONOFS_PASSPHRASE` environment variable.
+///
+/// ## Arguments
+/// * `path` - Path within the filesystem. Defaults to the current directory
+///
+/// ## Returns
+/// The revisions, newest first
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// for revision in management::get_revisions(None).await? {
+///     println!("{} {}", revision.get_cid(), revision.get_status());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn get_revisions(path: Option<PathBuf>) -> FsResult<Vec<Revision>> {
+    let (fs_db_path, mfs_root) = find_fs_db(path).await?;
+    let Some(head) = head::get_head(&fs_db_path, &mfs_root).await? else {
+        return Ok(Vec::new());
+    };
+
+    let mfs_data_dir = mfs::get_mfs_data_dir(&mfs_root).await?;
+    let blocks = FlatFsStore::new(mfs_data_dir.join(BLOCKS_SUBDIR));
+    let keyring = encryption::unlock_mfs(&fs_db_path, &mfs_root, None).await?;
+    match keyring {
+        Some(keyring) => verify_chain(&blocks, keyring.open_store(blocks.clone()), head).await,
+        None => verify_chain(&blocks, blocks.clone(), head).await,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Find the filesystem containing `path`, defaulting to the current directory
+///
+/// ## Returns
+/// The path to its filesystem database and its mount directory
+async fn find_fs_db(path: Option<PathBuf>) -> FsResult<(PathBuf, PathBuf)> {
+    let path = fs::canonicalize(path.unwrap_or_else(|| PathBuf::from("."))).await?;
+    let mfs_root = find::find_mfs_root(&path).await?;
+    let fs_db_path = mfs::get_mfs_data_dir(&mfs_root).await?.join(FS_DB_FILENAME);
+
+    Ok((fs_db_path, mfs_root))
+}
+
+/// Read the multibase private key in a keyfile
+async fn read_signing_key(keyfile: &Path) -> FsResult<KeyPair> {
+    let contents = Zeroizing::new(fs::read_to_string(keyfile).await?);
+    Ok(KeyPair::from_multibase(contents.trim())?)
+}
+
+/// Walk the `previous` chain from `head` and verify every revision on it
+///
+/// `blocks` is the store as it is on disk, which the blocks are checked against their CIDs in.
+/// `store` is the same store the revisions are read from, decrypted if the filesystem is
+/// encrypted.
+async fn verify_chain<S>(blocks: &FlatFsStore, store: S, head: Cid) -> FsResult<Vec<Revision>>
+where
+    S: IpldStore + Send + Sync,
+{
+    let mut revisions = Vec::new();
+    let mut next = Some(head);
+    while let Some(cid) = next.take() {
+        if !blocks.has(&cid).await {
+            revisions.push(Revision {
+                cid,
+                previous: None,
+                signer: None,
+                status: RevisionStatus::Missing,
+            });
+            break;
+        }
+
+        let bytes = blocks.get_block(&cid).await?;
+        let intact = utils::generate_cid(cid.codec().try_into()?, &bytes) == cid;
+        let fields = match load_fields(&store, &cid).await {
+            Ok(fields) => Some(fields),
+            Err(e) if intact => return Err(e),
+            Err(_) => None,
+        };
+
+        let previous = fields.as_ref().and_then(get_previous);
+        let (signer, status) = match fields.filter(|_| intact) {
+            None => (
+                None,
+                RevisionStatus::Tampered("block does not match its cid".to_string()),
+            ),
+            Some(fields) => match get_fields_signature(&fields) {
+                Ok(None) => (None, RevisionStatus::Unsigned),
+                Ok(Some(signature)) => {
+                    let status = match signature.verify() {
+                        Ok(()) => RevisionStatus::Verified,
+                        Err(_) => RevisionStatus::Tampered("invalid signature".to_string()),
+                    };
+                    (Some(signature.get_signer().clone()), status)
+                }
+                Err(e) => (None, RevisionStatus::Tampered(e.to_string())),
+            },
+        };
+
+        if status.is_flagged() {
+            tracing::warn!("revision {} is {}", cid, status);
+        }
+
+        revisions.push(Revision {
+            cid,
+            previous,
+            signer,
+            status,
+        });
+        next = previous;
+    }
+
+    Ok(revisions)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for RevisionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verified => write!(f, "verified"),
+            Self::Unsigned => write!(f, "unsigned"),
+            Self::Tampered(reason) => write!(f, "tampered ({})", reason),
+            Self::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{ipld::ipld::Ipld, Storable};
+    use tempfile::TempDir;
+
+    use crate::filesystem::{Dir, File, SIGNATURE_FIELD};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_revisions_flags_unsigned_and_tampered_revisions() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (mount_dir, mfs_data_dir) =
+            mfs::init_mfs_data_dir(Some(temp_dir.path().join("mfs"))).await?;
+        let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+
+        // An unsigned revision, then two signed ones
+        let store = FlatFsStore::new(mfs_data_dir.join(BLOCKS_SUBDIR));
+        let mut root = Dir::new(store.clone());
+        let unsigned = root.checkpoint().await?;
+
+        let keyfile = temp_dir.path().join("signing.key");
+        let did =
+            configure_signer_mfs(Some(mount_dir.clone()), &keyfile, Some(KeyType::Ed25519)).await?;
+        let signer = RevisionSigner::open(&fs_db_path, &mount_dir)
+            .await?
+            .unwrap();
+        assert_eq!(signer.get_did(), did);
+
+        root.put_adapted_file("a.txt", File::new(store.clone()))
+            .await?;
+        let first = root.checkpoint_signed(signer.get_keypair()).await?;
+
+        root.put_adapted_file("b.txt", File::new(store.clone()))
+            .await?;
+        let second = root.checkpoint_signed(signer.get_keypair()).await?;
+        head::set_head(&fs_db_path, &mount_dir, &second, None).await?;
+
+        let revisions = get_revisions(Some(mount_dir.clone())).await?;
+        let statuses = revisions
+            .iter()
+            .map(|revision| (*revision.get_cid(), revision.get_status().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                (second, RevisionStatus::Verified),
+                (first, RevisionStatus::Verified),
+                (unsigned, RevisionStatus::Unsigned),
+            ]
+        );
+        assert_eq!(revisions[0].get_signer().as_ref(), Some(&did));
+        assert_eq!(revisions[0].get_previous(), &Some(first));
+
+        // A signature moved onto another history is flagged, and so is a signature by someone
+        // else than it claims
+        let fields = load_fields(&store, &second).await?;
+        let mut moved = fields.clone();
+        moved.insert("previous".to_string(), Ipld::Link(unsigned));
+
+        let mut impostor = fields.clone();
+        let other = Did::key(KeyPair::generate(KeyType::P256).get_public_key());
+        if let Some(Ipld::Map(signature)) = impostor.get_mut(SIGNATURE_FIELD) {
+            signature.insert("signer".to_string(), Ipld::String(other.to_string()));
+        }
+
+        let mut current = second;
+        for forged in [moved, impostor] {
+            let forged = store.put_node(&Ipld::Map(forged)).await?;
+            head::set_head(&fs_db_path, &mount_dir, &forged, Some(&current)).await?;
+            current = forged;
+
+            let revisions = get_revisions(Some(mount_dir.clone())).await?;
+            assert_eq!(
+                revisions[0].get_status(),
+                &RevisionStatus::Tampered("invalid signature".to_string())
+            );
+        }
+        head::set_head(&fs_db_path, &mount_dir, &second, Some(&current)).await?;
+
+        // And a revision whose block was changed on disk
+        let block = store.get_block(&unsigned).await?;
+        assert!(!block.is_empty());
+        let mut tampered = Dir::new(store.clone());
+        tampered
+            .put_adapted_file("evil.txt", File::new(store.clone()))
+            .await?;
+        let tampered = tampered.store().await?;
+        let tampered_block = store.get_block(&tampered).await?;
+        overwrite_block(&mfs_data_dir.join(BLOCKS_SUBDIR), &block, &tampered_block).await?;
+        let revisions = get_revisions(Some(mount_dir)).await?;
+        assert_eq!(
+            revisions[2].get_status(),
+            &RevisionStatus::Tampered("block does not match its cid".to_string())
+        );
+
+        Ok(())
+    }
+
+    /// Replace the contents of the block file holding `block` with `replacement`
+    async fn overwrite_block(
+        blocks_dir: &Path,
+        block: &[u8],
+        replacement: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut dirs = vec![blocks_dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+
+                let contents = fs::read(entry.path()).await?;
+                if contents.ends_with(block) {
+                    let header = &contents[..contents.len() - block.len()];
+                    fs::write(entry.path(), [header, replacement].concat()).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        anyhow::bail!("block not found")
+    }
+}
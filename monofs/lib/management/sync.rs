@@ -0,0 +1,518 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::{fs, net::TcpListener};
+
+use crate::{
+    config::{FsConfig, Origin},
+    filesystem::{self, ConflictResolution, MergeOutcome},
+    management::{config, db, encryption, find, mfs, RevisionSigner, FS_DB_MIGRATOR},
+    store::FlatFsStore,
+    sync::{self, LocalPeer, RemotePeer, SyncMode, SyncPeer, SyncStats},
+    utils::path::{self, BLOCKS_SUBDIR, FS_DB_FILENAME, MFS_LINK_FILENAME},
+    FsError, FsResult,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Sync the filesystem containing `source` with the filesystem at `uri`
+///
+/// The destination is either the URL of a peer started with `monofs serve`, or a local path. A
+/// local destination that is not a filesystem yet gets a data directory next to it, i.e.
+/// `<path>.mfs`, that can be synced to again later.
+///
+/// The destination should not be mounted while it is synced to, since its NFS server does not
+/// pick up the new head.
+///
+/// ## Arguments
+/// * `uri` - URL or local path of the destination filesystem
+/// * `sync_mode` - The kind of sync to perform
+/// * `source` - Path within the source filesystem. Defaults to the current directory
+///
+/// ## Example
+/// ```no_run
+/// use monofs::{management, sync::SyncMode};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let stats = management::sync_mfs("/backups/mfs", SyncMode::Backup, None).await?;
+/// println!("copied {} blocks", stats.get_blocks_copied());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn sync_mfs(
+    uri: impl AsRef<str>,
+    sync_mode: SyncMode,
+    source: Option<PathBuf>,
+) -> FsResult<SyncStats> {
+    let uri = uri.as_ref();
+    let source = open_local_peer(source.unwrap_or_else(|| PathBuf::from("."))).await?;
+
+    match sync_mode {
+        SyncMode::Backup => {
+            tracing::info!("backing up {} to {}", source.get_mount_dir().display(), uri);
+            if is_remote_uri(uri) {
+                sync::backup(&source, &RemotePeer::new(uri)).await
+            } else {
+                let destination = init_local_peer(uri).await?;
+                sync::backup(&source, &destination).await
+            }
+        }
+        SyncMode::Crdt => {
+            tracing::info!("merging {} with {}", source.get_mount_dir().display(), uri);
+            if is_remote_uri(uri) {
+                sync::sync_crdt(&source, &RemotePeer::new(uri)).await
+            } else {
+                let other = init_local_peer(uri).await?;
+                sync::sync_crdt(&source, &other).await
+            }
+        }
+    }
+}
+
+/// Merge the head of the filesystem at `uri` into the filesystem containing `path`
+///
+/// The other head and every block reachable from it are copied into the local filesystem, and
+/// both heads are merged with [`filesystem::merge`] against their common ancestor. Changes that
+/// conflict are resolved with `resolution`. Only the local head is moved, the other filesystem is
+/// left as is. The merged head is signed with the filesystem's signing key, if it has one.
+///
+/// The filesystem should not be mounted while it is merged into, since its NFS server does not
+/// pick up the new head.
+///
+/// ## Arguments
+/// * `uri` - URL of a peer started with `monofs serve`, or a path within a local filesystem
+/// * `resolution` - How conflicting changes are resolved
+/// * `path` - Path within the filesystem to merge into. Defaults to the current directory
+///
+/// ## Example
+/// ```no_run
+/// use monofs::{filesystem::ConflictResolution, management};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let outcome = management::merge_mfs("../other", ConflictResolution::KeepBoth, None).await?;
+/// for (conflict, resolution) in outcome.get_conflicts() {
+///     println!("{}: {} ({})", conflict.get_path(), conflict.get_kind(), resolution);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn merge_mfs(
+    uri: impl AsRef<str>,
+    resolution: ConflictResolution,
+    path: Option<PathBuf>,
+) -> FsResult<MergeOutcome> {
+    let uri = uri.as_ref();
+    let local = open_local_peer(path.unwrap_or_else(|| PathBuf::from("."))).await?;
+    let other = open_peer(uri).await?;
+
+    let ours = local
+        .get_head()
+        .await?
+        .ok_or_else(|| FsError::NoHead(local.get_mount_dir().display().to_string()))?;
+    let theirs = other
+        .get_head()
+        .await?
+        .ok_or_else(|| FsError::NoHead(uri.to_string()))?;
+
+    tracing::info!(
+        "merging {} from {} into {}",
+        theirs,
+        uri,
+        local.get_mount_dir().display()
+    );
+    sync::copy_dag(other.as_ref(), &local, theirs).await?;
+
+    let mut outcome = filesystem::merge(local.get_store(), &ours, &theirs, &resolution).await?;
+    if outcome.is_new(&ours, &theirs) {
+        let signer = RevisionSigner::open(local.get_fs_db_path(), local.get_mount_dir()).await?;
+        if let Some(signer) = signer {
+            let head = signer.sign(local.get_store(), outcome.get_head()).await?;
+            outcome.set_head(head);
+        }
+    }
+
+    if *outcome.get_head() != ours {
+        local.set_head(outcome.get_head(), Some(&ours)).await?;
+    }
+
+    Ok(outcome)
+}
+
+/// Clone the filesystem at `uri` into a new filesystem at `mount_dir` and mount it
+///
+/// The clone gets its own `.mfs` data directory and starts at the head of the source. Unless the
+/// clone is lazy, every block reachable from the head is copied up front. A lazy clone copies
+/// nothing and instead fetches blocks from the source the first time they are read, so the source
+/// has to stay reachable while the clone is in use.
+///
+/// The source is recorded as the origin of the clone in its filesystem database.
+///
+/// ## Arguments
+/// * `uri` - URL of a peer started with `monofs serve`, or a path within a local filesystem
+/// * `mount_dir` - The path where the clone will be initialized and mounted
+/// * `lazy` - Whether to fetch blocks on first read instead of at clone time
+///
+/// ## Returns
+/// The port number that was successfully used for mounting
+///
+/// ## Example
+/// ```no_run
+/// use monofs::management;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// management::clone_mfs("http://origin.local:3049", "mfsclone", true).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn clone_mfs(
+    uri: impl AsRef<str>,
+    mount_dir: impl Into<PathBuf>,
+    lazy: bool,
+) -> FsResult<u32> {
+    let (mount_dir, mfs_data_dir) = mfs::init_mfs_data_dir(Some(mount_dir.into())).await?;
+    clone_into(uri.as_ref(), &mount_dir, &mfs_data_dir, lazy).await?;
+
+    mfs::start_mfs(&mount_dir, &mfs_data_dir, None).await
+}
+
+/// Serve the filesystem containing `path` to remote peers until the process is stopped
+///
+/// Other machines can then sync with it by passing `http://<host>:<port>` to `monofs sync`.
+///
+/// ## Arguments
+/// * `path` - Path within the filesystem to serve. Defaults to the current directory
+/// * `host` - The host to bind to
+/// * `port` - The port to listen on
+pub async fn serve_mfs(path: Option<PathBuf>, host: &str, port: u32) -> FsResult<()> {
+    let peer = open_local_peer(path.unwrap_or_else(|| PathBuf::from("."))).await?;
+    let listener = TcpListener::bind(format!("{}:{}", host, port)).await?;
+    tracing::info!(
+        "serving {} on {}",
+        peer.get_mount_dir().display(),
+        listener.local_addr()?
+    );
+
+    axum::serve(listener, sync::peer_router(peer.get_store().clone(), peer)).await?;
+
+    Ok(())
+}
+
+/// Open the filesystem at `uri` as a sync peer
+///
+/// ## Arguments
+/// * `uri` - URL of a peer started with `monofs serve`, or a path within a local filesystem
+pub async fn open_peer(uri: impl AsRef<str>) -> FsResult<Arc<dyn SyncPeer>> {
+    let uri = uri.as_ref();
+    if is_remote_uri(uri) {
+        Ok(Arc::new(RemotePeer::new(uri)))
+    } else {
+        Ok(Arc::new(open_local_peer(uri).await?))
+    }
+}
+
+/// Open the filesystem containing `path` as a sync peer
+///
+/// Encrypted filesystems can't be opened as sync peers, since their blocks can't be followed
+/// without their key.
+pub async fn open_local_peer(path: impl AsRef<Path>) -> FsResult<LocalPeer<FlatFsStore>> {
+    let path = fs::canonicalize(path.as_ref()).await?;
+    let mfs_root = find::find_mfs_root(&path).await?;
+    let mfs_data_dir = mfs::get_mfs_data_dir(&mfs_root).await?;
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+    encryption::ensure_unencrypted(&fs_db_path, &mfs_root, "sync").await?;
+
+    Ok(LocalPeer::new(
+        FlatFsStore::new(mfs_data_dir.join(BLOCKS_SUBDIR)),
+        fs_db_path,
+        mfs_root,
+    ))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Returns true if the URI refers to a remote peer rather than a local path
+fn is_remote_uri(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+/// Copy the head of the filesystem at `uri` into the freshly initialized filesystem at
+/// `mount_dir`, and record `uri` as its origin
+async fn clone_into(uri: &str, mount_dir: &Path, mfs_data_dir: &Path, lazy: bool) -> FsResult<()> {
+    // Local origins are recorded by the absolute path of their root, so the clone can be mounted
+    // from anywhere
+    let uri = if is_remote_uri(uri) {
+        uri.to_string()
+    } else {
+        let path = fs::canonicalize(uri).await?;
+        find::find_mfs_root(&path)
+            .await?
+            .to_string_lossy()
+            .to_string()
+    };
+
+    let source = open_peer(&uri).await?;
+    let destination = LocalPeer::new(
+        FlatFsStore::new(mfs_data_dir.join(BLOCKS_SUBDIR)),
+        mfs_data_dir.join(FS_DB_FILENAME),
+        mount_dir,
+    );
+
+    // Never replace the history of an existing filesystem
+    if let Some(found) = destination.get_head().await? {
+        return Err(FsError::InvalidOperation(format!(
+            "cannot clone into {}, it already has head {}",
+            mount_dir.display(),
+            found
+        )));
+    }
+
+    if lazy {
+        let head = source
+            .get_head()
+            .await?
+            .ok_or_else(|| FsError::NoHead(uri.clone()))?;
+        destination.set_head(&head, None).await?;
+        tracing::info!("cloned head {} from {} without blocks", head, uri);
+    } else {
+        let stats = sync::backup(source.as_ref(), &destination).await?;
+        tracing::info!(
+            "cloned head {} from {} ({} blocks, {} bytes copied)",
+            stats.get_head(),
+            uri,
+            stats.get_blocks_copied(),
+            stats.get_bytes_copied()
+        );
+    }
+
+    config::set_fs_config(
+        destination.get_fs_db_path(),
+        mount_dir,
+        &FsConfig::new(Some(Origin::new(uri, lazy))),
+    )
+    .await
+}
+
+/// Open the filesystem at exactly `mount_dir` as a sync peer, creating its data directory if it
+/// has none
+///
+/// Unlike [`open_local_peer`], parent directories are not searched, since a destination inside
+/// another filesystem is not that filesystem.
+async fn init_local_peer(mount_dir: impl AsRef<Path>) -> FsResult<LocalPeer<FlatFsStore>> {
+    let mount_dir = absolute_path(mount_dir.as_ref()).await?;
+
+    let mfs_data_dir = match fs::read_link(mount_dir.join(MFS_LINK_FILENAME)).await {
+        Ok(link) if !fs::try_exists(path::get_mfs_data_dir(&mount_dir)).await? => link,
+        _ => path::get_mfs_data_dir(&mount_dir),
+    };
+
+    let blocks_dir = mfs_data_dir.join(BLOCKS_SUBDIR);
+    fs::create_dir_all(&blocks_dir).await?;
+
+    let fs_db_path = mfs_data_dir.join(FS_DB_FILENAME);
+    db::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+    Ok(LocalPeer::new(
+        FlatFsStore::new(blocks_dir),
+        fs_db_path,
+        mount_dir,
+    ))
+}
+
+/// Make a path absolute, resolving symlinks in whatever part of it already exists
+async fn absolute_path(path: &Path) -> FsResult<PathBuf> {
+    if fs::try_exists(path).await? {
+        return Ok(fs::canonicalize(path).await?);
+    }
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| FsError::InvalidOperation(format!("invalid path: {}", path.display())))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    Ok(fs::canonicalize(parent).await?.join(file_name))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{IpldStore, Storable};
+    use monoutils_did::{Did, KeyPair, KeyType};
+    use tempfile::TempDir;
+
+    use crate::{
+        filesystem::Dir,
+        management::{self, RevisionStatus},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sync_mfs_backup_to_local_path() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Lay out a filesystem the way `monofs init` does, minus the mount
+        let mount_dir = temp_dir.path().join("mfs");
+        fs::create_dir_all(&mount_dir).await?;
+        let source = init_local_peer(&mount_dir).await?;
+
+        let mut root = Dir::new(source.get_store().clone());
+        root.create_dir("docs").await?;
+        let head = root.checkpoint().await?;
+        source.set_head(&head, None).await?;
+
+        // Back up to a path that does not exist yet
+        let backup_dir = temp_dir.path().join("backup");
+        let stats = sync_mfs(
+            backup_dir.to_string_lossy(),
+            SyncMode::Backup,
+            Some(mount_dir.clone()),
+        )
+        .await?;
+        assert_eq!(stats.get_head(), &head);
+
+        let destination = init_local_peer(&backup_dir).await?;
+        assert_eq!(destination.get_head().await?, Some(head));
+        assert!(path::get_mfs_data_dir(&backup_dir).exists());
+        Dir::load(&head, destination.get_store().clone()).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clone_into_copies_or_defers_blocks() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (source_dir, source, head) = helper::setup_source(&temp_dir).await?;
+
+        // An eager clone copies every block and records its origin
+        let eager_dir = temp_dir.path().join("eager");
+        let eager = init_local_peer(&eager_dir).await?;
+        clone_into(
+            &source_dir.join("docs").to_string_lossy(),
+            eager.get_mount_dir(),
+            &path::get_mfs_data_dir(&eager_dir),
+            false,
+        )
+        .await?;
+
+        assert_eq!(eager.get_head().await?, Some(head));
+        assert_eq!(
+            eager.get_store().get_block_count().await?,
+            source.get_store().get_block_count().await?
+        );
+        let config = config::get_fs_config(eager.get_fs_db_path(), eager.get_mount_dir()).await?;
+        assert_eq!(
+            config.get_origin(),
+            &Some(Origin::new(source_dir.to_string_lossy(), false))
+        );
+
+        // A lazy clone only takes the head
+        let lazy_dir = temp_dir.path().join("lazy");
+        let lazy = init_local_peer(&lazy_dir).await?;
+        clone_into(
+            &source_dir.to_string_lossy(),
+            lazy.get_mount_dir(),
+            &path::get_mfs_data_dir(&lazy_dir),
+            true,
+        )
+        .await?;
+
+        assert_eq!(lazy.get_head().await?, Some(head));
+        assert_eq!(lazy.get_store().get_block_count().await?, 0);
+        let config = config::get_fs_config(lazy.get_fs_db_path(), lazy.get_mount_dir()).await?;
+        assert!(config.get_origin().as_ref().is_some_and(|o| *o.get_lazy()));
+
+        // Cloning into a filesystem that already has a head is refused
+        let result = clone_into(
+            &source_dir.to_string_lossy(),
+            lazy.get_mount_dir(),
+            &path::get_mfs_data_dir(&lazy_dir),
+            false,
+        )
+        .await;
+        assert!(matches!(result, Err(FsError::InvalidOperation(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clone_into_keeps_revision_signatures() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (source_dir, source, head) = helper::setup_source(&temp_dir).await?;
+
+        // Sign a revision on top of the unsigned head
+        let keypair = KeyPair::generate(KeyType::Ed25519);
+        let mut root = Dir::load(&head, source.get_store().clone()).await?;
+        root.create_dir("signed").await?;
+        let signed = root.checkpoint_signed(&keypair).await?;
+        source.set_head(&signed, Some(&head)).await?;
+
+        let clone_dir = temp_dir.path().join("clone");
+        fs::create_dir_all(&clone_dir).await?;
+        let clone = init_local_peer(&clone_dir).await?;
+        clone_into(
+            &source_dir.to_string_lossy(),
+            clone.get_mount_dir(),
+            &path::get_mfs_data_dir(&clone_dir),
+            false,
+        )
+        .await?;
+
+        // The clone verifies the signature without anything but the blocks
+        let revisions = management::get_revisions(Some(clone_dir)).await?;
+        let statuses = revisions
+            .iter()
+            .map(|revision| (*revision.get_cid(), revision.get_status().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                (signed, RevisionStatus::Verified),
+                (head, RevisionStatus::Unsigned),
+            ]
+        );
+        assert_eq!(
+            revisions[0].get_signer(),
+            &Some(Did::key(keypair.get_public_key()))
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod helper {
+    use tempfile::TempDir;
+
+    use crate::{filesystem::Dir, sync::SyncPeer};
+
+    use super::*;
+
+    /// Lays out a filesystem the way `monofs init` does, minus the mount, with a checkpointed
+    /// head.
+    pub(super) async fn setup_source(
+        temp_dir: &TempDir,
+    ) -> anyhow::Result<(PathBuf, LocalPeer<FlatFsStore>, ipldstore::ipld::cid::Cid)> {
+        let mount_dir = temp_dir.path().join("mfs");
+        fs::create_dir_all(mount_dir.join("docs")).await?;
+        let source = init_local_peer(&mount_dir).await?;
+
+        let mut root = Dir::new(source.get_store().clone());
+        root.create_dir("docs").await?;
+        let head = root.checkpoint().await?;
+        source.set_head(&head, None).await?;
+
+        Ok((source.get_mount_dir().to_path_buf(), source, head))
+    }
+}
@@ -1,16 +1,16 @@
 use std::{
-    io::Write,
     path::{Path, PathBuf},
+    process::ExitStatus,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
-use monoutils::{MonoutilsError, MonoutilsResult, ProcessMonitor, RotatingLog, LOG_SUFFIX};
-use sqlx::{Pool, Sqlite};
-use tokio::{
-    io::AsyncReadExt,
-    process::{ChildStderr, ChildStdout},
+use monoutils::{
+    copy_log_records, LogStream, MonoutilsError, MonoutilsResult, ProcessMonitor, RotatingLog,
+    LOG_SUFFIX,
 };
+use sqlx::{Pool, Sqlite};
+use tokio::process::{ChildStderr, ChildStdout};
 
 use crate::{management, utils::MFSRUN_LOG_PREFIX, FsResult};
 
@@ -87,75 +87,75 @@ impl ProcessMonitor for NfsServerMonitor {
         &mut self,
         pid: u32,
         name: String,
-        mut stdout: ChildStdout,
-        mut stderr: ChildStderr,
+        stdout: ChildStdout,
+        stderr: ChildStderr,
     ) -> MonoutilsResult<()> {
         // Setup child's log
         let log_name = self.generate_log_name(pid, &name);
         let log_path = self.log_dir.join(&log_name);
 
         let nfs_server_log = RotatingLog::new(&log_path).await?;
-        let mut stdout_writer = nfs_server_log.get_sync_writer();
-        let mut stderr_writer = nfs_server_log.get_sync_writer();
+        let stdout_writer = nfs_server_log.get_sync_writer();
+        let stderr_writer = nfs_server_log.get_sync_writer();
 
         self.log_path = Some(log_path);
 
-        // Insert filesystem entry into fs_db
-        sqlx::query(
+        // Register the running servers on the filesystem entry, creating it if needed. The entry
+        // outlives the server since it holds the filesystem's head and tags.
+        let mount_dir = self.mount_dir.to_string_lossy().to_string();
+        let updated = sqlx::query(
             r#"
-            INSERT INTO filesystems (name, mount_dir, supervisor_pid, nfsserver_pid)
-            VALUES (?, ?, ?, ?)
+            UPDATE filesystems
+            SET name = ?, supervisor_pid = ?, nfsserver_pid = ?, modified_at = CURRENT_TIMESTAMP
+            WHERE mount_dir = ?
             "#,
         )
-        .bind(name)
-        .bind(self.mount_dir.to_string_lossy().to_string())
+        .bind(&name)
         .bind(self.supervisor_pid)
         .bind(pid)
+        .bind(&mount_dir)
         .execute(&self.fs_db)
         .await
         .map_err(MonoutilsError::custom)?;
 
-        // Spawn tasks to handle stdout/stderr
+        if updated.rows_affected() == 0 {
+            sqlx::query(
+                r#"
+                INSERT INTO filesystems (name, mount_dir, supervisor_pid, nfsserver_pid)
+                VALUES (?, ?, ?, ?)
+                "#,
+            )
+            .bind(name)
+            .bind(&mount_dir)
+            .bind(self.supervisor_pid)
+            .bind(pid)
+            .execute(&self.fs_db)
+            .await
+            .map_err(MonoutilsError::custom)?;
+        }
+
+        // Spawn tasks to copy stdout/stderr into the log as records
         tokio::spawn(async move {
-            let mut buf = [0u8; 1024];
-
-            while let Ok(n) = stdout.read(&mut buf).await {
-                if n == 0 {
-                    break;
-                }
-                if let Err(e) = stdout_writer.write_all(&buf[..n]) {
-                    tracing::error!(pid = pid, error = %e, "Failed to write to nfs server stdout log");
-                }
-                if let Err(e) = stdout_writer.flush() {
-                    tracing::error!(pid = pid, error = %e, "Failed to flush nfs server stdout log");
-                }
+            if let Err(e) = copy_log_records(stdout, LogStream::Stdout, stdout_writer).await {
+                tracing::error!(pid = pid, error = %e, "failed to write nfs server stdout log");
             }
         });
 
         tokio::spawn(async move {
-            let mut buf = [0u8; 1024];
-
-            while let Ok(n) = stderr.read(&mut buf).await {
-                if n == 0 {
-                    break;
-                }
-                if let Err(e) = stderr_writer.write_all(&buf[..n]) {
-                    tracing::error!(pid = pid, error = %e, "Failed to write to nfs server stderr log");
-                }
-                if let Err(e) = stderr_writer.flush() {
-                    tracing::error!(pid = pid, error = %e, "Failed to flush nfs server stderr log");
-                }
+            if let Err(e) = copy_log_records(stderr, LogStream::Stderr, stderr_writer).await {
+                tracing::error!(pid = pid, error = %e, "failed to write nfs server stderr log");
             }
         });
 
         Ok(())
     }
 
-    async fn stop(&mut self) -> MonoutilsResult<()> {
-        // Remove filesystem entry from fs_db
+    async fn stop(&mut self, _exit_status: Option<ExitStatus>) -> MonoutilsResult<()> {
+        // Clear the server PIDs but keep the filesystem entry
         sqlx::query(
             r#"
-            DELETE FROM filesystems
+            UPDATE filesystems
+            SET supervisor_pid = NULL, nfsserver_pid = NULL, modified_at = CURRENT_TIMESTAMP
             WHERE mount_dir = ? AND supervisor_pid = ?
             "#,
         )
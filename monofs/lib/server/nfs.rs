@@ -1,8 +1,9 @@
 use std::{
     collections::HashMap,
+    ops::{Deref, DerefMut},
     str,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
 };
@@ -11,7 +12,11 @@ use async_trait::async_trait;
 use chrono::{TimeZone, Utc};
 use getset::Getters;
 use intaglio::{Symbol, SymbolTable};
-use ipldstore::{ipld::ipld::Ipld, IpldStore, IpldStoreSeekable, MemoryStore, Storable};
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld},
+    IpldStore, IpldStoreSeekable, MemoryStore, Storable,
+};
+use monoutils_did::{Did, KeyPair};
 use nfsserve::{
     nfs::{
         fattr3, fileid3, filename3, ftype3, nfspath3, nfsstat3, nfstime3, sattr3, set_atime,
@@ -19,15 +24,15 @@ use nfsserve::{
     },
     vfs::{DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities},
 };
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
 
 use crate::{
     filesystem::{
-        Dir, Entity, EntityType, File, Metadata, SymPathLink, UNIX_ATIME_KEY, UNIX_GID_KEY,
-        UNIX_MODE_KEY, UNIX_UID_KEY,
+        self, ConflictResolver, Dir, Entity, EntityType, File, MergeOutcome, Metadata, SymPathLink,
+        UNIX_ATIME_KEY, UNIX_GID_KEY, UNIX_MODE_KEY, UNIX_UID_KEY,
     },
     store::FlatFsStore,
-    FsError,
+    FsError, FsResult,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -95,16 +100,34 @@ pub type DiskMonofsNFS = MonofsNFS<FlatFsStore>;
 /// // Or create a custom server with your own store implementation
 /// // let custom_server = MonofsNFS::new(CustomStore::default());
 /// ```
-#[derive(Debug, Getters)]
+///
+/// Clones share the same underlying filesystem, so a clone can be kept around to checkpoint the
+/// filesystem while another one is being served.
+#[derive(Debug, Clone, Getters)]
 pub struct MonofsNFS<S>
 where
     S: IpldStore + Send + Sync + 'static,
 {
     root: Arc<Mutex<Dir<S>>>,
-    next_fileid: AtomicU64,
+    next_fileid: Arc<AtomicU64>,
+    dirty: Arc<AtomicBool>,
     filenames: Arc<Mutex<SymbolTable>>,
     fileid_to_path_map: Arc<Mutex<HashMap<fileid3, Vec<Symbol>>>>,
     path_to_fileid_map: Arc<Mutex<HashMap<Vec<Symbol>, fileid3>>>,
+    read_only: Arc<AtomicBool>,
+}
+
+/// The root directory, locked for a mutating operation.
+///
+/// The filesystem is marked as changed when the lock is released, while it is still held, so a
+/// checkpoint either stores the whole operation or runs before it and leaves it for the next
+/// one. This happens even if the operation fails, since it may have changed part of the tree.
+struct RootMut<'a, S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    root: MutexGuard<'a, Dir<S>>,
+    dirty: &'a AtomicBool,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -125,12 +148,177 @@ where
     /// let server = MemoryMonofsNFS::new(MemoryStore::default());
     /// ```
     pub fn new(store: S) -> Self {
+        Self::with_root(Dir::new(store), false)
+    }
+
+    /// Creates a new MonofsNFS instance that serves the given root directory.
+    ///
+    /// This is used to serve an existing revision of a filesystem, for example one loaded by CID.
+    /// If `read_only` is true, every mutating NFS procedure fails with `NFS3ERR_ROFS`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use monofs::{filesystem::Dir, server::MemoryMonofsNFS};
+    /// use ipldstore::MemoryStore;
+    ///
+    /// let root = Dir::new(MemoryStore::default());
+    /// let server = MemoryMonofsNFS::with_root(root, true);
+    /// assert!(server.is_read_only());
+    /// ```
+    pub fn with_root(root: Dir<S>, read_only: bool) -> Self {
         Self {
-            root: Arc::new(Mutex::new(Dir::new(store))),
+            root: Arc::new(Mutex::new(root)),
             filenames: Arc::new(Mutex::new(SymbolTable::new())),
-            next_fileid: AtomicU64::new(1),
+            next_fileid: Arc::new(AtomicU64::new(1)),
+            dirty: Arc::new(AtomicBool::new(false)),
             fileid_to_path_map: Arc::new(Mutex::new(HashMap::from([(0, vec![])]))),
             path_to_fileid_map: Arc::new(Mutex::new(HashMap::from([(vec![], 0)]))),
+            read_only: Arc::new(AtomicBool::new(read_only)),
+        }
+    }
+
+    /// Returns true if the server rejects mutating operations.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    /// Makes the server reject or accept mutating operations from now on.
+    ///
+    /// Followers of a Raft group serve their filesystem read-only, and become writable when they
+    /// are elected leader.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst);
+    }
+
+    /// Replaces the root directory being served, e.g. with a head committed by another node.
+    ///
+    /// ## Returns
+    /// True if changes made since the last checkpoint were dropped
+    pub async fn replace_root(&self, root: Dir<S>) -> bool {
+        let mut current = self.root.lock().await;
+        *current = root;
+
+        self.dirty.swap(false, Ordering::SeqCst)
+    }
+
+    /// Stores the root directory if it has changed since the last checkpoint.
+    ///
+    /// ## Returns
+    /// The CID of the new root directory, or None if nothing has changed
+    ///
+    /// ## Example
+    /// ```rust
+    /// use monofs::server::MemoryMonofsNFS;
+    /// use ipldstore::MemoryStore;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    /// let server = MemoryMonofsNFS::new(MemoryStore::default());
+    /// assert!(server.checkpoint().await?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn checkpoint(&self) -> FsResult<Option<Cid>> {
+        let mut root = self.root.lock().await;
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        let cid = root.checkpoint().await.inspect_err(|_| self.mark_dirty())?;
+        tracing::debug!("checkpointed root directory: {}", cid);
+
+        Ok(Some(cid))
+    }
+
+    /// Stores the root directory if it has changed since the last checkpoint, and signs the new
+    /// revision with the given key.
+    ///
+    /// ## Returns
+    /// The CID of the signed root directory, or None if nothing has changed
+    pub async fn checkpoint_signed(&self, keypair: &KeyPair) -> FsResult<Option<Cid>> {
+        let mut root = self.root.lock().await;
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        let cid = root
+            .checkpoint_signed(keypair)
+            .await
+            .inspect_err(|_| self.mark_dirty())?;
+        tracing::debug!(
+            "checkpointed root directory {} signed by {}",
+            cid,
+            Did::key(keypair.get_public_key())
+        );
+
+        Ok(Some(cid))
+    }
+
+    /// Marks the filesystem as changed so the next checkpoint stores it, e.g. again after a
+    /// checkpoint that could not be made the head.
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Merges the last checkpoint of the filesystem with `theirs` and serves the merged
+    /// directory, e.g. when the head was moved by someone else while the filesystem was mounted.
+    ///
+    /// Nothing is merged if the filesystem changed since its last checkpoint, so that those
+    /// changes are not lost. They are checkpointed on top of the last one, after which the merge
+    /// can be tried again.
+    ///
+    /// ## Arguments
+    /// * `theirs` - The directory to merge with, which must be in the store
+    /// * `resolver` - Decides how conflicts are resolved
+    /// * `keypair` - The key a new merged revision is signed with, if any
+    ///
+    /// ## Returns
+    /// The outcome of the merge, or None if the filesystem changed since its last checkpoint
+    pub async fn merge_root(
+        &self,
+        theirs: &Cid,
+        resolver: &impl ConflictResolver,
+        keypair: Option<&KeyPair>,
+    ) -> FsResult<Option<MergeOutcome>> {
+        let mut root = self.root.lock().await;
+        if self.dirty.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        let ours = *root.get_initial_load_cid().ok_or_else(|| {
+            FsError::InvalidOperation("the root directory was never checkpointed".to_string())
+        })?;
+        let store = root.get_store().clone();
+        let mut outcome = filesystem::merge(&store, &ours, theirs, resolver).await?;
+        if let Some(keypair) = keypair.filter(|_| outcome.is_new(&ours, theirs)) {
+            let head = filesystem::sign_revision(&store, keypair, outcome.get_head()).await?;
+            outcome.set_head(head);
+        }
+        *root = Dir::load(outcome.get_head(), store).await?;
+        tracing::debug!(
+            "merged root directory {} with {} into {}",
+            ours,
+            theirs,
+            outcome.get_head()
+        );
+
+        Ok(Some(outcome))
+    }
+
+    /// Returns `NFS3ERR_ROFS` if the server is read-only.
+    fn ensure_writable(&self) -> Result<(), nfsstat3> {
+        if self.is_read_only() {
+            return Err(nfsstat3::NFS3ERR_ROFS);
+        }
+
+        Ok(())
+    }
+
+    /// Locks the root directory for a mutating operation. See [`RootMut`].
+    async fn lock_root_mut(&self) -> RootMut<'_, S> {
+        RootMut {
+            root: self.root.lock().await,
+            dirty: &self.dirty,
         }
     }
 
@@ -407,7 +595,11 @@ where
     }
 
     fn capabilities(&self) -> VFSCapabilities {
-        VFSCapabilities::ReadWrite
+        if self.is_read_only() {
+            VFSCapabilities::ReadOnly
+        } else {
+            VFSCapabilities::ReadWrite
+        }
     }
 
     async fn lookup(&self, dirid: fileid3, filename: &filename3) -> Result<fileid3, nfsstat3> {
@@ -478,11 +670,14 @@ where
     async fn setattr(&self, id: fileid3, setattr: sattr3) -> Result<fattr3, nfsstat3> {
         tracing::trace!("setattr: id: {}, setattr: {:?}", id, setattr);
 
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Get path from fileid
         let path = self.fileid_to_path(id).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get metadata
         let (metadata, size) = if path.is_empty() {
@@ -570,11 +765,14 @@ where
     async fn write(&self, id: fileid3, offset: u64, data: &[u8]) -> Result<fattr3, nfsstat3> {
         tracing::trace!("write: id: {}, offset: {}, data: {:?}", id, offset, data);
 
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Get path from fileid
         let path = self.fileid_to_path(id).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get the file
         let entity = if path.is_empty() {
@@ -704,6 +902,10 @@ where
             filename,
             attr
         );
+
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert filename bytes to string, ensuring valid UTF-8
         let filename_str = str::from_utf8(filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -716,7 +918,7 @@ where
         let parent_path = self.fileid_to_path(dirid).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get parent directory - handle root directory case specially
         let parent_dir = if parent_path.is_empty() {
@@ -782,6 +984,10 @@ where
             dirid,
             filename
         );
+
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert filename bytes to string, ensuring valid UTF-8
         let filename_str = str::from_utf8(filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -794,7 +1000,7 @@ where
         let parent_path = self.fileid_to_path(dirid).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get parent directory - handle root directory case specially
         let parent_dir = if parent_path.is_empty() {
@@ -841,6 +1047,10 @@ where
         dirname: &filename3,
     ) -> Result<(fileid3, fattr3), nfsstat3> {
         tracing::trace!("mkdir: dirid: {}, dirname: {:?}", dirid, dirname);
+
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert dirname bytes to string, ensuring valid UTF-8
         let dirname_str = str::from_utf8(dirname).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -853,7 +1063,7 @@ where
         let parent_path = self.fileid_to_path(dirid).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get parent directory - handle root directory case specially
         let parent_dir = if parent_path.is_empty() {
@@ -902,6 +1112,9 @@ where
     async fn remove(&self, dirid: fileid3, filename: &filename3) -> Result<(), nfsstat3> {
         tracing::trace!("remove: dirid: {}, filename: {:?}", dirid, filename);
 
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert filename bytes to string, ensuring valid UTF-8
         let filename_str = str::from_utf8(filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -914,7 +1127,7 @@ where
         let parent_path = self.fileid_to_path(dirid).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Construct the full path
         let full_path = join_path(&parent_path, filename_str);
@@ -938,6 +1151,9 @@ where
             to_filename
         );
 
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert filenames to strings, ensuring valid UTF-8
         let from_filename_str =
             str::from_utf8(from_filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
@@ -957,7 +1173,7 @@ where
         let to_path = join_path(&to_dir_path, to_filename_str);
 
         // Get root directory and use Dir's rename operation
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
         root.rename(&from_path, &to_path)
             .await
             .map_err(nfsstat3::from)
@@ -1059,6 +1275,9 @@ where
             attr
         );
 
+        // Reject writes on read-only mounts
+        self.ensure_writable()?;
+
         // Convert linkname bytes to string, ensuring valid UTF-8
         let linkname_str = str::from_utf8(linkname).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
 
@@ -1074,7 +1293,7 @@ where
         let parent_path = self.fileid_to_path(dirid).await?;
 
         // Get root directory
-        let mut root = self.root.lock().await;
+        let mut root = self.lock_root_mut().await;
 
         // Get parent directory - handle root directory case specially
         let parent_dir = if parent_path.is_empty() {
@@ -1171,6 +1390,36 @@ impl From<FsError> for nfsstat3 {
     }
 }
 
+impl<S> Deref for RootMut<'_, S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    type Target = Dir<S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.root
+    }
+}
+
+impl<S> DerefMut for RootMut<'_, S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.root
+    }
+}
+
+impl<S> Drop for RootMut<'_, S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        // The guard is dropped after this, so the root is still locked
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
@@ -1189,6 +1438,8 @@ fn join_path(base_path: &str, name: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use crate::filesystem::ConflictResolution;
+
     use super::*;
 
     #[tokio::test]
@@ -1932,4 +2183,199 @@ mod tests {
             Err(nfsstat3::NFS3ERR_NOENT)
         ));
     }
+
+    #[tokio::test]
+    async fn test_nfs_read_only() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+
+        // Build and checkpoint a revision with a file and a directory
+        let mut root = Dir::new(store.clone());
+        root.create_dir("docs").await?;
+        root.put_adapted_file(
+            "hello.txt",
+            File::with_content(store.clone(), b"Hello, monofs!".as_slice()).await?,
+        )
+        .await?;
+        let cid = root.checkpoint().await?;
+
+        // Serve the revision loaded by CID in read-only mode
+        let revision = Dir::load(&cid, store).await?;
+        let server = MemoryMonofsNFS::with_root(revision, true);
+        assert!(matches!(server.capabilities(), VFSCapabilities::ReadOnly));
+
+        // Reads still work
+        let file_id = server
+            .lookup(0, &filename3::from("hello.txt".as_bytes()))
+            .await
+            .unwrap();
+        let dir_id = server
+            .lookup(0, &filename3::from("docs".as_bytes()))
+            .await
+            .unwrap();
+        let (data, eof) = server.read(file_id, 0, 64).await.unwrap();
+        assert_eq!(&data, b"Hello, monofs!");
+        assert!(eof);
+        assert_eq!(server.readdir(0, 0, 10).await.unwrap().entries.len(), 2);
+
+        // Every mutating procedure is rejected
+        let name = filename3::from("new.txt".as_bytes());
+        assert!(matches!(
+            server.setattr(file_id, sattr3::default()).await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server.write(file_id, 0, b"changed").await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server.create(0, &name, sattr3::default()).await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server.create_exclusive(0, &name).await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server.mkdir(dir_id, &name).await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server
+                .remove(0, &filename3::from("hello.txt".as_bytes()))
+                .await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server
+                .rename(0, &filename3::from("hello.txt".as_bytes()), dir_id, &name)
+                .await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+        assert!(matches!(
+            server
+                .symlink(
+                    0,
+                    &name,
+                    &nfspath3::from("hello.txt".as_bytes()),
+                    &sattr3::default()
+                )
+                .await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+
+        // The revision is left untouched
+        let (data, _) = server.read(file_id, 0, 64).await.unwrap();
+        assert_eq!(&data, b"Hello, monofs!");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nfs_checkpoint() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let server = MemoryMonofsNFS::new(store.clone());
+
+        // Nothing to checkpoint before the first write
+        assert!(server.checkpoint().await?.is_none());
+
+        let (file_id, _) = server
+            .create(
+                0,
+                &filename3::from("hello.txt".as_bytes()),
+                sattr3::default(),
+            )
+            .await
+            .unwrap();
+        server.write(file_id, 0, b"Hello, monofs!").await.unwrap();
+
+        // A clone shares the filesystem, so it can checkpoint the server's changes
+        let first = server
+            .clone()
+            .checkpoint()
+            .await?
+            .expect("changes were made");
+        assert!(server.checkpoint().await?.is_none());
+
+        // The checkpointed root has the file, and later checkpoints link back to it
+        let root = Dir::load(&first, store.clone()).await?;
+        assert!(root.has_entry("hello.txt")?);
+
+        server
+            .mkdir(0, &filename3::from("docs".as_bytes()))
+            .await
+            .unwrap();
+        let second = server.checkpoint().await?.expect("changes were made");
+        let root = Dir::load(&second, store).await?;
+        assert_eq!(root.get_previous(), Some(&first));
+        assert_eq!(root.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nfs_checkpoint_keeps_concurrent_changes() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let server = MemoryMonofsNFS::new(store.clone());
+        let first = {
+            server
+                .mkdir(0, &filename3::from("docs".as_bytes()))
+                .await
+                .unwrap();
+            server.checkpoint().await?.expect("changes were made")
+        };
+
+        // A checkpoint that gets the root before a write that already started doesn't take the
+        // write's change away from the next checkpoint
+        let guard = server.root.lock().await;
+        let checkpoint = tokio::spawn({
+            let server = server.clone();
+            async move { server.checkpoint().await }
+        });
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        let mkdir = tokio::spawn({
+            let server = server.clone();
+            async move { server.mkdir(0, &filename3::from("src".as_bytes())).await }
+        });
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        drop(guard);
+
+        assert!(checkpoint.await??.is_none());
+        mkdir.await?.unwrap();
+        let second = server
+            .checkpoint()
+            .await?
+            .expect("the write is checkpointed");
+        assert!(Dir::load(&second, store.clone()).await?.has_entry("src")?);
+
+        // Their version of the first checkpoint is merged with ours
+        let mut theirs = Dir::load(&first, store.clone()).await?;
+        theirs.create_dir("tests").await?;
+        let theirs = theirs.checkpoint().await?;
+
+        server
+            .mkdir(0, &filename3::from("bin".as_bytes()))
+            .await
+            .unwrap();
+        assert!(server
+            .merge_root(&theirs, &ConflictResolution::Ours, None)
+            .await?
+            .is_none());
+        server.checkpoint().await?.expect("changes were made");
+
+        let outcome = server
+            .merge_root(&theirs, &ConflictResolution::Ours, None)
+            .await?
+            .expect("nothing changed since the checkpoint");
+        assert_eq!(outcome.get_base(), &Some(first));
+        let root = server.root.lock().await;
+        for name in ["docs", "src", "bin", "tests"] {
+            assert!(root.has_entry(name)?, "{} is missing", name);
+        }
+
+        Ok(())
+    }
 }
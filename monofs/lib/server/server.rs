@@ -1,8 +1,22 @@
 use getset::Getters;
+use ipldstore::{ipld::cid::Cid, IpldStoreSeekable, MemoryStore, Storable};
 use nfsserve::tcp::{NFSTcp, NFSTcpListener};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
+    task::JoinHandle,
+    time,
+};
 
-use crate::store::FlatFsStore;
+use crate::{
+    config::{DEFAULT_CHECKPOINT_INTERVAL, DEFAULT_HOST, DEFAULT_RAFT_HEARTBEAT_INTERVAL},
+    filesystem::{ConflictResolution, Dir},
+    management::{self, Keyring, RevisionSigner},
+    store::{FlatFsStore, LazyStore},
+    sync::{self, LocalPeer, RaftNode, RaftRole, SyncPeer},
+    FsError, FsResult,
+};
 
 use super::MonofsNFS;
 
@@ -11,18 +25,32 @@ use super::MonofsNFS;
 //--------------------------------------------------------------------------------------------------
 
 /// A server that provides NFS access to a content-addressed store.
-/// This server uses a flat filesystem store as its backing store.
+/// This server uses a flat filesystem store as its backing store, optionally encrypted, or an
+/// in-memory store for ephemeral filesystems.
 #[derive(Debug, Getters)]
 #[getset(get = "pub with_prefix")]
 pub struct MonofsServer {
-    /// The path to the store.
-    store_dir: PathBuf,
+    /// The path to the store. If not set, an in-memory store is used.
+    store_dir: Option<PathBuf>,
 
     /// The host to bind to.
     host: String,
 
     /// The port to listen on.
     port: u32,
+
+    /// The CID of the root directory to serve. If not set, an empty root directory is served.
+    root_cid: Option<Cid>,
+
+    /// Whether the server rejects mutating operations.
+    read_only: bool,
+
+    /// The filesystem database and mount directory the head of the filesystem is tracked under.
+    /// If set, the server starts from the tracked head and moves it as changes are checkpointed.
+    head_tracking: Option<(PathBuf, PathBuf)>,
+
+    /// The keys the store is encrypted with. If not set, the store is not encrypted.
+    keyring: Option<Keyring>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -33,22 +61,628 @@ impl MonofsServer {
     /// Creates a new MonofsServer with the given store path and host:port.
     pub fn new(store_dir: impl Into<PathBuf>, host: impl Into<String>, port: u32) -> Self {
         Self {
-            store_dir: store_dir.into(),
+            store_dir: Some(store_dir.into()),
+            host: host.into(),
+            port,
+            root_cid: None,
+            read_only: false,
+            head_tracking: None,
+            keyring: None,
+        }
+    }
+
+    /// Creates a new MonofsServer backed by an in-memory store.
+    ///
+    /// All data is lost when the server exits.
+    pub fn in_memory(host: impl Into<String>, port: u32) -> Self {
+        Self {
+            store_dir: None,
             host: host.into(),
             port,
+            root_cid: None,
+            read_only: false,
+            head_tracking: None,
+            keyring: None,
+        }
+    }
+
+    /// Creates a new read-only MonofsServer that serves the revision of a directory with the
+    /// given CID.
+    ///
+    /// ## Arguments
+    /// * `store_dir` - The path to the store containing the revision
+    /// * `host` - The host to bind to
+    /// * `port` - The port to listen on
+    /// * `root_cid` - The CID of the directory to serve as root
+    pub fn read_only(
+        store_dir: impl Into<PathBuf>,
+        host: impl Into<String>,
+        port: u32,
+        root_cid: Cid,
+    ) -> Self {
+        Self {
+            root_cid: Some(root_cid),
+            read_only: true,
+            ..Self::new(store_dir, host, port)
         }
     }
 
+    /// Tracks the head of the filesystem in the given database.
+    ///
+    /// The server serves the tracked head if there is one, periodically checkpoints changes and
+    /// moves the head to them, and checkpoints one last time when it receives SIGTERM or SIGINT.
+    /// This has no effect on read-only servers.
+    ///
+    /// If the filesystem is configured as a node of a Raft group, the server joins the group
+    /// instead and serves its committed head. See [`RaftNode`].
+    ///
+    /// ## Arguments
+    /// * `fs_db_path` - The path to the filesystem database
+    /// * `mount_dir` - The mount directory the filesystem is registered under
+    pub fn with_head_tracking(
+        mut self,
+        fs_db_path: impl Into<PathBuf>,
+        mount_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.head_tracking = Some((fs_db_path.into(), mount_dir.into()));
+        self
+    }
+
+    /// Encrypts the store with the given keys.
+    ///
+    /// Every block is encrypted with the current key of the keyring before it is written to the
+    /// store, and can be read with any key of the keyring. This has no effect on in-memory
+    /// servers.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
     /// Starts the NFS server and blocks until it is shut down.
     pub async fn start(&self) -> anyhow::Result<()> {
         // Create the store and NFS filesystem
-        let store = FlatFsStore::new(&self.store_dir);
-        let fs = MonofsNFS::new(store);
+        let Some(store_dir) = &self.store_dir else {
+            return self
+                .serve(MonofsNFS::new(MemoryStore::default()), None)
+                .await;
+        };
+
+        let store = FlatFsStore::new(store_dir);
+        match &self.keyring {
+            Some(keyring) => self.start_with_store(keyring.open_store(store)).await,
+            None => self.start_with_store(store).await,
+        }
+    }
+
+    /// Serves the filesystem from the given persistent store, following the head tracking and
+    /// replication configuration of the server.
+    async fn start_with_store<S>(&self, store: S) -> anyhow::Result<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let Some((fs_db_path, mount_dir)) = &self.head_tracking else {
+            return self.load_and_serve(store, self.root_cid).await;
+        };
+
+        // Filesystems in a Raft group only move their head through the group
+        let config = management::get_fs_config(fs_db_path, mount_dir).await?;
+        if let Some(node_id) = config.get_raft_node_id() {
+            return self
+                .serve_raft(store, fs_db_path, mount_dir, *node_id)
+                .await;
+        }
+
+        let root_cid = match self.root_cid {
+            Some(cid) => Some(cid),
+            None => management::get_head(fs_db_path, mount_dir).await?,
+        };
+
+        // Lazy clones fetch the blocks they are missing from their origin
+        match config.get_origin() {
+            Some(origin) if *origin.get_lazy() => {
+                tracing::info!("fetching missing blocks from origin {}", origin.get_uri());
+                let peer = management::open_peer(origin.get_uri()).await?;
+                self.load_and_serve(LazyStore::new(store, peer), root_cid)
+                    .await
+            }
+            _ => self.load_and_serve(store, root_cid).await,
+        }
+    }
+
+    /// Loads the root directory with the given CID from the store, or starts from an empty one,
+    /// and serves it.
+    async fn load_and_serve<S>(&self, store: S, root_cid: Option<Cid>) -> anyhow::Result<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let fs = match root_cid {
+            Some(cid) => {
+                tracing::info!("loading root directory {}", cid);
+                let root = Dir::load(&cid, store).await?;
+                MonofsNFS::with_root(root, self.read_only)
+            }
+            None => MonofsNFS::new(store),
+        };
+
+        self.serve(fs, root_cid).await
+    }
+
+    /// Binds the NFS listener for the given filesystem and handles requests until it is shut down.
+    async fn serve<S>(&self, fs: MonofsNFS<S>, mut head: Option<Cid>) -> anyhow::Result<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let addr = format!("{}:{}", self.host, self.port);
+        let listener = NFSTcpListener::bind(&addr, fs.clone()).await?;
+
+        let Some((fs_db_path, mount_dir)) = self.head_tracking.as_ref().filter(|_| !self.read_only)
+        else {
+            listener.handle_forever().await?;
+            return Ok(());
+        };
+
+        let signer = RevisionSigner::open(fs_db_path, mount_dir).await?;
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut interval = time::interval(DEFAULT_CHECKPOINT_INTERVAL);
+        let handle_forever = listener.handle_forever();
+        tokio::pin!(handle_forever);
+
+        loop {
+            tokio::select! {
+                result = &mut handle_forever => {
+                    result?;
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Err(e) = Self::persist_head(&fs, signer.as_ref(), fs_db_path, mount_dir, &mut head).await {
+                        tracing::error!("failed to checkpoint filesystem: {}", e);
+                    }
+                }
+                _ = sigterm.recv() => {
+                    tracing::info!("received SIGTERM signal");
+                    break;
+                }
+                _ = sigint.recv() => {
+                    tracing::info!("received SIGINT signal");
+                    break;
+                }
+            }
+        }
+
+        // Checkpoint whatever changed since the last tick before exiting
+        Self::persist_head(&fs, signer.as_ref(), fs_db_path, mount_dir, &mut head).await?;
+
+        Ok(())
+    }
+
+    /// Joins the Raft group of the filesystem and serves the group's committed head.
+    ///
+    /// Only the leader accepts writes, which it proposes to the group as it checkpoints them.
+    /// Followers serve the committed head read-only and switch to every new head as it is
+    /// committed. Changes a node couldn't get committed, e.g. because it lost leadership, are
+    /// merged with the heads committed since and proposed again once it leads the group.
+    async fn serve_raft<S>(
+        &self,
+        store: S,
+        fs_db_path: &Path,
+        mount_dir: &Path,
+        node_id: u64,
+    ) -> anyhow::Result<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let signer = RevisionSigner::open(fs_db_path, mount_dir).await?;
+        let node = RaftNode::open(
+            LocalPeer::new(store.clone(), fs_db_path, mount_dir),
+            node_id,
+        )
+        .await?;
+
+        // Serve the group on the address of the node's member entry
+        let url = reqwest::Url::parse(node.get_member().get_url())?;
+        let raft_addr = format!(
+            "{}:{}",
+            url.host_str().unwrap_or(DEFAULT_HOST),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let raft_listener = TcpListener::bind(&raft_addr).await?;
+        tracing::info!("serving raft node {} on {}", node_id, raft_addr);
+
+        let router = sync::raft_router(node.clone());
+        let raft_server = tokio::spawn(async move { axum::serve(raft_listener, router).await });
+        let runner = tokio::spawn(node.clone().run());
+
+        let mut head = node.get_head().await?;
+        let root = match head {
+            Some(cid) => Dir::load(&cid, store.clone()).await?,
+            None => Dir::new(store.clone()),
+        };
+        let fs = MonofsNFS::with_root(root, true);
 
-        // Create and start the NFS listener
         let addr = format!("{}:{}", self.host, self.port);
-        let listener = NFSTcpListener::bind(&addr, fs).await?;
-        listener.handle_forever().await?;
+        let listener = NFSTcpListener::bind(&addr, fs.clone()).await?;
+
+        let mut committed = node.subscribe();
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut checkpoints = time::interval(DEFAULT_CHECKPOINT_INTERVAL);
+        let mut heartbeats = time::interval(DEFAULT_RAFT_HEARTBEAT_INTERVAL);
+        let handle_forever = listener.handle_forever();
+        tokio::pin!(handle_forever);
+
+        // The last checkpoint not committed yet and the task proposing it, which runs on its own
+        // so waiting for the commit doesn't hold up the loop
+        let mut pending = None;
+        let mut proposal: Option<JoinHandle<FsResult<()>>> = None;
+
+        loop {
+            tokio::select! {
+                result = &mut handle_forever => {
+                    result?;
+                    break;
+                }
+                _ = heartbeats.tick() => {
+                    fs.set_read_only(node.get_role().await != RaftRole::Leader);
+                }
+                Ok(()) = committed.changed() => {
+                    let new_head = *committed.borrow_and_update();
+                    if let Some(cid) = new_head.filter(|_| new_head != head) {
+                        tracing::info!("switching to committed head {}", cid);
+                        Self::switch_head(&fs, signer.as_ref(), &store, &mut pending, cid).await?;
+                        head = new_head;
+                    }
+                }
+                _ = checkpoints.tick(), if proposal.is_none() && !fs.is_read_only() => {
+                    let checkpoint = Self::checkpoint(&fs, signer.as_ref())
+                        .await
+                        .unwrap_or_else(|e| {
+                            tracing::error!("failed to checkpoint filesystem: {}", e);
+                            None
+                        });
+                    if let Some(cid) = checkpoint.or(pending) {
+                        pending = Some(cid);
+                        let node = node.clone();
+                        proposal = Some(tokio::spawn(async move {
+                            node.propose(cid, head.as_ref()).await
+                        }));
+                    }
+                }
+                result = async {
+                    match proposal.as_mut() {
+                        Some(proposal) => proposal.await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    proposal = None;
+                    let result = result.map_err(FsError::custom).and_then(|result| result);
+                    Self::finish_proposal(&fs, &mut head, &mut pending, result);
+                }
+                _ = sigterm.recv() => {
+                    tracing::info!("received SIGTERM signal");
+                    break;
+                }
+                _ = sigint.recv() => {
+                    tracing::info!("received SIGINT signal");
+                    break;
+                }
+            }
+        }
+
+        // Propose whatever changed since the last tick before leaving the group
+        if let Some(proposal) = proposal {
+            let result = proposal
+                .await
+                .map_err(FsError::custom)
+                .and_then(|result| result);
+            Self::finish_proposal(&fs, &mut head, &mut pending, result);
+        }
+        if !fs.is_read_only() {
+            if let Some(cid) = Self::checkpoint(&fs, signer.as_ref()).await?.or(pending) {
+                pending = Some(cid);
+                let result = node.propose(cid, head.as_ref()).await;
+                Self::finish_proposal(&fs, &mut head, &mut pending, result);
+            }
+        }
+        if let Some(cid) = pending {
+            tracing::warn!("leaving the group without committing head {}", cid);
+        }
+        runner.abort();
+        raft_server.abort();
+
+        Ok(())
+    }
+
+    /// Checkpoints the filesystem, signing the new root if the filesystem has a signing key.
+    async fn checkpoint<S>(
+        fs: &MonofsNFS<S>,
+        signer: Option<&RevisionSigner>,
+    ) -> FsResult<Option<Cid>>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        match signer {
+            Some(signer) => fs.checkpoint_signed(signer.get_keypair()).await,
+            None => fs.checkpoint().await,
+        }
+    }
+
+    /// Records the outcome of proposing the pending checkpoint to the Raft group.
+    ///
+    /// If the proposal fails, the checkpoint stays pending and is proposed again, or merged with
+    /// the head the group commits instead. A node that finds out it isn't the leader stops
+    /// accepting writes right away rather than at the next heartbeat.
+    fn finish_proposal<S>(
+        fs: &MonofsNFS<S>,
+        head: &mut Option<Cid>,
+        pending: &mut Option<Cid>,
+        result: FsResult<()>,
+    ) where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let Some(cid) = *pending else {
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                *head = Some(cid);
+                *pending = None;
+            }
+            Err(e) => {
+                if matches!(e, FsError::NotLeader(_)) {
+                    fs.set_read_only(true);
+                }
+
+                tracing::warn!("failed to propose head {}, keeping it pending: {}", cid, e);
+            }
+        }
+    }
+
+    /// Switches the filesystem to a head committed by the Raft group.
+    ///
+    /// Changes not committed yet, checkpointed or not, are merged with the committed head rather
+    /// than dropped, keeping our version of conflicting changes. The merged root is served from
+    /// then on and stays pending until it is committed.
+    async fn switch_head<S>(
+        fs: &MonofsNFS<S>,
+        signer: Option<&RevisionSigner>,
+        store: &S,
+        pending: &mut Option<Cid>,
+        cid: Cid,
+    ) -> FsResult<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        // Our own proposal was committed
+        if *pending == Some(cid) {
+            *pending = None;
+            return Ok(());
+        }
+
+        let outcome = loop {
+            if let Some(checkpoint) = Self::checkpoint(fs, signer).await? {
+                *pending = Some(checkpoint);
+            }
+
+            if pending.is_none() {
+                if fs.replace_root(Dir::load(&cid, store.clone()).await?).await {
+                    tracing::warn!("dropped changes made while switching to head {}", cid);
+                }
+                return Ok(());
+            }
+
+            // Writes made since the checkpoint are checkpointed before merging
+            let keypair = signer.map(|signer| signer.get_keypair());
+            if let Some(outcome) = fs
+                .merge_root(&cid, &ConflictResolution::Ours, keypair)
+                .await?
+            {
+                break outcome;
+            }
+        };
+
+        for (conflict, resolution) in outcome.get_conflicts() {
+            tracing::warn!(
+                "resolved {} conflict on {} with {}",
+                conflict.get_kind(),
+                conflict.get_path(),
+                resolution
+            );
+        }
+
+        let merged = *outcome.get_head();
+        *pending = None;
+        if merged != cid {
+            tracing::info!(
+                "merged uncommitted changes with head {} into {}",
+                cid,
+                merged
+            );
+            *pending = Some(merged);
+        }
+
+        Ok(())
+    }
+
+    /// Checkpoints the filesystem and moves the tracked head to the new root if anything changed.
+    ///
+    /// If the head was moved by someone else since, e.g. by a sync, the checkpoint is merged with
+    /// it and the head is moved to the merged root, which is served from then on. Conflicting
+    /// changes keep our version. If the head can't be moved, the filesystem stays marked as
+    /// changed so the next checkpoint tries again.
+    async fn persist_head<S>(
+        fs: &MonofsNFS<S>,
+        signer: Option<&RevisionSigner>,
+        fs_db_path: &Path,
+        mount_dir: &Path,
+        head: &mut Option<Cid>,
+    ) -> FsResult<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let Some(cid) = Self::checkpoint(fs, signer).await? else {
+            return Ok(());
+        };
+
+        Self::move_head(fs, signer, fs_db_path, mount_dir, head, cid)
+            .await
+            .inspect_err(|_| fs.mark_dirty())
+    }
+
+    /// Moves the tracked head to a checkpoint of the filesystem, merging the checkpoint with the
+    /// head if someone else moved it.
+    async fn move_head<S>(
+        fs: &MonofsNFS<S>,
+        signer: Option<&RevisionSigner>,
+        fs_db_path: &Path,
+        mount_dir: &Path,
+        head: &mut Option<Cid>,
+        cid: Cid,
+    ) -> FsResult<()>
+    where
+        S: IpldStoreSeekable + Send + Sync + 'static,
+    {
+        let found = match management::set_head(fs_db_path, mount_dir, &cid, head.as_ref()).await {
+            Ok(()) => {
+                *head = Some(cid);
+                return Ok(());
+            }
+            Err(FsError::HeadConflict(Some(found))) => found,
+            Err(e) => return Err(e),
+        };
+
+        tracing::warn!(
+            "head of {} was moved to {} while mounted, merging {} with it",
+            mount_dir.display(),
+            found,
+            cid
+        );
+        let keypair = signer.map(|signer| signer.get_keypair());
+        let Some(outcome) = fs
+            .merge_root(&found, &ConflictResolution::Ours, keypair)
+            .await?
+        else {
+            // Changes made since the checkpoint are merged with the next one
+            return Err(FsError::HeadConflict(Some(found)));
+        };
+
+        for (conflict, resolution) in outcome.get_conflicts() {
+            tracing::warn!(
+                "resolved {} conflict on {} with {}",
+                conflict.get_kind(),
+                conflict.get_path(),
+                resolution
+            );
+        }
+
+        let merged = *outcome.get_head();
+        if merged != found {
+            management::set_head(fs_db_path, mount_dir, &merged, Some(&found)).await?;
+        }
+
+        *head = Some(merged);
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::MemoryStore;
+    use nfsserve::{nfs::filename3, vfs::NFSFileSystem};
+    use tempfile::TempDir;
+
+    use crate::management::FS_DB_MIGRATOR;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_persist_head_merges_head_moved_while_mounted() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let fs_db_path = temp_dir.path().join("fs.db");
+        let mount_dir = temp_dir.path().join("mfs");
+        management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+        let store = MemoryStore::default();
+        let fs = MonofsNFS::new(store.clone());
+        let mkdir = |name: &'static str| {
+            let fs = fs.clone();
+            async move {
+                fs.mkdir(0, &filename3::from(name.as_bytes()))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let mut head = None;
+        mkdir("docs").await;
+        MonofsServer::persist_head(&fs, None, &fs_db_path, &mount_dir, &mut head).await?;
+        let first = head.expect("the head was moved");
+
+        // Someone else moves the head while ours has changes of its own
+        let mut theirs = Dir::load(&first, store.clone()).await?;
+        theirs.create_dir("tests").await?;
+        let theirs = theirs.checkpoint().await?;
+        management::set_head(&fs_db_path, &mount_dir, &theirs, Some(&first)).await?;
+        mkdir("src").await;
+
+        MonofsServer::persist_head(&fs, None, &fs_db_path, &mount_dir, &mut head).await?;
+        let merged = head.expect("the head was moved");
+        assert_eq!(
+            management::get_head(&fs_db_path, &mount_dir).await?,
+            Some(merged)
+        );
+        let root = Dir::load(&merged, store).await?;
+        for name in ["docs", "src", "tests"] {
+            assert!(root.has_entry(name)?, "{} is missing", name);
+        }
+
+        // Later changes go on top of the merged head
+        mkdir("bin").await;
+        MonofsServer::persist_head(&fs, None, &fs_db_path, &mount_dir, &mut head).await?;
+        assert_ne!(head, Some(merged));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_switch_head_merges_uncommitted_changes() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut base = Dir::new(store.clone());
+        base.create_dir("docs").await?;
+        let base = base.checkpoint().await?;
+
+        let fs = MonofsNFS::with_root(Dir::load(&base, store.clone()).await?, false);
+        fs.mkdir(0, &filename3::from(b"src".as_slice()))
+            .await
+            .unwrap();
+        let mut pending = fs.checkpoint().await?;
+        fs.mkdir(0, &filename3::from(b"bin".as_slice()))
+            .await
+            .unwrap();
+
+        // Another leader got a different head committed on top of the same base
+        let mut theirs = Dir::load(&base, store.clone()).await?;
+        theirs.create_dir("tests").await?;
+        let theirs = theirs.checkpoint().await?;
+
+        MonofsServer::switch_head(&fs, None, &store, &mut pending, theirs).await?;
+        let merged = pending.expect("the merged root is pending");
+        assert_ne!(merged, theirs);
+        let root = Dir::load(&merged, store.clone()).await?;
+        for name in ["docs", "src", "bin", "tests"] {
+            assert!(root.has_entry(name)?, "{} is missing", name);
+        }
+
+        // Our own head being committed leaves nothing pending
+        MonofsServer::switch_head(&fs, None, &store, &mut pending, merged).await?;
+        assert_eq!(pending, None);
+        assert_eq!(fs.checkpoint().await?, None);
 
         Ok(())
     }
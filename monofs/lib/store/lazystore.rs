@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use ipldstore::{
+    ipld::cid::Cid, Codec, IpldReferences, IpldStore, IpldStoreSeekable, RawStore, StoreError,
+    StoreResult,
+};
+use monoutils::SeekableReader;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::AsyncRead;
+
+use crate::sync::{self, BlockPeer};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// How long a block the origin doesn't have, or couldn't be asked about, is taken to be missing
+/// before the origin is asked again. Long enough to cover an operation that checks the same
+/// blocks many times, like walking a version history.
+pub const MISSING_BLOCK_TTL: Duration = Duration::from_secs(5);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// An [`IpldStore`] for partial clones that fetches the blocks it is missing from the filesystem
+/// it was cloned from.
+///
+/// ## Read Behavior
+/// Blocks are read from the local store. A node that is not there yet is fetched from the origin
+/// and kept locally before it is returned. Byte streams need every chunk under them, so the whole
+/// DAG under the requested CID is fetched at once on first read.
+///
+/// ## Write Behavior
+/// All writes go to the local store. The origin is never written to.
+///
+/// ## Has Behavior
+/// A block is there if it is stored locally or the origin has it. Blocks the origin doesn't have,
+/// or fails to answer for, are remembered as missing for [`MISSING_BLOCK_TTL`] so that repeated
+/// checks don't each cost a round trip.
+///
+/// ## Example
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use monofs::{store::{FlatFsStore, LazyStore}, sync::RemotePeer};
+///
+/// let store = LazyStore::new(
+///     FlatFsStore::new("mfs.mfs/blocks"),
+///     Arc::new(RemotePeer::new("http://origin.local:3049")),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct LazyStore<S>
+where
+    S: IpldStore,
+{
+    /// The store blocks are read from and written to.
+    local: S,
+
+    /// The peer missing blocks are fetched from.
+    origin: Arc<dyn BlockPeer>,
+
+    /// The blocks the origin was found to be missing, and when.
+    missing: Arc<Mutex<HashMap<Cid, Instant>>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<S> LazyStore<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    /// Creates a new `LazyStore` over the given local store, fetching missing blocks from
+    /// `origin`.
+    pub fn new(local: S, origin: Arc<dyn BlockPeer>) -> Self {
+        Self {
+            local,
+            origin,
+            missing: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the store blocks are read from and written to.
+    pub fn get_local(&self) -> &S {
+        &self.local
+    }
+
+    /// Fetches the block with the given CID from the origin if it is not stored locally.
+    async fn ensure_block(&self, cid: &Cid) -> StoreResult<()> {
+        if self.local.has(cid).await {
+            return Ok(());
+        }
+
+        tracing::debug!("fetching block {} from origin", cid);
+        let bytes = self
+            .origin
+            .get_block(cid)
+            .await
+            .map_err(StoreError::custom)?;
+
+        ipldstore::IpldStoreExt::put_block(&self.local, cid, bytes).await
+    }
+
+    /// Returns true if the origin has the block with the given CID, asking it only if the block
+    /// wasn't found missing within the last [`MISSING_BLOCK_TTL`].
+    async fn origin_has(&self, cid: &Cid) -> bool {
+        {
+            let mut missing = self.missing.lock().unwrap();
+            missing.retain(|_, found_at| found_at.elapsed() < MISSING_BLOCK_TTL);
+            if missing.contains_key(cid) {
+                return false;
+            }
+        }
+
+        let has = match self.origin.has_block(cid).await {
+            Ok(has) => has,
+            Err(e) => {
+                tracing::warn!("failed to ask origin for block {}: {}", cid, e);
+                false
+            }
+        };
+
+        if !has {
+            self.missing.lock().unwrap().insert(*cid, Instant::now());
+        }
+
+        has
+    }
+
+    /// Fetches every block under the given CID that is not stored locally from the origin.
+    async fn ensure_dag(&self, cid: &Cid) -> StoreResult<()> {
+        if self.local.has(cid).await {
+            return Ok(());
+        }
+
+        tracing::debug!("fetching blocks under {} from origin", cid);
+        sync::copy_dag(self.origin.as_ref(), &self.local, *cid)
+            .await
+            .map_err(StoreError::custom)?;
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl<S> IpldStore for LazyStore<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn put_node<T>(&self, data: &T) -> StoreResult<Cid>
+    where
+        T: Serialize + IpldReferences + Sync,
+    {
+        self.local.put_node(data).await
+    }
+
+    async fn put_bytes(&self, reader: impl AsyncRead + Send + Sync) -> StoreResult<Cid> {
+        self.local.put_bytes(reader).await
+    }
+
+    async fn get_node<T>(&self, cid: &Cid) -> StoreResult<T>
+    where
+        T: DeserializeOwned + Send,
+    {
+        self.ensure_block(cid).await?;
+        self.local.get_node(cid).await
+    }
+
+    async fn get_bytes(&self, cid: &Cid) -> StoreResult<Pin<Box<dyn AsyncRead + Send>>> {
+        self.ensure_dag(cid).await?;
+        self.local.get_bytes(cid).await
+    }
+
+    async fn get_bytes_size(&self, cid: &Cid) -> StoreResult<u64> {
+        self.ensure_dag(cid).await?;
+        self.local.get_bytes_size(cid).await
+    }
+
+    async fn has(&self, cid: &Cid) -> bool {
+        self.local.has(cid).await || self.origin_has(cid).await
+    }
+
+    async fn get_supported_codecs(&self) -> HashSet<Codec> {
+        self.local.get_supported_codecs().await
+    }
+
+    async fn get_max_node_block_size(&self) -> StoreResult<Option<u64>> {
+        self.local.get_max_node_block_size().await
+    }
+
+    async fn get_block_count(&self) -> StoreResult<u64> {
+        self.local.get_block_count().await
+    }
+
+    async fn supports_garbage_collection(&self) -> bool {
+        self.local.supports_garbage_collection().await
+    }
+
+    async fn garbage_collect(&self, cid: &Cid) -> StoreResult<HashSet<Cid>> {
+        self.local.garbage_collect(cid).await
+    }
+}
+
+#[async_trait]
+impl<S> RawStore for LazyStore<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn put_raw_block(&self, bytes: impl Into<Bytes> + Send) -> StoreResult<Cid> {
+        self.local.put_raw_block(bytes).await
+    }
+
+    async fn get_raw_block(&self, cid: &Cid) -> StoreResult<Bytes> {
+        self.ensure_block(cid).await?;
+        self.local.get_raw_block(cid).await
+    }
+
+    async fn get_max_raw_block_size(&self) -> StoreResult<Option<u64>> {
+        self.local.get_max_raw_block_size().await
+    }
+}
+
+#[async_trait]
+impl<S> IpldStoreSeekable for LazyStore<S>
+where
+    S: IpldStoreSeekable + Send + Sync + 'static,
+{
+    async fn get_seekable_bytes(
+        &self,
+        cid: &Cid,
+    ) -> StoreResult<Pin<Box<dyn SeekableReader + Send + 'static>>> {
+        self.ensure_dag(cid).await?;
+        self.local.get_seekable_bytes(cid).await
+    }
+}
+
+impl<S> fmt::Debug for LazyStore<S>
+where
+    S: IpldStore + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyStore")
+            .field("local", &self.local)
+            .finish_non_exhaustive()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{MemoryStore, Storable};
+    use tokio::io::AsyncReadExt;
+
+    use std::sync::atomic::Ordering;
+
+    use crate::filesystem::{Dir, File};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lazystore_fetches_on_first_read() -> anyhow::Result<()> {
+        let origin = MemoryStore::default();
+        let mut root = Dir::new(origin.clone());
+        root.create_dir("docs").await?;
+        root.put_adapted_file(
+            "hello.txt",
+            File::with_content(origin.clone(), b"Hello, monofs!".as_slice()).await?,
+        )
+        .await?;
+        let head = root.checkpoint().await?;
+
+        let local = MemoryStore::default();
+        let store = LazyStore::new(local.clone(), Arc::new(origin.clone()));
+        assert_eq!(local.get_block_count().await?, 0);
+
+        // Loading the root only fetches the root node
+        let root = Dir::load(&head, store.clone()).await?;
+        assert_eq!(local.get_block_count().await?, 1);
+        assert!(local.has(&head).await);
+
+        // Reading a file fetches the file node and its content
+        let file = root.get_file("hello.txt").await?.unwrap();
+        let mut content = Vec::new();
+        file.get_input_stream()
+            .await?
+            .read_to_end(&mut content)
+            .await?;
+        assert_eq!(content, b"Hello, monofs!");
+        assert!(local.has(file.get_content().unwrap()).await);
+
+        // The untouched directory is still only at the origin
+        let docs_cid = root.get_entry("docs")?.unwrap().resolve_cid().await?;
+        assert!(!local.has(&docs_cid).await);
+        assert!(store.has(&docs_cid).await);
+
+        // Writes stay local
+        let cid = store.put_raw_block(b"local".to_vec()).await?;
+        assert!(local.has(&cid).await);
+        assert!(!origin.has(&cid).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lazystore_remembers_missing_blocks() -> anyhow::Result<()> {
+        let origin = Arc::new(helper::CountingPeer::default());
+        let store = LazyStore::new(MemoryStore::default(), origin.clone());
+
+        // The origin is asked about a missing block only once
+        let missing = ipldstore::utils::generate_cid(Codec::Raw, b"missing");
+        assert!(!store.has(&missing).await);
+        assert!(!store.has(&missing).await);
+        assert_eq!(origin.queries.load(Ordering::SeqCst), 1);
+
+        // Blocks the origin has are not remembered
+        let cid = origin.inner.put_raw_block(b"present".to_vec()).await?;
+        assert!(store.has(&cid).await);
+        assert!(store.has(&cid).await);
+        assert_eq!(origin.queries.load(Ordering::SeqCst), 3);
+
+        // Neither is a failing origin asked again
+        origin.failing.store(true, Ordering::SeqCst);
+        let unreachable = ipldstore::utils::generate_cid(Codec::Raw, b"unreachable");
+        assert!(!store.has(&unreachable).await);
+        assert!(!store.has(&unreachable).await);
+        assert_eq!(origin.queries.load(Ordering::SeqCst), 4);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod helper {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use ipldstore::MemoryStore;
+
+    use crate::{FsError, FsResult};
+
+    use super::*;
+
+    /// An origin that counts how often it is asked for blocks, and can be made to fail.
+    #[derive(Default)]
+    pub(super) struct CountingPeer {
+        pub(super) inner: MemoryStore,
+        pub(super) queries: AtomicUsize,
+        pub(super) failing: AtomicBool,
+    }
+
+    #[async_trait]
+    impl BlockPeer for CountingPeer {
+        async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(FsError::RemotePeer("unreachable".to_string()));
+            }
+
+            self.inner.has_block(cid).await
+        }
+
+        async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+            self.inner.get_block(cid).await
+        }
+
+        async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+            self.inner.put_block(cid, bytes).await
+        }
+    }
+}
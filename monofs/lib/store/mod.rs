@@ -2,6 +2,7 @@
 
 mod flatfsstore;
 mod layeredfsstore;
+mod lazystore;
 mod membufferstore;
 
 //--------------------------------------------------------------------------------------------------
@@ -10,4 +11,5 @@ mod membufferstore;
 
 pub use flatfsstore::*;
 pub use layeredfsstore::*;
+pub use lazystore::*;
 pub use membufferstore::*;
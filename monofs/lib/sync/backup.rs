@@ -0,0 +1,338 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use getset::Getters;
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld},
+    Codec, IpldReferences,
+};
+
+use crate::{FsError, FsResult};
+
+use super::{BlockPeer, SyncPeer};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The outcome of a sync.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct SyncStats {
+    /// The head the destination was moved to.
+    pub(super) head: Cid,
+
+    /// The number of blocks copied between the peers.
+    pub(super) blocks_copied: u64,
+
+    /// The number of bytes copied between the peers.
+    pub(super) bytes_copied: u64,
+}
+
+/// A block the destination is missing, in the order it has to be written.
+enum MissingBlock {
+    /// A node whose bytes were already fetched to find its links.
+    Node(Cid, Bytes),
+
+    /// A leaf that is fetched only when it is written.
+    Leaf(Cid),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Backs up the filesystem of `source` to `destination`.
+///
+/// Every block reachable from the source head that the destination does not have is copied with
+/// [`copy_dag`], and the destination head is then moved to the source head.
+///
+/// The destination head is moved with a compare-and-swap against the head it had when the backup
+/// started, so a destination that changed in the meantime fails with
+/// [`FsError::HeadConflict`] instead of losing those changes. The copied blocks are kept either
+/// way and make the next attempt cheaper.
+///
+/// ## Arguments
+/// * `source` - The peer to back up
+/// * `destination` - The peer to back up to
+///
+/// ## Example
+/// ```no_run
+/// use monofs::sync::{self, RemotePeer, LocalPeer};
+/// use monofs::store::FlatFsStore;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let source = LocalPeer::new(FlatFsStore::new("mfs.mfs/blocks"), "mfs.mfs/fs.db", "/data/mfs");
+/// let destination = RemotePeer::new("http://backup.local:3049");
+///
+/// let stats = sync::backup(&source, &destination).await?;
+/// println!("copied {} blocks", stats.get_blocks_copied());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn backup(
+    source: &(impl SyncPeer + ?Sized),
+    destination: &(impl SyncPeer + ?Sized),
+) -> FsResult<SyncStats> {
+    let head = source
+        .get_head()
+        .await?
+        .ok_or_else(|| FsError::NoHead("source".to_string()))?;
+    let expected = destination.get_head().await?;
+
+    if expected == Some(head) {
+        tracing::info!("destination is already at {}", head);
+        return Ok(SyncStats {
+            head,
+            blocks_copied: 0,
+            bytes_copied: 0,
+        });
+    }
+
+    let (blocks_copied, bytes_copied) = copy_dag(source, destination, head).await?;
+    destination.set_head(&head, expected.as_ref()).await?;
+
+    Ok(SyncStats {
+        head,
+        blocks_copied,
+        bytes_copied,
+    })
+}
+
+/// Copies every block reachable from `root` that `destination` does not have from `source`.
+///
+/// Blocks are written after everything they link to, so the walk can stop at blocks the
+/// destination already has and an interrupted copy never leaves a block without its links.
+///
+/// ## Returns
+/// The number of blocks and the number of bytes copied
+pub async fn copy_dag(
+    source: &(impl BlockPeer + ?Sized),
+    destination: &(impl BlockPeer + ?Sized),
+    root: Cid,
+) -> FsResult<(u64, u64)> {
+    // Find the missing blocks, children before the nodes linking to them
+    let missing = find_missing_blocks(source, destination, root).await?;
+    tracing::debug!("copying {} missing blocks under {}", missing.len(), root);
+
+    let mut bytes_copied = 0;
+    for block in &missing {
+        let (cid, bytes) = match block {
+            MissingBlock::Node(cid, bytes) => (cid, bytes.clone()),
+            MissingBlock::Leaf(cid) => (cid, source.get_block(cid).await?),
+        };
+
+        bytes_copied += bytes.len() as u64;
+        destination.put_block(cid, bytes).await?;
+    }
+
+    Ok((missing.len() as u64, bytes_copied))
+}
+
+/// Walks the DAG under `root` on `source` and returns the blocks `destination` does not have in
+/// post order, so that writing them in order never writes a node before its links.
+async fn find_missing_blocks(
+    source: &(impl BlockPeer + ?Sized),
+    destination: &(impl BlockPeer + ?Sized),
+    root: Cid,
+) -> FsResult<Vec<MissingBlock>> {
+    let mut missing = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(root, None)];
+
+    while let Some((cid, bytes)) = stack.pop() {
+        // Node revisited after all its links, so it can be written now
+        if let Some(bytes) = bytes {
+            missing.push(MissingBlock::Node(cid, bytes));
+            continue;
+        }
+
+        if !visited.insert(cid) || destination.has_block(&cid).await? {
+            continue;
+        }
+
+        match Codec::try_from(cid.codec())? {
+            Codec::Raw => missing.push(MissingBlock::Leaf(cid)),
+            _ => {
+                let bytes = source.get_block(&cid).await?;
+                let node: Ipld = serde_ipld_dagcbor::from_slice(&bytes)?;
+
+                stack.push((cid, Some(bytes)));
+                stack.extend(node.get_references().map(|link| (*link, None)));
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{IpldStore, MemoryStore, Storable};
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    use crate::{
+        filesystem::{Dir, File},
+        management::{self, FS_DB_MIGRATOR},
+        sync::{BlockPeer, LocalPeer},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backup_copies_only_missing_blocks() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = helper::setup_peer(&temp_dir, "source").await?;
+        let destination = helper::setup_peer(&temp_dir, "destination").await?;
+
+        // Nothing to back up without a head
+        assert!(matches!(
+            backup(&source, &destination).await,
+            Err(FsError::NoHead(_))
+        ));
+
+        // Back up a first revision
+        let store = source.get_store().clone();
+        let mut root = Dir::new(store.clone());
+        root.put_adapted_file(
+            "hello.txt",
+            File::with_content(store.clone(), b"Hello, monofs!".as_slice()).await?,
+        )
+        .await?;
+        root.create_dir("docs").await?;
+        let first = root.checkpoint().await?;
+        source.set_head(&first, None).await?;
+
+        let stats = backup(&source, &destination).await?;
+        assert_eq!(stats.get_head(), &first);
+        assert_eq!(
+            *stats.get_blocks_copied(),
+            destination.get_store().get_block_count().await?
+        );
+        assert_eq!(destination.get_head().await?, Some(first));
+
+        let copy = Dir::load(&first, destination.get_store().clone()).await?;
+        let file = copy.get_file("hello.txt").await?.unwrap();
+        let mut content = Vec::new();
+        file.get_input_stream()
+            .await?
+            .read_to_end(&mut content)
+            .await?;
+        assert_eq!(content, b"Hello, monofs!");
+
+        // An unchanged head copies nothing
+        let stats = backup(&source, &destination).await?;
+        assert_eq!(*stats.get_blocks_copied(), 0);
+
+        // A second revision only copies what changed
+        root.put_adapted_file(
+            "new.txt",
+            File::with_content(store.clone(), b"new".as_slice()).await?,
+        )
+        .await?;
+        let second = root.checkpoint().await?;
+        source.set_head(&second, Some(&first)).await?;
+
+        let before = destination.get_store().get_block_count().await?;
+        let stats = backup(&source, &destination).await?;
+        let after = destination.get_store().get_block_count().await?;
+        assert!(*stats.get_blocks_copied() > 0);
+        assert_eq!(*stats.get_blocks_copied(), after - before);
+        assert_eq!(after, store.get_block_count().await?);
+        assert_eq!(destination.get_head().await?, Some(second));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_rejects_concurrently_moved_head() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = helper::setup_peer(&temp_dir, "source").await?;
+        let destination = helper::setup_peer(&temp_dir, "destination").await?;
+
+        let mut root = Dir::new(source.get_store().clone());
+        root.create_dir("docs").await?;
+        let head = root.checkpoint().await?;
+        source.set_head(&head, None).await?;
+
+        // Another writer moves the destination head while blocks are being copied
+        let concurrent = Dir::new(destination.get_store().clone()).store().await?;
+        let destination = helper::MovingHeadPeer {
+            inner: destination,
+            head: concurrent,
+        };
+
+        let result = backup(&source, &destination).await;
+        assert!(matches!(
+            result,
+            Err(FsError::HeadConflict(Some(found))) if found == concurrent
+        ));
+
+        // The other writer's head is kept, while the copied blocks stay around
+        assert_eq!(destination.inner.get_head().await?, Some(concurrent));
+        assert!(destination.inner.has_block(&head).await?);
+
+        Ok(())
+    }
+
+    mod helper {
+        use async_trait::async_trait;
+
+        use super::*;
+
+        pub(super) async fn setup_peer(
+            temp_dir: &TempDir,
+            name: &str,
+        ) -> anyhow::Result<LocalPeer<MemoryStore>> {
+            let fs_db_path = temp_dir.path().join(format!("{name}.db"));
+            management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+
+            Ok(LocalPeer::new(
+                MemoryStore::default(),
+                fs_db_path,
+                temp_dir.path().join(name),
+            ))
+        }
+
+        /// A peer whose head is moved to `head` by someone else as soon as a block is written.
+        pub(super) struct MovingHeadPeer {
+            pub(super) inner: LocalPeer<MemoryStore>,
+            pub(super) head: Cid,
+        }
+
+        #[async_trait]
+        impl BlockPeer for MovingHeadPeer {
+            async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+                self.inner.has_block(cid).await
+            }
+
+            async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+                self.inner.get_block(cid).await
+            }
+
+            async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+                if self.inner.get_head().await?.is_none() {
+                    self.inner.set_head(&self.head, None).await?;
+                }
+
+                self.inner.put_block(cid, bytes).await
+            }
+        }
+
+        #[async_trait]
+        impl SyncPeer for MovingHeadPeer {
+            async fn get_head(&self) -> FsResult<Option<Cid>> {
+                self.inner.get_head().await
+            }
+
+            async fn set_head(&self, head: &Cid, expected: Option<&Cid>) -> FsResult<()> {
+                self.inner.set_head(head, expected).await
+            }
+        }
+    }
+}
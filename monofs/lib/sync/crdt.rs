@@ -0,0 +1,695 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_recursion::async_recursion;
+use chrono::{DateTime, Utc};
+use getset::Getters;
+use ipldstore::{
+    ipld::{cid::Cid, ipld::Ipld, serde as ipld_serde},
+    IpldStore, Storable,
+};
+use serde::Deserialize;
+
+use crate::{
+    filesystem::{
+        self, is_ancestor, Dir, Entries, Hlc, HybridClock, MetadataSerializable, DIR_TYPE_TAG,
+        SIGNATURE_FIELD,
+    },
+    FsError, FsResult,
+};
+
+use super::{copy_dag, BlockPeer, LocalPeer, SyncPeer, SyncStats};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A replica of a filesystem that syncs with other replicas as a [Merkle-CRDT][merkle-crdt].
+///
+/// Replicas share nothing but blocks. Each one commits its own changes, receives the heads of
+/// other replicas along with the blocks reachable from them, and merges them with
+/// [`merge_heads`] into a head that every replica arrives at once it has seen the same changes.
+///
+/// Heads that have been received but not merged yet are all kept, so nothing is lost between
+/// receiving and merging.
+///
+/// Every entity that changed in a commit is stamped with a timestamp from the replica's
+/// [`HybridClock`], which is what concurrent changes to the same file are ordered by.
+///
+/// ## Examples
+///
+/// ```
+/// use ipldstore::MemoryStore;
+/// use monofs::{filesystem::File, sync::CrdtReplica};
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let mut alice = CrdtReplica::new(MemoryStore::default(), 1);
+/// let mut bob = CrdtReplica::new(MemoryStore::default(), 2);
+///
+/// // Alice and Bob add different files concurrently
+/// let mut root = alice.load_root().await?;
+/// root.put_adapted_file("a.txt", File::new(alice.get_store().clone())).await?;
+/// let alice_head = alice.commit(&root).await?;
+///
+/// let mut root = bob.load_root().await?;
+/// root.put_adapted_file("b.txt", File::new(bob.get_store().clone())).await?;
+/// let bob_head = bob.commit(&root).await?;
+///
+/// // They exchange heads and end up with both files
+/// alice.receive(bob.get_store(), bob_head).await?;
+/// bob.receive(alice.get_store(), alice_head).await?;
+/// assert_eq!(alice.merge().await?, bob.merge().await?);
+///
+/// let root = alice.load_root().await?;
+/// assert!(root.has_entry("a.txt")? && root.has_entry("b.txt")?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [merkle-crdt]: https://research.protocol.ai/publications/merkle-crdts-merkle-dags-meet-crdts/psaras2020.pdf
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct CrdtReplica<S>
+where
+    S: IpldStore,
+{
+    /// The store holding the replica's blocks.
+    store: S,
+
+    /// The clock changes are stamped with.
+    clock: HybridClock,
+
+    /// The heads the replica has not merged yet. A replica in sync has exactly one.
+    heads: BTreeSet<Cid>,
+}
+
+/// The fields every entity node has.
+#[derive(Debug, Deserialize)]
+struct NodeHeader {
+    /// The type tag of the entity.
+    r#type: String,
+
+    /// The metadata of the entity.
+    metadata: MetadataSerializable,
+}
+
+/// An entity node loaded from the store.
+struct Node {
+    /// The CID of the node.
+    cid: Cid,
+
+    /// The fields of the node.
+    fields: BTreeMap<String, Ipld>,
+
+    /// The fields every entity node has, decoded.
+    header: NodeHeader,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<S> CrdtReplica<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    /// Creates a new replica without any heads.
+    ///
+    /// ## Arguments
+    /// * `store` - The store holding the replica's blocks
+    /// * `node` - An identifier for the replica, unique among the replicas it syncs with
+    pub fn new(store: S, node: u64) -> Self {
+        Self {
+            store,
+            clock: HybridClock::new(node),
+            heads: BTreeSet::new(),
+        }
+    }
+
+    /// Creates a replica that starts from an existing head, e.g. one tracked in a filesystem
+    /// database.
+    pub fn with_head(store: S, node: u64, head: Cid) -> Self {
+        Self {
+            heads: BTreeSet::from([head]),
+            ..Self::new(store, node)
+        }
+    }
+
+    /// Loads the root directory of the replica's head, or a new empty directory if the replica
+    /// has no head yet.
+    ///
+    /// Fails if the replica has heads that have not been [merged][Self::merge] yet.
+    pub async fn load_root(&self) -> FsResult<Dir<S>> {
+        match self.single_head()? {
+            Some(head) => Ok(Dir::load(&head, self.store.clone()).await?),
+            None => Ok(Dir::new(self.store.clone())),
+        }
+    }
+
+    /// Commits a new version of the root directory and makes it the replica's only head.
+    ///
+    /// Pending heads are merged first. The root should be a directory loaded from the replica
+    /// and then changed, so that only what changed since is stamped.
+    ///
+    /// ## Returns
+    /// The new head
+    pub async fn commit(&mut self, root: &Dir<S>) -> FsResult<Cid> {
+        let base = self.merge().await?;
+        let cid = root.store().await?;
+        let head = stamp(&self.store, &mut self.clock, cid, base).await?;
+
+        self.heads = BTreeSet::from([head]);
+        Ok(head)
+    }
+
+    /// Fetches the blocks reachable from the head of another replica and adds it to the pending
+    /// heads.
+    ///
+    /// A head that one of the replica's heads already descends from is ignored, and heads the new
+    /// one descends from are dropped.
+    ///
+    /// ## Arguments
+    /// * `source` - Where to fetch missing blocks from, usually the other replica's store
+    /// * `head` - The head of the other replica
+    pub async fn receive(&mut self, source: &(impl BlockPeer + ?Sized), head: Cid) -> FsResult<()> {
+        copy_dag(source, &self.store, head).await?;
+
+        let header = load_header(&self.store, &head).await?;
+        if let Some(clock) = header.metadata.get_clock() {
+            self.clock.observe(clock);
+        }
+
+        let mut heads = BTreeSet::from([head]);
+        for existing in &self.heads {
+            if is_ancestor(&self.store, &head, existing).await? {
+                return Ok(());
+            }
+
+            if !is_ancestor(&self.store, existing, &head).await? {
+                heads.insert(*existing);
+            }
+        }
+
+        self.heads = heads;
+        Ok(())
+    }
+
+    /// Merges the pending heads into one.
+    ///
+    /// ## Returns
+    /// The merged head, or None if the replica has no head yet
+    pub async fn merge(&mut self) -> FsResult<Option<Cid>> {
+        let mut heads = self.heads.iter();
+        let Some(mut merged) = heads.next().copied() else {
+            return Ok(None);
+        };
+
+        for head in heads {
+            merged = merge_heads(&self.store, &merged, head).await?;
+        }
+
+        tracing::debug!("merged {} heads into {}", self.heads.len(), merged);
+        self.heads = BTreeSet::from([merged]);
+
+        Ok(Some(merged))
+    }
+
+    /// Returns the only head of the replica, or fails if there are unmerged heads.
+    fn single_head(&self) -> FsResult<Option<Cid>> {
+        match self.heads.len() {
+            0 | 1 => Ok(self.heads.first().copied()),
+            n => Err(FsError::InvalidOperation(format!(
+                "replica has {} unmerged heads",
+                n
+            ))),
+        }
+    }
+}
+
+impl Node {
+    /// Returns true if the node is a directory.
+    fn is_dir(&self) -> bool {
+        self.header.r#type == DIR_TYPE_TAG
+    }
+
+    /// Returns the sync type of the node, with `Default` resolved to `inherited`.
+    fn sync_type(&self, inherited: filesystem::SyncType) -> filesystem::SyncType {
+        match self.header.metadata.get_sync_type() {
+            filesystem::SyncType::Default => inherited,
+            sync_type => *sync_type,
+        }
+    }
+
+    /// Returns the key the last writer is picked by: the clock, then the modification time for
+    /// nodes that were never stamped, then the CID so that ties are broken the same way
+    /// everywhere.
+    fn lww_key(&self) -> (Option<Hlc>, DateTime<Utc>, Vec<u8>) {
+        (
+            *self.header.metadata.get_clock(),
+            *self.header.metadata.get_modified_at(),
+            self.cid.to_bytes(),
+        )
+    }
+
+    /// Decodes the entries of a directory node.
+    fn entries(&self) -> FsResult<Entries> {
+        filesystem::get_dir_entries(&self.fields)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Merges two heads of a filesystem into one.
+///
+/// The merge is deterministic: every replica that merges the same heads, in any order, ends up
+/// with the same CID.
+///
+/// - If one head descends from the other through its `previous` chain, it is the result.
+/// - Directories synced with [`MerkleCRDT`][filesystem::SyncType::MerkleCRDT], which is what
+///   `Default` resolves to here, are merged entry by entry. Entries are add-wins: an entry
+///   removed on one side survives if the other side re-added or changed it since the version the
+///   removal saw.
+/// - Anything else, e.g. file content, symlinks, directory metadata or whole directories synced
+///   with [`Raft`][filesystem::SyncType::Raft], is last-writer-wins by hybrid logical clock.
+///
+/// ## Arguments
+/// * `store` - A store holding every block reachable from both heads
+/// * `a` - The first head
+/// * `b` - The second head
+pub async fn merge_heads<S>(store: &S, a: &Cid, b: &Cid) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    merge_entity(store, *a, *b, filesystem::SyncType::MerkleCRDT).await
+}
+
+/// Syncs the filesystem of `local` with `remote` as a Merkle-CRDT.
+///
+/// The blocks of each side's head are exchanged, the heads are merged with [`merge_heads`] and
+/// both sides are moved to the merged head with a compare-and-swap.
+///
+/// ## Arguments
+/// * `local` - The peer whose store the merge is done in
+/// * `remote` - The other peer
+pub async fn sync_crdt<S>(
+    local: &LocalPeer<S>,
+    remote: &(impl SyncPeer + ?Sized),
+) -> FsResult<SyncStats>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    let local_head = local.get_head().await?;
+    let remote_head = remote.get_head().await?;
+
+    let mut stats = SyncStats {
+        head: Cid::default(),
+        blocks_copied: 0,
+        bytes_copied: 0,
+    };
+
+    // Bring the remote head into the local store and merge it there
+    if let Some(remote_head) = remote_head {
+        let (blocks, bytes) = copy_dag(remote, local, remote_head).await?;
+        stats.blocks_copied += blocks;
+        stats.bytes_copied += bytes;
+    }
+
+    let head = match (local_head, remote_head) {
+        (None, None) => return Err(FsError::NoHead("either side".to_string())),
+        (Some(head), None) | (None, Some(head)) => head,
+        (Some(local_head), Some(remote_head)) => {
+            merge_heads(local.get_store(), &local_head, &remote_head).await?
+        }
+    };
+
+    if local_head != Some(head) {
+        local.set_head(&head, local_head.as_ref()).await?;
+    }
+
+    if remote_head != Some(head) {
+        let (blocks, bytes) = copy_dag(local, remote, head).await?;
+        stats.blocks_copied += blocks;
+        stats.bytes_copied += bytes;
+        remote.set_head(&head, remote_head.as_ref()).await?;
+    }
+
+    stats.head = head;
+    Ok(stats)
+}
+
+/// Merges two versions of an entity.
+#[async_recursion]
+async fn merge_entity<S>(
+    store: &S,
+    a: Cid,
+    b: Cid,
+    inherited: filesystem::SyncType,
+) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    if a == b || is_ancestor(store, &b, &a).await? {
+        return Ok(a);
+    }
+
+    if is_ancestor(store, &a, &b).await? {
+        return Ok(b);
+    }
+
+    let a = load_node(store, &a).await?;
+    let b = load_node(store, &b).await?;
+
+    let crdt = filesystem::SyncType::MerkleCRDT;
+    if a.is_dir() && b.is_dir() && a.sync_type(inherited) == crdt && b.sync_type(inherited) == crdt
+    {
+        return merge_dirs(store, a, b).await;
+    }
+
+    if a.lww_key() >= b.lww_key() {
+        Ok(a.cid)
+    } else {
+        Ok(b.cid)
+    }
+}
+
+/// Merges two concurrent versions of a directory entry by entry.
+///
+/// The metadata of the last writer is kept, and the merged directory follows it in the version
+/// history.
+async fn merge_dirs<S>(store: &S, a: Node, b: Node) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    let (winner, loser) = if a.lww_key() >= b.lww_key() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+
+    let winner_entries = winner.entries()?;
+    let mut entries = winner_entries.clone();
+    for (name, theirs) in loser.entries()? {
+        let merged = match entries.get(&name) {
+            Some(ours) => merge_entry(store, *ours, theirs).await?,
+            None => theirs,
+        };
+
+        entries.insert(name, merged);
+    }
+
+    if entries == winner_entries {
+        return Ok(winner.cid);
+    }
+
+    let mut fields = winner.fields;
+    fields.insert(
+        "entries".to_string(),
+        ipld_serde::to_ipld(&entries).map_err(FsError::custom)?,
+    );
+    fields.insert("previous".to_string(), Ipld::Link(winner.cid));
+    fields.remove(SIGNATURE_FIELD);
+
+    Ok(store.put_node(&Ipld::Map(fields)).await?)
+}
+
+/// Merges two versions of the same directory entry.
+async fn merge_entry<S>(store: &S, a: (bool, Cid), b: (bool, Cid)) -> FsResult<(bool, Cid)>
+where
+    S: IpldStore + Send + Sync,
+{
+    let crdt = filesystem::SyncType::MerkleCRDT;
+    match (a, b) {
+        _ if a == b => Ok(a),
+        ((true, removed), (false, live)) | ((false, live), (true, removed)) => {
+            // Add-wins: the removal only applies to the version it saw
+            if live == removed || is_ancestor(store, &live, &removed).await? {
+                Ok((true, removed))
+            } else {
+                Ok((false, live))
+            }
+        }
+        ((deleted, a), (_, b)) => Ok((deleted, merge_entity(store, a, b, crdt).await?)),
+    }
+}
+
+/// Stamps every entity that changed between `old` and `new` with a new clock timestamp.
+///
+/// Removed entries keep pointing at the version that was removed, which is what tells a later
+/// merge whether the removal saw a concurrent change.
+#[async_recursion]
+async fn stamp<S>(store: &S, clock: &mut HybridClock, new: Cid, old: Option<Cid>) -> FsResult<Cid>
+where
+    S: IpldStore + Send + Sync,
+{
+    if Some(new) == old {
+        return Ok(new);
+    }
+
+    let node = load_node(store, &new).await?;
+    let old = match old {
+        Some(old) if store.has(&old).await => Some(load_node(store, &old).await?),
+        _ => None,
+    };
+
+    let mut fields = node.fields.clone();
+    if node.is_dir() {
+        let old_entries = match &old {
+            Some(old) if old.is_dir() => old.entries()?,
+            _ => Entries::new(),
+        };
+
+        let mut entries = node.entries()?;
+        for (name, (deleted, link)) in entries.iter_mut() {
+            if *deleted {
+                continue;
+            }
+
+            let previous = old_entries
+                .get(name)
+                .filter(|(deleted, _)| !deleted)
+                .map(|(_, link)| *link);
+
+            *link = stamp(store, clock, *link, previous).await?;
+        }
+
+        fields.insert(
+            "entries".to_string(),
+            ipld_serde::to_ipld(&entries).map_err(FsError::custom)?,
+        );
+    }
+
+    let Some(Ipld::Map(metadata)) = fields.get_mut("metadata") else {
+        return Err(FsError::InvalidOperation(format!(
+            "entity {} has no metadata",
+            new
+        )));
+    };
+
+    let clock = ipld_serde::to_ipld(clock.tick()).map_err(FsError::custom)?;
+    metadata.insert("clock".to_string(), clock);
+
+    // A signature doesn't cover the stamped version
+    fields.remove(SIGNATURE_FIELD);
+
+    Ok(store.put_node(&Ipld::Map(fields)).await?)
+}
+
+/// Loads the fields every entity node has.
+async fn load_header<S>(store: &S, cid: &Cid) -> FsResult<NodeHeader>
+where
+    S: IpldStore + Send + Sync,
+{
+    Ok(store.get_node(cid).await?)
+}
+
+/// Loads an entity node.
+async fn load_node<S>(store: &S, cid: &Cid) -> FsResult<Node>
+where
+    S: IpldStore + Send + Sync,
+{
+    let ipld: Ipld = store.get_node(cid).await?;
+    let header = ipld_serde::from_ipld(ipld.clone()).map_err(FsError::custom)?;
+    let Ipld::Map(fields) = ipld else {
+        return Err(FsError::InvalidOperation(format!(
+            "entity {} is not a map",
+            cid
+        )));
+    };
+
+    Ok(Node {
+        cid: *cid,
+        fields,
+        header,
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ipldstore::MemoryStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_crdt_replicas_converge() -> anyhow::Result<()> {
+        let mut replicas = vec![
+            CrdtReplica::new(MemoryStore::default(), 1),
+            CrdtReplica::new(MemoryStore::default(), 2),
+            CrdtReplica::new(MemoryStore::default(), 3),
+        ];
+
+        // Replica 1 creates the tree and everyone receives it
+        let mut root = replicas[0].load_root().await?;
+        helper::write(&mut root, "a.txt", "a").await?;
+        helper::write(&mut root, "b.txt", "b").await?;
+        helper::write(&mut root, "d.txt", "d").await?;
+        root.create_dir("docs").await?;
+        let head = replicas[0].commit(&root).await?;
+        let source = replicas[0].get_store().clone();
+        for replica in &mut replicas[1..] {
+            replica.receive(&source, head).await?;
+        }
+
+        // Concurrent edits on every replica
+        let mut root = replicas[0].load_root().await?;
+        root.remove_entry("a.txt")?;
+        root.remove_entry("d.txt")?;
+        helper::write(&mut root, "b.txt", "b from 1").await?;
+        replicas[0].commit(&root).await?;
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let mut root = replicas[1].load_root().await?;
+        helper::write(&mut root, "a.txt", "a from 2").await?;
+        helper::write(&mut root, "b.txt", "b from 2").await?;
+        replicas[1].commit(&root).await?;
+
+        let mut root = replicas[2].load_root().await?;
+        helper::write(&mut root, "c.txt", "c from 3").await?;
+        let docs = root.get_dir_mut("docs").await?.unwrap();
+        helper::write(docs, "notes.txt", "notes from 3").await?;
+        replicas[2].commit(&root).await?;
+
+        // Exchange heads, keeping them apart until merged
+        helper::exchange(&mut replicas).await?;
+        assert!(replicas
+            .iter()
+            .all(|replica| replica.get_heads().len() == 3));
+
+        let mut merged = BTreeSet::new();
+        for replica in &mut replicas {
+            merged.insert(replica.merge().await?.unwrap());
+        }
+        assert_eq!(merged.len(), 1);
+
+        // Add-wins for entries, last writer wins for content
+        let root = replicas[0].load_root().await?;
+        assert_eq!(helper::read(&root, "a.txt").await?, Some("a from 2".into()));
+        assert_eq!(helper::read(&root, "b.txt").await?, Some("b from 2".into()));
+        assert_eq!(helper::read(&root, "c.txt").await?, Some("c from 3".into()));
+        assert_eq!(helper::read(&root, "d.txt").await?, None);
+        let docs = root.get_dir("docs").await?.unwrap();
+        assert_eq!(
+            helper::read(docs, "notes.txt").await?,
+            Some("notes from 3".into())
+        );
+
+        // Another round changes nothing
+        helper::exchange(&mut replicas).await?;
+        for replica in &mut replicas {
+            assert_eq!(replica.merge().await?, merged.first().copied());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_heads_is_deterministic() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut replica = CrdtReplica::new(store.clone(), 1);
+        let mut root = replica.load_root().await?;
+        helper::write(&mut root, "a.txt", "a").await?;
+        let base = replica.commit(&root).await?;
+
+        let mut root = replica.load_root().await?;
+        helper::write(&mut root, "b.txt", "b").await?;
+        let left = replica.commit(&root).await?;
+
+        let mut replica = CrdtReplica::with_head(store.clone(), 2, base);
+        let mut root = replica.load_root().await?;
+        helper::write(&mut root, "c.txt", "c").await?;
+        let right = replica.commit(&root).await?;
+
+        // Commutative and idempotent
+        let merged = merge_heads(&store, &left, &right).await?;
+        assert_eq!(merge_heads(&store, &right, &left).await?, merged);
+        assert_eq!(merge_heads(&store, &left, &merged).await?, merged);
+        assert_eq!(merge_heads(&store, &merged, &right).await?, merged);
+
+        // Descendants win outright
+        assert_eq!(merge_heads(&store, &base, &left).await?, left);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod helper {
+    use ipldstore::MemoryStore;
+    use tokio::io::AsyncReadExt;
+
+    use crate::filesystem::File;
+
+    use super::*;
+
+    /// Writes a file with the given content into the directory.
+    pub(super) async fn write(
+        dir: &mut Dir<MemoryStore>,
+        name: &str,
+        content: &str,
+    ) -> anyhow::Result<()> {
+        let file = File::with_content(dir.get_store().clone(), content.as_bytes()).await?;
+        dir.put_adapted_file(name, file).await?;
+        Ok(())
+    }
+
+    /// Reads the content of a file in the directory.
+    pub(super) async fn read(dir: &Dir<MemoryStore>, name: &str) -> anyhow::Result<Option<String>> {
+        let Some(file) = dir.get_file(name).await? else {
+            return Ok(None);
+        };
+
+        let mut content = String::new();
+        file.get_input_stream()
+            .await?
+            .read_to_string(&mut content)
+            .await?;
+        Ok(Some(content))
+    }
+
+    /// Sends the head of every replica to every other replica.
+    pub(super) async fn exchange(replicas: &mut [CrdtReplica<MemoryStore>]) -> anyhow::Result<()> {
+        let heads = replicas
+            .iter()
+            .map(|replica| (replica.get_store().clone(), replica.get_heads().clone()))
+            .collect::<Vec<_>>();
+
+        for (i, replica) in replicas.iter_mut().enumerate() {
+            for (j, (store, heads)) in heads.iter().enumerate() {
+                if i != j {
+                    for head in heads {
+                        replica.receive(store, *head).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
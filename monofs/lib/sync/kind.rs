@@ -0,0 +1,71 @@
+use std::{fmt, str::FromStr};
+
+use crate::FsError;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The kind of sync to perform between two filesystems.
+///
+/// Replicating a head with a Raft group is not a sync between two filesystems, so it has no mode
+/// here and is set up with `monofs raft` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// One-way copy of the source head and every block reachable from it to the destination.
+    Backup,
+
+    /// Two-way merge of both heads as a Merkle-CRDT, after which both filesystems have the same
+    /// head.
+    Crdt,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for SyncMode {
+    type Err = FsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "backup" => Ok(SyncMode::Backup),
+            "crdt" => Ok(SyncMode::Crdt),
+            "raft" => Err(FsError::UnsupportedSyncMode(
+                "raft (replicate with a raft group using `monofs raft` instead)".to_string(),
+            )),
+            _ => Err(FsError::UnsupportedSyncMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncMode::Backup => write!(f, "backup"),
+            SyncMode::Crdt => write!(f, "crdt"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_mode_from_str() {
+        assert_eq!("backup".parse::<SyncMode>().unwrap(), SyncMode::Backup);
+        assert_eq!("crdt".parse::<SyncMode>().unwrap(), SyncMode::Crdt);
+
+        let error = "raft".parse::<SyncMode>().unwrap_err().to_string();
+        assert!(error.contains("monofs raft"), "{}", error);
+        assert!(matches!(
+            "mirror".parse::<SyncMode>(),
+            Err(FsError::UnsupportedSyncMode(mode)) if mode == "mirror"
+        ));
+    }
+}
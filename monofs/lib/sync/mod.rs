@@ -0,0 +1,47 @@
+//! Synchronization of filesystems between stores and machines.
+//!
+//! A filesystem is identified by its head, the CID of its current root directory. Syncing moves
+//! the blocks reachable from one head into another store and then moves the other filesystem's
+//! head, which is only ever done with a compare-and-swap so concurrent writers are never
+//! silently overwritten.
+//!
+//! # Main Types
+//!
+//! - [`SyncPeer`]: One side of a sync. It can tell which blocks it has, exchange blocks and move
+//!   its head.
+//!
+//! - [`LocalPeer`]: A peer backed by a local store and filesystem database.
+//!
+//! - [`RemotePeer`]: A peer on another machine, reached over the HTTP block-transfer protocol
+//!   served by [`peer_router`], which is the block exchange protocol of `ipldstore` with the head
+//!   of the filesystem on top.
+//!
+//! - [`RaftNode`]: A node of a Raft group that agrees on every move of a filesystem's head. Only
+//!   the leader accepts new heads, and followers fetch their blocks from it. See [`raft_router`].
+//!
+//! # Sync Modes
+//!
+//! - [`SyncMode::Backup`]: One-way copy of everything reachable from the source head that the
+//!   destination does not have yet. See [`backup`].
+//!
+//! - [`SyncMode::Crdt`]: Two-way merge of concurrent changes as a Merkle-CRDT. Directory entries
+//!   are add-wins and everything else is last-writer-wins by hybrid logical clock. See
+//!   [`sync_crdt`] and [`CrdtReplica`].
+
+mod backup;
+mod crdt;
+mod kind;
+mod peer;
+mod raft;
+mod remote;
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use backup::*;
+pub use crdt::*;
+pub use kind::*;
+pub use peer::*;
+pub use raft::*;
+pub use remote::*;
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use ipldstore::{ipld::cid::Cid, IpldStore, IpldStoreExt};
+
+use crate::{management, FsResult};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A source or destination of blocks.
+///
+/// Blocks are exchanged in their encoded form and are always checked against their CID before
+/// they are stored. Every [`IpldStore`] is a block peer.
+#[async_trait]
+pub trait BlockPeer: Send + Sync {
+    /// Returns true if the peer has the block with the given CID.
+    async fn has_block(&self, cid: &Cid) -> FsResult<bool>;
+
+    /// Gets the encoded bytes of the block with the given CID.
+    async fn get_block(&self, cid: &Cid) -> FsResult<Bytes>;
+
+    /// Stores the encoded bytes of a block under the given CID.
+    async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()>;
+}
+
+/// One side of a filesystem sync: a block peer with a head.
+#[async_trait]
+pub trait SyncPeer: BlockPeer {
+    /// Gets the head of the peer's filesystem, if it has one.
+    async fn get_head(&self) -> FsResult<Option<Cid>>;
+
+    /// Moves the head of the peer's filesystem to `head` if it is still `expected`.
+    ///
+    /// Fails with [`FsError::HeadConflict`][crate::FsError::HeadConflict] otherwise.
+    async fn set_head(&self, head: &Cid, expected: Option<&Cid>) -> FsResult<()>;
+}
+
+/// A peer backed by a local store, with its head tracked in a filesystem database.
+#[derive(Debug, Clone)]
+pub struct LocalPeer<S>
+where
+    S: IpldStore,
+{
+    /// The store holding the filesystem's blocks.
+    store: S,
+
+    /// The path to the filesystem database.
+    fs_db_path: PathBuf,
+
+    /// The mount directory the filesystem is registered under in the database.
+    mount_dir: PathBuf,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<S> LocalPeer<S>
+where
+    S: IpldStore,
+{
+    /// Creates a new local peer.
+    ///
+    /// ## Arguments
+    /// * `store` - The store holding the filesystem's blocks
+    /// * `fs_db_path` - The path to the filesystem database
+    /// * `mount_dir` - The mount directory the filesystem is registered under
+    ///
+    /// ## Example
+    /// ```
+    /// use ipldstore::MemoryStore;
+    /// use monofs::sync::LocalPeer;
+    ///
+    /// let peer = LocalPeer::new(MemoryStore::default(), "/data/mfs.mfs/fs.db", "/data/mfs");
+    /// assert_eq!(peer.get_mount_dir().to_str(), Some("/data/mfs"));
+    /// ```
+    pub fn new(store: S, fs_db_path: impl Into<PathBuf>, mount_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            store,
+            fs_db_path: fs_db_path.into(),
+            mount_dir: mount_dir.into(),
+        }
+    }
+
+    /// Returns the store holding the filesystem's blocks.
+    pub fn get_store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the path to the filesystem database.
+    pub fn get_fs_db_path(&self) -> &Path {
+        &self.fs_db_path
+    }
+
+    /// Returns the mount directory the filesystem is registered under.
+    pub fn get_mount_dir(&self) -> &Path {
+        &self.mount_dir
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl<S> BlockPeer for S
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+        Ok(self.has(cid).await)
+    }
+
+    async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+        Ok(IpldStoreExt::get_block(self, cid).await?)
+    }
+
+    async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+        Ok(IpldStoreExt::put_block(self, cid, bytes).await?)
+    }
+}
+
+#[async_trait]
+impl<S> BlockPeer for LocalPeer<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+        BlockPeer::has_block(&self.store, cid).await
+    }
+
+    async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+        BlockPeer::get_block(&self.store, cid).await
+    }
+
+    async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+        BlockPeer::put_block(&self.store, cid, bytes).await
+    }
+}
+
+#[async_trait]
+impl<S> SyncPeer for LocalPeer<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn get_head(&self) -> FsResult<Option<Cid>> {
+        management::get_head(&self.fs_db_path, &self.mount_dir).await
+    }
+
+    async fn set_head(&self, head: &Cid, expected: Option<&Cid>) -> FsResult<()> {
+        management::set_head(&self.fs_db_path, &self.mount_dir, head, expected).await
+    }
+}
@@ -0,0 +1,267 @@
+use getset::Getters;
+use ipldstore::ipld::cid::Cid;
+use serde::{Deserialize, Serialize};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The state a Raft node persists in its filesystem database: its term, its vote and its log.
+///
+/// Every entry of the log is a proposed head. Since only the latest committed head matters, the
+/// log is compacted as soon as entries are committed: they are folded into a [`RaftSnapshot`]
+/// holding the committed head, so the log only ever holds entries that are not committed yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RaftLog {
+    /// The latest term the node has seen.
+    term: u64,
+
+    /// The candidate the node voted for in the current term, if any.
+    voted_for: Option<u64>,
+
+    /// The committed entries the log was compacted into.
+    snapshot: RaftSnapshot,
+
+    /// The entries after the snapshot.
+    entries: Vec<RaftEntry>,
+}
+
+/// The committed prefix of a Raft log, compacted into the head it ends at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RaftSnapshot {
+    /// The index of the last compacted entry.
+    index: u64,
+
+    /// The term of the last compacted entry.
+    term: u64,
+
+    /// The committed head, i.e. the checkpoint CID of the filesystem.
+    #[serde(with = "super::cid_serde::option")]
+    head: Option<Cid>,
+}
+
+/// An entry of a Raft log: a head proposed by the leader of a term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub with_prefix")]
+pub struct RaftEntry {
+    /// The term of the leader that proposed the head.
+    term: u64,
+
+    /// The proposed head.
+    #[serde(with = "super::cid_serde")]
+    head: Cid,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RaftLog {
+    /// Returns the index of the last entry.
+    pub fn last_index(&self) -> u64 {
+        self.snapshot.index + self.entries.len() as u64
+    }
+
+    /// Returns the term of the last entry.
+    pub fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map(|entry| entry.term)
+            .unwrap_or(self.snapshot.term)
+    }
+
+    /// Returns the head of the last entry, committed or not.
+    pub fn last_head(&self) -> Option<Cid> {
+        self.entries
+            .last()
+            .map(|entry| entry.head)
+            .or(self.snapshot.head)
+    }
+
+    /// Returns the term of the entry at `index`, or None if there is no such entry or it has been
+    /// compacted.
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.index {
+            return Some(self.snapshot.term);
+        }
+
+        self.entry(index).map(|entry| entry.term)
+    }
+
+    /// Returns the entries after `index`. `index` must not have been compacted.
+    pub(super) fn entries_after(&self, index: u64) -> &[RaftEntry] {
+        let start = (index.saturating_sub(self.snapshot.index) as usize).min(self.entries.len());
+        &self.entries[start..]
+    }
+
+    /// Moves to a newer term, forgetting the vote of the old one.
+    pub(super) fn set_term(&mut self, term: u64) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+    }
+
+    /// Records the vote of the node in the current term.
+    pub(super) fn vote(&mut self, candidate: u64) {
+        self.voted_for = Some(candidate);
+    }
+
+    /// Appends an entry proposed in the current term.
+    pub(super) fn append(&mut self, head: Cid) -> u64 {
+        self.entries.push(RaftEntry {
+            term: self.term,
+            head,
+        });
+
+        self.last_index()
+    }
+
+    /// Adds the entries a leader sent after `prev_index`, dropping any conflicting entries and
+    /// everything after them.
+    pub(super) fn merge(&mut self, prev_index: u64, entries: &[RaftEntry]) {
+        for (index, entry) in (prev_index + 1..).zip(entries) {
+            // Compacted entries are committed, so they already match
+            if index <= self.snapshot.index {
+                continue;
+            }
+
+            match self.term_at(index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => self
+                    .entries
+                    .truncate((index - self.snapshot.index - 1) as usize),
+                None => {}
+            }
+
+            self.entries.push(*entry);
+        }
+    }
+
+    /// Compacts the entries up to and including `index` into the snapshot.
+    ///
+    /// ## Returns
+    /// True if anything was compacted
+    pub(super) fn compact(&mut self, index: u64) -> bool {
+        let index = index.min(self.last_index());
+        if index <= self.snapshot.index {
+            return false;
+        }
+
+        let compacted = self
+            .entries
+            .drain(..(index - self.snapshot.index) as usize)
+            .collect::<Vec<_>>();
+        if let Some(last) = compacted.last() {
+            self.snapshot = RaftSnapshot {
+                index,
+                term: last.term,
+                head: Some(last.head),
+            };
+        }
+
+        true
+    }
+
+    /// Replaces the compacted prefix of the log with a snapshot sent by the leader.
+    ///
+    /// Entries after the snapshot are kept if the log agrees with the snapshot, otherwise the
+    /// whole log is dropped.
+    ///
+    /// ## Returns
+    /// True if the snapshot was newer than the node's own
+    pub(super) fn install(&mut self, snapshot: RaftSnapshot) -> bool {
+        if snapshot.index <= self.snapshot.index {
+            return false;
+        }
+
+        if self.term_at(snapshot.index) == Some(snapshot.term) {
+            self.entries
+                .drain(..(snapshot.index - self.snapshot.index) as usize);
+        } else {
+            self.entries.clear();
+        }
+
+        self.snapshot = snapshot;
+        true
+    }
+
+    /// Returns the entry at `index` if it has not been compacted.
+    fn entry(&self, index: u64) -> Option<&RaftEntry> {
+        let offset = index.checked_sub(self.snapshot.index + 1)?;
+        self.entries.get(offset as usize)
+    }
+}
+
+impl RaftEntry {
+    /// Creates a new log entry.
+    pub fn new(term: u64, head: Cid) -> Self {
+        Self { term, head }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{IpldStore, MemoryStore};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_raft_log_merge_and_compact() -> anyhow::Result<()> {
+        let store = MemoryStore::default();
+        let mut heads = Vec::new();
+        for i in 0..4 {
+            heads.push(store.put_node(&format!("head {}", i)).await?);
+        }
+
+        let mut log = RaftLog::default();
+        log.set_term(1);
+        log.append(heads[0]);
+        log.append(heads[1]);
+        assert_eq!((log.last_index(), log.last_term()), (2, 1));
+
+        // A leader of term 2 overwrites the uncommitted second entry
+        log.set_term(2);
+        log.merge(
+            1,
+            &[RaftEntry::new(2, heads[2]), RaftEntry::new(2, heads[3])],
+        );
+        assert_eq!(log.last_index(), 3);
+        assert_eq!(log.term_at(2), Some(2));
+        assert_eq!(log.last_head(), Some(heads[3]));
+
+        // Resending the same entries changes nothing
+        log.merge(
+            0,
+            &[RaftEntry::new(1, heads[0]), RaftEntry::new(2, heads[2])],
+        );
+        assert_eq!(log.last_index(), 3);
+
+        // Committed entries are compacted into the snapshot
+        assert!(log.compact(2));
+        assert_eq!(*log.get_snapshot().get_head(), Some(heads[2]));
+        assert_eq!(log.get_entries().len(), 1);
+        assert_eq!(log.term_at(1), None);
+        assert_eq!(log.term_at(2), Some(2));
+        assert_eq!(log.entries_after(2), &[RaftEntry::new(2, heads[3])]);
+
+        // A snapshot from a leader that disagrees drops the rest of the log
+        let snapshot = RaftSnapshot {
+            index: 3,
+            term: 3,
+            head: Some(heads[1]),
+        };
+        assert!(log.install(snapshot));
+        assert!(log.get_entries().is_empty());
+        assert_eq!(log.last_head(), Some(heads[1]));
+        assert!(!log.install(snapshot));
+
+        Ok(())
+    }
+}
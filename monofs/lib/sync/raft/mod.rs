@@ -0,0 +1,78 @@
+//! Replication of filesystem heads with the [Raft consensus algorithm][raft].
+//!
+//! A Raft group is a small, fixed set of nodes that agree on every move of a filesystem's head.
+//! Only the leader of the group accepts new heads. It appends them to its log and replicates the
+//! log to the other nodes, the followers, which fetch the blocks of every head they are sent from
+//! the leader before accepting it. A head is committed once a majority of the group has it, after
+//! which every node moves its head to it and serves it.
+//!
+//! Since only the latest committed head matters, committed entries are compacted into a snapshot
+//! holding that head, i.e. the checkpoint CID of the filesystem. Followers that fall behind the
+//! compacted log are sent the snapshot instead and fetch its blocks from the leader.
+//!
+//! The members of the group and each node's term, vote and log are stored in the node's
+//! filesystem database. Nodes talk to each other over HTTP, with JSON bodies:
+//!
+//! - `POST /raft/vote` asks for a vote in an election
+//! - `POST /raft/append` replicates log entries, and doubles as the leader's heartbeat
+//! - `POST /raft/snapshot` installs the committed head on a follower that fell behind
+//!
+//! Besides these, every node serves its blocks and head with the protocol understood by
+//! [`RemotePeer`][super::RemotePeer], so a group can be synced with like any other peer. Moving
+//! the head of a node proposes the new head to the group.
+//!
+//! [raft]: https://raft.github.io/
+
+mod log;
+mod node;
+mod rpc;
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use log::*;
+pub use node::*;
+pub use rpc::*;
+
+//--------------------------------------------------------------------------------------------------
+// Modules
+//--------------------------------------------------------------------------------------------------
+
+/// Serializes CIDs as strings in JSON bodies and the filesystem database.
+mod cid_serde {
+    use ipldstore::ipld::cid::Cid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(cid: &Cid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(cid)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+        let cid = String::deserialize(deserializer)?;
+        Cid::try_from(cid.as_str()).map_err(D::Error::custom)
+    }
+
+    /// Serializes optional CIDs as optional strings.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            cid: &Option<Cid>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match cid {
+                Some(cid) => serializer.collect_str(cid),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Cid>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|cid| Cid::try_from(cid.as_str()).map_err(D::Error::custom))
+                .transpose()
+        }
+    }
+}
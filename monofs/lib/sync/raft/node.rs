@@ -0,0 +1,1054 @@
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future;
+use ipldstore::{ipld::cid::Cid, IpldStore};
+use rand::Rng;
+use tokio::{
+    sync::{watch, Mutex, Notify},
+    time::{self, Instant},
+};
+
+use crate::{
+    config::{
+        RaftMember, DEFAULT_RAFT_COMMIT_TIMEOUT, DEFAULT_RAFT_ELECTION_TIMEOUT,
+        DEFAULT_RAFT_HEARTBEAT_INTERVAL,
+    },
+    management, FsError, FsResult,
+};
+
+use super::{
+    super::{copy_dag, BlockPeer, LocalPeer, RemotePeer, SyncPeer},
+    rpc::{
+        self, AppendRequest, AppendResponse, SnapshotRequest, SnapshotResponse, VoteRequest,
+        VoteResponse,
+    },
+    RaftLog,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A node of a Raft group that replicates the head of a filesystem.
+///
+/// The node keeps its term, vote and log in the filesystem database of its [`LocalPeer`], next
+/// to the members of the group, and moves the peer's head whenever a new head is committed.
+/// [`run`][Self::run] drives elections and replication, and [`raft_router`][super::raft_router]
+/// serves the node to the rest of the group.
+///
+/// The node is itself a [`SyncPeer`]. Its head is the committed head of the group, and moving it
+/// proposes a new head, which only the leader accepts.
+///
+/// Clones share the same node.
+#[derive(Clone)]
+pub struct RaftNode<S>
+where
+    S: IpldStore,
+{
+    inner: Arc<RaftNodeInner<S>>,
+}
+
+/// The role of a node in its Raft group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaftRole {
+    /// Follows a leader, or waits for one to be elected.
+    Follower,
+
+    /// Asks the other nodes to elect it as leader.
+    Candidate,
+
+    /// Accepts new heads and replicates them to the followers.
+    Leader,
+}
+
+/// The state shared by clones of a [`RaftNode`].
+struct RaftNodeInner<S>
+where
+    S: IpldStore,
+{
+    /// The ID of the node.
+    id: u64,
+
+    /// The filesystem whose head is replicated.
+    peer: LocalPeer<S>,
+
+    /// The members of the group, the node included.
+    members: Vec<RaftMember>,
+
+    /// The client used to talk to the other members.
+    client: reqwest::Client,
+
+    /// The mutable state of the node.
+    state: Mutex<RaftState>,
+
+    /// The committed head.
+    committed: watch::Sender<Option<Cid>>,
+
+    /// Wakes the replication tasks of a leader when there is something new to replicate.
+    replicate: Notify,
+}
+
+/// The mutable state of a [`RaftNode`].
+struct RaftState {
+    /// The persisted term, vote and log.
+    log: RaftLog,
+
+    /// The role of the node.
+    role: RaftRole,
+
+    /// The leader of the current term, if known.
+    leader: Option<u64>,
+
+    /// When the node starts an election if it has not heard from a leader.
+    election_deadline: Instant,
+
+    /// The votes a candidate has received in the current term.
+    votes: usize,
+
+    /// The replication progress of every follower, while the node is leader.
+    progress: HashMap<u64, Progress>,
+}
+
+/// How far the log of a follower is known to match the leader's.
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    /// The index of the next entry to send.
+    next_index: u64,
+
+    /// The index of the last entry known to be replicated.
+    match_index: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<S> RaftNode<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    /// Opens the node with the given ID for the filesystem of `peer`.
+    ///
+    /// The members of the group are read from the filesystem database, where the node's persisted
+    /// state is read from too if it has run before.
+    ///
+    /// ## Arguments
+    /// * `peer` - The filesystem whose head is replicated
+    /// * `id` - The ID of the node, which must be a member of the group
+    pub async fn open(peer: LocalPeer<S>, id: u64) -> FsResult<Self> {
+        let fs_db_path = peer.get_fs_db_path();
+        let mount_dir = peer.get_mount_dir();
+
+        let members = management::get_raft_members(fs_db_path, mount_dir).await?;
+        if !members.iter().any(|member| *member.get_id() == id) {
+            return Err(FsError::Raft(format!(
+                "node {} is not a member of the group of {}",
+                id,
+                mount_dir.display()
+            )));
+        }
+
+        let log = management::get_raft_state(fs_db_path, mount_dir)
+            .await?
+            .unwrap_or_default();
+        let (committed, _) = watch::channel(*log.get_snapshot().get_head());
+
+        let state = RaftState {
+            log,
+            role: RaftRole::Follower,
+            leader: None,
+            election_deadline: Instant::now() + random_election_timeout(),
+            votes: 0,
+            progress: HashMap::new(),
+        };
+
+        Ok(Self {
+            inner: Arc::new(RaftNodeInner {
+                id,
+                peer,
+                members,
+                client: reqwest::Client::new(),
+                state: Mutex::new(state),
+                committed,
+                replicate: Notify::new(),
+            }),
+        })
+    }
+
+    /// Returns the ID of the node.
+    pub fn get_id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Returns the members of the group, the node included.
+    pub fn get_members(&self) -> &[RaftMember] {
+        &self.inner.members
+    }
+
+    /// Returns the member entry of the node itself.
+    pub fn get_member(&self) -> &RaftMember {
+        self.member(self.inner.id)
+            .expect("node is a member of its group")
+    }
+
+    /// Returns the filesystem whose head is replicated.
+    pub fn get_peer(&self) -> &LocalPeer<S> {
+        &self.inner.peer
+    }
+
+    /// Returns the current role of the node.
+    pub async fn get_role(&self) -> RaftRole {
+        self.inner.state.lock().await.role
+    }
+
+    /// Returns the current term of the node.
+    pub async fn get_term(&self) -> u64 {
+        *self.inner.state.lock().await.log.get_term()
+    }
+
+    /// Returns the leader of the current term, if known.
+    pub async fn get_leader(&self) -> Option<u64> {
+        self.inner.state.lock().await.leader
+    }
+
+    /// Returns a copy of the node's persisted state.
+    pub async fn get_log(&self) -> RaftLog {
+        self.inner.state.lock().await.log.clone()
+    }
+
+    /// Returns a receiver that is notified whenever a new head is committed.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Cid>> {
+        self.inner.committed.subscribe()
+    }
+
+    /// Proposes a new head to the group and waits until it is committed.
+    ///
+    /// The blocks of the head must already be in the node's store.
+    ///
+    /// ## Arguments
+    /// * `head` - The new head
+    /// * `expected` - The head the caller last saw. The proposal fails with
+    ///   [`FsError::HeadConflict`] if another head was proposed since
+    ///
+    /// ## Errors
+    /// Fails with [`FsError::NotLeader`] if the node is not the leader of the group.
+    pub async fn propose(&self, head: Cid, expected: Option<&Cid>) -> FsResult<()> {
+        let (index, term) = {
+            let mut state = self.inner.state.lock().await;
+            if state.role != RaftRole::Leader {
+                return Err(FsError::NotLeader(
+                    state
+                        .leader
+                        .and_then(|leader| self.member(leader))
+                        .map(|member| member.get_url().clone()),
+                ));
+            }
+
+            let last_head = state.log.last_head();
+            if last_head.as_ref() != expected {
+                return Err(FsError::HeadConflict(last_head));
+            }
+
+            let index = state.log.append(head);
+            self.persist(&state.log).await?;
+            self.advance_commit(&mut state).await?;
+
+            (index, *state.log.get_term())
+        };
+
+        tracing::info!("proposed head {} at index {} in term {}", head, index, term);
+        self.inner.replicate.notify_waiters();
+
+        // Wait for the entry to be committed
+        let deadline = Instant::now() + DEFAULT_RAFT_COMMIT_TIMEOUT;
+        let mut committed = self.subscribe();
+        loop {
+            {
+                let state = self.inner.state.lock().await;
+                if *state.log.get_term() != term {
+                    return Err(FsError::Raft(format!(
+                        "lost leadership before head {} was committed",
+                        head
+                    )));
+                }
+
+                if *state.log.get_snapshot().get_index() >= index {
+                    return Ok(());
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(FsError::Raft(format!(
+                    "timed out waiting for head {} to be committed",
+                    head
+                )));
+            }
+
+            let _ = time::timeout(DEFAULT_RAFT_HEARTBEAT_INTERVAL, committed.changed()).await;
+        }
+    }
+
+    /// Runs elections and, while the node is leader, replication to the followers. Runs until
+    /// the future is dropped or persisting the node's state fails.
+    pub async fn run(self) -> FsResult<()> {
+        tracing::info!(
+            "starting raft node {} of {}",
+            self.inner.id,
+            self.inner.peer.get_mount_dir().display()
+        );
+
+        loop {
+            let (role, deadline) = {
+                let state = self.inner.state.lock().await;
+                (state.role, state.election_deadline)
+            };
+
+            match role {
+                RaftRole::Leader => {
+                    // Replicate to every follower until the node steps down
+                    let term = self.get_term().await;
+                    future::join_all(
+                        self.others()
+                            .map(|member| self.replicate_to(member.clone(), term)),
+                    )
+                    .await;
+                    time::sleep(DEFAULT_RAFT_HEARTBEAT_INTERVAL).await;
+                }
+                _ if Instant::now() >= deadline => self.start_election().await?,
+                _ => time::sleep_until(deadline).await,
+            }
+        }
+    }
+
+    /// Answers a candidate's request for a vote.
+    pub(super) async fn handle_vote(&self, request: VoteRequest) -> FsResult<VoteResponse> {
+        let mut state = self.inner.state.lock().await;
+        let before = state.log.clone();
+        if request.term > *state.log.get_term() {
+            self.step_down(&mut state, request.term);
+        }
+
+        let log = &state.log;
+        let granted = request.term == *log.get_term()
+            && (*log.get_voted_for()).is_none_or(|id| id == request.candidate)
+            && (request.last_log_term, request.last_log_index)
+                >= (log.last_term(), log.last_index());
+
+        if granted {
+            tracing::debug!(
+                "voting for node {} in term {}",
+                request.candidate,
+                request.term
+            );
+            state.log.vote(request.candidate);
+            state.election_deadline = Instant::now() + random_election_timeout();
+        }
+
+        self.persist_changes(&before, &state.log).await?;
+        Ok(VoteResponse {
+            term: *state.log.get_term(),
+            granted,
+        })
+    }
+
+    /// Takes the entries a leader sent, after fetching the blocks of their heads from it.
+    pub(super) async fn handle_append(&self, request: AppendRequest) -> FsResult<AppendResponse> {
+        if !self.accepts_leader(request.term).await {
+            return self.reject_append().await;
+        }
+
+        for entry in &request.entries {
+            self.fetch(request.leader, *entry.get_head()).await?;
+        }
+
+        let mut state = self.inner.state.lock().await;
+        if request.term < *state.log.get_term() {
+            drop(state);
+            return self.reject_append().await;
+        }
+
+        let before = state.log.clone();
+        self.follow(&mut state, request.term, request.leader);
+
+        // Entries before the snapshot are committed, so they match the leader's
+        let matches = request.prev_log_index < *state.log.get_snapshot().get_index()
+            || state.log.term_at(request.prev_log_index) == Some(request.prev_log_term);
+        if !matches {
+            self.persist_changes(&before, &state.log).await?;
+            return Ok(AppendResponse {
+                term: *state.log.get_term(),
+                success: false,
+                last_log_index: state.log.last_index(),
+            });
+        }
+
+        state.log.merge(request.prev_log_index, &request.entries);
+        self.persist_changes(&before, &state.log).await?;
+
+        let last_new_index = request.prev_log_index + request.entries.len() as u64;
+        self.commit(&mut state, request.leader_commit.min(last_new_index))
+            .await?;
+
+        Ok(AppendResponse {
+            term: *state.log.get_term(),
+            success: true,
+            last_log_index: state.log.last_index(),
+        })
+    }
+
+    /// Installs the snapshot a leader sent, after fetching the blocks of its head from it.
+    pub(super) async fn handle_snapshot(
+        &self,
+        request: SnapshotRequest,
+    ) -> FsResult<SnapshotResponse> {
+        if self.accepts_leader(request.term).await {
+            if let Some(head) = request.snapshot.get_head() {
+                self.fetch(request.leader, *head).await?;
+            }
+        }
+
+        let mut state = self.inner.state.lock().await;
+        if request.term >= *state.log.get_term() {
+            self.follow(&mut state, request.term, request.leader);
+
+            if state.log.install(request.snapshot) {
+                tracing::info!(
+                    "installed snapshot at index {} from node {}",
+                    request.snapshot.get_index(),
+                    request.leader
+                );
+                self.persist(&state.log).await?;
+                self.apply(&state).await?;
+            }
+        }
+
+        Ok(SnapshotResponse {
+            term: *state.log.get_term(),
+        })
+    }
+
+    /// Starts an election for the next term, and becomes leader if a majority votes for the node.
+    async fn start_election(&self) -> FsResult<()> {
+        let request = {
+            let mut state = self.inner.state.lock().await;
+            let term = *state.log.get_term() + 1;
+            state.log.set_term(term);
+            state.log.vote(self.inner.id);
+            state.role = RaftRole::Candidate;
+            state.leader = None;
+            state.votes = 1;
+            state.election_deadline = Instant::now() + random_election_timeout();
+            self.persist(&state.log).await?;
+
+            tracing::info!("node {} starting election for term {}", self.inner.id, term);
+            if self.is_majority(state.votes) {
+                return self.become_leader(&mut state).await;
+            }
+
+            VoteRequest {
+                term,
+                candidate: self.inner.id,
+                last_log_index: state.log.last_index(),
+                last_log_term: state.log.last_term(),
+            }
+        };
+
+        let responses = future::join_all(self.others().map(|member| async {
+            let call = rpc::call::<_, VoteResponse>(&self.inner.client, member, "vote", &request);
+            match time::timeout(DEFAULT_RAFT_ELECTION_TIMEOUT, call).await {
+                Ok(Ok(response)) => Some(response),
+                Ok(Err(e)) => {
+                    tracing::debug!("vote request to node {} failed: {}", member.get_id(), e);
+                    None
+                }
+                Err(_) => None,
+            }
+        }))
+        .await;
+
+        let mut state = self.inner.state.lock().await;
+        for response in responses.into_iter().flatten() {
+            if response.term > *state.log.get_term() {
+                self.step_down(&mut state, response.term);
+                return self.persist(&state.log).await;
+            }
+
+            if state.role == RaftRole::Candidate
+                && *state.log.get_term() == request.term
+                && response.granted
+            {
+                state.votes += 1;
+            }
+        }
+
+        if state.role == RaftRole::Candidate
+            && *state.log.get_term() == request.term
+            && self.is_majority(state.votes)
+        {
+            self.become_leader(&mut state).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes over as leader of the current term. [`run`][Self::run] replicates to the followers
+    /// from then on.
+    async fn become_leader(&self, state: &mut RaftState) -> FsResult<()> {
+        let term = *state.log.get_term();
+        tracing::info!("node {} became leader for term {}", self.inner.id, term);
+
+        state.role = RaftRole::Leader;
+        state.leader = Some(self.inner.id);
+        state.progress = self
+            .others()
+            .map(|member| {
+                let progress = Progress {
+                    next_index: state.log.last_index() + 1,
+                    match_index: 0,
+                };
+                (*member.get_id(), progress)
+            })
+            .collect();
+
+        // Entries of earlier terms can only be committed along with one of the current term
+        if let Some(head) = state
+            .log
+            .get_entries()
+            .last()
+            .map(|entry| *entry.get_head())
+        {
+            state.log.append(head);
+            self.persist(&state.log).await?;
+        }
+
+        self.advance_commit(state).await
+    }
+
+    /// Replicates the log to one follower for as long as the node leads the given term.
+    async fn replicate_to(&self, member: RaftMember, term: u64) {
+        loop {
+            match self.replicate_once(&member, term).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => tracing::debug!("replication to node {} failed: {}", member.get_id(), e),
+            }
+
+            let notified = self.inner.replicate.notified();
+            let _ = time::timeout(DEFAULT_RAFT_HEARTBEAT_INTERVAL, notified).await;
+        }
+    }
+
+    /// Sends the follower whatever it is missing, or a heartbeat if it is up to date.
+    ///
+    /// ## Returns
+    /// False once the node no longer leads the given term
+    async fn replicate_once(&self, member: &RaftMember, term: u64) -> FsResult<bool> {
+        let id = *member.get_id();
+        let request = {
+            let state = self.inner.state.lock().await;
+            if state.role != RaftRole::Leader || *state.log.get_term() != term {
+                return Ok(false);
+            }
+
+            let next_index = state.progress[&id].next_index;
+            let snapshot = *state.log.get_snapshot();
+            if next_index <= *snapshot.get_index() {
+                Err(SnapshotRequest {
+                    term,
+                    leader: self.inner.id,
+                    snapshot,
+                })
+            } else {
+                let prev_log_index = next_index - 1;
+                Ok(AppendRequest {
+                    term,
+                    leader: self.inner.id,
+                    prev_log_index,
+                    prev_log_term: state.log.term_at(prev_log_index).unwrap_or_default(),
+                    entries: state.log.entries_after(prev_log_index).to_vec(),
+                    leader_commit: *snapshot.get_index(),
+                })
+            }
+        };
+
+        let client = &self.inner.client;
+        match request {
+            Ok(request) => {
+                let response: AppendResponse =
+                    rpc::call(client, member, "append", &request).await?;
+
+                let mut state = self.inner.state.lock().await;
+                if !self.still_leads(&mut state, term, response.term).await? {
+                    return Ok(false);
+                }
+
+                let progress = state.progress.get_mut(&id).expect("follower has progress");
+                if response.success {
+                    progress.match_index = request.prev_log_index + request.entries.len() as u64;
+                    progress.next_index = progress.match_index + 1;
+                    self.advance_commit(&mut state).await?;
+                } else {
+                    progress.next_index = (progress.next_index - 1)
+                        .min(response.last_log_index + 1)
+                        .max(1);
+                }
+            }
+            Err(request) => {
+                let response: SnapshotResponse =
+                    rpc::call(client, member, "snapshot", &request).await?;
+
+                let mut state = self.inner.state.lock().await;
+                if !self.still_leads(&mut state, term, response.term).await? {
+                    return Ok(false);
+                }
+
+                let progress = state.progress.get_mut(&id).expect("follower has progress");
+                progress.match_index = progress.match_index.max(*request.snapshot.get_index());
+                progress.next_index = progress.match_index + 1;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Steps down if a follower answered with a newer term.
+    ///
+    /// ## Returns
+    /// True if the node still leads the given term
+    async fn still_leads(&self, state: &mut RaftState, term: u64, seen: u64) -> FsResult<bool> {
+        if seen > *state.log.get_term() {
+            self.step_down(state, seen);
+            self.persist(&state.log).await?;
+        }
+
+        Ok(state.role == RaftRole::Leader && *state.log.get_term() == term)
+    }
+
+    /// Commits the latest entry of the current term that a majority has replicated.
+    async fn advance_commit(&self, state: &mut RaftState) -> FsResult<()> {
+        let term = *state.log.get_term();
+        let committed = *state.log.get_snapshot().get_index();
+
+        let mut index = state.log.last_index();
+        while index > committed {
+            let replicas = 1 + state
+                .progress
+                .values()
+                .filter(|progress| progress.match_index >= index)
+                .count();
+
+            if state.log.term_at(index) == Some(term) && self.is_majority(replicas) {
+                return self.commit(state, index).await;
+            }
+
+            index -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Commits the entries up to `index`, compacting them into the snapshot, and moves the head
+    /// of the filesystem to the committed head.
+    async fn commit(&self, state: &mut RaftState, index: u64) -> FsResult<()> {
+        if !state.log.compact(index) {
+            return Ok(());
+        }
+
+        tracing::debug!("committed index {}", state.log.get_snapshot().get_index());
+        self.persist(&state.log).await?;
+        self.apply(state).await
+    }
+
+    /// Moves the head of the filesystem to the committed head.
+    async fn apply(&self, state: &RaftState) -> FsResult<()> {
+        let Some(head) = *state.log.get_snapshot().get_head() else {
+            return Ok(());
+        };
+
+        let peer = &self.inner.peer;
+        let current = peer.get_head().await?;
+        if current != Some(head) {
+            peer.set_head(&head, current.as_ref()).await?;
+        }
+
+        self.inner.committed.send_replace(Some(head));
+        Ok(())
+    }
+
+    /// Returns true if a leader of the given term is not outdated.
+    async fn accepts_leader(&self, term: u64) -> bool {
+        term >= *self.inner.state.lock().await.log.get_term()
+    }
+
+    /// Answers an append request from an outdated leader.
+    async fn reject_append(&self) -> FsResult<AppendResponse> {
+        let state = self.inner.state.lock().await;
+        Ok(AppendResponse {
+            term: *state.log.get_term(),
+            success: false,
+            last_log_index: state.log.last_index(),
+        })
+    }
+
+    /// Follows the leader of the given term.
+    fn follow(&self, state: &mut RaftState, term: u64, leader: u64) {
+        if term > *state.log.get_term() || state.role != RaftRole::Follower {
+            self.step_down(state, term);
+        }
+
+        if state.leader != Some(leader) {
+            tracing::info!(
+                "node {} following node {} in term {}",
+                self.inner.id,
+                leader,
+                term
+            );
+        }
+
+        state.leader = Some(leader);
+        state.election_deadline = Instant::now() + random_election_timeout();
+    }
+
+    /// Becomes a follower in the given term. The caller persists the log.
+    fn step_down(&self, state: &mut RaftState, term: u64) {
+        if state.role == RaftRole::Leader {
+            tracing::info!("node {} stepping down in term {}", self.inner.id, term);
+        }
+
+        state.log.set_term(term);
+        state.role = RaftRole::Follower;
+        state.leader = None;
+        state.progress.clear();
+        state.election_deadline = Instant::now() + random_election_timeout();
+    }
+
+    /// Copies the blocks reachable from `head` from the given member.
+    async fn fetch(&self, from: u64, head: Cid) -> FsResult<()> {
+        if self.inner.peer.has_block(&head).await? {
+            return Ok(());
+        }
+
+        let member = self
+            .member(from)
+            .ok_or_else(|| FsError::Raft(format!("unknown raft member: {}", from)))?;
+
+        let source = RemotePeer::new(member.get_url());
+        let (blocks, bytes) = copy_dag(&source, &self.inner.peer, head).await?;
+        tracing::debug!(
+            "fetched {} blocks ({} bytes) of {} from node {}",
+            blocks,
+            bytes,
+            head,
+            from
+        );
+
+        Ok(())
+    }
+
+    /// Persists the node's term, vote and log.
+    async fn persist(&self, log: &RaftLog) -> FsResult<()> {
+        let peer = &self.inner.peer;
+        management::set_raft_state(peer.get_fs_db_path(), peer.get_mount_dir(), log).await
+    }
+
+    /// Persists the node's term, vote and log if they changed, which heartbeats usually do not.
+    async fn persist_changes(&self, before: &RaftLog, log: &RaftLog) -> FsResult<()> {
+        if before != log {
+            self.persist(log).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the member with the given ID.
+    fn member(&self, id: u64) -> Option<&RaftMember> {
+        self.inner
+            .members
+            .iter()
+            .find(|member| *member.get_id() == id)
+    }
+
+    /// Returns the other members of the group.
+    fn others(&self) -> impl Iterator<Item = &RaftMember> {
+        self.inner
+            .members
+            .iter()
+            .filter(|member| *member.get_id() != self.inner.id)
+    }
+
+    /// Returns true if the given number of nodes is a majority of the group.
+    fn is_majority(&self, count: usize) -> bool {
+        count > self.inner.members.len() / 2
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns a random election timeout, so that nodes rarely start elections at the same time.
+fn random_election_timeout() -> Duration {
+    let base = DEFAULT_RAFT_ELECTION_TIMEOUT;
+    rand::rng().random_range(base..base * 2)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<S> fmt::Debug for RaftNode<S>
+where
+    S: IpldStore,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RaftNode")
+            .field("id", &self.inner.id)
+            .field("members", &self.inner.members)
+            .field("mount_dir", &self.inner.peer.get_mount_dir())
+            .finish()
+    }
+}
+
+impl fmt::Display for RaftRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RaftRole::Follower => write!(f, "follower"),
+            RaftRole::Candidate => write!(f, "candidate"),
+            RaftRole::Leader => write!(f, "leader"),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> BlockPeer for RaftNode<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+        self.inner.peer.has_block(cid).await
+    }
+
+    async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+        self.inner.peer.get_block(cid).await
+    }
+
+    async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+        self.inner.peer.put_block(cid, bytes).await
+    }
+}
+
+#[async_trait]
+impl<S> SyncPeer for RaftNode<S>
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    async fn get_head(&self) -> FsResult<Option<Cid>> {
+        Ok(*self.inner.committed.borrow())
+    }
+
+    async fn set_head(&self, head: &Cid, expected: Option<&Cid>) -> FsResult<()> {
+        self.propose(*head, expected).await
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{MemoryStore, Storable};
+    use tempfile::TempDir;
+    use tokio::{net::TcpListener, task::JoinHandle};
+
+    use crate::{
+        filesystem::Dir,
+        management::{self, FS_DB_MIGRATOR},
+        sync::{self, raft_router},
+    };
+
+    use super::*;
+
+    /// A node running in the test, with the tasks serving and driving it.
+    struct TestNode {
+        node: RaftNode<MemoryStore>,
+        tasks: Vec<JoinHandle<()>>,
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_raft_group_replicates_heads() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Start three nodes on loopback, each with its own store and database
+        let mut listeners = Vec::new();
+        let mut members = Vec::new();
+        for id in 1..=3 {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            members.push(RaftMember::new(
+                id,
+                format!("http://{}", listener.local_addr()?),
+            ));
+            listeners.push(listener);
+        }
+
+        let mut nodes = Vec::new();
+        for (id, listener) in (1..=3).zip(listeners) {
+            let fs_db_path = temp_dir.path().join(format!("node{}.db", id));
+            let mount_dir = temp_dir.path().join(format!("node{}", id));
+            management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+            management::set_raft_members(&fs_db_path, &mount_dir, &members).await?;
+
+            let peer = LocalPeer::new(MemoryStore::default(), &fs_db_path, &mount_dir);
+            let node = RaftNode::open(peer, id).await?;
+            let server = {
+                let router = raft_router(node.clone());
+                tokio::spawn(async move {
+                    axum::serve(listener, router).await.ok();
+                })
+            };
+            let runner = {
+                let node = node.clone();
+                tokio::spawn(async move {
+                    node.run().await.ok();
+                })
+            };
+
+            nodes.push(TestNode {
+                node,
+                tasks: vec![server, runner],
+            });
+        }
+
+        // A leader is elected and the others follow it
+        let leader = helper::wait_for_leader(&nodes).await?;
+        let follower = nodes
+            .iter()
+            .position(|n| n.node.get_id() != leader)
+            .unwrap();
+        let follower_url = nodes[follower].node.get_member().get_url().clone();
+
+        // Write a filesystem and back it up to the group through a follower, which refuses it
+        let store = MemoryStore::default();
+        let fs_db_path = temp_dir.path().join("writer.db");
+        management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+        let writer = LocalPeer::new(store.clone(), &fs_db_path, temp_dir.path().join("writer"));
+
+        let mut root = Dir::new(store.clone());
+        root.create_dir("config").await?;
+        let first = root.checkpoint().await?;
+        writer.set_head(&first, None).await?;
+
+        let result = sync::backup(&writer, &RemotePeer::new(&follower_url)).await;
+        assert!(matches!(result, Err(FsError::RemotePeer(message)) if message.contains("421")));
+
+        let leader_node = &nodes[helper::index_of(&nodes, leader)].node;
+        let leader_url = leader_node.get_member().get_url().clone();
+        sync::backup(&writer, &RemotePeer::new(&leader_url)).await?;
+
+        // Every node ends up with the head and its blocks, fetched from the leader
+        helper::wait_for_head(&nodes, first).await?;
+        for test_node in &nodes {
+            let peer = test_node.node.get_peer();
+            assert_eq!(peer.get_head().await?, Some(first));
+            Dir::load(&first, peer.get_store().clone()).await?;
+
+            // The committed entry was compacted away
+            let log = test_node.node.get_log().await;
+            assert_eq!(*log.get_snapshot().get_head(), Some(first));
+            assert!(log.get_entries().is_empty());
+            assert_eq!(
+                management::get_raft_state(peer.get_fs_db_path(), peer.get_mount_dir()).await?,
+                Some(log)
+            );
+        }
+
+        // Stop the leader, and the remaining two elect a new one that accepts writes
+        let stopped = nodes.remove(helper::index_of(&nodes, leader));
+        for task in stopped.tasks {
+            task.abort();
+        }
+
+        let new_leader = helper::wait_for_leader(&nodes).await?;
+        assert_ne!(new_leader, leader);
+
+        root.create_dir("config/app").await?;
+        let second = root.checkpoint().await?;
+        let new_leader_node = &nodes[helper::index_of(&nodes, new_leader)].node;
+        let result = new_leader_node.propose(second, None).await;
+        assert!(matches!(result, Err(FsError::HeadConflict(Some(found))) if found == first));
+
+        let new_leader_url = new_leader_node.get_member().get_url().clone();
+        writer.set_head(&second, Some(&first)).await?;
+        sync::backup(&writer, &RemotePeer::new(&new_leader_url)).await?;
+
+        helper::wait_for_head(&nodes, second).await?;
+        for test_node in &nodes {
+            let store = test_node.node.get_peer().get_store().clone();
+            let root = Dir::load(&second, store).await?;
+            assert!(root.find("config/app").await?.is_some());
+        }
+
+        Ok(())
+    }
+
+    mod helper {
+        use super::*;
+
+        /// How long to wait for the group to settle.
+        const TIMEOUT: Duration = Duration::from_secs(15);
+
+        /// Waits until exactly one node leads and every other node follows it.
+        pub(super) async fn wait_for_leader(nodes: &[TestNode]) -> anyhow::Result<u64> {
+            let deadline = Instant::now() + TIMEOUT;
+            while Instant::now() < deadline {
+                let mut leaders = Vec::new();
+                let mut followed = Vec::new();
+                for test_node in nodes {
+                    if test_node.node.get_role().await == RaftRole::Leader {
+                        leaders.push(test_node.node.get_id());
+                    }
+                    followed.push(test_node.node.get_leader().await);
+                }
+
+                if let [leader] = leaders[..] {
+                    if followed.iter().all(|id| *id == Some(leader)) {
+                        return Ok(leader);
+                    }
+                }
+
+                time::sleep(Duration::from_millis(50)).await;
+            }
+
+            anyhow::bail!("no leader was elected")
+        }
+
+        /// Waits until every node has committed the given head.
+        pub(super) async fn wait_for_head(nodes: &[TestNode], head: Cid) -> anyhow::Result<()> {
+            let deadline = Instant::now() + TIMEOUT;
+            while Instant::now() < deadline {
+                let mut synced = true;
+                for test_node in nodes {
+                    synced &= test_node.node.get_head().await? == Some(head);
+                }
+
+                if synced {
+                    return Ok(());
+                }
+
+                time::sleep(Duration::from_millis(50)).await;
+            }
+
+            anyhow::bail!("head {} was not replicated", head)
+        }
+
+        /// Returns the position of the node with the given ID.
+        pub(super) fn index_of(nodes: &[TestNode], id: u64) -> usize {
+            nodes
+                .iter()
+                .position(|test_node| test_node.node.get_id() == id)
+                .unwrap()
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use ipldstore::IpldStore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{config::RaftMember, FsResult};
+
+use super::{
+    super::{peer_router, remote},
+    RaftEntry, RaftNode, RaftSnapshot,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A candidate asking for a vote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct VoteRequest {
+    /// The term of the election.
+    pub(super) term: u64,
+
+    /// The candidate asking for the vote.
+    pub(super) candidate: u64,
+
+    /// The index of the candidate's last log entry.
+    pub(super) last_log_index: u64,
+
+    /// The term of the candidate's last log entry.
+    pub(super) last_log_term: u64,
+}
+
+/// The answer to a [`VoteRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct VoteResponse {
+    /// The term of the voter, for the candidate to catch up with.
+    pub(super) term: u64,
+
+    /// Whether the vote was granted.
+    pub(super) granted: bool,
+}
+
+/// A leader replicating its log, or just asserting its leadership if there are no entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct AppendRequest {
+    /// The term of the leader.
+    pub(super) term: u64,
+
+    /// The leader.
+    pub(super) leader: u64,
+
+    /// The index of the entry right before the new ones.
+    pub(super) prev_log_index: u64,
+
+    /// The term of the entry right before the new ones.
+    pub(super) prev_log_term: u64,
+
+    /// The new entries.
+    pub(super) entries: Vec<RaftEntry>,
+
+    /// The index of the last entry the leader has committed.
+    pub(super) leader_commit: u64,
+}
+
+/// The answer to an [`AppendRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct AppendResponse {
+    /// The term of the follower, for the leader to catch up with.
+    pub(super) term: u64,
+
+    /// Whether the follower's log matched the leader's and took the entries.
+    pub(super) success: bool,
+
+    /// The index of the follower's last entry, so the leader knows where to resume.
+    pub(super) last_log_index: u64,
+}
+
+/// A leader sending its snapshot to a follower that fell behind the compacted log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SnapshotRequest {
+    /// The term of the leader.
+    pub(super) term: u64,
+
+    /// The leader.
+    pub(super) leader: u64,
+
+    /// The snapshot.
+    pub(super) snapshot: RaftSnapshot,
+}
+
+/// The answer to a [`SnapshotRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SnapshotResponse {
+    /// The term of the follower, for the leader to catch up with.
+    pub(super) term: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Creates a router that serves a Raft node to the other nodes of its group.
+///
+/// Besides the Raft requests, the router serves the node's blocks and head like
+/// [`peer_router`], which is where followers fetch the blocks of new heads from.
+///
+/// ## Example
+/// ```no_run
+/// use ipldstore::MemoryStore;
+/// use monofs::sync::{self, LocalPeer, RaftNode};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let peer = LocalPeer::new(MemoryStore::default(), "fs.db", "/data/mfs");
+/// let node = RaftNode::open(peer, 1).await?;
+/// tokio::spawn(node.clone().run());
+///
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:3050").await?;
+/// axum::serve(listener, sync::raft_router(node)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn raft_router<S>(node: RaftNode<S>) -> Router
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/raft/vote", post(vote::<S>))
+        .route("/raft/append", post(append::<S>))
+        .route("/raft/snapshot", post(snapshot::<S>))
+        .with_state(node.clone())
+        .merge(peer_router(node.get_peer().get_store().clone(), node))
+}
+
+/// Sends a Raft request to another member of the group.
+pub(super) async fn call<Req, Resp>(
+    client: &reqwest::Client,
+    member: &RaftMember,
+    path: &str,
+    request: &Req,
+) -> FsResult<Resp>
+where
+    Req: Serialize,
+    Resp: DeserializeOwned,
+{
+    let response = client
+        .post(format!("{}/raft/{}", member.get_url(), path))
+        .json(request)
+        .send()
+        .await?;
+
+    Ok(remote::check_response(response).await?.json().await?)
+}
+
+/// Answers a vote request.
+async fn vote<S>(State(node): State<RaftNode<S>>, Json(request): Json<VoteRequest>) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    respond(node.handle_vote(request).await)
+}
+
+/// Answers an append request.
+async fn append<S>(State(node): State<RaftNode<S>>, Json(request): Json<AppendRequest>) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    respond(node.handle_append(request).await)
+}
+
+/// Answers a snapshot request.
+async fn snapshot<S>(
+    State(node): State<RaftNode<S>>,
+    Json(request): Json<SnapshotRequest>,
+) -> Response
+where
+    S: IpldStore + Send + Sync + 'static,
+{
+    respond(node.handle_snapshot(request).await)
+}
+
+/// Turns the result of handling a request into a response.
+fn respond<T: Serialize>(result: FsResult<T>) -> Response {
+    match result {
+        Ok(body) => Json(body).into_response(),
+        Err(e) => remote::error_response(e),
+    }
+}
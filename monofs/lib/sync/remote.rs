@@ -0,0 +1,323 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use ipldstore::{ipld::cid::Cid, IpldStore, IpldStoreExt, RemoteStore, StoreError};
+use serde::{Deserialize, Serialize};
+
+use crate::{FsError, FsResult};
+
+use super::{BlockPeer, SyncPeer};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A peer on another machine, reached over the block-transfer protocol served by
+/// [`peer_router`].
+///
+/// The blocks of the peer are exchanged over the block exchange protocol of
+/// [`ipldstore::exchange_router`] through a [`RemoteStore`], which checks every block it fetches
+/// against its CID. On top of it, the peer serves its head:
+///
+/// - `GET /head` returns the head of the peer's filesystem
+/// - `PUT /head` moves the head if it is still the expected one, or answers `409 Conflict`
+#[derive(Debug, Clone)]
+pub struct RemotePeer {
+    /// The blocks of the peer.
+    store: RemoteStore,
+
+    /// The HTTP client used to talk to the peer about its head.
+    client: reqwest::Client,
+}
+
+/// The body of head requests and responses.
+#[derive(Debug, Serialize, Deserialize)]
+struct HeadBody {
+    /// The head, if there is one.
+    head: Option<String>,
+
+    /// The head the caller expects to replace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RemotePeer {
+    /// Creates a new remote peer for the server at the given base URL.
+    ///
+    /// ## Example
+    /// ```
+    /// use monofs::sync::RemotePeer;
+    ///
+    /// let peer = RemotePeer::new("http://127.0.0.1:3049/");
+    /// assert_eq!(peer.get_url(), "http://127.0.0.1:3049");
+    /// ```
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            store: RemoteStore::new(url),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the base URL of the peer.
+    pub fn get_url(&self) -> &str {
+        self.store.get_url()
+    }
+
+    /// Returns the store the blocks of the peer are exchanged through.
+    pub fn get_store(&self) -> &RemoteStore {
+        &self.store
+    }
+
+    /// Returns the URL of the head.
+    fn head_url(&self) -> String {
+        format!("{}/head", self.get_url())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Creates a router that serves the given peer over the block-transfer protocol understood by
+/// [`RemotePeer`].
+///
+/// The blocks are served from the peer's store by [`ipldstore::exchange_router`], and the head
+/// from the peer.
+///
+/// ## Arguments
+/// * `store` - The store holding the blocks of the peer
+/// * `peer` - The peer whose head is served
+///
+/// ## Example
+/// ```no_run
+/// use ipldstore::MemoryStore;
+/// use monofs::sync::{self, LocalPeer};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let store = MemoryStore::default();
+/// let peer = LocalPeer::new(store.clone(), "fs.db", "/data/mfs");
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:3049").await?;
+/// axum::serve(listener, sync::peer_router(store, peer)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn peer_router<S, P>(store: S, peer: P) -> Router
+where
+    S: IpldStore + Send + Sync + 'static,
+    P: SyncPeer + 'static,
+{
+    Router::new()
+        .route("/head", get(get_head::<P>).put(set_head::<P>))
+        .with_state(Arc::new(peer))
+        .merge(ipldstore::exchange_router(store))
+}
+
+/// Returns the head of the peer's filesystem.
+async fn get_head<P: SyncPeer>(State(peer): State<Arc<P>>) -> Response {
+    match peer.get_head().await {
+        Ok(head) => Json(HeadBody {
+            head: head.map(|cid| cid.to_string()),
+            expected: None,
+        })
+        .into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Moves the head of the peer's filesystem.
+async fn set_head<P: SyncPeer>(State(peer): State<Arc<P>>, Json(body): Json<HeadBody>) -> Response {
+    let result = async {
+        let head = body
+            .head
+            .as_deref()
+            .ok_or_else(|| FsError::RemotePeer("missing head".to_string()))?;
+        let head = Cid::try_from(head)?;
+        let expected = parse_cid(body.expected)?;
+
+        peer.set_head(&head, expected.as_ref()).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(FsError::HeadConflict(found)) => (
+            StatusCode::CONFLICT,
+            Json(HeadBody {
+                head: found.map(|cid| cid.to_string()),
+                expected: None,
+            }),
+        )
+            .into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Maps an error to the status code a [`RemotePeer`] expects for it.
+pub(super) fn error_response(error: FsError) -> Response {
+    let status = match &error {
+        FsError::IpldStore(StoreError::BlockNotFound(_)) => StatusCode::NOT_FOUND,
+        FsError::CidError(_) | FsError::RemotePeer(_) => StatusCode::BAD_REQUEST,
+        FsError::NotLeader(_) => StatusCode::MISDIRECTED_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    tracing::error!("sync request failed: {}", error);
+    (status, error.to_string()).into_response()
+}
+
+/// Parses an optional CID string.
+fn parse_cid(cid: Option<String>) -> FsResult<Option<Cid>> {
+    cid.map(|cid| Cid::try_from(cid.as_str()).map_err(FsError::from))
+        .transpose()
+}
+
+/// Turns an unsuccessful response into an error.
+pub(super) async fn check_response(response: reqwest::Response) -> FsResult<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let message = response.text().await.unwrap_or_default();
+    Err(FsError::RemotePeer(format!("{}: {}", status, message)))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+#[async_trait]
+impl BlockPeer for RemotePeer {
+    async fn has_block(&self, cid: &Cid) -> FsResult<bool> {
+        Ok(self.store.has_block(cid).await?)
+    }
+
+    async fn get_block(&self, cid: &Cid) -> FsResult<Bytes> {
+        Ok(IpldStoreExt::get_block(&self.store, cid).await?)
+    }
+
+    async fn put_block(&self, cid: &Cid, bytes: Bytes) -> FsResult<()> {
+        Ok(IpldStoreExt::put_block(&self.store, cid, bytes).await?)
+    }
+}
+
+#[async_trait]
+impl SyncPeer for RemotePeer {
+    async fn get_head(&self) -> FsResult<Option<Cid>> {
+        let response = self.client.get(self.head_url()).send().await?;
+        let body: HeadBody = check_response(response).await?.json().await?;
+
+        parse_cid(body.head)
+    }
+
+    async fn set_head(&self, head: &Cid, expected: Option<&Cid>) -> FsResult<()> {
+        let response = self
+            .client
+            .put(self.head_url())
+            .json(&HeadBody {
+                head: Some(head.to_string()),
+                expected: expected.map(|cid| cid.to_string()),
+            })
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            let body: HeadBody = response.json().await?;
+            return Err(FsError::HeadConflict(parse_cid(body.head)?));
+        }
+
+        check_response(response).await?;
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use ipldstore::{IpldStore, MemoryStore, RawStore, Storable};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    use crate::{
+        filesystem::Dir,
+        management::{self, FS_DB_MIGRATOR},
+        sync::{self, LocalPeer},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remote_peer_backup() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Serve a destination peer on a local port
+        let fs_db_path = temp_dir.path().join("remote.db");
+        management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+        let remote_store = MemoryStore::default();
+        let served = LocalPeer::new(
+            remote_store.clone(),
+            &fs_db_path,
+            temp_dir.path().join("remote"),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let router = peer_router(remote_store.clone(), served);
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let remote = RemotePeer::new(format!("http://{}", addr));
+
+        // Back up a local filesystem to it
+        let fs_db_path = temp_dir.path().join("local.db");
+        management::init_db(&fs_db_path, &FS_DB_MIGRATOR).await?;
+        let store = MemoryStore::default();
+        let local = LocalPeer::new(store.clone(), &fs_db_path, temp_dir.path().join("local"));
+
+        let mut root = Dir::new(store.clone());
+        root.create_dir("docs").await?;
+        let head = root.checkpoint().await?;
+        local.set_head(&head, None).await?;
+
+        assert_eq!(remote.get_head().await?, None);
+        let stats = sync::backup(&local, &remote).await?;
+        assert!(*stats.get_blocks_copied() > 0);
+        assert_eq!(remote.get_head().await?, Some(head));
+        assert!(remote.has_block(&head).await?);
+        Dir::load(&head, remote_store.clone()).await?;
+
+        // Head moves are compare-and-swap over the wire too
+        let result = remote.set_head(&head, None).await;
+        assert!(matches!(
+            result,
+            Err(FsError::HeadConflict(Some(found))) if found == head
+        ));
+
+        // Blocks that do not match their CID are refused
+        let cid = store.put_raw_block(b"block".to_vec()).await?;
+        assert!(!remote.has_block(&cid).await?);
+        assert!(remote
+            .put_block(&cid, Bytes::from_static(b"tampered"))
+            .await
+            .is_err());
+        assert!(!remote_store.has(&cid).await);
+
+        remote.put_block(&cid, Bytes::from_static(b"block")).await?;
+        assert_eq!(remote.get_block(&cid).await?.as_ref(), b"block");
+
+        Ok(())
+    }
+}
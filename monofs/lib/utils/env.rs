@@ -6,3 +6,16 @@
 
 /// Environment variable for the mfsrun binary path
 pub const MFSRUN_BIN_PATH_ENV_VAR: &str = "MFSRUN_BIN_PATH";
+
+/// Environment variable for the passphrase an encrypted filesystem's key is derived from
+pub const PASSPHRASE_ENV_VAR: &str = "MONOFS_PASSPHRASE";
+
+/// Environment variable for the passphrase the new key of an encrypted filesystem is derived from
+/// when its key is rotated
+pub const NEW_PASSPHRASE_ENV_VAR: &str = "MONOFS_NEW_PASSPHRASE";
+
+/// Environment variable for the path of the keyfile an encrypted filesystem's key is derived from
+pub const KEYFILE_ENV_VAR: &str = "MONOFS_KEYFILE";
+
+/// Environment variable the keys of an encrypted filesystem are handed to its NFS server in
+pub const ENCRYPTION_KEYS_ENV_VAR: &str = "MONOFS_ENCRYPTION_KEYS";
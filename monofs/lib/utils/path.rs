@@ -1,5 +1,7 @@
 //! Path utilities.
 
+use std::path::{Path, PathBuf};
+
 use typed_path::Utf8UnixPath;
 
 use crate::{filesystem::Utf8UnixPathSegment, FsError, FsResult};
@@ -54,6 +56,15 @@ pub fn split_last(path: &Utf8UnixPath) -> FsResult<(Option<&Utf8UnixPath>, Utf8U
     Ok((parent, filename))
 }
 
+/// Get the path of the data directory that sits next to a mount directory, i.e. `<mount_dir>.mfs`.
+pub fn get_mfs_data_dir(mount_dir: impl AsRef<Path>) -> PathBuf {
+    PathBuf::from(format!(
+        "{}.{}",
+        mount_dir.as_ref().display(),
+        MFS_DIR_SUFFIX
+    ))
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
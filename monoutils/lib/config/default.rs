@@ -4,3 +4,6 @@
 
 /// Default maximum log file size (10MB)
 pub const DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Longest line of process output kept in one log record (16KiB). Longer lines are split.
+pub const DEFAULT_LOG_MAX_LINE_LEN: usize = 16 * 1024;
@@ -25,6 +25,14 @@ pub enum MonoutilsError {
     #[error("binary not found at: {0}\nSource: {1}")]
     BinaryNotFound(String, String),
 
+    /// An error that occurred when parsing a restart policy
+    #[error("invalid restart policy: {0}")]
+    InvalidRestartPolicy(String),
+
+    /// An error that occurred when parsing a log record
+    #[error("invalid log record: {0}")]
+    InvalidLogRecord(String),
+
     /// An error that occurred when performing an IO operation
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
@@ -1,9 +1,11 @@
 //! `monoutils::log` is a module containing logging utilities for the monocore project.
 
+mod record;
 mod rotating;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub use record::*;
 pub use rotating::*;
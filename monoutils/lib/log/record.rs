@@ -0,0 +1,246 @@
+//! Structured records of process output.
+//!
+//! Each line a process writes to stdout or stderr becomes a record holding when it was written,
+//! which stream it came from and the line itself. Records are stored as JSON lines, so the two
+//! streams can share a log without their lines getting mixed up.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{MonoutilsError, DEFAULT_LOG_MAX_LINE_LEN};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The output stream a log record came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    /// The standard output of the process.
+    Stdout,
+
+    /// The standard error of the process.
+    Stderr,
+}
+
+/// A line of process output.
+///
+/// # Example
+///
+/// ```
+/// use monoutils::log::{LogRecord, LogStream};
+///
+/// let record = LogRecord::new(LogStream::Stderr, "connection refused");
+/// let json = record.to_json_line();
+///
+/// let parsed: LogRecord = std::str::from_utf8(&json).unwrap().trim_end().parse().unwrap();
+/// assert_eq!(parsed, record);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// When the line was written.
+    #[serde(rename = "ts")]
+    timestamp: DateTime<Utc>,
+
+    /// The stream the line was written to.
+    stream: LogStream,
+
+    /// The line, without its newline.
+    line: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl LogRecord {
+    /// Creates a record of a line written now.
+    pub fn new(stream: LogStream, line: impl Into<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            stream,
+            line: line.into(),
+        }
+    }
+
+    /// Returns when the line was written.
+    pub fn get_timestamp(&self) -> &DateTime<Utc> {
+        &self.timestamp
+    }
+
+    /// Returns the stream the line was written to.
+    pub fn get_stream(&self) -> LogStream {
+        self.stream
+    }
+
+    /// Returns the line.
+    pub fn get_line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the record as a JSON line, ending with a newline.
+    pub fn to_json_line(&self) -> Vec<u8> {
+        // A record only holds strings and a timestamp, which always serialize
+        let mut json = serde_json::to_vec(self).unwrap_or_default();
+        json.push(b'\n');
+        json
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Copies the output of a process stream into a log as records, one per line.
+///
+/// Output is buffered until a line is complete, so lines written in pieces still make a single
+/// record. CRLF line endings are dropped like LF ones. Lines longer than
+/// [`DEFAULT_LOG_MAX_LINE_LEN`] are split into several records, and whatever is left when the
+/// stream ends is recorded as a last line.
+///
+/// Each record is written to `writer` with a single write, so records from streams copied into
+/// the same log concurrently don't mix.
+///
+/// ## Arguments
+///
+/// * `reader` - The process stream to read
+/// * `stream` - Which stream `reader` is
+/// * `writer` - The log to write the records to
+pub async fn copy_log_records(
+    mut reader: impl AsyncRead + Unpin,
+    stream: LogStream,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut buf = [0u8; 8192];
+    let mut pending = Vec::new();
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = &line[..end];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            for chunk in line.chunks(DEFAULT_LOG_MAX_LINE_LEN) {
+                write_record(&mut writer, stream, chunk)?;
+            }
+        }
+
+        while pending.len() >= DEFAULT_LOG_MAX_LINE_LEN {
+            let line: Vec<u8> = pending.drain(..DEFAULT_LOG_MAX_LINE_LEN).collect();
+            write_record(&mut writer, stream, &line)?;
+        }
+    }
+
+    if !pending.is_empty() {
+        write_record(&mut writer, stream, &pending)?;
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions: Helpers
+//--------------------------------------------------------------------------------------------------
+
+/// Writes a line as a record.
+fn write_record(writer: &mut impl Write, stream: LogStream, line: &[u8]) -> io::Result<()> {
+    let record = LogRecord::new(stream, String::from_utf8_lossy(line));
+    writer.write_all(&record.to_json_line())?;
+    writer.flush()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromStr for LogRecord {
+    type Err = MonoutilsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|e| MonoutilsError::InvalidLogRecord(e.to_string()))
+    }
+}
+
+impl FromStr for LogStream {
+    type Err = MonoutilsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            _ => Err(MonoutilsError::InvalidLogRecord(format!(
+                "unknown stream '{}', expected 'stdout' or 'stderr'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_log_records_buffers_partial_lines() -> anyhow::Result<()> {
+        let mut log = Vec::new();
+        let (mut process, reader) = tokio::io::duplex(64);
+        let copy = copy_log_records(reader, LogStream::Stderr, &mut log);
+        let write = async {
+            process.write_all(b"warn").await?;
+            process.write_all(b"ing: disk\r\nsecond\n").await?;
+            process
+                .write_all(&vec![b'x'; DEFAULT_LOG_MAX_LINE_LEN + 1])
+                .await?;
+            process.write_all(b"\nno newline").await?;
+            drop(process);
+            io::Result::Ok(())
+        };
+        let (copied, written) = tokio::join!(copy, write);
+        copied?;
+        written?;
+
+        let records: Vec<LogRecord> = std::str::from_utf8(&log)?
+            .lines()
+            .map(|line| line.parse())
+            .collect::<Result<_, _>>()?;
+        let lines: Vec<_> = records
+            .iter()
+            .map(|record| record.get_line().len())
+            .collect();
+        assert_eq!(lines, [13, 6, DEFAULT_LOG_MAX_LINE_LEN, 1, 10]);
+        assert_eq!(records[0].get_line(), "warning: disk");
+        assert!(records
+            .iter()
+            .all(|record| record.get_stream() == LogStream::Stderr));
+        assert!("{}".parse::<LogRecord>().is_err());
+
+        Ok(())
+    }
+}
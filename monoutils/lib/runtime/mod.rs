@@ -1,6 +1,7 @@
 //! `monoutils::runtime` is a module containing runtime utilities for the monocore project.
 
 mod monitor;
+mod restart;
 mod supervisor;
 
 //--------------------------------------------------------------------------------------------------
@@ -8,4 +9,5 @@ mod supervisor;
 //--------------------------------------------------------------------------------------------------
 
 pub use monitor::*;
+pub use restart::*;
 pub use supervisor::*;
@@ -1,3 +1,5 @@
+use std::process::ExitStatus;
+
 use async_trait::async_trait;
 use tokio::process::{ChildStderr, ChildStdout};
 
@@ -19,6 +21,15 @@ pub trait ProcessMonitor {
         stderr: ChildStderr,
     ) -> MonoutilsResult<()>;
 
-    /// Stop monitoring
-    async fn stop(&mut self) -> MonoutilsResult<()>;
+    /// Stop monitoring once the process has exited, with its exit status if it could be waited
+    /// for
+    async fn stop(&mut self, exit_status: Option<ExitStatus>) -> MonoutilsResult<()>;
+
+    /// Called after the process has exited and before it is started again, with the number of
+    /// times it has been restarted so far, this restart included. If the supervisor is stopped
+    /// before the process is started again, `stop` is called again with the same exit status
+    async fn restart(&mut self, restart_count: u32) -> MonoutilsResult<()> {
+        let _ = restart_count;
+        Ok(())
+    }
 }
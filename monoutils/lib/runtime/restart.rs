@@ -0,0 +1,283 @@
+use std::{fmt, process::ExitStatus, str::FromStr, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::MonoutilsError;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The number of times a process is restarted by an `on-failure` policy without a retry count.
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// The delay before the first restart of a process.
+pub const DEFAULT_RESTART_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest delay between restarts of a process.
+pub const DEFAULT_RESTART_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a process has to run for its restart delay to go back to the initial delay.
+pub const DEFAULT_RESTART_RESET_AFTER: Duration = Duration::from_secs(10);
+
+/// How many times in a row a process can exit before [`DEFAULT_RESTART_RESET_AFTER`] before it is
+/// considered to be crash looping.
+pub const DEFAULT_CRASH_LOOP_THRESHOLD: u32 = 5;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// When a supervised process is restarted after it exits.
+///
+/// A process is never restarted when the supervisor itself is asked to stop.
+///
+/// Policies are written as `never`, `always`, `on-failure` or `on-failure:<max retries>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// The process is not restarted.
+    #[default]
+    Never,
+
+    /// The process is restarted when it exits with a non-zero status or is killed by a signal,
+    /// up to `max_retries` times.
+    OnFailure {
+        /// The most times the process is restarted.
+        max_retries: u32,
+    },
+
+    /// The process is restarted however it exits.
+    Always,
+}
+
+/// The delays between restarts of a process.
+///
+/// The delay doubles with each restart, up to a maximum, and has a random part so processes
+/// that fail together don't restart in lockstep. A process that runs for long enough has its
+/// delay go back to the initial delay, while one that keeps exiting quickly is considered to be
+/// crash looping and waits the maximum delay between restarts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartBackoff {
+    /// The delay before the first restart.
+    initial_delay: Duration,
+
+    /// The longest delay between restarts.
+    max_delay: Duration,
+
+    /// How long a process has to run for the delay to be reset.
+    reset_after: Duration,
+
+    /// How many quick exits in a row make a crash loop.
+    crash_loop_threshold: u32,
+
+    /// The number of quick exits in a row so far.
+    quick_exits: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RestartPolicy {
+    /// Returns whether a process that exited with the given status should be restarted, given the
+    /// number of times it has already been restarted.
+    ///
+    /// ## Arguments
+    ///
+    /// * `exit_status` - The exit status of the process, or None if it couldn't be waited for
+    /// * `restart_count` - The number of times the process has been restarted
+    pub fn should_restart(&self, exit_status: Option<&ExitStatus>, restart_count: u32) -> bool {
+        match self {
+            Self::Never => false,
+            Self::OnFailure { max_retries } => {
+                !exit_status.is_some_and(ExitStatus::success) && restart_count < *max_retries
+            }
+            Self::Always => true,
+        }
+    }
+}
+
+impl RestartBackoff {
+    /// Creates a backoff going from `initial_delay` up to `max_delay`.
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay: max_delay.max(initial_delay),
+            ..Default::default()
+        }
+    }
+
+    /// Sets how long a process has to run for the delay to go back to the initial delay.
+    pub fn with_reset_after(mut self, reset_after: Duration) -> Self {
+        self.reset_after = reset_after;
+        self
+    }
+
+    /// Sets how many quick exits in a row make a crash loop.
+    pub fn with_crash_loop_threshold(mut self, crash_loop_threshold: u32) -> Self {
+        self.crash_loop_threshold = crash_loop_threshold;
+        self
+    }
+
+    /// Returns the delay before restarting a process that exited after running for `uptime`.
+    pub fn next_delay(&mut self, uptime: Duration) -> Duration {
+        if uptime >= self.reset_after {
+            self.quick_exits = 0;
+        }
+
+        self.quick_exits = self.quick_exits.saturating_add(1);
+        if self.is_crash_looping() {
+            return self.max_delay;
+        }
+
+        // Half the delay is fixed and half is random
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(self.quick_exits - 1))
+            .min(self.max_delay);
+        let jitter = rand::rng().random_range(0.0..=0.5);
+
+        delay.mul_f64(1.0 - jitter)
+    }
+
+    /// Returns whether the process has exited quickly enough times in a row to be crash looping.
+    pub fn is_crash_looping(&self) -> bool {
+        self.quick_exits >= self.crash_loop_threshold
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for RestartBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delay: DEFAULT_RESTART_INITIAL_DELAY,
+            max_delay: DEFAULT_RESTART_MAX_DELAY,
+            reset_after: DEFAULT_RESTART_RESET_AFTER,
+            crash_loop_threshold: DEFAULT_CRASH_LOOP_THRESHOLD,
+            quick_exits: 0,
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = MonoutilsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "never" => Ok(Self::Never),
+            None if s == "always" => Ok(Self::Always),
+            None if s == "on-failure" => Ok(Self::OnFailure {
+                max_retries: DEFAULT_MAX_RETRIES,
+            }),
+            Some(("on-failure", max_retries)) => {
+                let max_retries = max_retries.parse().map_err(|_| {
+                    MonoutilsError::InvalidRestartPolicy(format!(
+                        "invalid retry count '{}' in '{}'",
+                        max_retries, s
+                    ))
+                })?;
+                Ok(Self::OnFailure { max_retries })
+            }
+            _ => Err(MonoutilsError::InvalidRestartPolicy(format!(
+                "unknown restart policy '{}', expected 'never', 'always', 'on-failure' or \
+                 'on-failure:<max retries>'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Never => write!(f, "never"),
+            Self::OnFailure { max_retries } => write!(f, "on-failure:{}", max_retries),
+            Self::Always => write!(f, "always"),
+        }
+    }
+}
+
+impl Serialize for RestartPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for RestartPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    #[test]
+    fn test_restart_policy_parse_and_decide() -> anyhow::Result<()> {
+        assert_eq!("never".parse::<RestartPolicy>()?, RestartPolicy::Never);
+        assert_eq!("always".parse::<RestartPolicy>()?, RestartPolicy::Always);
+        assert_eq!(
+            "on-failure".parse::<RestartPolicy>()?,
+            RestartPolicy::OnFailure {
+                max_retries: DEFAULT_MAX_RETRIES
+            }
+        );
+        let on_failure: RestartPolicy = "on-failure:2".parse()?;
+        assert_eq!(on_failure, RestartPolicy::OnFailure { max_retries: 2 });
+        assert_eq!(on_failure.to_string(), "on-failure:2");
+        assert!("on-failure:many".parse::<RestartPolicy>().is_err());
+        assert!("sometimes".parse::<RestartPolicy>().is_err());
+
+        let success = ExitStatus::from_raw(0);
+        let failure = ExitStatus::from_raw(1 << 8);
+        assert!(!on_failure.should_restart(Some(&success), 0));
+        assert!(on_failure.should_restart(Some(&failure), 1));
+        assert!(on_failure.should_restart(None, 1));
+        assert!(!on_failure.should_restart(Some(&failure), 2));
+        assert!(RestartPolicy::Always.should_restart(Some(&success), 100));
+        assert!(!RestartPolicy::Never.should_restart(Some(&failure), 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_backoff_grows_and_detects_crash_loops() {
+        let mut backoff = RestartBackoff::new(Duration::from_secs(1), Duration::from_secs(8))
+            .with_reset_after(Duration::from_secs(10))
+            .with_crash_loop_threshold(6);
+
+        // Quick exits double the delay, which is between half of it and all of it
+        for expected in [1, 2, 4, 8, 8] {
+            let delay = backoff.next_delay(Duration::ZERO);
+            let expected = Duration::from_secs(expected);
+            assert!(delay >= expected / 2 && delay <= expected, "{:?}", delay);
+        }
+        assert!(!backoff.is_crash_looping());
+
+        // Crash looping processes wait the whole maximum delay
+        assert_eq!(backoff.next_delay(Duration::ZERO), Duration::from_secs(8));
+        assert!(backoff.is_crash_looping());
+
+        // A process that ran long enough starts over
+        assert!(backoff.next_delay(Duration::from_secs(10)) <= Duration::from_secs(1));
+        assert!(!backoff.is_crash_looping());
+    }
+}
@@ -4,9 +4,13 @@ use tokio::{
     fs::create_dir_all,
     process::Command,
     signal::unix::{signal, SignalKind},
+    time::{self, Instant},
 };
 
-use crate::{path::SUPERVISOR_LOG_FILENAME, MonoutilsResult, ProcessMonitor, RotatingLog};
+use crate::{
+    path::SUPERVISOR_LOG_FILENAME, MonoutilsResult, ProcessMonitor, RestartBackoff, RestartPolicy,
+    RotatingLog,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -37,6 +41,15 @@ where
 
     /// The metrics monitor
     process_monitor: M,
+
+    /// When the child process is restarted after it exits
+    restart_policy: RestartPolicy,
+
+    /// The delays between restarts of the child process
+    restart_backoff: RestartBackoff,
+
+    /// The number of times the child process has been restarted
+    restart_count: u32,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -76,15 +89,39 @@ where
             child_pid: None,
             log_dir: log_dir.into(),
             process_monitor,
+            restart_policy: RestartPolicy::default(),
+            restart_backoff: RestartBackoff::default(),
+            restart_count: 0,
         }
     }
 
+    /// Sets when the child process is restarted after it exits.
+    pub fn with_restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    /// Sets the delays between restarts of the child process.
+    pub fn with_restart_backoff(mut self, restart_backoff: RestartBackoff) -> Self {
+        self.restart_backoff = restart_backoff;
+        self
+    }
+
+    /// Returns the number of times the child process has been restarted.
+    pub fn get_restart_count(&self) -> u32 {
+        self.restart_count
+    }
+
     /// Starts the supervisor and the child process.
     ///
     /// This method:
     /// 1. Creates the log directory if it doesn't exist
     /// 2. Starts the child process
     /// 3. Passes stdout/stderr to the process monitor
+    /// 4. Waits for the child to exit, forwarding SIGTERM and SIGINT to it as SIGTERM
+    /// 5. Stops the process monitor with the exit status of the child
+    /// 6. Restarts the child after a backoff delay if the restart policy says so, unless the
+    ///    supervisor was signalled to stop
     pub async fn start(&mut self) -> MonoutilsResult<()> {
         // Create log directory if it doesn't exist
         create_dir_all(&self.log_dir).await?;
@@ -92,110 +129,114 @@ where
         // Setup supervisor's rotating log
         let _supervisor_log = RotatingLog::new(self.log_dir.join(SUPERVISOR_LOG_FILENAME)).await?;
 
-        // Start child process
-        let mut child = Command::new(&self.child_exe)
-            .args(&self.child_args)
-            .envs(self.child_envs.iter().map(|(k, v)| (k, v)))
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let child_pid = child.id().expect("Failed to get child process ID");
-        self.child_pid = Some(child_pid);
-
-        // Take ownership of child's stdout/stderr and start monitoring
-        let stdout = child.stdout.take().expect("Failed to take child stdout");
-        let stderr = child.stderr.take().expect("Failed to take child stderr");
-        self.process_monitor
-            .start(child_pid, self.child_name.clone(), stdout, stderr)
-            .await?;
-
         // Setup signal handlers
         let mut sigterm = signal(SignalKind::terminate())?;
         let mut sigint = signal(SignalKind::interrupt())?;
 
-        // Wait for either child process to exit or signal to be received
-        tokio::select! {
-            status = child.wait() => {
-                tracing::info!("Child process {} exited", child_pid);
-
-                // Stop process monitoring
-                self.process_monitor.stop().await?;
-
-                if status.is_ok() {
-                    if let Ok(status) = status {
-                        if status.success() {
-                            tracing::info!(
-                                "Child process {} exited successfully",
-                                child_pid
-                            );
-                        } else {
-                            tracing::error!(
-                                "Child process {} exited with status: {:?}",
-                                child_pid,
-                                status
-                            );
-                        }
-                    }
-                } else {
+        loop {
+            // Start child process
+            let mut child = Command::new(&self.child_exe)
+                .args(&self.child_args)
+                .envs(self.child_envs.iter().map(|(k, v)| (k, v)))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+
+            let started_at = Instant::now();
+            let child_pid = child.id().expect("Failed to get child process ID");
+            self.child_pid = Some(child_pid);
+
+            // Take ownership of child's stdout/stderr and start monitoring
+            let stdout = child.stdout.take().expect("Failed to take child stdout");
+            let stderr = child.stderr.take().expect("Failed to take child stderr");
+            self.process_monitor
+                .start(child_pid, self.child_name.clone(), stdout, stderr)
+                .await?;
+
+            // Wait for either child process to exit or signal to be received
+            let (status, signalled) = tokio::select! {
+                status = child.wait() => {
+                    tracing::info!("Child process {} exited", child_pid);
+                    (status, false)
+                }
+                _ = sigterm.recv() => {
+                    tracing::info!("Received SIGTERM signal");
+                    self.terminate_child();
+                    (child.wait().await, true)
+                }
+                _ = sigint.recv() => {
+                    tracing::info!("Received SIGINT signal");
+                    self.terminate_child();
+                    (child.wait().await, true)
+                }
+            };
+
+            match &status {
+                Ok(status) if status.success() => {
+                    tracing::info!("Child process {} exited successfully", child_pid);
+                }
+                Ok(status) => {
                     tracing::error!(
-                        "Failed to wait for child process {}: {:?}",
+                        "Child process {} exited with status: {:?}",
                         child_pid,
                         status
                     );
                 }
-            }
-            _ = sigterm.recv() => {
-                tracing::info!("Received SIGTERM signal");
-
-                // Stop process monitoring
-                self.process_monitor.stop().await?;
-
-                if let Some(pid) = self.child_pid.take() {
-                    if let Err(e) = nix::sys::signal::kill(Pid::from_raw(pid as i32), nix::sys::signal::Signal::SIGTERM) {
-                        tracing::error!(
-                            "Failed to send SIGTERM to process {}: {}",
-                            pid,
-                            e
-                        );
-                    }
+                Err(e) => {
+                    tracing::error!("Failed to wait for child process {}: {:?}", child_pid, e);
                 }
+            }
 
-                // Wait for child to exit after sending signal
-                if let Err(e) = child.wait().await {
-                    tracing::error!(
-                        "Error waiting for child after SIGTERM: {}",
-                        e
-                    );
-                }
+            // Stop process monitoring
+            let status = status.ok();
+            self.process_monitor.stop(status).await?;
+            self.child_pid = None;
+
+            if signalled
+                || !self
+                    .restart_policy
+                    .should_restart(status.as_ref(), self.restart_count)
+            {
+                return Ok(());
             }
-            _ = sigint.recv() => {
-                tracing::info!("Received SIGINT signal");
-
-                // Stop process monitoring
-                self.process_monitor.stop().await?;
-
-                if let Some(pid) = self.child_pid.take() {
-                    if let Err(e) = nix::sys::signal::kill(Pid::from_raw(pid as i32), nix::sys::signal::Signal::SIGTERM) {
-                        tracing::error!(
-                            "Failed to send SIGTERM to process {}: {}",
-                            pid,
-                            e
-                        );
-                    }
-                }
 
-                // Wait for child to exit after sending signal
-                if let Err(e) = child.wait().await {
-                    tracing::error!(
-                        "Error waiting for child after SIGINT: {}",
-                        e
-                    );
-                }
+            let delay = self.restart_backoff.next_delay(started_at.elapsed());
+            if self.restart_backoff.is_crash_looping() {
+                tracing::warn!(
+                    "Child process {} is crash looping, restarting in {:?}",
+                    child_pid,
+                    delay
+                );
+            } else {
+                tracing::info!("Restarting child process {} in {:?}", child_pid, delay);
+            }
+
+            self.restart_count += 1;
+            self.process_monitor.restart(self.restart_count).await?;
+
+            // Stop instead of restarting if asked to while waiting
+            let signalled = tokio::select! {
+                _ = time::sleep(delay) => false,
+                _ = sigterm.recv() => true,
+                _ = sigint.recv() => true,
+            };
+
+            if signalled {
+                tracing::info!("Received signal while waiting to restart, stopping");
+                self.process_monitor.stop(status).await?;
+                return Ok(());
             }
         }
+    }
 
-        self.child_pid = None;
-        Ok(())
+    /// Sends SIGTERM to the child process so it can shut down.
+    fn terminate_child(&mut self) {
+        if let Some(pid) = self.child_pid.take() {
+            if let Err(e) =
+                nix::sys::signal::kill(Pid::from_raw(pid as i32), nix::sys::signal::Signal::SIGTERM)
+            {
+                tracing::error!("Failed to send SIGTERM to process {}: {}", pid, e);
+            }
+        }
     }
 }